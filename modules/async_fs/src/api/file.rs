@@ -15,7 +15,7 @@ pub type Permissions = fops::FilePerm;
 pub type File = fops::File;
 
 /// Metadata information about a file.
-pub struct Metadata(fops::FileAttr);
+pub struct Metadata(pub(crate) fops::FileAttr);
 
 /// Options and flags which can be used to configure how a file is opened.
 #[derive(Clone, Debug)]
@@ -105,6 +105,16 @@ impl Metadata {
         self.0.set_perm(perm)
     }
 
+    /// Returns the user ID of the owner of this file.
+    pub const fn uid(&self) -> u32 {
+        self.0.uid()
+    }
+
+    /// Returns the group ID of the owner of this file.
+    pub const fn gid(&self) -> u32 {
+        self.0.gid()
+    }
+
     /// Returns the total size of this file in bytes.
     pub const fn size(&self) -> u64 {
         self.0.size()
@@ -8,9 +8,10 @@ pub mod port;
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
 pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
 use axerrno::AxResult;
-use async_vfs::VfsNodeRef;
+use async_vfs::{AsyncVfsNodeOps, VfsNodeRef};
 pub use async_io::{Read, Seek, SeekFrom, Write, Result};
 pub use port::*;
+pub use crate::cred::{current_cred, register_cred_provider, AccessMode, FsCred};
 
 use alloc::{string::String, vec::Vec};
 
@@ -61,7 +62,59 @@ pub async fn write<C: AsRef<[u8]>>(path: &str, contents: C) -> Result<()> {
 /// Given a path, query the file system to get information about a file,
 /// directory, etc.
 pub async fn metadata(path: &str) -> Result<Metadata> {
-    File::open(path).await?.metadata().await
+    let node = crate::root::lookup(None, path).await?;
+    node.get_attr().await.map(Metadata)
+}
+
+/// Changes the permissions of a file or directory.
+///
+/// Only the owner of the file or root can do this.
+pub async fn set_permissions(path: &str, perm: Permissions) -> Result<()> {
+    let node = crate::root::lookup(None, path).await?;
+    let mut attr = node.get_attr().await?;
+    let cred = current_cred();
+    if !cred.is_root() && cred.uid != attr.uid() {
+        return axerrno::ax_err!(PermissionDenied);
+    }
+    attr.set_perm(perm);
+    // 不支持修改权限的文件系统返回 Unsupported
+    node.set_attr(attr).await
+}
+
+/// Changes the owner and the group of a file or directory. `None` leaves the
+/// corresponding ID unchanged.
+///
+/// Only root can change the owner. The owner of the file can change its group
+/// to one of the groups the owner belongs to.
+pub async fn set_owner(path: &str, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+    let node = crate::root::lookup(None, path).await?;
+    let mut attr = node.get_attr().await?;
+    let cred = current_cred();
+    if !cred.is_root()
+        && (cred.uid != attr.uid()
+            || uid.is_some_and(|uid| uid != attr.uid())
+            || gid.is_some_and(|gid| !cred.in_group(gid)))
+    {
+        return axerrno::ax_err!(PermissionDenied);
+    }
+    attr.set_owner(uid.unwrap_or(attr.uid()), gid.unwrap_or(attr.gid()));
+    // 不支持修改所有者的文件系统返回 Unsupported
+    node.set_attr(attr).await
+}
+
+/// Checks whether the current user can access the file in `mode`.
+pub async fn access(path: &str, mode: AccessMode) -> Result<()> {
+    let attr = crate::root::lookup(None, path).await?.get_attr().await?;
+    crate::cred::check_access(&attr, mode.into())
+}
+
+/// Checks whether the current user can execute the file.
+pub async fn check_executable(path: &str) -> Result<()> {
+    let attr = crate::root::lookup(None, path).await?.get_attr().await?;
+    if !attr.is_file() {
+        return axerrno::ax_err!(PermissionDenied);
+    }
+    crate::cred::check_access(&attr, capability::Cap::EXECUTE)
 }
 
 /// Creates a new, empty directory at the provided path.
@@ -102,3 +155,15 @@ pub async fn path_exists(path: &str) -> bool {
 pub async fn lookup(path: &str) -> AxResult<VfsNodeRef> {
     crate::root::lookup(None, path).await
 }
+
+/// 在 procfs 的目录 `dir` 中添加名为 `name` 的只读文件，文件的内容在每次读取时由 `render` 生成
+#[cfg(feature = "procfs")]
+pub async fn add_generated_file(dir: &str, name: &str, render: fn() -> String) -> AxResult {
+    use crate::fs::ramfs::{DirNode, GeneratedFile};
+    let node = lookup(dir).await?;
+    let dir = node
+        .as_any()
+        .downcast_ref::<DirNode>()
+        .ok_or(axerrno::AxError::Unsupported)?;
+    dir.add_node(name, alloc::sync::Arc::new(GeneratedFile::new(render)))
+}
@@ -117,6 +117,8 @@ bitflags! {
         const NOCTTY = 1 << 8;
         /// 同上，在不同的库中可能会用到这个或者上一个
         const TRUNC = 1 << 9;
+        /// 每次写入之前将读写位置移动到文件末尾
+        const APPEND = 1 << 10;
         /// 非阻塞读写?(虽然不知道为什么但 date.lua 也要)
         /// 在 socket 中使用得较多
         const NON_BLOCK = 1 << 11;
@@ -0,0 +1,126 @@
+//! 文件系统进行权限检查时使用的用户凭证。
+//!
+//! 凭证由进程管理模块通过 [`register_cred_provider`] 提供，未注册时以 root 身份进行访问。
+
+use alloc::vec::Vec;
+use async_vfs::VfsNodeAttr;
+use axerrno::{ax_err, AxResult};
+use capability::Cap;
+use lazy_init::LazyInit;
+
+/// 进行权限检查时使用的用户凭证
+#[derive(Debug, Clone, Default)]
+pub struct FsCred {
+    /// 访问文件系统使用的用户 id，一般为有效用户 id
+    pub uid: u32,
+    /// 访问文件系统使用的组 id，一般为有效组 id
+    pub gid: u32,
+    /// 附加组
+    pub groups: Vec<u32>,
+}
+
+impl FsCred {
+    /// root 用户的凭证
+    pub const fn root() -> Self {
+        Self {
+            uid: 0,
+            gid: 0,
+            groups: Vec::new(),
+        }
+    }
+
+    /// 是否为 root 用户
+    pub fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+
+    /// 是否属于组 `gid`
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.gid == gid || self.groups.contains(&gid)
+    }
+}
+
+bitflags::bitflags! {
+    /// access 检查的权限，与 faccessat 的 mode 相同
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct AccessMode: u32 {
+        /// 可执行
+        const EXEC = 1;
+        /// 可写
+        const WRITE = 2;
+        /// 可读
+        const READ = 4;
+    }
+}
+
+impl From<AccessMode> for Cap {
+    fn from(mode: AccessMode) -> Cap {
+        let mut cap = Cap::empty();
+        if mode.contains(AccessMode::READ) {
+            cap |= Cap::READ;
+        }
+        if mode.contains(AccessMode::WRITE) {
+            cap |= Cap::WRITE;
+        }
+        if mode.contains(AccessMode::EXEC) {
+            cap |= Cap::EXECUTE;
+        }
+        cap
+    }
+}
+
+static CRED_PROVIDER: LazyInit<fn() -> FsCred> = LazyInit::new();
+
+/// 注册获取当前用户凭证的函数，只能注册一次
+pub fn register_cred_provider(provider: fn() -> FsCred) {
+    CRED_PROVIDER.init_by(provider);
+}
+
+/// 当前用户的凭证
+pub fn current_cred() -> FsCred {
+    if CRED_PROVIDER.is_init() {
+        CRED_PROVIDER()
+    } else {
+        FsCred::root()
+    }
+}
+
+/// 用户 `cred` 对属性为 `attr` 的节点拥有的权限
+///
+/// root 可以读写任意节点，可以搜索任意目录，但只能执行至少有一个执行位的文件。
+pub(crate) fn node_cap(attr: &VfsNodeAttr, cred: &FsCred) -> Cap {
+    let perm = attr.perm().bits();
+    let bits = if cred.is_root() {
+        if attr.is_dir() || perm & 0o111 != 0 {
+            0o7
+        } else {
+            0o6
+        }
+    } else if cred.uid == attr.uid() {
+        (perm >> 6) & 0o7
+    } else if cred.in_group(attr.gid()) {
+        (perm >> 3) & 0o7
+    } else {
+        perm & 0o7
+    };
+    let mut cap = Cap::empty();
+    if bits & 0o4 != 0 {
+        cap |= Cap::READ;
+    }
+    if bits & 0o2 != 0 {
+        cap |= Cap::WRITE;
+    }
+    if bits & 0o1 != 0 {
+        cap |= Cap::EXECUTE;
+    }
+    cap
+}
+
+/// 检查当前用户对属性为 `attr` 的节点是否拥有权限 `cap`
+pub(crate) fn check_access(attr: &VfsNodeAttr, cap: Cap) -> AxResult {
+    if node_cap(attr, &current_cred()).contains(cap) {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
+    }
+}
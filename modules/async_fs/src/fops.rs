@@ -4,11 +4,15 @@ use axerrno::{ax_err, ax_err_type, AxResult};
 use async_vfs::{AsyncVfsNodeOps, VfsError, VfsNodeOps, VfsNodeRef};
 use async_io::{AsyncRead, AsyncSeek, AsyncWrite, SeekFrom};
 use capability::{Cap, WithCap};
+use alloc::{boxed::Box, vec, vec::Vec};
 use core::fmt;
+use core::future::Future;
 use core::pin::Pin;
 use core::task::{Context, Poll};
 
 use crate::api::FileExtTrait;
+use crate::page_cache;
+use sync::SpinNoIrq;
 #[cfg(feature = "myfs")]
 pub use crate::dev::Disk;
 #[cfg(feature = "myfs")]
@@ -29,6 +33,38 @@ pub struct File {
     node: WithCap<VfsNodeRef>,
     is_append: bool,
     offset: u64,
+    /// 普通文件的读写经过页缓存
+    cached: bool,
+    /// 通过 `AsyncRead`/`AsyncWrite` 发起的、尚未完成的页缓存操作
+    in_flight: InFlight,
+}
+
+type ReadFuture = Pin<Box<dyn Future<Output = AxResult<Vec<u8>>> + Send>>;
+type FlushFuture = Pin<Box<dyn Future<Output = AxResult<()>> + Send>>;
+
+/// 页缓存操作的 future 返回 `Pending` 时保存在这里，下一次 poll 时取出并继续同一个操作，而不是重新开始。
+/// 锁只用于让 [`File`] 保持 `Sync`，poll 时不持有锁。
+///
+/// 读取的 future 自己持有缓冲区，完成后再复制到调用者的缓冲区，并记录发起时的偏移与长度：
+/// 调用者放弃了之前的读取、以不同的参数再次读取时重新开始。复制 [`File`] 时不复制进行中的操作
+struct InFlight {
+    read: SpinNoIrq<Option<(u64, usize, ReadFuture)>>,
+    flush: SpinNoIrq<Option<FlushFuture>>,
+}
+
+impl InFlight {
+    const fn new() -> Self {
+        Self {
+            read: SpinNoIrq::new(None),
+            flush: SpinNoIrq::new(None),
+        }
+    }
+}
+
+impl Clone for InFlight {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
 }
 
 impl FileExtTrait for File {
@@ -44,11 +80,31 @@ impl AsyncRead for File {
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> Poll<AxResult<usize>> {
-        let Self { node, offset, .. } = self.get_mut();
+        let Self { node, offset, cached, in_flight, .. } = self.get_mut();
         let node = node.access(Cap::READ)?;
-        let read_len = core::task::ready!(
-            VfsNodeOps::poll_read_at(Pin::new(node), cx, *offset, buf)
-        )?;
+        let read_len = if *cached {
+            let mut fut = match in_flight.read.lock().take() {
+                Some((start, len, fut)) if start == *offset && len == buf.len() => fut,
+                _ => {
+                    let (node, start, len) = (node.clone(), *offset, buf.len());
+                    Box::pin(async move {
+                        let mut data = vec![0; len];
+                        let n = page_cache::read_at(&node, start, &mut data).await?;
+                        data.truncate(n);
+                        Ok(data)
+                    })
+                }
+            };
+            let Poll::Ready(result) = fut.as_mut().poll(cx) else {
+                *in_flight.read.lock() = Some((*offset, buf.len(), fut));
+                return Poll::Pending;
+            };
+            let data = result?;
+            buf[..data.len()].copy_from_slice(&data);
+            data.len()
+        } else {
+            core::task::ready!(VfsNodeOps::poll_read_at(Pin::new(node), cx, *offset, buf))?
+        };
         *offset += read_len as u64;
         Poll::Ready(Ok(read_len))
     }
@@ -60,7 +116,7 @@ impl AsyncWrite for File {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<AxResult<usize>> {
-        let Self { node, is_append, offset } = self.get_mut();
+        let Self { node, is_append, offset, cached, .. } = self.get_mut();
         let node = node.access(Cap::WRITE)?;
         if *is_append {
             let attr = core::task::ready!(VfsNodeOps::poll_get_attr(Pin::new(node), cx)).unwrap();
@@ -69,12 +125,27 @@ impl AsyncWrite for File {
         let write_len = core::task::ready!(
             VfsNodeOps::poll_write_at(Pin::new(node), cx, *offset, buf)
         ).unwrap();
+        if *cached {
+            page_cache::update(node, *offset, &buf[..write_len]);
+        }
         *offset += write_len as u64;
         Poll::Ready(Ok(write_len))
     }
 
     fn flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<AxResult<()>> {
-        let node = self.get_mut().node.access(Cap::WRITE)?;
+        let Self { node, cached, in_flight, .. } = self.get_mut();
+        let node = node.access(Cap::WRITE)?;
+        if *cached {
+            let mut fut = in_flight.flush.lock().take().unwrap_or_else(|| {
+                let node = node.clone();
+                Box::pin(async move { page_cache::writeback(&node).await })
+            });
+            let Poll::Ready(result) = fut.as_mut().poll(cx) else {
+                *in_flight.flush.lock() = Some(fut);
+                return Poll::Pending;
+            };
+            result?;
+        }
         VfsNodeOps::poll_fsync(Pin::new(node), cx)
     }
 
@@ -198,6 +269,7 @@ impl File {
         }
 
         let node_option = crate::root::lookup(dir, path).await;
+        let mut created = false;
         let node = if opts.create || opts.create_new {
             match node_option {
                 Ok(node) => {
@@ -208,7 +280,10 @@ impl File {
                     node
                 }
                 // not exists, create new
-                Err(VfsError::NotFound) => crate::root::create_file(dir, path).await?,
+                Err(VfsError::NotFound) => {
+                    created = true;
+                    crate::root::create_file(dir, path).await?
+                }
                 Err(e) => return Err(e),
             }
         } else {
@@ -222,17 +297,21 @@ impl File {
             return ax_err!(IsADirectory);
         }
         let access_cap = opts.into();
-        if !perm_to_cap(attr.perm()).contains(access_cap) {
-            return ax_err!(PermissionDenied);
+        // 新建的文件不受其权限位的限制
+        if !created {
+            crate::cred::check_access(&attr, access_cap)?;
         }
         node.open().await?;
         if opts.truncate {
             node.truncate(0).await?;
+            page_cache::truncate(&node, 0);
         }
         Ok(Self {
             node: WithCap::new(node, access_cap),
             is_append: opts.append,
             offset: 0,
+            cached: attr.is_file(),
+            in_flight: InFlight::new(),
         })
     }
 
@@ -244,7 +323,11 @@ impl File {
 
     /// Truncates the file to the specified size.
     pub async fn truncate(&self, size: u64) -> AxResult {
-        self.node.access(Cap::WRITE)?.truncate(size).await?;
+        let node = self.node.access(Cap::WRITE)?;
+        node.truncate(size).await?;
+        if self.cached {
+            page_cache::truncate(node, size);
+        }
         Ok(())
     }
 
@@ -253,8 +336,7 @@ impl File {
     ///
     /// After the read, the cursor will be advanced by the number of bytes read.
     pub async fn read(&mut self, buf: &mut [u8]) -> AxResult<usize> {
-        let node = self.node.access(Cap::READ)?;
-        let read_len = node.read_at(self.offset, buf).await?;
+        let read_len = self.read_at(self.offset, buf).await?;
         self.offset += read_len as u64;
         Ok(read_len)
     }
@@ -264,6 +346,9 @@ impl File {
     /// It does not update the file cursor.
     pub async fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
         let node = self.node.access(Cap::READ)?;
+        if self.cached {
+            return page_cache::read_at(node, offset, buf).await;
+        }
         let read_len = node.read_at(offset, buf).await?;
         Ok(read_len)
     }
@@ -279,6 +364,9 @@ impl File {
             self.offset = self.get_attr().await?.size();
         };
         let write_len = node.write_at(self.offset, buf).await?;
+        if self.cached {
+            page_cache::update(node, self.offset, &buf[..write_len]);
+        }
         self.offset += write_len as u64;
         Ok(write_len)
     }
@@ -290,12 +378,19 @@ impl File {
     pub async fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
         let node = self.node.access(Cap::WRITE)?;
         let write_len = node.write_at(offset, buf).await?;
+        if self.cached {
+            page_cache::update(node, offset, &buf[..write_len]);
+        }
         Ok(write_len)
     }
 
     /// Flushes the file, writes all buffered data to the underlying device.
     pub async fn flush(&self) -> AxResult {
-        self.node.access(Cap::WRITE)?.fsync().await?;
+        let node = self.node.access(Cap::WRITE)?;
+        if self.cached {
+            page_cache::writeback(node).await?;
+        }
+        node.fsync().await?;
         Ok(())
     }
 
@@ -318,6 +413,38 @@ impl File {
         self.node.access(Cap::empty())?.get_attr().await
     }
 
+    /// Returns an identifier of the underlying node. All the opened files of the
+    /// same node share the identifier while any of them is alive.
+    pub fn node_id(&self) -> usize {
+        self.node.access(Cap::empty()).map_or(0, page_cache::node_id)
+    }
+
+    /// Returns the `index`-th page of the file in the page cache, or `None` if
+    /// the file is not a regular file.
+    pub async fn cache_page(&self, index: u64) -> AxResult<Option<page_cache::CachePage>> {
+        if !self.cached {
+            return Ok(None);
+        }
+        let node = self.node.access(Cap::empty())?;
+        page_cache::get_page(node, index).await.map(Some)
+    }
+
+    /// Marks the `index`-th cached page of the file dirty.
+    pub fn mark_page_dirty(&self, index: u64) {
+        if let Ok(node) = self.node.access(Cap::empty()) {
+            page_cache::mark_dirty(node, index);
+        }
+    }
+
+    /// Writes the dirty cached pages in `[start, end)` back to the file system.
+    pub async fn writeback_pages(&self, start: u64, end: u64) -> AxResult {
+        if !self.cached {
+            return Ok(());
+        }
+        let node = self.node.access(Cap::empty())?;
+        page_cache::writeback_range(node, start, end).await
+    }
+
     #[allow(unused)]
     /// whether the file is readable.
     pub fn readable(&self) -> bool {
@@ -353,9 +480,7 @@ impl Directory {
             return ax_err!(NotADirectory);
         }
         let access_cap = opts.into();
-        if !perm_to_cap(attr.perm()).contains(access_cap) {
-            return ax_err!(PermissionDenied);
-        }
+        crate::cred::check_access(&attr, access_cap)?;
 
         node.open().await?;
         Ok(Self {
@@ -494,17 +619,3 @@ impl From<&OpenOptions> for Cap {
         cap
     }
 }
-
-fn perm_to_cap(perm: FilePerm) -> Cap {
-    let mut cap = Cap::empty();
-    if perm.owner_readable() {
-        cap |= Cap::READ;
-    }
-    if perm.owner_writable() {
-        cap |= Cap::WRITE;
-    }
-    if perm.owner_executable() {
-        cap |= Cap::EXECUTE;
-    }
-    cap
-}
@@ -4,6 +4,8 @@
 //! 
 //! root.rs 中定义了文件系统根目录的实现，包括根目录的初始化、根目录的操作等。
 //! 
+//! page_cache.rs 中定义了普通文件的页缓存，文件读写与 mmap 共用其中的页面。
+//! 
 //! fops.rs 中定义了 File、Directory、OpenOptions 等结构。
 //!     1. File：其内部的实现为任意实现了 VfsNodeOps + Unpin trait 的对象，File 实现了 AsyncRead、AsyncWrite、AsyncSeek trait，提供了 IO 接口
 //! 
@@ -19,11 +21,13 @@ extern crate alloc;
 mod fs;
 mod dev;
 mod root;
+mod cred;
 #[allow(unused)]
 mod mounts;
 
 pub mod api;
 pub mod fops;
+pub mod page_cache;
 pub use fs::BLOCK_SIZE;
 
 
@@ -1,5 +1,5 @@
 use alloc::sync::Arc;
-use async_vfs::{VfsNodeType, VfsOps, VfsResult, AsyncVfsOps, AsyncVfsNodeOps};
+use async_vfs::{VfsNodePerm, VfsNodeType, VfsOps, VfsResult, AsyncVfsOps, AsyncVfsNodeOps};
 
 use crate::fs;
 
@@ -55,6 +55,24 @@ pub(crate) async fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
     let file_over = proc_root.clone().lookup("./sys/vm/overcommit_memory")?;
     file_over.write_at(0, b"0\n").await?;
 
+    // Create /proc/sys/kernel/core_pattern，转储 core 文件时读取，只有 root 可以修改
+    proc_root.create("sys/kernel", VfsNodeType::Dir).await?;
+    proc_root.create("sys/kernel/core_pattern", VfsNodeType::File).await?;
+    let file_core = proc_root.clone().lookup("./sys/kernel/core_pattern")?;
+    file_core.write_at(0, b"core.%p\n").await?;
+    let mut attr = file_core.get_attr().await?;
+    attr.set_perm(VfsNodePerm::from_bits_truncate(0o644));
+    file_core.set_attr(attr).await?;
+
+    // Create /proc/sys/fs/binfmt_misc/register，root 写入注册字符串注册可执行文件格式
+    proc_root.create("sys/fs", VfsNodeType::Dir).await?;
+    proc_root.create("sys/fs/binfmt_misc", VfsNodeType::Dir).await?;
+    proc_root.create("sys/fs/binfmt_misc/register", VfsNodeType::File).await?;
+    let file_register = proc_root.clone().lookup("./sys/fs/binfmt_misc/register")?;
+    let mut attr = file_register.get_attr().await?;
+    attr.set_perm(VfsNodePerm::from_bits_truncate(0o200));
+    file_register.set_attr(attr).await?;
+
     // Create /proc/self/stat
     proc_root.create("self", VfsNodeType::Dir).await?;
     proc_root.create("self/stat", VfsNodeType::File).await?;
@@ -67,7 +85,7 @@ pub(crate) async fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
     #[cfg(feature = "monolithic")]
     {
         // Create other file to pass the testcases
-        proc_root.create("meminfo", VfsNodeType::File).await?;
+        // meminfo 与 vmstat 的内容随内核状态变化，由内核初始化时通过 `api::add_generated_file` 添加
         proc_root.create("mounts", VfsNodeType::File).await?;
         proc_root.create("interrupts", VfsNodeType::File).await?;
         // procfs.mount("interrupts", Arc::new(fs::devfs::Interrupts::default()))?;
@@ -0,0 +1,336 @@
+//! 普通文件的页缓存。
+//!
+//! 以（文件节点，页号）为键缓存文件的内容，文件读写与 mmap 使用同一份页面：
+//! 读取时从缓存中复制，缓存中没有时先从文件系统读入；写入时同时写入文件系统与已经缓存的页面（写穿）；
+//! 共享的文件映射直接映射缓存中的页面，被写入的页面标记为脏页，在 msync、fsync 或者被回收时写回文件系统。
+//!
+//! 仍被某个地址空间映射的页面在写回之后依然保持为脏页，因为之后还可能通过映射被写入。
+//! 内存不足时 [`reclaim`] 回收没有被映射的页面，脏页先写回。
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use async_vfs::{AsyncVfsNodeOps, VfsNodeRef};
+use axalloc::PhysPage;
+use axerrno::{AxError, AxResult};
+use sync::{Mutex, SpinNoIrq};
+
+/// 缓存页的大小
+pub const PAGE_SIZE: usize = 0x1000;
+
+/// 缓存中的页面，与 `MapArea` 中的物理页类型相同，可以直接映射到用户地址空间
+pub type CachePage = Arc<Mutex<PhysPage>>;
+
+struct CacheEntry {
+    page: CachePage,
+    /// 页面在内核中的地址，读写页面内容时不需要获取页面的锁
+    vaddr: usize,
+    dirty: bool,
+}
+
+impl CacheEntry {
+    /// 页面是否只被缓存引用
+    fn unmapped(&self) -> bool {
+        Arc::strong_count(&self.page) == 1
+    }
+}
+
+struct CachedFile {
+    /// 持有文件节点，保证节点的标识在缓存期间不会被重用
+    node: VfsNodeRef,
+    pages: BTreeMap<u64, CacheEntry>,
+    /// 每次写入或者截断文件时递增。从文件系统读入页面期间发生了变化时，读到的内容可能已经过时，不能放入缓存
+    generation: u64,
+}
+
+impl CachedFile {
+    fn new(node: &VfsNodeRef) -> Self {
+        Self {
+            node: node.clone(),
+            pages: BTreeMap::new(),
+            generation: 0,
+        }
+    }
+}
+
+/// 所有文件的缓存页，键为 [`node_id`]
+static PAGE_CACHE: SpinNoIrq<BTreeMap<usize, CachedFile>> = SpinNoIrq::new(BTreeMap::new());
+
+/// 文件节点的标识
+pub fn node_id(node: &VfsNodeRef) -> usize {
+    Arc::as_ptr(node) as *const () as usize
+}
+
+/// 将缓存页 `vaddr` 中 `offset` 处开始的内容复制到 `buf`
+fn copy_from_page(vaddr: usize, offset: usize, buf: &mut [u8]) {
+    unsafe {
+        core::ptr::copy_nonoverlapping((vaddr + offset) as *const u8, buf.as_mut_ptr(), buf.len())
+    }
+}
+
+/// 将 `buf` 复制到缓存页 `vaddr` 中 `offset` 处
+fn copy_to_page(vaddr: usize, offset: usize, buf: &[u8]) {
+    unsafe { core::ptr::copy_nonoverlapping(buf.as_ptr(), (vaddr + offset) as *mut u8, buf.len()) }
+}
+
+/// 分配一个页面，内存不足时先回收缓存
+async fn alloc_page() -> AxResult<PhysPage> {
+    if let Ok(page) = PhysPage::alloc() {
+        return Ok(page);
+    }
+    reclaim(1).await;
+    PhysPage::alloc()
+}
+
+/// 从文件系统读入 `node` 的第 `index` 页，文件末尾之后的部分为 0
+async fn read_page(node: &VfsNodeRef, index: u64) -> AxResult<PhysPage> {
+    let mut page = alloc_page().await?;
+    page.fill(0);
+    let buf = page.as_slice_mut();
+    let mut read = 0;
+    while read < PAGE_SIZE {
+        let n = node
+            .read_at(index * PAGE_SIZE as u64 + read as u64, &mut buf[read..])
+            .await?;
+        if n == 0 {
+            break;
+        }
+        read += n;
+    }
+    Ok(page)
+}
+
+/// 获取 `node` 的第 `index` 页，不在缓存中时从文件系统读入，文件末尾之后的部分为 0
+pub async fn get_page(node: &VfsNodeRef, index: u64) -> AxResult<CachePage> {
+    get_page_vaddr(node, index).await.map(|(page, _)| page)
+}
+
+/// 获取页面及其内核地址。页面不在缓存中时，先记录文件的写入代数再读入，
+/// 放入缓存之前代数发生了变化说明读入期间文件被写入或者截断，丢弃读到的内容重新读取
+async fn get_page_vaddr(node: &VfsNodeRef, index: u64) -> AxResult<(CachePage, usize)> {
+    let id = node_id(node);
+    loop {
+        let generation = {
+            let mut cache = PAGE_CACHE.lock();
+            // 读入之前就建立文件的缓存，使得读入期间的写入能够递增代数
+            let file = cache.entry(id).or_insert_with(|| CachedFile::new(node));
+            if let Some(entry) = file.pages.get(&index) {
+                return Ok((entry.page.clone(), entry.vaddr));
+            }
+            file.generation
+        };
+        let page = match read_page(node, index).await {
+            Ok(page) => page,
+            Err(err) => {
+                let mut cache = PAGE_CACHE.lock();
+                if cache.get(&id).is_some_and(|file| file.pages.is_empty()) {
+                    cache.remove(&id);
+                }
+                return Err(err);
+            }
+        };
+        let vaddr = page.start_vaddr.as_usize();
+
+        let mut cache = PAGE_CACHE.lock();
+        // 文件的缓存在读入期间被整个回收时，无法知道期间是否有写入，同样重新读取
+        let Some(file) = cache
+            .get_mut(&id)
+            .filter(|file| file.generation == generation)
+        else {
+            continue;
+        };
+        // 读取期间可能已经有其他任务读入了同一页
+        let entry = file.pages.entry(index).or_insert(CacheEntry {
+            page: Arc::new(Mutex::new(page)),
+            vaddr,
+            dirty: false,
+        });
+        return Ok((entry.page.clone(), entry.vaddr));
+    }
+}
+
+/// 通过缓存读取 `node` 中 `offset` 处的内容，返回读取的长度
+pub async fn read_at(node: &VfsNodeRef, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+    let size = node.get_attr().await?.size();
+    if offset >= size {
+        return Ok(0);
+    }
+    let len = buf.len().min((size - offset) as usize);
+    let mut done = 0;
+    while done < len {
+        let pos = offset + done as u64;
+        let in_page = pos as usize % PAGE_SIZE;
+        let n = (PAGE_SIZE - in_page).min(len - done);
+        let (_page, vaddr) = get_page_vaddr(node, pos / PAGE_SIZE as u64).await?;
+        copy_from_page(vaddr, in_page, &mut buf[done..done + n]);
+        done += n;
+    }
+    Ok(len)
+}
+
+/// 将已经写入文件系统的 `buf` 同步到缓存的页面中，没有缓存的页面不需要处理
+pub fn update(node: &VfsNodeRef, offset: u64, buf: &[u8]) {
+    let mut cache = PAGE_CACHE.lock();
+    let Some(file) = cache.get_mut(&node_id(node)) else {
+        return;
+    };
+    file.generation += 1;
+    let mut done = 0;
+    while done < buf.len() {
+        let pos = offset + done as u64;
+        let in_page = pos as usize % PAGE_SIZE;
+        let n = (PAGE_SIZE - in_page).min(buf.len() - done);
+        if let Some(entry) = file.pages.get(&(pos / PAGE_SIZE as u64)) {
+            copy_to_page(entry.vaddr, in_page, &buf[done..done + n]);
+        }
+        done += n;
+    }
+}
+
+/// 文件被截断为 `size`，丢弃之后的页面并将最后一页超出的部分清零
+///
+/// 仍被映射的页面从缓存中移除后由映射继续持有
+pub fn truncate(node: &VfsNodeRef, size: u64) {
+    let mut cache = PAGE_CACHE.lock();
+    let Some(file) = cache.get_mut(&node_id(node)) else {
+        return;
+    };
+    file.generation += 1;
+    let first_dropped = size.div_ceil(PAGE_SIZE as u64);
+    drop(file.pages.split_off(&first_dropped));
+    let in_page = size as usize % PAGE_SIZE;
+    if in_page != 0 {
+        if let Some(entry) = file.pages.get(&(size / PAGE_SIZE as u64)) {
+            copy_to_page(entry.vaddr, in_page, &[0; PAGE_SIZE][in_page..]);
+        }
+    }
+}
+
+/// 将 `node` 的第 `index` 页标记为脏页，共享的可写映射第一次写入页面时调用
+pub fn mark_dirty(node: &VfsNodeRef, index: u64) {
+    if let Some(entry) = PAGE_CACHE
+        .lock()
+        .get_mut(&node_id(node))
+        .and_then(|file| file.pages.get_mut(&index))
+    {
+        entry.dirty = true;
+    }
+}
+
+/// 将一页写回文件系统，只写回文件末尾之前的部分
+async fn write_page(node: &VfsNodeRef, index: u64, vaddr: usize) -> AxResult<()> {
+    let size = node.get_attr().await?.size();
+    let offset = index * PAGE_SIZE as u64;
+    if offset >= size {
+        return Ok(());
+    }
+    let len = PAGE_SIZE.min((size - offset) as usize);
+    let mut buf = [0u8; PAGE_SIZE];
+    copy_from_page(vaddr, 0, &mut buf[..len]);
+    let mut written = 0;
+    while written < len {
+        let n = node
+            .write_at(offset + written as u64, &buf[written..len])
+            .await?;
+        if n == 0 {
+            return Err(AxError::Io);
+        }
+        written += n;
+    }
+    Ok(())
+}
+
+/// 将 `node` 在 `[start, end)` 页之间的脏页写回文件系统
+///
+/// 写回后没有被映射的页面不再是脏页
+pub async fn writeback_range(node: &VfsNodeRef, start: u64, end: u64) -> AxResult<()> {
+    let dirty: Vec<(u64, usize)> = match PAGE_CACHE.lock().get(&node_id(node)) {
+        Some(file) => file
+            .pages
+            .range(start..end)
+            .filter(|(_, entry)| entry.dirty)
+            .map(|(index, entry)| (*index, entry.vaddr))
+            .collect(),
+        None => return Ok(()),
+    };
+    for (index, vaddr) in dirty {
+        write_page(node, index, vaddr).await?;
+        if let Some(entry) = PAGE_CACHE
+            .lock()
+            .get_mut(&node_id(node))
+            .and_then(|file| file.pages.get_mut(&index))
+        {
+            if entry.unmapped() {
+                entry.dirty = false;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 将 `node` 的所有脏页写回文件系统
+pub async fn writeback(node: &VfsNodeRef) -> AxResult<()> {
+    writeback_range(node, 0, u64::MAX).await
+}
+
+/// 返回缓存的页数以及其中没有被映射、可以被回收的页数
+pub fn stats() -> (usize, usize) {
+    let cache = PAGE_CACHE.lock();
+    let pages = cache.values().flat_map(|file| file.pages.values());
+    pages.fold((0, 0), |(cached, reclaimable), entry| {
+        (cached + 1, reclaimable + entry.unmapped() as usize)
+    })
+}
+
+/// 回收至多 `target` 个没有被映射的页面，脏页先写回，返回回收的页数
+pub async fn reclaim(target: usize) -> usize {
+    let candidates: Vec<(VfsNodeRef, u64, usize)> = PAGE_CACHE
+        .lock()
+        .values()
+        .flat_map(|file| {
+            file.pages
+                .iter()
+                .filter(|(_, entry)| entry.unmapped())
+                .map(|(index, entry)| (file.node.clone(), *index, entry.vaddr))
+        })
+        .collect();
+    let mut reclaimed = 0;
+    for (node, index, vaddr) in candidates {
+        if reclaimed >= target {
+            break;
+        }
+        let id = node_id(&node);
+        // 写回之前清除脏页标记，写回期间页面被重新映射并写入时会再次被标记
+        let dirty = PAGE_CACHE
+            .lock()
+            .get_mut(&id)
+            .and_then(|file| file.pages.get_mut(&index))
+            .is_some_and(|entry| core::mem::replace(&mut entry.dirty, false));
+        if dirty && write_page(&node, index, vaddr).await.is_err() {
+            warn!("failed to write back page {} before reclaiming it", index);
+            if let Some(entry) = PAGE_CACHE
+                .lock()
+                .get_mut(&id)
+                .and_then(|file| file.pages.get_mut(&index))
+            {
+                entry.dirty = true;
+            }
+            continue;
+        }
+        let mut cache = PAGE_CACHE.lock();
+        let Some(file) = cache.get_mut(&id) else {
+            continue;
+        };
+        // 写回期间页面可能又被映射或者写入
+        if file
+            .pages
+            .get(&index)
+            .is_some_and(|entry| entry.unmapped() && !entry.dirty)
+        {
+            file.pages.remove(&index);
+            reclaimed += 1;
+        }
+        if file.pages.is_empty() {
+            cache.remove(&id);
+        }
+    }
+    debug!("page cache: reclaimed {} pages", reclaimed);
+    reclaimed
+}
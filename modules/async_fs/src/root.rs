@@ -14,7 +14,8 @@ use lazy_init::LazyInit;
 use core::pin::Pin;
 use core::task::{Context, Poll};
 
-use crate::{api::FileType, fs, mounts};
+use crate::{api::FileType, cred, fs, mounts};
+use capability::Cap;
 
 static CURRENT_DIR_PATH: Mutex<String> = Mutex::new(String::new());
 static CURRENT_DIR: LazyInit<Mutex<VfsNodeRef>> = LazyInit::new();
@@ -276,21 +277,58 @@ pub(crate) async fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<Vfs
     }
 }
 
+/// 查找 `path` 所在的目录
+async fn parent_dir_of(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+    let trimmed = path.trim_end_matches('/');
+    match trimmed.rfind('/') {
+        Some(0) => Ok(ROOT_DIR.clone() as VfsNodeRef),
+        Some(idx) => lookup(dir, &trimmed[..=idx]).await,
+        None => Ok(parent_node_of(dir, path).await),
+    }
+}
+
+/// 检查当前用户是否可以在 `path` 所在的目录中创建或删除节点
+async fn check_parent_writable(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+    let attr = parent_dir_of(dir, path).await?.get_attr().await?;
+    cred::check_access(&attr, Cap::WRITE | Cap::EXECUTE)
+}
+
+/// 将新建的节点的所有者设为当前用户，文件系统不支持时忽略
+async fn set_owner_to_current(node: &VfsNodeRef) {
+    let cred = cred::current_cred();
+    if cred.is_root() {
+        return;
+    }
+    if let Ok(mut attr) = node.get_attr().await {
+        attr.set_owner(cred.uid, cred.gid);
+        let _ = node.set_attr(attr).await;
+    }
+}
+
 pub(crate) async fn create_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
     if path.is_empty() {
         return ax_err!(NotFound);
     } else if path.ends_with('/') {
         return ax_err!(NotADirectory);
     }
+    check_parent_writable(dir, path).await?;
     let parent = parent_node_of(dir, path).await;
     parent.create(path, VfsNodeType::File).await?;
-    parent.lookup(path)
+    let node = parent.lookup(path)?;
+    set_owner_to_current(&node).await;
+    Ok(node)
 }
 
 pub(crate) async fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     match lookup(dir, path).await {
         Ok(_) => ax_err!(AlreadyExists),
-        Err(AxError::NotFound) => parent_node_of(dir, path).await.create(path, VfsNodeType::Dir).await,
+        Err(AxError::NotFound) => {
+            check_parent_writable(dir, path).await?;
+            let parent = parent_node_of(dir, path).await;
+            parent.create(path, VfsNodeType::Dir).await?;
+            set_owner_to_current(&parent.lookup(path)?).await;
+            Ok(())
+        }
         Err(e) => Err(e),
     }
 }
@@ -300,9 +338,8 @@ pub(crate) async fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResul
     let attr = node.get_attr().await?;
     if attr.is_dir() {
         ax_err!(IsADirectory)
-    } else if !attr.perm().owner_writable() {
-        ax_err!(PermissionDenied)
     } else {
+        check_parent_writable(dir, path).await?;
         parent_node_of(dir, path).await.remove(path).await
     }
 }
@@ -329,9 +366,8 @@ pub(crate) async fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult
     let attr = node.get_attr().await?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
-    } else if !attr.perm().owner_writable() {
-        ax_err!(PermissionDenied)
     } else {
+        check_parent_writable(dir, path).await?;
         parent_node_of(dir, path).await.remove(path).await
     }
 }
@@ -355,9 +391,8 @@ pub(crate) async fn set_current_dir(path: &str) -> AxResult {
     let attr = node.get_attr().await?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
-    } else if !attr.perm().owner_executable() {
-        ax_err!(PermissionDenied)
     } else {
+        cred::check_access(&attr, Cap::EXECUTE)?;
         *CURRENT_DIR.lock().await = node;
         *CURRENT_DIR_PATH.lock().await = abs_path;
         Ok(())
@@ -365,6 +400,9 @@ pub(crate) async fn set_current_dir(path: &str) -> AxResult {
 }
 
 pub(crate) async fn rename(old: &str, new: &str) -> AxResult {
+    // 所有的权限检查都在修改之前完成，检查失败时不会删除已经存在的目标
+    check_parent_writable(None, old).await?;
+    check_parent_writable(None, new).await?;
     if parent_node_of(None, new).await.lookup(new).is_ok() {
         warn!("dst file already exist, now remove it");
         remove_file(None, new).await?;
@@ -1,7 +1,8 @@
-use alloc::{sync::Arc, vec::Vec};
+use alloc::{collections::BTreeSet, sync::Arc, vec::Vec};
+use async_fs::page_cache::CachePage;
 use async_io::{Seek, SeekFrom};
 use axalloc::PhysPage;
-use axerrno::AxResult;
+use axerrno::{AxError, AxResult};
 use axhal::{
     mem::{virt_to_phys, PhysAddr, VirtAddr, PAGE_SIZE_4K},
     paging::{MappingFlags, PageSize, PageTable},
@@ -9,12 +10,146 @@ use axhal::{
 use core::ptr::copy_nonoverlapping;
 use sync::Mutex;
 
-use crate::MemBackend;
+use crate::{
+    huge::{self, HUGE_PAGE_PAGES, PAGE_SIZE_2M},
+    pin, swap, zero, MemBackend,
+};
+
+/// 物理页是否仍被其他区域或者页缓存引用，写入之前需要先复制。固定页面持有的引用不算作共享
+fn page_shared(page: &Arc<Mutex<PhysPage>>) -> bool {
+    Arc::strong_count(page).saturating_sub(pin::pin_count(page)) > 1
+}
+
+/// 为缺页分配物理页，内存不足时先回收页缓存与其他地址空间中的页面
+async fn alloc_page() -> Option<PhysPage> {
+    if let Ok(page) = PhysPage::alloc() {
+        return Some(page);
+    }
+    swap::reclaim(swap::SWAP_CLUSTER).await;
+    PhysPage::alloc().ok()
+}
+
+/// 文件映射缺页时默认预读的页数
+pub(crate) const READAHEAD_NORMAL: usize = 4;
+/// 顺序访问（`MADV_SEQUENTIAL`）的文件映射缺页时预读的页数
+pub(crate) const READAHEAD_SEQUENTIAL: usize = 32;
+
+/// 从交换槽 `slot` 中读出一页
+async fn read_swapped(slot: usize) -> AxResult<PhysPage> {
+    let Some(mut page) = alloc_page().await else {
+        warn!("Error allocating new phys page for swapping in");
+        return Err(AxError::NoMemory);
+    };
+    debug!("swap in from slot {}", slot);
+    swap::read_slot(slot, page.as_slice_mut()).await?;
+    Ok(page)
+}
+
+/// 文件映射缺页时读到的页面
+pub(crate) enum FilePage {
+    /// 页缓存中的页面
+    Cache(CachePage),
+    /// 不能与页缓存共享的页面读入的私有物理页
+    Private(PhysPage),
+}
+
+/// 读取文件映射中 `delta` 处的一页：能与页缓存共享的页面直接使用缓存页，否则读入新的物理页
+///
+/// 使用页缓存时还会将之后的 `readahead` 页预读到页缓存中
+async fn fetch_file_page(
+    backend: &mut MemBackend,
+    delta: usize,
+    readahead: usize,
+) -> AxResult<FilePage> {
+    match backend.cache_page(delta).await {
+        Ok(Some(page)) => {
+            backend.readahead(delta + PAGE_SIZE_4K, readahead).await;
+            return Ok(FilePage::Cache(page));
+        }
+        Ok(None) => {}
+        Err(err) => {
+            error!("Failed to load file page for page fault: {:?}", err);
+            return Err(err);
+        }
+    }
+    let Some(mut page) = alloc_page().await else {
+        warn!("Error allocating new phys page for page fault");
+        return Err(AxError::NoMemory);
+    };
+    if backend.read_page(delta, page.as_slice_mut()).await.is_err() {
+        warn!("Failed to read from backend to memory");
+        page.fill(0);
+    }
+    Ok(FilePage::Private(page))
+}
+
+/// 缺页时需要在释放地址空间的锁之后完成的读取
+pub(crate) enum FaultIo {
+    /// 从交换槽中读出页面
+    Swap(usize),
+    /// 从文件中读出页面，`key` 为文件节点的标识与页面在文件中的偏移
+    File {
+        backend: MemBackend,
+        delta: usize,
+        key: (usize, u64),
+        readahead: usize,
+    },
+}
+
+/// 在后台预读文件映射的内容，不需要持有地址空间的锁
+pub struct Prefetch {
+    backend: MemBackend,
+    pages: usize,
+}
+
+impl Prefetch {
+    /// 将文件内容读入页缓存，之后的缺页可以直接使用缓存页
+    pub async fn run(mut self) {
+        self.backend.readahead(0, self.pages).await;
+    }
+}
+
+/// 读取完成、等待重新获取地址空间的锁之后安装的页面
+pub(crate) enum FaultPage {
+    Swap { slot: usize, page: PhysPage },
+    File { key: (usize, u64), page: FilePage },
+}
+
+impl FaultIo {
+    /// 读取期间额外引用的交换槽
+    pub(crate) fn pinned_slot(&self) -> Option<usize> {
+        match self {
+            FaultIo::Swap(slot) => Some(*slot),
+            FaultIo::File { .. } => None,
+        }
+    }
+
+    /// 进行读取，调用者不应持有地址空间的锁
+    pub(crate) async fn read(self) -> AxResult<FaultPage> {
+        match self {
+            FaultIo::Swap(slot) => Ok(FaultPage::Swap {
+                slot,
+                page: read_swapped(slot).await?,
+            }),
+            FaultIo::File {
+                mut backend,
+                delta,
+                key,
+                readahead,
+            } => Ok(FaultPage::File {
+                key,
+                page: fetch_file_page(&mut backend, delta, readahead).await?,
+            }),
+        }
+    }
+}
 
 /// A continuous virtual area in user memory.
 ///
-/// NOTE: Cloning a `MapArea` needs allocating new phys pages and modifying a page table. So
-/// `Clone` trait won't implemented.
+/// NOTE: Cloning a `MapArea` needs modifying page tables. So `Clone` trait won't implemented.
+///
+/// 非共享的区域在 fork 时采用写时复制（COW）：父子进程共享物理页，并同时去掉页表中的写权限，
+/// 发生写缺页时再复制。物理页的引用计数即为 `Arc` 的强引用计数。
 pub struct MapArea {
     /// phys pages of this area
     pub pages: Vec<Option<Arc<Mutex<PhysPage>>>>,
@@ -26,6 +161,18 @@ pub struct MapArea {
     pub flags: MappingFlags,
     /// whether the area is backed by a file
     pub backend: Option<MemBackend>,
+    /// 缺页时是否尝试使用大页
+    huge: bool,
+    /// 以大页映射的范围的起始地址
+    huge_pages: BTreeSet<usize>,
+    /// fork 时子进程不继承该区域（`MADV_DONTFORK`）
+    dontfork: bool,
+    /// 文件映射缺页时预读的页数
+    readahead: usize,
+    /// 区域被锁定（mlock），其中的页面不会被换出或者丢弃
+    locked: bool,
+    /// 向下增长的栈区域（`MAP_GROWSDOWN`），访问其下方的地址时向下扩展
+    grows_down: bool,
 }
 
 impl MapArea {
@@ -52,6 +199,12 @@ impl MapArea {
             shared: false,
             flags,
             backend,
+            huge: false,
+            dontfork: false,
+            readahead: READAHEAD_NORMAL,
+            locked: false,
+            grows_down: false,
+            huge_pages: BTreeSet::new(),
         }
     }
 
@@ -78,6 +231,12 @@ impl MapArea {
             shared: false,
             flags,
             backend: None,
+            huge: false,
+            dontfork: false,
+            readahead: READAHEAD_NORMAL,
+            locked: false,
+            grows_down: false,
+            huge_pages: BTreeSet::new(),
         })
     }
 
@@ -117,6 +276,12 @@ impl MapArea {
             shared: false,
             flags,
             backend,
+            huge: false,
+            dontfork: false,
+            readahead: READAHEAD_NORMAL,
+            locked: false,
+            grows_down: false,
+            huge_pages: BTreeSet::new(),
         })
     }
 
@@ -130,19 +295,51 @@ impl MapArea {
         self.shared
     }
 
+    /// 设置缺页时是否尝试使用大页，只对匿名区域有效。已经映射的大页不受影响
+    pub(crate) fn set_huge(&mut self, huge: bool) {
+        self.huge = huge && self.backend.is_none();
+    }
+
+    /// 设置区域是否为向下增长的栈区域，只对匿名区域有效
+    pub(crate) fn set_grows_down(&mut self, grows_down: bool) {
+        self.grows_down = grows_down && self.backend.is_none();
+    }
+
+    /// 区域是否为向下增长的栈区域
+    pub(crate) fn is_grows_down(&self) -> bool {
+        self.grows_down
+    }
+
+    /// 将区域的起始地址向下扩展到 `new_start`，新的页面在缺页时分配
+    pub(crate) fn grow_down(&mut self, new_start: VirtAddr, page_table: &mut PageTable) {
+        assert!(new_start.is_aligned_4k() && new_start < self.vaddr);
+        let size = self.vaddr.as_usize() - new_start.as_usize();
+        page_table
+            .map_fault_region(new_start, size, self.flags)
+            .unwrap();
+        self.pages
+            .splice(0..0, core::iter::repeat(None).take(size / PAGE_SIZE_4K));
+        self.vaddr = new_start;
+    }
+
     /// Deallocate all phys pages and unmap the area in page table.
     pub fn dealloc(&mut self, page_table: &mut PageTable) {
+        self.free_swap(0, self.pages.len(), page_table);
+        huge::unmapped(self.huge_pages.len());
+        self.huge_pages.clear();
         page_table.unmap_region(self.vaddr, self.size()).unwrap();
         self.pages.clear();
     }
 
-    /// 如果处理失败，返回false，此时直接退出当前程序
+    /// 处理缺页，被换出的页面从交换空间中读回
+    ///
+    /// 内存不足时返回 [`AxError::NoMemory`]，调用者可以回收内存后重试；其他错误时应当结束当前程序
     pub async fn handle_page_fault(
         &mut self,
         addr: VirtAddr,
         flags: MappingFlags,
         page_table: &mut PageTable,
-    ) -> bool {
+    ) -> AxResult {
         trace!(
             "handling {:?} page fault in area [{:?}, {:?})",
             addr,
@@ -158,82 +355,644 @@ impl MapArea {
                 "Try to access {:?} memory addr: {:?} with {:?} flag",
                 self.flags, addr, flags
             );
-            return false;
+            return Err(AxError::BadAddress);
         }
 
         let page_index = (usize::from(addr) - usize::from(self.vaddr)) / PAGE_SIZE_4K;
         if page_index >= self.pages.len() {
             error!("Phys page index out of bound");
-            return false;
+            return Err(AxError::BadAddress);
         }
         if self.pages[page_index].is_some() {
+            if flags.contains(MappingFlags::WRITE) && self.is_cow() {
+                return self.handle_cow_fault(page_index, page_table).await;
+            }
+            // 共享文件映射的页面第一次被写入，标记为脏页后恢复写权限
+            if flags.contains(MappingFlags::WRITE) && self.shared {
+                if let Some(backend) = &mut self.backend {
+                    backend.mark_dirty(page_index * PAGE_SIZE_4K).await;
+                    page_table
+                        .update(addr.align_down_4k(), None, Some(self.flags))
+                        .unwrap();
+                    axhal::arch::flush_tlb(addr.align_down_4k().into());
+                    return Ok(());
+                }
+            }
             debug!("Page fault in page already loaded");
-            return true;
+            swap::mark_accessed(
+                page_table,
+                addr.align_down_4k(),
+                flags.contains(MappingFlags::WRITE),
+            );
+            return Ok(());
         }
 
         debug!("page index {}", page_index);
 
+        if let Some(slot) = swap::swap_entry(page_table, addr.align_down_4k()) {
+            let page = read_swapped(slot).await?;
+            swap::free_slot(slot);
+            self.map_new_page(page_index, page, page_table);
+            return Ok(());
+        }
+        let readahead = self.readahead_pages(page_index);
+        if let Some(backend) = &mut self.backend {
+            let page = fetch_file_page(backend, page_index * PAGE_SIZE_4K, readahead).await?;
+            return self
+                .install_file_page(
+                    page_index,
+                    flags.contains(MappingFlags::WRITE),
+                    page,
+                    page_table,
+                )
+                .await;
+        }
+
+        if self.try_map_huge(page_index, page_table) {
+            return Ok(());
+        }
+
+        // 私有匿名映射的读缺页只读地映射零页，写入时再由写时复制分配
+        if !flags.contains(MappingFlags::WRITE) && !self.shared {
+            if let Some((zero, paddr)) = zero::zero_page() {
+                let vaddr = self.vaddr + page_index * PAGE_SIZE_4K;
+                page_table
+                    .map_overwrite(
+                        vaddr,
+                        paddr,
+                        PageSize::Size4K,
+                        self.flags - MappingFlags::WRITE,
+                    )
+                    .expect("Map in page fault handler failed");
+                axhal::arch::flush_tlb(Some(vaddr));
+                self.pages[page_index] = Some(zero);
+                return Ok(());
+            }
+        }
+
         // Allocate new page
-        let mut page = PhysPage::alloc().expect("Error allocating new phys page for page fault");
+        let Some(mut page) = alloc_page().await else {
+            warn!("Error allocating new phys page for page fault");
+            return Err(AxError::NoMemory);
+        };
+        page.fill(0);
+        self.map_new_page(page_index, page, page_table);
+        Ok(())
+    }
 
-        debug!(
-            "new phys page virtual (offset) address {:?}",
-            page.start_vaddr
+    /// 第 `page_index` 页所在的大页的起始地址，该页没有以大页映射时返回 `None`
+    fn huge_page_of(&self, page_index: usize) -> Option<usize> {
+        let vaddr = (self.vaddr + page_index * PAGE_SIZE_4K).align_down(PAGE_SIZE_2M);
+        self.huge_pages
+            .contains(&vaddr.as_usize())
+            .then_some(vaddr.as_usize())
+    }
+
+    /// 若第 `page_index` 页所在的 2 MiB 范围完全位于区域之内且其中还没有任何页面，则以一个大页映射整个范围
+    ///
+    /// 返回是否映射了大页，分配失败时由调用者退回普通页面
+    fn try_map_huge(&mut self, page_index: usize, page_table: &mut PageTable) -> bool {
+        if !self.huge {
+            return false;
+        }
+        let vaddr = (self.vaddr + page_index * PAGE_SIZE_4K).align_down(PAGE_SIZE_2M);
+        if vaddr < self.vaddr || vaddr + PAGE_SIZE_2M > self.end_va() {
+            return false;
+        }
+        let first = (vaddr.as_usize() - self.vaddr.as_usize()) / PAGE_SIZE_4K;
+        let empty = (first..first + HUGE_PAGE_PAGES).all(|idx| {
+            self.pages[idx].is_none()
+                && swap::swap_entry(page_table, self.vaddr + idx * PAGE_SIZE_4K).is_none()
+        });
+        if !empty {
+            return false;
+        }
+        let Some(pages) = huge::alloc_huge() else {
+            return false;
+        };
+        debug!("map huge page {:?}", vaddr);
+        huge::map_huge(
+            page_table,
+            vaddr,
+            virt_to_phys(pages[0].start_vaddr),
+            self.flags,
         );
+        for (idx, page) in (first..).zip(pages) {
+            self.pages[idx] = Some(Arc::new(Mutex::new(page)));
+        }
+        self.huge_pages.insert(vaddr.as_usize());
+        true
+    }
 
-        // Read data from backend to fill with 0.
-        match &mut self.backend {
-            Some(backend) => {
-                if backend
-                    .read_from_seek(
-                        SeekFrom::Current((page_index * PAGE_SIZE_4K) as i64),
-                        page.as_slice_mut(),
-                    )
-                    .await
-                    .is_err()
-                {
-                    warn!("Failed to read from backend to memory");
-                    page.fill(0);
+    /// 拆分以大页映射的范围 `vaddr`
+    fn split_huge_page(&mut self, vaddr: usize, page_table: &mut PageTable) {
+        if self.huge_pages.remove(&vaddr) {
+            huge::split_huge(page_table, vaddr.into());
+        }
+    }
+
+    /// 若 `addr` 位于某个大页的中间，则拆分该大页，以便在 `addr` 处拆分区域或者修改映射
+    pub(crate) fn split_huge_at(&mut self, addr: VirtAddr, page_table: &mut PageTable) {
+        if !addr.is_aligned(PAGE_SIZE_2M) {
+            self.split_huge_page(addr.align_down(PAGE_SIZE_2M).as_usize(), page_table);
+        }
+    }
+
+    /// 第 `page_index` 页缺页时预读的页数，不超过区域的末尾
+    fn readahead_pages(&self, page_index: usize) -> usize {
+        self.readahead.min(self.pages.len() - page_index - 1)
+    }
+
+    /// 设置文件映射缺页时预读的页数
+    pub(crate) fn set_readahead(&mut self, pages: usize) {
+        self.readahead = pages;
+    }
+
+    /// 设置 fork 时子进程是否继承该区域
+    pub(crate) fn set_dontfork(&mut self, dontfork: bool) {
+        self.dontfork = dontfork;
+    }
+
+    /// fork 时子进程是否不继承该区域
+    pub(crate) fn is_dontfork(&self) -> bool {
+        self.dontfork
+    }
+
+    /// 将第 `page_index` 页恢复为尚未分配的状态，释放其物理页或者交换槽。You need to flush TLB after this.
+    fn reset_page(&mut self, page_index: usize, page_table: &mut PageTable) {
+        let vaddr = self.vaddr + page_index * PAGE_SIZE_4K;
+        if self.pages[page_index].take().is_none() {
+            match swap::swap_entry(page_table, vaddr) {
+                Some(slot) => swap::free_slot(slot),
+                None => return,
+            }
+        }
+        page_table
+            .update(vaddr, Some(PhysAddr::from(0)), Some(MappingFlags::empty()))
+            .unwrap();
+        page_table
+            .map_fault(vaddr, PageSize::Size4K, self.flags)
+            .unwrap();
+    }
+
+    /// 丢弃 `[start, end)` 页（`MADV_DONTNEED`），之后访问时重新缺页：匿名页面被清零，文件页面重新从文件中读取。
+    /// You need to flush TLB after this.
+    ///
+    /// 共享的匿名映射不会被丢弃，否则会与其他进程断开共享；被锁定的区域与被固定的页面也不会被丢弃。
+    /// 共享的文件映射先将页面写回文件
+    pub(crate) async fn discard(&mut self, start: usize, end: usize, page_table: &mut PageTable) {
+        if self.shared && self.backend.is_none() || self.locked {
+            return;
+        }
+        let first = self.vaddr + start * PAGE_SIZE_4K;
+        let last = self.vaddr + end * PAGE_SIZE_4K;
+        self.split_huge_at(first, page_table);
+        self.split_huge_at(last, page_table);
+        // 整个被丢弃的大页直接解除映射
+        let huge_pages: Vec<_> = self
+            .huge_pages
+            .range(first.as_usize()..last.as_usize())
+            .copied()
+            .collect();
+        for huge_page in huge_pages {
+            let idx = (huge_page - self.vaddr.as_usize()) / PAGE_SIZE_4K;
+            // 含有被固定页面的大页拆分后逐页丢弃
+            if self.pages[idx..idx + HUGE_PAGE_PAGES]
+                .iter()
+                .flatten()
+                .any(pin::is_pinned)
+            {
+                self.split_huge_page(huge_page, page_table);
+                continue;
+            }
+            self.huge_pages.remove(&huge_page);
+            huge::unmap_huge(page_table, huge_page.into());
+            page_table
+                .map_fault_region(huge_page.into(), PAGE_SIZE_2M, self.flags)
+                .unwrap();
+            self.pages[idx..idx + HUGE_PAGE_PAGES].fill(None);
+        }
+        for idx in start..end {
+            if self.pages[idx].as_ref().is_some_and(pin::is_pinned) {
+                continue;
+            }
+            if self.shared {
+                self.sync_page_with_backend(idx).await;
+            }
+            self.reset_page(idx, page_table);
+        }
+    }
+
+    /// 将 `[start, end)` 页标记为可以释放（`MADV_FREE`），只对私有的匿名映射有效。You need to flush TLB after this.
+    ///
+    /// 已经分配的页面被去掉写权限，返回这些页面的地址，由调用者记录；被换出的页面直接释放。
+    /// 被记录的页面在下一次被写入之前可以被直接丢弃而不用换出。被固定的页面保持不变
+    pub(crate) fn lazy_free(
+        &mut self,
+        start: usize,
+        end: usize,
+        page_table: &mut PageTable,
+    ) -> Vec<usize> {
+        if !self.swappable() {
+            return Vec::new();
+        }
+        let first = (self.vaddr + start * PAGE_SIZE_4K).align_down(PAGE_SIZE_2M);
+        let last = self.vaddr + end * PAGE_SIZE_4K;
+        let huge_pages: Vec<_> = self
+            .huge_pages
+            .range(first.as_usize()..last.as_usize())
+            .copied()
+            .collect();
+        for huge_page in huge_pages {
+            self.split_huge_page(huge_page, page_table);
+        }
+        let mut freed = Vec::new();
+        for idx in start..end {
+            let vaddr = self.vaddr + idx * PAGE_SIZE_4K;
+            if let Some(page) = self.pages[idx].as_ref() {
+                if pin::is_pinned(page) {
+                    continue;
                 }
+                page_table
+                    .update(vaddr, None, Some(self.flags - MappingFlags::WRITE))
+                    .unwrap();
+                freed.push(vaddr.as_usize());
+            } else {
+                self.reset_page(idx, page_table);
             }
-            None => page.fill(0),
-        };
+        }
+        freed
+    }
+
+    /// 丢弃被标记为可以释放且之后没有被写入的第 `page_index` 页，返回是否丢弃了页面
+    ///
+    /// 仍被其他地址空间共享或者被固定的页面不会被丢弃
+    pub(crate) fn drop_lazy_free(&mut self, page_index: usize, page_table: &mut PageTable) -> bool {
+        if !self.swappable()
+            || !self.pages[page_index]
+                .as_ref()
+                .is_some_and(|page| !page_shared(page) && !pin::is_pinned(page))
+        {
+            return false;
+        }
+        debug!(
+            "drop lazily freed page {:?}",
+            self.vaddr + page_index * PAGE_SIZE_4K
+        );
+        self.reset_page(page_index, page_table);
+        true
+    }
 
-        // Map newly allocated page in the page_table
+    /// 预读 `[start, end)` 页对应的文件内容（`MADV_WILLNEED`），匿名区域返回 `None`
+    pub(crate) async fn prefetch(&self, start: usize, end: usize) -> Option<Prefetch> {
+        let backend = self.backend.as_ref()?;
+        Some(Prefetch {
+            backend: backend
+                .clone_with_delta((start * PAGE_SIZE_4K) as i64)
+                .await,
+            pages: end - start,
+        })
+    }
+
+    /// 将新分配的物理页映射为第 `page_index` 页
+    fn map_new_page(&mut self, page_index: usize, page: PhysPage, page_table: &mut PageTable) {
+        let vaddr = self.vaddr + page_index * PAGE_SIZE_4K;
+        debug!(
+            "new phys page virtual (offset) address {:?}",
+            page.start_vaddr
+        );
         page_table
             .map_overwrite(
-                addr.align_down_4k(),
+                vaddr,
                 virt_to_phys(page.start_vaddr),
-                axhal::paging::PageSize::Size4K,
+                PageSize::Size4K,
                 self.flags,
             )
             .expect("Map in page fault handler failed");
-
-        axhal::arch::flush_tlb(addr.align_down_4k().into());
+        axhal::arch::flush_tlb(Some(vaddr));
         self.pages[page_index] = Some(Arc::new(Mutex::new(page)));
-        true
     }
 
-    /// Sync pages in index back to `self.backend` (if there is one).
+    /// 将从文件中读到的页面映射为第 `page_index` 页，`write` 表示缺页是否由写操作引起
+    async fn install_file_page(
+        &mut self,
+        page_index: usize,
+        write: bool,
+        page: FilePage,
+        page_table: &mut PageTable,
+    ) -> AxResult {
+        let vaddr = self.vaddr + page_index * PAGE_SIZE_4K;
+        match page {
+            // 共享映射直接映射缓存页，写入前不给写权限以便记录脏页；
+            // 私有映射的读缺页只读地映射缓存页，写入时由写时复制复制一份
+            FilePage::Cache(page) if self.shared || !write => {
+                let map_flags = if self.shared && write {
+                    self.backend
+                        .as_mut()
+                        .unwrap()
+                        .mark_dirty(page_index * PAGE_SIZE_4K)
+                        .await;
+                    self.flags
+                } else {
+                    self.flags - MappingFlags::WRITE
+                };
+                let paddr = virt_to_phys(page.lock().await.start_vaddr);
+                page_table
+                    .map_overwrite(vaddr, paddr, PageSize::Size4K, map_flags)
+                    .expect("Map in page fault handler failed");
+                axhal::arch::flush_tlb(Some(vaddr));
+                self.pages[page_index] = Some(page);
+            }
+            FilePage::Cache(cached) => {
+                let Some(mut page) = alloc_page().await else {
+                    warn!("Error allocating new phys page for page fault");
+                    return Err(AxError::NoMemory);
+                };
+                page.as_slice_mut()
+                    .copy_from_slice(cached.lock().await.as_slice());
+                self.map_new_page(page_index, page, page_table);
+            }
+            FilePage::Private(page) => self.map_new_page(page_index, page, page_table),
+        }
+        Ok(())
+    }
+
+    /// 缺页是否需要读取交换空间或者文件，需要时返回读取的内容，由调用者在释放地址空间的锁之后完成读取
+    ///
+    /// 被换出的页面在读取期间额外引用其交换槽，读取结束后调用者需要调用 [`swap::free_slot`] 释放
+    pub(crate) async fn fault_io(
+        &mut self,
+        addr: VirtAddr,
+        flags: MappingFlags,
+        page_table: &PageTable,
+    ) -> Option<FaultIo> {
+        let page_index = (usize::from(addr) - usize::from(self.vaddr)) / PAGE_SIZE_4K;
+        if !self.flags.contains(flags) || self.pages.get(page_index)?.is_some() {
+            return None;
+        }
+        if let Some(slot) = swap::swap_entry(page_table, addr.align_down_4k()) {
+            // 交换槽的引用计数已满时在持有锁的情况下读取
+            return swap::dup_slot(slot).is_ok().then_some(FaultIo::Swap(slot));
+        }
+        let readahead = self.readahead_pages(page_index);
+        let backend = self.backend.as_mut()?;
+        let delta = page_index * PAGE_SIZE_4K;
+        let key = backend.page_key(delta).await.ok()?;
+        Some(FaultIo::File {
+            backend: backend.clone(),
+            delta,
+            key,
+            readahead,
+        })
+    }
+
+    /// 安装在释放地址空间的锁期间读到的页面
+    ///
+    /// 等待期间页面已经被其他任务换入，或者映射已经被修改时不做处理并返回 false，调用者应当重新处理缺页
+    pub(crate) async fn install_fault(
+        &mut self,
+        addr: VirtAddr,
+        flags: MappingFlags,
+        page: FaultPage,
+        page_table: &mut PageTable,
+    ) -> AxResult<bool> {
+        let page_index = (usize::from(addr) - usize::from(self.vaddr)) / PAGE_SIZE_4K;
+        if !self.flags.contains(flags) || self.pages[page_index].is_some() {
+            return Ok(false);
+        }
+        match page {
+            FaultPage::Swap { slot, page } => {
+                if swap::swap_entry(page_table, addr.align_down_4k()) != Some(slot) {
+                    return Ok(false);
+                }
+                swap::free_slot(slot);
+                self.map_new_page(page_index, page, page_table);
+            }
+            FaultPage::File { key, page } => {
+                let Some(backend) = &mut self.backend else {
+                    return Ok(false);
+                };
+                if backend.page_key(page_index * PAGE_SIZE_4K).await.ok() != Some(key) {
+                    return Ok(false);
+                }
+                self.install_file_page(
+                    page_index,
+                    flags.contains(MappingFlags::WRITE),
+                    page,
+                    page_table,
+                )
+                .await?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// 该区域在 fork 后是否采用写时复制
+    pub(crate) fn is_cow(&self) -> bool {
+        !self.shared && self.flags.contains(MappingFlags::WRITE)
+    }
+
+    /// 区域中的页面是否可以被换出：只换出私有的匿名映射，被锁定的区域除外
+    fn swappable(&self) -> bool {
+        !self.shared && self.backend.is_none() && !self.locked
+    }
+
+    /// 设置区域是否被锁定
+    pub(crate) fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    /// 区域是否被锁定
+    pub(crate) fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// 将第 `page_index` 页换出到交换空间，返回是否换出了页面
+    ///
+    /// 只换出私有匿名映射中没有被其他地址空间共享也没有被固定的页面，大页先被拆分。
+    /// 交换空间已满时返回 [`AxError::NoMemory`]
+    pub(crate) async fn swap_out(
+        &mut self,
+        page_index: usize,
+        page_table: &mut PageTable,
+    ) -> AxResult<bool> {
+        if !self.swappable()
+            || !self.pages[page_index]
+                .as_ref()
+                .is_some_and(|page| !page_shared(page) && !pin::is_pinned(page))
+        {
+            return Ok(false);
+        }
+        if !swap::enabled() {
+            return Ok(false);
+        }
+        let slot = swap::alloc_slot().ok_or(AxError::NoMemory)?;
+        if let Some(huge_page) = self.huge_page_of(page_index) {
+            self.split_huge_page(huge_page, page_table);
+        }
+        let vaddr = self.vaddr + page_index * PAGE_SIZE_4K;
+        let page = self.pages[page_index].take().unwrap();
+        // 先换成换出项，写入交换空间期间用户不能再修改页面
+        swap::set_swap_entry(page_table, vaddr, slot);
+        axhal::arch::flush_tlb(Some(vaddr));
+        let result = swap::write_slot(slot, page.lock().await.as_slice()).await;
+        if let Err(err) = result {
+            swap::free_slot(slot);
+            let paddr = virt_to_phys(page.lock().await.start_vaddr);
+            page_table
+                .map_overwrite(vaddr, paddr, PageSize::Size4K, self.flags)
+                .expect("Map in page fault handler failed");
+            self.pages[page_index] = Some(page);
+            return Err(err);
+        }
+        debug!("swap out page {:?} to slot {}", vaddr, slot);
+        Ok(true)
+    }
+
+    /// 释放 `[start, end)` 页中被换出的页面占用的交换槽，需要在解除映射之前调用
+    fn free_swap(&self, start: usize, end: usize, page_table: &PageTable) {
+        for idx in start..end {
+            if self.pages[idx].is_none() {
+                if let Some(slot) = swap::swap_entry(page_table, self.vaddr + idx * PAGE_SIZE_4K) {
+                    swap::free_slot(slot);
+                }
+            }
+        }
+    }
+
+    /// 处理写时复制的写缺页。
+    ///
+    /// 若物理页仍被其他进程或者页缓存引用，则复制一份新的物理页；否则直接恢复写权限。
+    /// 大页中的页面都不再被共享时恢复整个大页的写权限，否则先拆分大页，只复制被写入的页面。
+    async fn handle_cow_fault(
+        &mut self,
+        page_index: usize,
+        page_table: &mut PageTable,
+    ) -> AxResult {
+        if let Some(huge_page) = self.huge_page_of(page_index) {
+            let first = (huge_page - self.vaddr.as_usize()) / PAGE_SIZE_4K;
+            let exclusive = self.pages[first..first + HUGE_PAGE_PAGES]
+                .iter()
+                .all(|page| !page_shared(page.as_ref().unwrap()));
+            if exclusive {
+                debug!("COW huge page {:#x} is not shared any more", huge_page);
+                page_table
+                    .update(huge_page.into(), None, Some(self.flags))
+                    .unwrap();
+                axhal::arch::flush_tlb(Some(huge_page.into()));
+                return Ok(());
+            }
+            self.split_huge_page(huge_page, page_table);
+        }
+        let vaddr = self.vaddr + page_index * PAGE_SIZE_4K;
+        let page = self.pages[page_index].as_ref().unwrap();
+        if !page_shared(page) {
+            debug!("COW page {:?} is not shared any more", vaddr);
+            let paddr = virt_to_phys(page.lock().await.start_vaddr);
+            page_table
+                .map_overwrite(vaddr, paddr, PageSize::Size4K, self.flags)
+                .expect("Map in page fault handler failed");
+        } else {
+            debug!("copy COW page {:?}", vaddr);
+            let Some(mut new_page) = alloc_page().await else {
+                warn!("Error allocating new phys page for COW page fault");
+                return Err(AxError::NoMemory);
+            };
+            if zero::is_zero_page(page) {
+                new_page.fill(0);
+            } else {
+                unsafe {
+                    copy_nonoverlapping(
+                        page.lock().await.as_ptr(),
+                        new_page.as_mut_ptr(),
+                        PAGE_SIZE_4K,
+                    );
+                }
+            }
+            page_table
+                .map_overwrite(
+                    vaddr,
+                    virt_to_phys(new_page.start_vaddr),
+                    PageSize::Size4K,
+                    self.flags,
+                )
+                .expect("Map in page fault handler failed");
+            self.pages[page_index] = Some(Arc::new(Mutex::new(new_page)));
+        }
+        axhal::arch::flush_tlb(Some(vaddr));
+        Ok(())
+    }
+
+    /// 获取 `addr` 所在的物理页在内核中的地址，供调试器读写其他进程的地址空间
+    ///
+    /// 页面尚未分配时先分配；`write` 为真时不检查映射的写权限，但私有页面若仍被其他地址空间引用，则先复制一份
+    pub(crate) async fn page_for_foreign_access(
+        &mut self,
+        addr: VirtAddr,
+        write: bool,
+        page_table: &mut PageTable,
+    ) -> AxResult<VirtAddr> {
+        let page_index = (usize::from(addr) - usize::from(self.vaddr)) / PAGE_SIZE_4K;
+        if self.pages.get(page_index).is_none() {
+            return Err(AxError::BadAddress);
+        }
+        if self.pages[page_index].is_none() {
+            self.handle_page_fault(addr, MappingFlags::empty(), page_table)
+                .await?;
+        }
+        if write && !self.shared && page_shared(self.pages[page_index].as_ref().unwrap()) {
+            self.handle_cow_fault(page_index, page_table).await?;
+        }
+        let start = self.pages[page_index]
+            .as_ref()
+            .unwrap()
+            .lock()
+            .await
+            .start_vaddr;
+        Ok(start + addr.align_offset_4k())
+    }
+
+    /// 去掉已经分配的页面在页表中的写权限，之后的写操作会触发写时复制
+    fn write_protect(&self, page_table: &mut PageTable) {
+        let flags = self.flags - MappingFlags::WRITE;
+        for (idx, slot) in self.pages.iter().enumerate() {
+            if slot.is_some() {
+                page_table
+                    .update(self.vaddr + idx * PAGE_SIZE_4K, None, Some(flags))
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Write the page in index back to the file of `self.backend` if the area is a shared
+    /// mapping. Private mappings are never written back.
     ///
     /// # Panics
     ///
     /// Panics if index is out of bounds.
     pub async fn sync_page_with_backend(&mut self, page_index: usize) {
-        if let Some(page) = &self.pages[page_index] {
-            if let Some(backend) = &mut self.backend {
-                if backend.writable().await {
-                    let _ = backend
-                        .write_to_seek(
-                            SeekFrom::Start((page_index * PAGE_SIZE_4K) as u64),
-                            page.lock().await.as_slice(),
-                        )
-                        .await
-                        .unwrap();
+        let Some(page) = &self.pages[page_index] else {
+            debug!("Tried to sync an unallocated page");
+            return;
+        };
+        if !self.shared {
+            return;
+        }
+        if let Some(backend) = &mut self.backend {
+            match backend.writeback_page(page_index * PAGE_SIZE_4K).await {
+                Ok(true) => {}
+                // 不在页缓存中的页面直接写入文件
+                Ok(false) => {
+                    if backend.writable().await {
+                        let pos = backend.seek(SeekFrom::Current(0)).await.unwrap()
+                            + (page_index * PAGE_SIZE_4K) as u64;
+                        let _ = backend
+                            .write_to_seek(SeekFrom::Start(pos), page.lock().await.as_slice())
+                            .await;
+                    }
                 }
+                Err(err) => warn!("Failed to write back page {}: {:?}", page_index, err),
             }
-        } else {
-            debug!("Tried to sync an unallocated page");
         }
     }
 
@@ -254,7 +1013,10 @@ impl MapArea {
         }
 
         // remove (dealloc) phys pages
+        self.free_swap(0, delete_pages, page_table);
         drop(self.pages.drain(0..delete_pages));
+        let kept = self.huge_pages.split_off(&new_start.as_usize());
+        huge::unmapped(core::mem::replace(&mut self.huge_pages, kept).len());
 
         // unmap deleted pages
         page_table.unmap_region(self.vaddr, delete_size).unwrap();
@@ -271,10 +1033,16 @@ impl MapArea {
         let delete_pages = delete_size / PAGE_SIZE_4K;
 
         // remove (dealloc) phys pages
+        self.free_swap(
+            self.pages.len() - delete_pages,
+            self.pages.len(),
+            page_table,
+        );
         drop(
             self.pages
                 .drain((self.pages.len() - delete_pages)..self.pages.len()),
         );
+        huge::unmapped(self.huge_pages.split_off(&new_end.as_usize()).len());
 
         // unmap deleted pages
         page_table.unmap_region(new_end, delete_size).unwrap();
@@ -307,6 +1075,12 @@ impl MapArea {
             flags: self.flags,
             shared: self.shared,
             backend,
+            huge: self.huge,
+            dontfork: self.dontfork,
+            readahead: self.readahead,
+            locked: self.locked,
+            grows_down: self.grows_down,
+            huge_pages: self.huge_pages.split_off(&addr.as_usize()),
         }
     }
 
@@ -347,12 +1121,19 @@ impl MapArea {
             None
         };
 
+        let right_huge_pages = self.huge_pages.split_off(&end.as_usize());
         let mid = Self {
             pages: mid_pages,
             vaddr: start,
             flags: self.flags,
             shared: self.shared,
             backend: mid_backend,
+            huge: self.huge,
+            dontfork: self.dontfork,
+            readahead: self.readahead,
+            locked: self.locked,
+            grows_down: self.grows_down,
+            huge_pages: self.huge_pages.split_off(&start.as_usize()),
         };
 
         let right_backend = if let Some(backend) = self.backend.as_ref() {
@@ -374,6 +1155,12 @@ impl MapArea {
             flags: self.flags,
             shared: self.shared,
             backend: right_backend,
+            huge: self.huge,
+            dontfork: self.dontfork,
+            readahead: self.readahead,
+            locked: self.locked,
+            grows_down: self.grows_down,
+            huge_pages: right_huge_pages,
         };
 
         (mid, right)
@@ -399,6 +1186,8 @@ impl MapArea {
         let delete_range = ((left_end.as_usize() - self.vaddr.as_usize()) / PAGE_SIZE_4K)
             ..((right_start.as_usize() - self.vaddr.as_usize()) / PAGE_SIZE_4K);
 
+        self.free_swap(delete_range.start, delete_range.end, page_table);
+
         // create a right area
         let pages = self
             .pages
@@ -424,10 +1213,17 @@ impl MapArea {
             flags: self.flags,
             shared: self.shared,
             backend: right_backend,
+            huge: self.huge,
+            dontfork: self.dontfork,
+            readahead: self.readahead,
+            locked: self.locked,
+            grows_down: self.grows_down,
+            huge_pages: self.huge_pages.split_off(&right_start.as_usize()),
         };
 
         // remove pages
         let _ = self.pages.drain(delete_range);
+        huge::unmapped(self.huge_pages.split_off(&left_end.as_usize()).len());
 
         page_table.unmap_region(left_end, delete_size).unwrap();
 
@@ -488,20 +1284,54 @@ impl MapArea {
 
     /// Update area's mapping flags and write it to page table. You need to flush TLB after calling
     /// this function.
+    ///
+    /// 仍被其他进程共享的写时复制页面与共享文件映射的页面不会获得写权限。
     pub fn update_flags(&mut self, flags: MappingFlags, page_table: &mut PageTable) {
         self.flags = flags;
+        // 换出项不能被改成有效的映射
+        let swapped: Vec<_> = (0..self.pages.len())
+            .filter(|&idx| self.pages[idx].is_none())
+            .filter_map(|idx| {
+                let vaddr = self.vaddr + idx * PAGE_SIZE_4K;
+                swap::swap_entry(page_table, vaddr).map(|slot| (vaddr, slot))
+            })
+            .collect();
         page_table
             .update_region(self.vaddr, self.size(), flags)
             .unwrap();
+        for (vaddr, slot) in swapped {
+            swap::set_swap_entry(page_table, vaddr, slot);
+        }
+        // 共享的文件映射在页面被写入之前保持只读，以便记录脏页
+        let file_shared = self.shared && self.backend.is_some();
+        if self.is_cow() || file_shared {
+            for (idx, slot) in self.pages.iter().enumerate() {
+                if slot
+                    .as_ref()
+                    .is_some_and(|page| file_shared || page_shared(page))
+                {
+                    page_table
+                        .update(
+                            self.vaddr + idx * PAGE_SIZE_4K,
+                            None,
+                            Some(flags - MappingFlags::WRITE),
+                        )
+                        .unwrap();
+                }
+            }
+        }
     }
     /// # Clone the area.
     ///
-    /// If the area is shared, we don't need to allocate new phys pages.
+    /// If the area is shared, the child process maps the same phys pages.
     ///
-    /// If the area is not shared and all the pages have been allocated,
-    /// we can allocate a contiguous area in phys memory.
+    /// Otherwise the allocated pages are shared copy-on-write: they are mapped read-only in both
+    /// the child and the parent page table, and copied on the first write page fault. Pages that
+    /// have not been allocated stay lazy in the child, and pages that have been swapped out share
+    /// the swap slot with the child. Huge pages are mapped as huge pages in the child as well.
     ///
-    /// This function will modify the page table as well.
+    /// This function will modify the page tables as well. You need to flush TLB of the parent
+    /// after this function.
     ///
     /// # Arguments
     ///
@@ -530,86 +1360,69 @@ impl MapArea {
                 .collect();
             for vaddr in fault_pages {
                 self.handle_page_fault(vaddr, MappingFlags::empty(), parent_page_table)
-                    .await;
+                    .await?;
             }
+        }
 
-            // Map the area in the child page table.
-            let mut pages = Vec::new();
-            for (idx, slot) in self.pages.iter().enumerate() {
-                let vaddr = self.vaddr + (idx * PAGE_SIZE_4K);
-                assert!(slot.is_some());
-                let page = slot.as_ref().unwrap().lock().await;
-                page_table
-                    .map(
-                        vaddr,
-                        virt_to_phys(page.start_vaddr),
-                        PageSize::Size4K,
-                        self.flags,
-                    )
-                    .unwrap();
-                drop(page);
-                pages.push(Some(Arc::clone(slot.as_ref().unwrap())));
-            }
-            return Ok(Self {
-                pages,
-                vaddr: self.vaddr,
-                flags: self.flags,
-                shared: self.shared,
-                backend: self.backend.clone(),
-            });
-        }
-        // All the pages have been allocated. Allocate a contiguous area in phys memory.
-        if self.allocated() {
-            MapArea::new_alloc(
-                self.vaddr,
-                self.pages.len(),
-                self.flags,
-                Some(unsafe { self.as_slice() }),
-                self.backend.clone(),
-                page_table,
-            )
-            .await
+        // 写时复制的页面在父子进程中都去掉写权限，共享文件映射的页面在子进程中写入时再记录脏页
+        let child_flags = if self.is_cow() {
+            self.write_protect(parent_page_table);
+            self.flags - MappingFlags::WRITE
+        } else if self.backend.is_some() {
+            self.flags - MappingFlags::WRITE
         } else {
-            let mut pages = Vec::new();
-            for (idx, slot) in self.pages.iter().enumerate() {
-                let vaddr = self.vaddr + (idx * PAGE_SIZE_4K);
-                match slot.as_ref() {
-                    Some(page) => {
-                        let mut new_page = PhysPage::alloc().unwrap();
-                        unsafe {
-                            copy_nonoverlapping(
-                                page.lock().await.as_ptr(),
-                                new_page.as_mut_ptr(),
-                                PAGE_SIZE_4K,
-                            );
-                        }
-
-                        page_table
-                            .map(
-                                vaddr,
-                                virt_to_phys(new_page.start_vaddr),
-                                PageSize::Size4K,
-                                self.flags,
-                            )
-                            .unwrap();
+            self.flags
+        };
 
-                        pages.push(Some(Arc::new(Mutex::new(new_page))));
-                    }
-                    None => {
-                        page_table
+        let mut pages = Vec::with_capacity(self.pages.len());
+        // 大页在子进程中同样以大页映射
+        for &huge_page in self.huge_pages.iter() {
+            let idx = (huge_page - self.vaddr.as_usize()) / PAGE_SIZE_4K;
+            let paddr = virt_to_phys(self.pages[idx].as_ref().unwrap().lock().await.start_vaddr);
+            huge::map_huge(page_table, huge_page.into(), paddr, child_flags);
+        }
+        for (idx, slot) in self.pages.iter().enumerate() {
+            let vaddr = self.vaddr + (idx * PAGE_SIZE_4K);
+            if self.huge_page_of(idx).is_some() {
+                pages.push(slot.clone());
+                continue;
+            }
+            match slot {
+                Some(page) => {
+                    let paddr = virt_to_phys(page.lock().await.start_vaddr);
+                    page_table
+                        .map(vaddr, paddr, PageSize::Size4K, child_flags)
+                        .unwrap();
+                    pages.push(Some(Arc::clone(page)));
+                }
+                None => {
+                    // 被换出的页面与子进程共享交换槽
+                    match swap::swap_entry(parent_page_table, vaddr) {
+                        Some(slot) => {
+                            swap::dup_slot(slot)?;
+                            swap::set_swap_entry(page_table, vaddr, slot);
+                        }
+                        None => page_table
                             .map_fault(vaddr, PageSize::Size4K, self.flags)
-                            .unwrap();
-                        pages.push(None);
+                            .unwrap(),
                     }
+                    pages.push(None);
                 }
             }
-            Ok(Self {
-                pages,
-                vaddr: self.vaddr,
-                flags: self.flags,
-                shared: self.shared,
-                backend: self.backend.clone(),
-            })
         }
+        Ok(Self {
+            pages,
+            vaddr: self.vaddr,
+            flags: self.flags,
+            shared: self.shared,
+            backend: self.backend.clone(),
+            huge: self.huge,
+            dontfork: self.dontfork,
+            readahead: self.readahead,
+            // 子进程不继承锁定
+            locked: false,
+            grows_down: self.grows_down,
+            huge_pages: self.huge_pages.clone(),
+        })
     }
 }
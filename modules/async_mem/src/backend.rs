@@ -1,6 +1,10 @@
 use alloc::boxed::Box;
-use async_fs::api::{File, FileExt};
+use async_fs::{
+    api::{File, FileExt},
+    page_cache::{CachePage, PAGE_SIZE},
+};
 use async_io::{AsyncRead, AsyncSeek, Seek, SeekFrom};
+use axerrno::AxResult;
 use core::{
     pin::Pin,
     task::{Context, Poll},
@@ -12,6 +16,8 @@ pub type BackEndFile = FileExt;
 /// `MemBackend` won't share a file with other things, so we use a `Box` here.
 pub struct MemBackend {
     file: BackEndFile,
+    /// The file content at and beyond this offset is read as zeros.
+    end: u64,
 }
 
 impl MemBackend {
@@ -19,7 +25,119 @@ impl MemBackend {
     pub async fn new(mut file: BackEndFile, offset: u64) -> Self {
         let _ = file.seek(SeekFrom::Start(offset)).await.unwrap();
 
-        Self { file }
+        Self {
+            file,
+            end: u64::MAX,
+        }
+    }
+
+    /// Create a new `MemBackend` which only maps `len` bytes of the file from `offset`. The
+    /// rest of the area is filled with zeros, like the bss part of an ELF segment.
+    pub async fn new_with_len(file: BackEndFile, offset: u64, len: u64) -> Self {
+        let mut backend = Self::new(file, offset).await;
+        backend.end = offset + len;
+        backend
+    }
+
+    fn file(&self) -> Option<&File> {
+        self.file.inner.as_any().downcast_ref::<File>()
+    }
+
+    /// The index in the page cache of the page `delta` bytes after the current offset. Return
+    /// `None` if the page is not page aligned in the file or not entirely within the mapped
+    /// length, such a page can't be shared with the page cache.
+    async fn cache_index(&mut self, delta: usize) -> Option<u64> {
+        let pos = self.seek(SeekFrom::Current(0)).await.ok()? + delta as u64;
+        if pos % PAGE_SIZE as u64 != 0 || pos.saturating_add(PAGE_SIZE as u64) > self.end {
+            return None;
+        }
+        Some(pos / PAGE_SIZE as u64)
+    }
+
+    /// The identifier of the file node and the file offset `delta` bytes after the current
+    /// offset, which tells whether two backends map the same page of the same file.
+    pub async fn page_key(&mut self, delta: usize) -> AxResult<(usize, u64)> {
+        let pos = self.seek(SeekFrom::Current(0)).await? + delta as u64;
+        Ok((self.file().map_or(0, File::node_id), pos))
+    }
+
+    /// The page `delta` bytes after the current offset in the page cache. Return `None` if the
+    /// page can't be shared with the page cache, or the file is not a regular file.
+    pub async fn cache_page(&mut self, delta: usize) -> AxResult<Option<CachePage>> {
+        match self.cache_index(delta).await {
+            Some(index) => self.file().unwrap().cache_page(index).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Read up to `pages` pages from `delta` bytes after the current offset into the page cache,
+    /// so that later page faults on them don't wait for the file system. Stop at the end of the
+    /// file or at the first page that can't be shared with the page cache.
+    pub async fn readahead(&mut self, delta: usize, pages: usize) {
+        if pages == 0 {
+            return;
+        }
+        let size = match self.file() {
+            Some(file) => match file.get_attr().await {
+                Ok(attr) => attr.size(),
+                Err(_) => return,
+            },
+            None => return,
+        };
+        for i in 0..pages {
+            let Some(index) = self.cache_index(delta + i * PAGE_SIZE).await else {
+                return;
+            };
+            if index * PAGE_SIZE as u64 >= size {
+                return;
+            }
+            if let Err(err) = self.file().unwrap().cache_page(index).await {
+                debug!("readahead of page {} failed: {:?}", index, err);
+                return;
+            }
+        }
+    }
+
+    /// Mark the cached page `delta` bytes after the current offset dirty.
+    pub async fn mark_dirty(&mut self, delta: usize) {
+        if let Some(index) = self.cache_index(delta).await {
+            self.file().unwrap().mark_page_dirty(index);
+        }
+    }
+
+    /// Write the cached page `delta` bytes after the current offset back to the file if it is
+    /// dirty. Return `false` if the page is not shared with the page cache.
+    pub async fn writeback_page(&mut self, delta: usize) -> AxResult<bool> {
+        match self.cache_index(delta).await {
+            Some(index) => {
+                self.file()
+                    .unwrap()
+                    .writeback_pages(index, index + 1)
+                    .await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Read a page `delta` bytes after the current offset into `buf`, the part beyond the end of
+    /// the file or the mapped length is filled with zeros.
+    pub async fn read_page(&mut self, delta: usize, buf: &mut [u8]) -> AxResult {
+        let pos = self.seek(SeekFrom::Current(0)).await? + delta as u64;
+        let len = (self.end.saturating_sub(pos) as usize).min(buf.len());
+        let mut read = 0;
+        while read < len {
+            let n = self
+                .file
+                .read_from_seek(SeekFrom::Start(pos + read as u64), &mut buf[read..len])
+                .await?;
+            if n == 0 {
+                break;
+            }
+            read += n;
+        }
+        buf[read..].fill(0);
+        Ok(())
     }
 
     /// clone a new `MemBackend` with a delta offset of the file of the original `MemBackend`.
@@ -63,10 +181,7 @@ impl MemBackend {
 impl Clone for MemBackend {
     fn clone(&self) -> Self {
         let file = self
-            .file
-            .inner
-            .as_any()
-            .downcast_ref::<File>()
+            .file()
             .expect("Cloning a MemBackend with a non-file object")
             .clone();
 
@@ -74,6 +189,7 @@ impl Clone for MemBackend {
             file: FileExt {
                 inner: Box::new(file),
             },
+            end: self.end,
         }
     }
 }
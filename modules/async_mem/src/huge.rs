@@ -0,0 +1,115 @@
+//! 匿名映射的 2 MiB 大页。
+//!
+//! 使用 `MAP_HUGETLB` 映射或者通过 `madvise(MADV_HUGEPAGE)` 标记的匿名区域，在缺页时若缺页地址所在的
+//! 2 MiB 对齐的范围完全位于区域之内且其中还没有任何页面，则分配 512 个连续的物理页，并用一个大页页表项映射。
+//! 分配失败时退回普通页面。
+//!
+//! 区域仍然按 4 KiB 记录物理页，因此拆分大页只需要把大页页表项换成 512 个普通页表项，不需要复制数据。
+//! 部分 munmap、mprotect、写时复制以及换出之前都会先拆分所在的大页。
+use alloc::vec::Vec;
+use axalloc::{global_allocator, PhysPage};
+use axhal::{
+    mem::{PhysAddr, VirtAddr, PAGE_SIZE_4K},
+    paging::{MappingFlags, PageSize, PageTable},
+};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// 大页的大小
+pub const PAGE_SIZE_2M: usize = 0x20_0000;
+
+/// 一个大页包含的普通页数
+pub(crate) const HUGE_PAGE_PAGES: usize = PAGE_SIZE_2M / PAGE_SIZE_4K;
+
+static FAULT_ALLOC: AtomicUsize = AtomicUsize::new(0);
+static FAULT_FALLBACK: AtomicUsize = AtomicUsize::new(0);
+static SPLIT: AtomicUsize = AtomicUsize::new(0);
+static MAPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// 大页的统计信息
+#[derive(Debug, Clone, Copy)]
+pub struct HugePageStats {
+    /// 缺页时分配的大页数
+    pub fault_alloc: usize,
+    /// 缺页时分配大页失败而退回普通页的次数
+    pub fault_fallback: usize,
+    /// 被拆分的大页数
+    pub split: usize,
+    /// 当前以大页映射的内存大小，单位为字节。fork 之后父子进程各自计算一次
+    pub mapped: usize,
+}
+
+/// 获取大页的统计信息
+pub fn stats() -> HugePageStats {
+    HugePageStats {
+        fault_alloc: FAULT_ALLOC.load(Ordering::Relaxed),
+        fault_fallback: FAULT_FALLBACK.load(Ordering::Relaxed),
+        split: SPLIT.load(Ordering::Relaxed),
+        mapped: MAPPED.load(Ordering::Relaxed) * PAGE_SIZE_2M,
+    }
+}
+
+/// 为缺页分配一个大页，返回清零后的 512 个连续的物理页
+pub(crate) fn alloc_huge() -> Option<Vec<PhysPage>> {
+    let pages = PhysPage::alloc_contiguous(HUGE_PAGE_PAGES, PAGE_SIZE_2M, None)
+        .ok()
+        .and_then(|pages| pages.into_iter().collect::<Option<Vec<_>>>());
+    let Some(mut pages) = pages else {
+        debug!("no contiguous memory for a huge page, fall back to 4K pages");
+        FAULT_FALLBACK.fetch_add(1, Ordering::Relaxed);
+        return None;
+    };
+    for page in pages.iter_mut() {
+        page.fill(0);
+    }
+    FAULT_ALLOC.fetch_add(1, Ordering::Relaxed);
+    Some(pages)
+}
+
+/// 用一个大页页表项将 `vaddr` 映射到 `paddr`
+///
+/// 原来覆盖这一范围的普通页表项所在的页表页会被释放，调用者需要保证其中没有仍在使用的页表项
+pub(crate) fn map_huge(
+    page_table: &mut PageTable,
+    vaddr: VirtAddr,
+    paddr: PhysAddr,
+    flags: MappingFlags,
+) {
+    let table = page_table
+        .get_entry_mut(vaddr)
+        .ok()
+        .filter(|(_, size)| *size == PageSize::Size4K)
+        .map(|(entry, _)| (entry as *mut _ as usize) & !(PAGE_SIZE_4K - 1));
+    page_table
+        .map_overwrite(vaddr, paddr, PageSize::Size2M, flags)
+        .expect("Map huge page failed");
+    if let Some(table) = table {
+        global_allocator().dealloc_pages(table, 1);
+    }
+    axhal::arch::flush_tlb(Some(vaddr));
+    MAPPED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 将 `vaddr` 处的大页拆分成普通页，保留原来的权限
+pub(crate) fn split_huge(page_table: &mut PageTable, vaddr: VirtAddr) {
+    let (_, flags, _) = page_table.query(vaddr).expect("Huge page not mapped");
+    let (paddr, size) = page_table.unmap(vaddr).expect("Huge page not mapped");
+    assert_eq!(size, PageSize::Size2M);
+    page_table
+        .map_region(vaddr, paddr, PAGE_SIZE_2M, flags, false)
+        .expect("Split huge page failed");
+    axhal::arch::flush_tlb(Some(vaddr));
+    debug!("split huge page {:?}", vaddr);
+    SPLIT.fetch_add(1, Ordering::Relaxed);
+    MAPPED.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// 解除 `vaddr` 处的大页的映射
+pub(crate) fn unmap_huge(page_table: &mut PageTable, vaddr: VirtAddr) {
+    page_table.unmap(vaddr).expect("Huge page not mapped");
+    MAPPED.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// 记录 `count` 个大页随区域一起被解除映射
+pub(crate) fn unmapped(count: usize) {
+    MAPPED.fetch_sub(count, Ordering::Relaxed);
+}
@@ -0,0 +1,157 @@
+//! System V IPC 对象共用的键空间与权限。
+//!
+//! 共享内存、消息队列与信号量集各自有一个从键到标识符的映射，按照相同的规则通过
+//! `IPC_PRIVATE`、`IPC_CREAT` 与 `IPC_EXCL` 查找或者创建对象。以 `IPC_PRIVATE` 创建的对象不记录在键空间中。
+//! 被 IPC_RMID 删除的对象立即从键空间中移除，之后以相同的键会创建新的对象。
+use alloc::collections::BTreeMap;
+use axerrno::{AxError, AxResult};
+use sync::SpinNoIrq;
+
+/// 总是创建新对象的键
+pub const IPC_PRIVATE: i32 = 0;
+/// 键不存在时创建新的对象
+pub const IPC_CREAT: i32 = 0o1000;
+/// 与 `IPC_CREAT` 一起使用，键已经存在时失败
+pub const IPC_EXCL: i32 = 0o2000;
+/// 操作需要等待时直接返回错误
+pub const IPC_NOWAIT: i32 = 0o4000;
+
+/// 删除对象
+pub const IPC_RMID: i32 = 0;
+/// 设置对象的所有者与权限
+pub const IPC_SET: i32 = 1;
+/// 获取对象的状态
+pub const IPC_STAT: i32 = 2;
+
+/// 从键到标识符的映射
+pub type IpcKeys = SpinNoIrq<BTreeMap<i32, i32>>;
+
+/// 按照 `key` 与 `flags` 查找对象的标识符，需要创建时调用 `create` 创建对象并返回其标识符。
+/// 返回的第二项表示对象是否为这次新建的，新建的对象不需要再检查访问权限
+///
+/// 键已经存在时，同时给出 `IPC_CREAT` 与 `IPC_EXCL` 返回 `AlreadyExists`；
+/// 键不存在且没有给出 `IPC_CREAT` 时返回 `NotFound`
+pub fn ipc_get(
+    keys: &IpcKeys,
+    key: i32,
+    flags: i32,
+    create: impl FnOnce() -> AxResult<i32>,
+) -> AxResult<(i32, bool)> {
+    if key == IPC_PRIVATE {
+        return Ok((create()?, true));
+    }
+    let mut keys = keys.lock();
+    match keys.get(&key) {
+        Some(_) if flags & IPC_CREAT != 0 && flags & IPC_EXCL != 0 => Err(AxError::AlreadyExists),
+        Some(&id) => Ok((id, false)),
+        None if flags & IPC_CREAT != 0 => {
+            let id = create()?;
+            keys.insert(key, id);
+            Ok((id, true))
+        }
+        None => Err(AxError::NotFound),
+    }
+}
+
+/// 将标识符为 `id` 的对象从键空间中移除
+pub fn ipc_remove(keys: &IpcKeys, id: i32) {
+    keys.lock().retain(|_, &mut value| value != id);
+}
+
+/// IPC 对象的键、所有者与权限
+#[derive(Debug, Clone, Copy)]
+pub struct IpcPerm {
+    pub key: i32,
+    pub uid: u32,
+    pub gid: u32,
+    /// 创建者的用户 id
+    pub cuid: u32,
+    /// 创建者的组 id
+    pub cgid: u32,
+    /// 低 9 位为访问权限
+    pub mode: u16,
+}
+
+impl IpcPerm {
+    /// 由创建者的用户与组创建，`mode` 只保留低 9 位
+    pub fn new(key: i32, uid: u32, gid: u32, mode: u16) -> Self {
+        Self {
+            key,
+            uid,
+            gid,
+            cuid: uid,
+            cgid: gid,
+            mode: mode & 0o777,
+        }
+    }
+
+    /// 有效用户 `uid`、有效组 `gid` 与附加组 `groups` 是否具有 `access` 权限，
+    /// `access` 为 0o4（读）与 0o2（写）的组合
+    pub fn permitted(&self, uid: u32, gid: u32, groups: &[u32], access: u16) -> bool {
+        if uid == 0 {
+            return true;
+        }
+        let mode = if uid == self.uid || uid == self.cuid {
+            self.mode >> 6
+        } else if [self.gid, self.cgid]
+            .iter()
+            .any(|owner| *owner == gid || groups.contains(owner))
+        {
+            self.mode >> 3
+        } else {
+            self.mode
+        };
+        mode & access == access
+    }
+
+    /// 用户 `uid` 能否修改或者删除对象，只有所有者、创建者与 root 可以
+    pub fn is_owner(&self, uid: u32) -> bool {
+        uid == 0 || uid == self.uid || uid == self.cuid
+    }
+
+    /// IPC_SET：修改所有者与访问权限
+    pub fn set(&mut self, uid: u32, gid: u32, mode: u16) {
+        self.uid = uid;
+        self.gid = gid;
+        self.mode = mode & 0o777;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IpcPerm;
+
+    #[test]
+    fn test_permitted_by_class() {
+        let perm = IpcPerm::new(1, 1000, 100, 0o640);
+        // 所有者可读写
+        assert!(perm.permitted(1000, 200, &[], 0o6));
+        // 同组用户只能读
+        assert!(perm.permitted(2000, 100, &[], 0o4));
+        assert!(!perm.permitted(2000, 100, &[], 0o2));
+        // 附加组同样按照组权限检查
+        assert!(perm.permitted(2000, 200, &[100], 0o4));
+        // 其他用户没有任何权限
+        assert!(!perm.permitted(2000, 200, &[], 0o4));
+        // root 不受限制
+        assert!(perm.permitted(0, 0, &[], 0o6));
+    }
+
+    #[test]
+    fn test_permitted_by_creator() {
+        let mut perm = IpcPerm::new(1, 1000, 100, 0o600);
+        perm.set(2000, 200, 0o600);
+        // 所有者被修改之后，创建者仍然按照所有者的权限检查
+        assert!(perm.permitted(1000, 300, &[], 0o6));
+        assert!(perm.permitted(2000, 300, &[], 0o6));
+        assert!(!perm.permitted(3000, 300, &[], 0o4));
+        assert!(perm.is_owner(1000));
+        assert!(!perm.is_owner(3000));
+    }
+
+    #[test]
+    fn test_mode_is_masked() {
+        let perm = IpcPerm::new(1, 1000, 100, 0o1666);
+        assert_eq!(0o666, perm.mode);
+    }
+}
@@ -2,23 +2,37 @@
 #![cfg_attr(not(test), no_std)]
 mod area;
 mod backend;
+pub mod huge;
+pub mod ipc;
+pub mod msg;
+mod pin;
+pub mod sem;
 mod shared;
-pub use area::MapArea;
+pub mod swap;
+mod zero;
+use area::FaultPage;
+pub use area::{MapArea, Prefetch};
 use axerrno::{AxError, AxResult};
 pub use backend::{BackEndFile, MemBackend};
+pub use pin::PinnedPages;
 
 extern crate alloc;
-use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
-use core::sync::atomic::{AtomicI32, Ordering};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+use huge::PAGE_SIZE_2M;
 use page_table_entry::GenericPTE;
 use shared::SharedMem;
-use spinlock::SpinNoIrq;
+use sync::{Mutex, SpinNoIrq};
 #[macro_use]
 extern crate log;
 
 use axhal::{
     arch::flush_tlb,
-    mem::{memory_regions, phys_to_virt, PhysAddr, VirtAddr, PAGE_SIZE_4K},
+    mem::{memory_regions, phys_to_virt, virt_to_phys, PhysAddr, VirtAddr, PAGE_SIZE_4K},
     paging::{MappingFlags, PageSize, PageTable, PagingError},
 };
 
@@ -34,7 +48,40 @@ static SHMID: AtomicI32 = AtomicI32::new(1);
 pub static SHARED_MEMS: SpinNoIrq<BTreeMap<i32, Arc<SharedMem>>> = SpinNoIrq::new(BTreeMap::new());
 
 /// The map from key to shmid. It's used to query shmid from key.
-pub static KEY_TO_SHMID: SpinNoIrq<BTreeMap<i32, i32>> = SpinNoIrq::new(BTreeMap::new());
+pub static KEY_TO_SHMID: ipc::IpcKeys = SpinNoIrq::new(BTreeMap::new());
+
+/// 向下增长的栈区域与其下方的映射之间至少保留的间隔。
+///
+/// 栈不能扩展到这一间隔之内，没有给出地址的 mmap 也不会把新的映射放在这里
+pub const STACK_GUARD_GAP: usize = 256 * PAGE_SIZE_4K;
+
+/// 一次 `MADV_WILLNEED` 最多预读的页数
+const MAX_PREFETCH_PAGES: usize = 1024;
+
+/// madvise 给出的使用建议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advice {
+    /// 恢复默认的预读页数
+    Normal,
+    /// 随机访问，文件映射缺页时不预读
+    Random,
+    /// 顺序访问，文件映射缺页时预读更多页面
+    Sequential,
+    /// 即将访问，在后台预读文件映射的内容
+    WillNeed,
+    /// 丢弃页面，之后访问时重新缺页
+    DontNeed,
+    /// 页面在下一次被写入之前可以被直接回收
+    Free,
+    /// fork 时子进程不继承这些映射
+    DontFork,
+    /// 取消 [`Advice::DontFork`]
+    DoFork,
+    /// 缺页时尝试使用大页
+    HugePage,
+    /// 缺页时不再使用大页
+    NoHugePage,
+}
 
 /// PageTable + MemoryArea for a process (task)
 pub struct MemorySet {
@@ -43,6 +90,26 @@ pub struct MemorySet {
 
     private_mem: BTreeMap<i32, Arc<SharedMem>>,
     attached_mem: Vec<(VirtAddr, MappingFlags, Arc<SharedMem>)>,
+
+    /// 地址空间的最大大小（RLIMIT_AS），由所属进程设置
+    as_limit: usize,
+    /// 私有可写映射的最大大小（RLIMIT_DATA），由所属进程设置
+    data_limit: usize,
+    /// 向下增长的栈区域的最大大小（RLIMIT_STACK），由所属进程设置
+    stack_limit: usize,
+    /// 没有给出地址的 mmap 从这里开始查找空闲区域，由加载器随机化
+    mmap_base: VirtAddr,
+
+    /// 最近被访问过的页面，换出时获得第二次机会
+    young: BTreeSet<usize>,
+    /// 被标记为可以释放（`MADV_FREE`）且之后没有被写入的页面，回收时直接丢弃
+    lazy_free: BTreeSet<usize>,
+    /// mlockall(MCL_FUTURE) 之后新的映射也被锁定，记录是否预先分配页面
+    future_lock: Option<bool>,
+    /// 被驱动固定的页数
+    pinned: Arc<AtomicUsize>,
+    /// 换出页面的时钟指针，下一次从这个地址开始扫描
+    clock_hand: usize,
 }
 
 impl MemorySet {
@@ -58,6 +125,15 @@ impl MemorySet {
             owned_mem: BTreeMap::new(),
             private_mem: BTreeMap::new(),
             attached_mem: Vec::new(),
+            as_limit: usize::MAX,
+            data_limit: usize::MAX,
+            stack_limit: usize::MAX,
+            mmap_base: axconfig::USER_MEMORY_START.into(),
+            young: BTreeSet::new(),
+            lazy_free: BTreeSet::new(),
+            future_lock: None,
+            pinned: Arc::new(AtomicUsize::new(0)),
+            clock_hand: 0,
         }
     }
 
@@ -90,6 +166,15 @@ impl MemorySet {
             owned_mem: BTreeMap::new(),
             private_mem: BTreeMap::new(),
             attached_mem: Vec::new(),
+            as_limit: usize::MAX,
+            data_limit: usize::MAX,
+            stack_limit: usize::MAX,
+            mmap_base: axconfig::USER_MEMORY_START.into(),
+            young: BTreeSet::new(),
+            lazy_free: BTreeSet::new(),
+            future_lock: None,
+            pinned: Arc::new(AtomicUsize::new(0)),
+            clock_hand: 0,
         }
     }
 
@@ -106,6 +191,11 @@ impl MemorySet {
             .unwrap_or_default()
     }
 
+    /// The areas owned by this memory set, in ascending order of address.
+    pub fn areas(&self) -> impl Iterator<Item = &MapArea> {
+        self.owned_mem.values()
+    }
+
     /// Allocate contiguous region. If no data, it will create a lazy load region.
     pub async fn new_region(
         &mut self,
@@ -189,6 +279,9 @@ impl MemorySet {
 
         // Modify areas and insert it back to BTree.
         for (_, mut area) in overlapped_area {
+            // 跨越边界的大页先拆分成普通页
+            area.split_huge_at(start, &mut self.page_table);
+            area.split_huge_at(end, &mut self.page_table);
             if area.contained_in(start, end) {
                 info!("  drop [{:?}, {:?})", area.vaddr, area.end_va());
                 area.dealloc(&mut self.page_table);
@@ -241,7 +334,15 @@ impl MemorySet {
         let mut segments: Vec<_> = self
             .owned_mem
             .iter()
-            .map(|(start, mem)| (*start, *start + mem.size()))
+            .map(|(start, mem)| {
+                // 向下增长的栈区域下方保留间隔
+                let gap = if mem.is_grows_down() {
+                    STACK_GUARD_GAP
+                } else {
+                    0
+                };
+                (start.saturating_sub(gap), *start + mem.size())
+            })
             .collect();
         segments.extend(
             self.attached_mem
@@ -255,12 +356,150 @@ impl MemorySet {
             if last_end + size <= start {
                 return Some(last_end.into());
             }
-            last_end = end;
+            last_end = last_end.max(end);
         }
 
         None
     }
 
+    /// Set the address from which mmap without an address hint searches for a free area.
+    pub fn set_mmap_base(&mut self, mmap_base: VirtAddr) {
+        self.mmap_base = mmap_base;
+    }
+
+    /// Set the limits of the total size and the private writable size of the mapped areas, and
+    /// the size up to which a stack area may grow.
+    pub fn set_limits(&mut self, as_limit: usize, data_limit: usize, stack_limit: usize) {
+        self.as_limit = as_limit;
+        self.data_limit = data_limit;
+        self.stack_limit = stack_limit;
+    }
+
+    /// 将起始于 `start` 的匿名区域标记为向下增长的栈区域（`MAP_GROWSDOWN`）
+    pub fn set_grows_down(&mut self, start: VirtAddr) {
+        if let Some(area) = self.owned_mem.get_mut(&start.as_usize()) {
+            area.set_grows_down(true);
+        }
+    }
+
+    /// 若 `addr` 位于某个向下增长的栈区域的下方，则向下扩展该区域以包含 `addr`，返回是否扩展了区域
+    ///
+    /// 扩展后区域的大小不能超过 RLIMIT_STACK，与下方的映射之间至少保留 [`STACK_GUARD_GAP`]，
+    /// 并且地址空间的大小不能超过 RLIMIT_AS。否则访问 `addr` 是真正的栈溢出
+    fn grow_stack(&mut self, addr: VirtAddr) -> bool {
+        let addr = addr.align_down_4k();
+        let Some((&start, area)) = self.owned_mem.range(addr.as_usize() + 1..).next() else {
+            return false;
+        };
+        if !area.is_grows_down() {
+            return false;
+        }
+        let prev_end = self
+            .owned_mem
+            .range(..start)
+            .next_back()
+            .map(|(_, area)| area.end_va().as_usize())
+            .into_iter()
+            .chain(
+                self.attached_mem
+                    .iter()
+                    .map(|(vaddr, _, mem)| vaddr.as_usize() + mem.size())
+                    .filter(|&end| end <= start),
+            )
+            .max()
+            .unwrap_or(0);
+        // `addr` 已经被映射
+        if prev_end > addr.as_usize() {
+            return false;
+        }
+        if addr.as_usize() < prev_end.saturating_add(STACK_GUARD_GAP) {
+            warn!(
+                "stack at {:#x} overflows into the guard gap at {:?}",
+                start, addr
+            );
+            return false;
+        }
+        let grown = start - addr.as_usize();
+        if area.size() + grown > self.stack_limit
+            || self.mapped_size(false, None) + grown > self.as_limit
+        {
+            warn!("stack at {:#x} exceeds its limit at {:?}", start, addr);
+            return false;
+        }
+        let mut area = self.owned_mem.remove(&start).unwrap();
+        debug!(
+            "grow stack [{:?}, {:?}) down to {:?}",
+            area.vaddr,
+            area.end_va(),
+            addr
+        );
+        area.grow_down(addr, &mut self.page_table);
+        self.owned_mem.insert(addr.as_usize(), area);
+        true
+    }
+
+    /// 已经映射的区域（包括共享内存）的大小之和，`data` 为真时只统计私有可写的区域。
+    ///
+    /// 若给定了 `range`，则只统计与其重叠的部分。
+    fn mapped_size(&self, data: bool, range: Option<(VirtAddr, VirtAddr)>) -> usize {
+        let overlap = |start: VirtAddr, end: VirtAddr| match range {
+            Some((range_start, range_end)) => {
+                let start = start.max(range_start).as_usize();
+                let end = end.min(range_end).as_usize();
+                end.saturating_sub(start)
+            }
+            None => end.as_usize() - start.as_usize(),
+        };
+        let owned: usize = self
+            .owned_mem
+            .values()
+            .filter(|area| !data || area.is_cow())
+            .map(|area| overlap(area.vaddr, area.end_va()))
+            .sum();
+        if data {
+            return owned;
+        }
+        let attached: usize = self
+            .attached_mem
+            .iter()
+            .map(|(start, _, mem)| overlap(*start, *start + mem.size()))
+            .sum();
+        owned + attached
+    }
+
+    /// 检查新映射 `[start, start + size)` 之后是否会超过 RLIMIT_AS 或 RLIMIT_DATA，
+    /// `fixed` 时被替换掉的部分不会计入。
+    fn check_limits(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        shared: bool,
+        fixed: bool,
+    ) -> AxResult<()> {
+        let range = (start, start + size);
+        let replaced = |data| {
+            if fixed {
+                self.mapped_size(data, Some(range))
+            } else {
+                0
+            }
+        };
+        // 溢出时同样视为超过限制
+        let exceeds = |data, limit| {
+            (self.mapped_size(data, None) - replaced(data))
+                .checked_add(size)
+                .is_none_or(|total| total > limit)
+        };
+        if exceeds(false, self.as_limit) {
+            return Err(AxError::NoMemory);
+        }
+        if !shared && flags.contains(MappingFlags::WRITE) && exceeds(true, self.data_limit) {
+            return Err(AxError::NoMemory);
+        }
+        Ok(())
+    }
+
     /// mmap. You need to flush tlb after this.
     pub async fn mmap(
         &mut self,
@@ -284,6 +523,8 @@ impl MemorySet {
             backend.is_some()
         );
 
+        self.check_limits(start, size, flags, shared, fixed)?;
+
         if fixed {
             self.split_for_area(start, size).await;
 
@@ -295,7 +536,15 @@ impl MemorySet {
             Ok(start.as_usize())
         } else {
             info!("find free area");
-            let start = self.find_free_area(start, size);
+            // 没有给出地址时从 mmap_base 开始查找，其上方没有空闲区域时再从头查找
+            let hint = if start.as_usize() == 0 {
+                self.mmap_base
+            } else {
+                start
+            };
+            let start = self
+                .find_free_area(hint, size)
+                .or_else(|| self.find_free_area(VirtAddr::from(0), size));
 
             match start {
                 Some(start) => {
@@ -310,6 +559,21 @@ impl MemorySet {
         }
     }
 
+    /// mlockall(MCL_FUTURE) 之后锁定新建的映射或者扩展的堆 `[start, start + size)`。You need to flush TLB after this.
+    ///
+    /// `limit` 是调用时进程可以锁定的内存大小。锁定失败时由调用者解除映射或者撤销堆的扩展
+    pub async fn lock_new_region(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        limit: usize,
+    ) -> AxResult<()> {
+        let Some(populate) = self.future_lock else {
+            return Ok(());
+        };
+        self.mlock(start, size, populate, limit).await
+    }
+
     /// munmap. You need to flush TLB after this.
     pub async fn munmap(&mut self, start: VirtAddr, size: usize) {
         // align up to 4k
@@ -317,6 +581,15 @@ impl MemorySet {
         info!("[munmap] [{:?}, {:?})", start, (start + size).align_up_4k());
 
         self.split_for_area(start, size).await;
+        let end = (start + size).as_usize();
+        self.forget_pages(start.as_usize(), end);
+    }
+
+    /// 页面 `[start, end)` 被解除映射或者丢弃，不再记录它们的访问状态
+    fn forget_pages(&mut self, start: usize, end: usize) {
+        self.young.retain(|&vaddr| vaddr < start || vaddr >= end);
+        self.lazy_free
+            .retain(|&vaddr| vaddr < start || vaddr >= end);
     }
 
     /// msync
@@ -353,84 +626,367 @@ impl MemorySet {
         let end = start + size;
         assert!(end.is_aligned_4k());
 
-        flush_tlb(None);
-        //self.manual_alloc_range_for_lazy(start, end - 1).unwrap();
-        // NOTE: There will be new areas but all old aree's start address won't change. But we
-        // can't iterating through `value_mut()` while `insert()` to BTree at the same time, so we
-        // `drain_filter()` out the overlapped areas first.
-        let mut overlapped_area: Vec<(usize, MapArea)> = Vec::new();
-        let mut prev_area: BTreeMap<usize, MapArea> = BTreeMap::new();
+        self.split_at_range(start, end).await;
+        for (_, area) in self.owned_mem.range_mut(start.as_usize()..end.as_usize()) {
+            area.update_flags(flags, &mut self.page_table);
+        }
+        // 可以释放的页面需要在被写入时取消标记，保持只读
+        for &vaddr in self.lazy_free.range(start.as_usize()..end.as_usize()) {
+            self.page_table
+                .update(vaddr.into(), None, Some(flags - MappingFlags::WRITE))
+                .unwrap();
+        }
+        axhal::arch::flush_tlb(None);
+    }
 
-        for _ in 0..self.owned_mem.len() {
-            let (idx, area) = self.owned_mem.pop_first().unwrap();
-            if area.overlap_with(start, end) {
-                overlapped_area.push((idx, area));
-            } else {
-                prev_area.insert(idx, area);
+    /// 拆分跨越 `start` 或者 `end` 的区域，使每个区域要么完全位于 `[start, end)` 之内，要么与其不相交。
+    /// 跨越边界的大页会被拆分成普通页
+    async fn split_at_range(&mut self, start: VirtAddr, end: VirtAddr) {
+        for addr in [start, end] {
+            let Some((_, area)) = self.owned_mem.range_mut(..addr.as_usize()).next_back() else {
+                continue;
+            };
+            if addr >= area.end_va() {
+                continue;
             }
+            area.split_huge_at(addr, &mut self.page_table);
+            let right = area.split(addr).await;
+            assert!(self.owned_mem.insert(right.vaddr.into(), right).is_none());
         }
+    }
 
-        self.owned_mem = prev_area;
-
-        for (_, mut area) in overlapped_area {
-            if area.contained_in(start, end) {
-                // update whole area
-                area.update_flags(flags, &mut self.page_table);
-            } else if area.strict_contain(start, end) {
-                // split into 3 areas, update the middle one
-                let (mut mid, right) = area.split3(start, end).await;
-                mid.update_flags(flags, &mut self.page_table);
-
-                assert!(self.owned_mem.insert(mid.vaddr.into(), mid).is_none());
-                assert!(self.owned_mem.insert(right.vaddr.into(), right).is_none());
-            } else if start <= area.vaddr && area.vaddr < end {
-                // split into 2 areas, update the left one
-                let right = area.split(end).await;
-                area.update_flags(flags, &mut self.page_table);
-
-                assert!(self.owned_mem.insert(right.vaddr.into(), right).is_none());
+    /// 以大页映射一块匿名区域（`MAP_HUGETLB`），大小按 2 MiB 向上对齐。You need to flush tlb after this.
+    ///
+    /// 没有给出固定地址时选取 2 MiB 对齐的起始地址，给出的固定地址没有对齐时返回 [`AxError::InvalidInput`]
+    pub async fn mmap_huge(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        shared: bool,
+        fixed: bool,
+    ) -> AxResult<usize> {
+        let size = size
+            .checked_add(PAGE_SIZE_2M - 1)
+            .ok_or(AxError::NoMemory)?
+            / PAGE_SIZE_2M
+            * PAGE_SIZE_2M;
+        let start = if fixed {
+            if !start.is_aligned(PAGE_SIZE_2M) {
+                return Err(AxError::InvalidInput);
+            }
+            start
+        } else {
+            let hint = if start.as_usize() == 0 {
+                self.mmap_base
             } else {
-                // split into 2 areas, update the right one
-                let mut right = area.split(start).await;
-                right.update_flags(flags, &mut self.page_table);
+                start
+            };
+            // 多查找一个大页的空间，以便将起始地址对齐
+            let search = size
+                .checked_add(PAGE_SIZE_2M - PAGE_SIZE_4K)
+                .ok_or(AxError::NoMemory)?;
+            self.find_free_area(hint, search)
+                .or_else(|| self.find_free_area(VirtAddr::from(0), search))
+                .ok_or(AxError::NoMemory)?
+                .align_up(PAGE_SIZE_2M)
+        };
+        let addr = self.mmap(start, size, flags, shared, true, None).await?;
+        if let Some(area) = self.owned_mem.get_mut(&addr) {
+            area.set_huge(true);
+        }
+        Ok(addr)
+    }
 
-                assert!(self.owned_mem.insert(right.vaddr.into(), right).is_none());
+    /// madvise：按照 `advice` 处理 `[start, start + size)` 中的映射。You need to flush TLB after this.
+    ///
+    /// [`Advice::WillNeed`] 需要读取文件，由 [`MemorySet::prefetch`] 处理。
+    /// 范围中有没有映射的部分时返回 [`AxError::NoMemory`]，丢弃被锁定的区域时返回 [`AxError::InvalidInput`]，
+    /// 两种情况下都不做任何处理
+    pub async fn madvise(&mut self, start: VirtAddr, size: usize, advice: Advice) -> AxResult<()> {
+        let end = (start + size).align_up_4k();
+        info!("[madvise] [{:?}, {:?}), {:?}", start, end, advice);
+        if self.owned_size(start, end, false) != end - start {
+            return Err(AxError::NoMemory);
+        }
+        if advice == Advice::DontNeed && self.owned_size(start, end, true) != 0 {
+            return Err(AxError::InvalidInput);
+        }
+        self.split_at_range(start, end).await;
+        let mut lazy_free = Vec::new();
+        for (_, area) in self.owned_mem.range_mut(start.as_usize()..end.as_usize()) {
+            let pages = area.pages.len();
+            match advice {
+                Advice::Normal => area.set_readahead(area::READAHEAD_NORMAL),
+                Advice::Random => area.set_readahead(0),
+                Advice::Sequential => area.set_readahead(area::READAHEAD_SEQUENTIAL),
+                Advice::WillNeed => {}
+                Advice::DontNeed => area.discard(0, pages, &mut self.page_table).await,
+                Advice::Free => lazy_free.extend(area.lazy_free(0, pages, &mut self.page_table)),
+                Advice::DontFork => area.set_dontfork(true),
+                Advice::DoFork => area.set_dontfork(false),
+                Advice::HugePage => area.set_huge(true),
+                Advice::NoHugePage => area.set_huge(false),
             }
+        }
+        match advice {
+            Advice::DontNeed => self.forget_pages(start.as_usize(), end.as_usize()),
+            Advice::Free => self.lazy_free.extend(lazy_free),
+            _ => {}
+        }
+        flush_tlb(None);
+        Ok(())
+    }
 
-            assert!(self.owned_mem.insert(area.vaddr.into(), area).is_none());
+    /// 预读 `[start, start + size)` 中文件映射的内容（`MADV_WILLNEED`）
+    ///
+    /// 返回的预读不需要持有地址空间的锁，调用者可以在后台完成。一次最多预读 [`MAX_PREFETCH_PAGES`] 页，
+    /// 范围中有没有映射的部分时返回 [`AxError::NoMemory`]
+    pub async fn prefetch(&self, start: VirtAddr, size: usize) -> AxResult<Vec<Prefetch>> {
+        let end = (start + size).align_up_4k();
+        if self.owned_size(start, end, false) != end - start {
+            return Err(AxError::NoMemory);
         }
-        axhal::arch::flush_tlb(None);
+        let end = end.min(start + MAX_PREFETCH_PAGES * PAGE_SIZE_4K);
+        let mut prefetch = Vec::new();
+        for area in self.owned_mem.values() {
+            if !area.overlap_with(start, end) {
+                continue;
+            }
+            let first = start.max(area.vaddr).as_usize() - area.vaddr.as_usize();
+            let last = end.min(area.end_va()).as_usize() - area.vaddr.as_usize();
+            if let Some(p) = area
+                .prefetch(first / PAGE_SIZE_4K, last.div_ceil(PAGE_SIZE_4K))
+                .await
+            {
+                prefetch.push(p);
+            }
+        }
+        Ok(prefetch)
     }
 
     /// It will map newly allocated page in the page table. You need to flush TLB after this.
     pub async fn handle_page_fault(&mut self, addr: VirtAddr, flags: MappingFlags) -> AxResult<()> {
-        match self
+        self.fault_in(addr, flags).await
+    }
+
+    /// 处理用户态的缺页，只挂起发生缺页的任务。You need to flush TLB after this.
+    ///
+    /// 需要读取文件或者交换空间的缺页在读取期间释放地址空间的锁，同一进程的其他任务可以继续运行并处理其他缺页。
+    /// 读取完成后重新加锁，若页面已经被其他任务换入或者映射在等待期间被修改，则重新处理缺页
+    pub async fn handle_user_page_fault(
+        memory_set: &Mutex<Self>,
+        addr: VirtAddr,
+        flags: MappingFlags,
+    ) -> AxResult<()> {
+        loop {
+            let io = {
+                let mut guard = memory_set.lock().await;
+                let this = &mut *guard;
+                let Some(area) = this
+                    .owned_mem
+                    .values_mut()
+                    .find(|area| area.vaddr <= addr && addr < area.end_va())
+                else {
+                    // 访问栈区域下方的地址时扩展栈
+                    if this.grow_stack(addr) {
+                        return this.fault_in(addr, flags).await;
+                    }
+                    error!("Page fault address {:?} not found in memory set ", addr);
+                    return Err(AxError::BadAddress);
+                };
+                match area.fault_io(addr, flags, &this.page_table).await {
+                    Some(io) => io,
+                    None => return this.fault_in(addr, flags).await,
+                }
+            };
+            let pinned = io.pinned_slot();
+            let page = io.read().await;
+
+            let mut this = memory_set.lock().await;
+            let result = match page {
+                Ok(page) => this.install_fault(addr, flags, page).await,
+                // 读取失败时在持有锁的情况下重新处理，得到当前映射下的结果
+                Err(err) => {
+                    warn!("page fault I/O at {:?} failed: {:?}", addr, err);
+                    this.fault_in(addr, flags).await.map(|_| true)
+                }
+            };
+            if let Some(slot) = pinned {
+                swap::free_slot(slot);
+            }
+            if result? {
+                return Ok(());
+            }
+            debug!("mapping at {:?} changed during page fault I/O, retry", addr);
+        }
+    }
+
+    /// 安装在释放锁期间读到的页面，映射在等待期间被修改时返回 false
+    async fn install_fault(
+        &mut self,
+        addr: VirtAddr,
+        flags: MappingFlags,
+        page: FaultPage,
+    ) -> AxResult<bool> {
+        let Some(area) = self
             .owned_mem
             .values_mut()
             .find(|area| area.vaddr <= addr && addr < area.end_va())
+        else {
+            return Ok(false);
+        };
+        match area
+            .install_fault(addr, flags, page, &mut self.page_table)
+            .await
         {
-            Some(area) => {
-                if !area
-                    .handle_page_fault(addr, flags, &mut self.page_table)
-                    .await
+            Ok(true) => {
+                self.young.insert(addr.align_down_4k().into());
+                Ok(true)
+            }
+            Err(AxError::NoMemory) => {
+                if self.swap_out(swap::SWAP_CLUSTER).await == 0
+                    && swap::reclaim(swap::SWAP_CLUSTER).await == 0
                 {
-                    return Err(AxError::BadAddress);
+                    return Err(AxError::NoMemory);
                 }
-                Ok(())
+                Ok(false)
             }
-            None => {
+            result => result,
+        }
+    }
+
+    /// 处理 `addr` 处的缺页，内存不足时回收内存后重试
+    async fn fault_in(&mut self, addr: VirtAddr, flags: MappingFlags) -> AxResult<()> {
+        loop {
+            let Some(area) = self
+                .owned_mem
+                .values_mut()
+                .find(|area| area.vaddr <= addr && addr < area.end_va())
+            else {
+                if self.grow_stack(addr) {
+                    continue;
+                }
                 error!("Page fault address {:?} not found in memory set ", addr);
-                Err(AxError::BadAddress)
+                return Err(AxError::BadAddress);
+            };
+            match area
+                .handle_page_fault(addr, flags, &mut self.page_table)
+                .await
+            {
+                Ok(()) => {
+                    self.young.insert(addr.align_down_4k().into());
+                    if flags.contains(MappingFlags::WRITE) {
+                        self.lazy_free.remove(&addr.align_down_4k().as_usize());
+                    }
+                    return Ok(());
+                }
+                // 本地址空间已经被加锁，其他任务回收时会跳过它，因此先换出自己的页面
+                Err(AxError::NoMemory) => {
+                    if self.swap_out(swap::SWAP_CLUSTER).await == 0
+                        && swap::reclaim(swap::SWAP_CLUSTER).await == 0
+                    {
+                        return Err(AxError::NoMemory);
+                    }
+                }
+                Err(err) => return Err(err),
             }
         }
     }
 
+    /// 按照时钟算法换出至多 `target` 个页面，返回换出的页数
+    ///
+    /// 从上一次停下的位置开始扫描，最近被访问过（记录在 `young` 中或者页表项的访问位被设置）的页面获得第二次机会。
+    /// 被标记为可以释放的页面直接丢弃，没有交换空间时也可以回收
+    pub async fn swap_out(&mut self, target: usize) -> usize {
+        if target == 0 || (!swap::enabled() && self.lazy_free.is_empty()) {
+            return 0;
+        }
+        let mut resident: Vec<usize> = self
+            .owned_mem
+            .values()
+            .flat_map(|area| {
+                area.pages
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, page)| page.is_some())
+                    .map(|(idx, _)| area.vaddr.as_usize() + idx * PAGE_SIZE_4K)
+            })
+            .collect();
+        let start = resident.partition_point(|&vaddr| vaddr < self.clock_hand);
+        resident.rotate_left(start);
+
+        let mut swapped = 0;
+        // 第一轮清除访问标记，第二轮从头再扫描一次
+        for _ in 0..2 {
+            for &vaddr in resident.iter() {
+                if swapped >= target {
+                    return swapped;
+                }
+                let lazy_free = self.lazy_free.remove(&vaddr);
+                // 两种访问记录都要清除
+                let accessed = swap::test_and_clear_accessed(&mut self.page_table, vaddr.into());
+                if !lazy_free && (self.young.remove(&vaddr) | accessed) {
+                    continue;
+                }
+                let Some((_, area)) = self.owned_mem.range_mut(..=vaddr).next_back() else {
+                    continue;
+                };
+                if vaddr >= area.end_va().as_usize() {
+                    continue;
+                }
+                let idx = (vaddr - area.vaddr.as_usize()) / PAGE_SIZE_4K;
+                // 可以释放的页面不需要换出，直接丢弃
+                if lazy_free && area.drop_lazy_free(idx, &mut self.page_table) {
+                    self.young.remove(&vaddr);
+                    swapped += 1;
+                    self.clock_hand = vaddr + PAGE_SIZE_4K;
+                    continue;
+                }
+                match area.swap_out(idx, &mut self.page_table).await {
+                    Ok(true) => {
+                        swapped += 1;
+                        self.clock_hand = vaddr + PAGE_SIZE_4K;
+                    }
+                    Ok(false) => {}
+                    Err(err) => {
+                        warn!("failed to swap out page {:#x}: {:?}", vaddr, err);
+                        return swapped;
+                    }
+                }
+            }
+        }
+        swapped
+    }
+
+    /// 换入所有被换出的页面，swapoff 时调用
+    pub async fn swap_in_all(&mut self) -> AxResult<()> {
+        let swapped: Vec<VirtAddr> = self
+            .owned_mem
+            .values()
+            .flat_map(|area| {
+                area.pages
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, page)| page.is_none())
+                    .map(|(idx, _)| area.vaddr + idx * PAGE_SIZE_4K)
+            })
+            .filter(|&vaddr| swap::swap_entry(&self.page_table, vaddr).is_some())
+            .collect();
+        for vaddr in swapped {
+            self.fault_in(vaddr, MappingFlags::empty()).await?;
+        }
+        Ok(())
+    }
+
     /// 将用户分配的页面从页表中直接解映射，内核分配的页面依然保留
     pub fn unmap_user_areas(&mut self) {
         for (_, area) in self.owned_mem.iter_mut() {
             area.dealloc(&mut self.page_table);
         }
         self.owned_mem.clear();
+        self.young.clear();
+        self.lazy_free.clear();
     }
 
     /// Query the page table to get the physical address, flags and page size of the given virtual
@@ -455,9 +1011,8 @@ impl MemorySet {
     }
 
     /// Create a new SharedMem with given key.
-    /// You need to add the returned SharedMem to global SHARED_MEMS or process's private_mem.
-    ///
-    /// Panics: SharedMem with the key already exist.
+    /// You need to add the returned SharedMem to global SHARED_MEMS or process's private_mem,
+    /// and record the key in KEY_TO_SHMID if it is not IPC_PRIVATE.
     pub fn create_shared_mem(
         key: i32,
         size: usize,
@@ -466,10 +1021,7 @@ impl MemorySet {
         gid: u32,
         mode: u16,
     ) -> AxResult<(i32, SharedMem)> {
-        let mut key_map = KEY_TO_SHMID.lock();
-
         let shmid = SHMID.fetch_add(1, Ordering::Release);
-        key_map.insert(key, shmid);
 
         let mem = SharedMem::try_new(key, size, pid, uid, gid, mode)?;
 
@@ -552,6 +1104,12 @@ impl MemorySet {
 
                 for addr in (old_page_start..=old_page_end).step_by(PAGE_SIZE_4K) {
                     let vaddr = VirtAddr::from(addr);
+                    // 被换出的页面先换入
+                    if swap::swap_entry(&self.page_table, vaddr).is_some()
+                        && self.fault_in(vaddr, MappingFlags::empty()).await.is_err()
+                    {
+                        return -1;
+                    }
                     match check_page_table_entry_validity(vaddr, &self.page_table) {
                         Ok(_) => {
                             // 如果旧地址已经分配内存，进行页copy；否则不做处理
@@ -596,32 +1154,41 @@ impl MemorySet {
     ///
     /// 若不在内存集中，则返回None。
     ///
-    /// 若在内存集中，且已经分配了物理页面，则不做处理。
+    /// 若在内存集中，且已经分配了物理页面，则不做处理；
+    /// 但若该页面因写时复制被写保护，则先进行复制，保证内核之后可以直接写入该页面。
     pub async fn manual_alloc_for_lazy(&mut self, addr: VirtAddr) -> AxResult<()> {
-        if let Some((_, area)) = self
+        // 内核访问栈区域下方的地址（例如构造信号栈帧）时同样扩展栈
+        self.grow_stack(addr);
+        let Some(area) = self
             .owned_mem
-            .iter_mut()
-            .find(|(_, area)| area.vaddr <= addr && addr < area.end_va())
-        {
-            match check_page_table_entry_validity(addr, &self.page_table) {
-                Err(PagingError::NoMemory) => Err(AxError::InvalidInput),
-                Err(PagingError::NotMapped) => {
-                    // 若未分配物理页面，则手动为其分配一个页面，写入到对应页表中
-                    let entry = self.page_table.get_entry_mut(addr).unwrap().0;
-
-                    if !area
-                        .handle_page_fault(addr, entry.flags(), &mut self.page_table)
+            .values()
+            .find(|area| area.vaddr <= addr && addr < area.end_va())
+        else {
+            return Err(AxError::InvalidInput);
+        };
+        let is_cow = area.is_cow();
+        match check_page_table_entry_validity(addr, &self.page_table) {
+            Err(PagingError::NoMemory) => return Err(AxError::InvalidInput),
+            Err(PagingError::NotMapped) => {
+                // 若未分配物理页面或者页面已被换出，则手动缺页，写入到对应页表中
+                let flags = self.page_table.get_entry_mut(addr).unwrap().0.flags();
+                self.fault_in(addr, flags)
+                    .await
+                    .map_err(|_| AxError::BadAddress)?;
+            }
+            Ok(_) if is_cow => {
+                let entry = self.page_table.get_entry_mut(addr).unwrap().0;
+                if !entry.flags().contains(MappingFlags::WRITE) {
+                    self.fault_in(addr, MappingFlags::WRITE)
                         .await
-                    {
-                        return Err(AxError::BadAddress);
-                    }
-                    Ok(())
+                        .map_err(|_| AxError::BadAddress)?;
                 }
-                _ => Ok(()),
             }
-        } else {
-            Err(AxError::InvalidInput)
+            _ => {}
         }
+        // 内核即将访问该页面
+        self.young.insert(addr.align_down_4k().into());
+        Ok(())
     }
     /// 暴力实现区间强制分配
     /// 传入区间左闭右闭
@@ -649,12 +1216,290 @@ impl MemorySet {
         self.manual_alloc_range_for_lazy(start.into(), end.into())
             .await
     }
+    /// 读写地址空间中从 `addr` 开始的数据，用于 ptrace 的 PEEK/POKE 等调试操作
+    ///
+    /// 写入时忽略映射的写权限，以便调试器在代码段中设置断点
+    pub async fn access_foreign(
+        &mut self,
+        addr: VirtAddr,
+        buf: &mut [u8],
+        write: bool,
+    ) -> AxResult<()> {
+        let mut done = 0;
+        while done < buf.len() {
+            let vaddr = addr + done;
+            let len = (PAGE_SIZE_4K - vaddr.align_offset_4k()).min(buf.len() - done);
+            let kaddr = match self
+                .owned_mem
+                .values_mut()
+                .find(|area| area.vaddr <= vaddr && vaddr < area.end_va())
+            {
+                Some(area) => {
+                    let kaddr = area
+                        .page_for_foreign_access(vaddr, write, &mut self.page_table)
+                        .await?;
+                    self.young.insert(vaddr.align_down_4k().into());
+                    if write {
+                        self.lazy_free.remove(&vaddr.align_down_4k().as_usize());
+                    }
+                    kaddr
+                }
+                // 附加的共享内存等区域不在 owned_mem 中，直接查询页表
+                None => match self.page_table.query(vaddr) {
+                    Ok((paddr, _, _)) => phys_to_virt(paddr),
+                    Err(_) => return Err(AxError::BadAddress),
+                },
+            };
+            let chunk = &mut buf[done..done + len];
+            unsafe {
+                if write {
+                    core::ptr::copy_nonoverlapping(chunk.as_ptr(), kaddr.as_mut_ptr(), len);
+                } else {
+                    core::ptr::copy_nonoverlapping(kaddr.as_ptr(), chunk.as_mut_ptr(), len);
+                }
+            }
+            done += len;
+        }
+        Ok(())
+    }
+
+    /// 读取 `vaddr` 所在的一页，供转储 core 文件使用，返回 false 表示该页的内容全为 0
+    ///
+    /// 被换出的页面以及以文件为后端、尚未读入的页面先读入内存；
+    /// 从未访问过的匿名页面不会为转储而分配，直接返回 false
+    pub async fn read_page_for_dump(
+        &mut self,
+        vaddr: VirtAddr,
+        buf: &mut [u8; PAGE_SIZE_4K],
+    ) -> AxResult<bool> {
+        let vaddr = vaddr.align_down_4k();
+        let Some(area) = self
+            .owned_mem
+            .values()
+            .find(|area| area.vaddr <= vaddr && vaddr < area.end_va())
+        else {
+            return Err(AxError::BadAddress);
+        };
+        let page_index = (vaddr.as_usize() - area.vaddr.as_usize()) / PAGE_SIZE_4K;
+        if area.pages[page_index].is_none()
+            && area.backend.is_none()
+            && swap::swap_entry(&self.page_table, vaddr).is_none()
+        {
+            return Ok(false);
+        }
+        self.access_foreign(vaddr, buf, false).await?;
+        Ok(true)
+    }
+}
+
+impl MemorySet {
+    /// `[start, end)` 中已经映射的区域的大小，`locked` 为真时只统计被锁定的区域
+    fn owned_size(&self, start: VirtAddr, end: VirtAddr, locked: bool) -> usize {
+        self.owned_mem
+            .values()
+            .filter(|area| !locked || area.is_locked())
+            .map(|area| {
+                let first = area.vaddr.max(start).as_usize();
+                let last = area.end_va().min(end).as_usize();
+                last.saturating_sub(first)
+            })
+            .sum()
+    }
+
+    /// 计入 RLIMIT_MEMLOCK 的内存大小：被锁定的区域与被固定的页面
+    fn locked_size(&self) -> usize {
+        self.owned_size(VirtAddr::from(0), VirtAddr::from(usize::MAX), true)
+            + self.pinned.load(Ordering::Relaxed) * PAGE_SIZE_4K
+    }
+
+    /// 预先分配 `[start, end)` 中的页面，私有的可写映射同时完成写时复制。没有访问权限的区域不会被分配
+    async fn populate(&mut self, start: VirtAddr, end: VirtAddr) -> AxResult<()> {
+        let ranges: Vec<_> = self
+            .owned_mem
+            .values()
+            .filter(|area| {
+                area.overlap_with(start, end)
+                    && area.flags.intersects(
+                        MappingFlags::READ | MappingFlags::WRITE | MappingFlags::EXECUTE,
+                    )
+            })
+            .map(|area| {
+                let flags = if area.is_cow() {
+                    MappingFlags::WRITE
+                } else {
+                    MappingFlags::empty()
+                };
+                (area.vaddr.max(start), area.end_va().min(end), flags)
+            })
+            .collect();
+        for (first, last, flags) in ranges {
+            for vaddr in (first.as_usize()..last.as_usize()).step_by(PAGE_SIZE_4K) {
+                if let Ok((_, mapped, _)) = self.page_table.query(vaddr.into()) {
+                    if mapped.contains(flags) {
+                        continue;
+                    }
+                }
+                self.fault_in(vaddr.into(), flags).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 锁定 `[start, start + size)` 中的映射（mlock），被锁定的页面不会被换出或者丢弃。You need to flush TLB after this.
+    ///
+    /// `populate` 为真时预先分配所有页面，否则页面在第一次访问时分配（`MLOCK_ONFAULT`）。
+    /// 范围中有没有映射的部分，或者锁定的内存将超过 `limit` 时返回 [`AxError::NoMemory`]；
+    /// 预先分配失败时撤销这次新加的锁定
+    pub async fn mlock(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        populate: bool,
+        limit: usize,
+    ) -> AxResult<()> {
+        let (start, end) = (start.align_down_4k(), (start + size).align_up_4k());
+        if self.owned_size(start, end, false) != end - start {
+            return Err(AxError::NoMemory);
+        }
+        if self.locked_size() - self.owned_size(start, end, true) + (end - start) > limit {
+            return Err(AxError::NoMemory);
+        }
+        self.split_at_range(start, end).await;
+        let newly_locked = self.lock_areas(start.as_usize()..end.as_usize());
+        if populate {
+            if let Err(err) = self.populate(start, end).await {
+                self.unlock_areas(&newly_locked);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// 锁定 `range` 中的所有区域，返回之前没有被锁定的区域的起始地址
+    fn lock_areas(&mut self, range: core::ops::Range<usize>) -> Vec<usize> {
+        let mut newly_locked = Vec::new();
+        for (&vaddr, area) in self.owned_mem.range_mut(range) {
+            if !area.is_locked() {
+                area.set_locked(true);
+                newly_locked.push(vaddr);
+            }
+        }
+        newly_locked
+    }
+
+    /// 撤销 [`MemorySet::lock_areas`] 新加的锁定
+    fn unlock_areas(&mut self, areas: &[usize]) {
+        for vaddr in areas {
+            if let Some(area) = self.owned_mem.get_mut(vaddr) {
+                area.set_locked(false);
+            }
+        }
+    }
+
+    /// 解除 `[start, start + size)` 中映射的锁定（munlock），范围中有没有映射的部分时返回 [`AxError::NoMemory`]
+    pub async fn munlock(&mut self, start: VirtAddr, size: usize) -> AxResult<()> {
+        let (start, end) = (start.align_down_4k(), (start + size).align_up_4k());
+        if self.owned_size(start, end, false) != end - start {
+            return Err(AxError::NoMemory);
+        }
+        self.split_at_range(start, end).await;
+        for (_, area) in self.owned_mem.range_mut(start.as_usize()..end.as_usize()) {
+            area.set_locked(false);
+        }
+        Ok(())
+    }
+
+    /// 锁定地址空间中的所有映射（mlockall）。You need to flush TLB after this.
+    ///
+    /// `current` 锁定现有的映射，`future` 锁定之后新建的映射，`populate` 为真时预先分配页面。
+    /// 锁定的内存将超过 `limit` 时返回 [`AxError::NoMemory`]，预先分配失败时撤销这次新加的锁定。
+    /// 之后新建的映射由 [`MemorySet::lock_new_region`] 按照届时的上限锁定
+    pub async fn mlockall(
+        &mut self,
+        current: bool,
+        future: bool,
+        populate: bool,
+        limit: usize,
+    ) -> AxResult<()> {
+        if current {
+            let all = (VirtAddr::from(0), VirtAddr::from(usize::MAX));
+            let unlocked =
+                self.owned_size(all.0, all.1, false) - self.owned_size(all.0, all.1, true);
+            if self.locked_size() + unlocked > limit {
+                return Err(AxError::NoMemory);
+            }
+            let newly_locked = self.lock_areas(0..usize::MAX);
+            if populate {
+                if let Err(err) = self.populate(all.0, all.1).await {
+                    self.unlock_areas(&newly_locked);
+                    return Err(err);
+                }
+            }
+        }
+        self.future_lock = future.then_some(populate);
+        Ok(())
+    }
+
+    /// 解除所有映射的锁定，之后新建的映射也不再被锁定（munlockall）
+    pub fn munlockall(&mut self) {
+        for area in self.owned_mem.values_mut() {
+            area.set_locked(false);
+        }
+        self.future_lock = None;
+    }
+
+    /// 为驱动程序的 DMA 固定 `[start, start + len)` 中的用户页面，`write` 表示设备是否会写入这些页面
+    ///
+    /// 页面先被分配，写入用的固定还会完成写时复制。固定的页面计入锁定的内存，超过 `limit` 时返回
+    /// [`AxError::NoMemory`]；范围中有没有映射或者没有相应权限的部分时返回 [`AxError::BadAddress`]
+    pub async fn pin_user_pages(
+        &mut self,
+        start: VirtAddr,
+        len: usize,
+        write: bool,
+        limit: usize,
+    ) -> AxResult<PinnedPages> {
+        let (first, end) = (start.align_down_4k(), (start + len).align_up_4k());
+        let count = (end.as_usize() - first.as_usize()) / PAGE_SIZE_4K;
+        if self.locked_size() + count * PAGE_SIZE_4K > limit {
+            return Err(AxError::NoMemory);
+        }
+        let flags = if write {
+            MappingFlags::WRITE
+        } else {
+            MappingFlags::empty()
+        };
+        let mut pages = Vec::with_capacity(count);
+        for vaddr in (first.as_usize()..end.as_usize()).step_by(PAGE_SIZE_4K) {
+            let vaddr = VirtAddr::from(vaddr);
+            self.fault_in(vaddr, flags).await?;
+            let Some((_, area)) = self.owned_mem.range(..=vaddr.as_usize()).next_back() else {
+                return Err(AxError::BadAddress);
+            };
+            let idx = (vaddr.as_usize() - area.vaddr.as_usize()) / PAGE_SIZE_4K;
+            let Some(page) = area.pages.get(idx).cloned().flatten() else {
+                return Err(AxError::BadAddress);
+            };
+            let paddr = virt_to_phys(page.lock().await.start_vaddr);
+            pages.push((paddr, page));
+        }
+        self.pinned.fetch_add(count, Ordering::Relaxed);
+        Ok(PinnedPages::new(
+            pages,
+            start.align_offset_4k(),
+            len,
+            self.pinned.clone(),
+        ))
+    }
 }
 
 impl MemorySet {
     /// Clone the MemorySet. This will create a new page table and map all the regions in the old
     /// page table to the new one.
     ///
+    /// Private areas are shared copy-on-write with the new MemorySet, while `MAP_SHARED` areas and
+    /// attached SysV shared memory keep mapping the same phys pages.
+    ///
     /// If it occurs error, the new MemorySet will be dropped and return the error.
     pub async fn clone_or_err(&mut self) -> AxResult<Self> {
         let mut page_table = PageTable::try_new().expect("Error allocating page table.");
@@ -671,6 +1516,10 @@ impl MemorySet {
         }
         let mut owned_mem: BTreeMap<usize, MapArea> = BTreeMap::new();
         for (vaddr, area) in self.owned_mem.iter_mut() {
+            // MADV_DONTFORK 的区域不被子进程继承
+            if area.is_dontfork() {
+                continue;
+            }
             info!("vaddr: {:X?}, new_area: {:X?}", vaddr, area.vaddr);
             match area
                 .clone_alloc(&mut page_table, &mut self.page_table)
@@ -691,12 +1540,24 @@ impl MemorySet {
 
             private_mem: self.private_mem.clone(),
             attached_mem: Vec::new(),
+            as_limit: self.as_limit,
+            data_limit: self.data_limit,
+            stack_limit: self.stack_limit,
+            mmap_base: self.mmap_base,
+            young: BTreeSet::new(),
+            lazy_free: BTreeSet::new(),
+            future_lock: None,
+            pinned: Arc::new(AtomicUsize::new(0)),
+            clock_hand: 0,
         };
 
         for (addr, flags, mem) in &self.attached_mem {
             new_memory.attach_shared_mem(mem.clone(), *addr, *flags);
         }
 
+        // 父进程的页面被写保护，需要刷新 TLB
+        flush_tlb(None);
+
         Ok(new_memory)
     }
 
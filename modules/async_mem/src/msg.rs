@@ -0,0 +1,341 @@
+//! System V 消息队列。
+//!
+//! 每个队列按发送的顺序保存消息，队列中消息的总字节数不超过 `qbytes`。发送与接收都不会在持有锁时等待：
+//! 操作无法立即完成时返回，由调用者在队列的等待队列上等待，队列的内容发生变化或者被删除时唤醒所有等待者。
+//! 被删除的队列立即从键空间中移除，仍在等待的任务被唤醒后得到 EIDRM。
+use alloc::{collections::BTreeMap, collections::VecDeque, sync::Arc, vec::Vec};
+use axerrno::{LinuxError, LinuxResult};
+use axhal::time::current_time;
+use core::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+use sync::{SpinNoIrq, WaitQueue};
+
+use crate::ipc::{ipc_get, ipc_remove, IpcKeys, IpcPerm};
+
+/// 一条消息的最大长度
+pub const MSGMAX: usize = 8192;
+/// 新建队列的默认容量，单位为字节
+pub const MSGMNB: usize = 16384;
+
+/// 接收时消息过长则截断，而不是返回 E2BIG
+pub const MSG_NOERROR: i32 = 0o10000;
+/// 接收第一条类型不等于 `msgtyp` 的消息
+pub const MSG_EXCEPT: i32 = 0o20000;
+
+static MSQID: AtomicI32 = AtomicI32::new(1);
+
+static MSG_QUEUES: SpinNoIrq<BTreeMap<i32, Arc<MsgQueue>>> = SpinNoIrq::new(BTreeMap::new());
+
+/// The map from key to msqid. It's used to query msqid from key.
+pub static KEY_TO_MSQID: IpcKeys = SpinNoIrq::new(BTreeMap::new());
+
+/// 一条消息
+pub struct Message {
+    pub mtype: isize,
+    pub data: Vec<u8>,
+}
+
+/// 消息队列的状态，由 IPC_STAT 返回
+#[derive(Debug, Clone, Copy)]
+pub struct MsgQueueInfo {
+    pub perm: IpcPerm,
+    /// 最近一次发送的时间
+    pub stime: usize,
+    /// 最近一次接收的时间
+    pub rtime: usize,
+    /// 最近一次修改的时间
+    pub ctime: usize,
+    /// 队列中消息的总字节数
+    pub cbytes: usize,
+    /// 队列中的消息数
+    pub qnum: usize,
+    /// 队列的容量
+    pub qbytes: usize,
+    /// 最近一次发送的进程
+    pub lspid: u64,
+    /// 最近一次接收的进程
+    pub lrpid: u64,
+}
+
+struct MsgQueueInner {
+    info: MsgQueueInfo,
+    messages: VecDeque<Message>,
+    removed: bool,
+}
+
+/// 一个消息队列
+pub struct MsgQueue {
+    inner: SpinNoIrq<MsgQueueInner>,
+    version: AtomicUsize,
+    /// 等待发送或者接收的任务
+    pub wq: WaitQueue,
+}
+
+/// 按照 `key` 与 `flags` 查找或者创建消息队列，返回其标识符以及是否为新建的队列
+pub fn msgget(key: i32, flags: i32, uid: u32, gid: u32) -> LinuxResult<(i32, bool)> {
+    let result = ipc_get(&KEY_TO_MSQID, key, flags, || {
+        let msqid = MSQID.fetch_add(1, Ordering::Relaxed);
+        let queue = MsgQueue::new(IpcPerm::new(key, uid, gid, flags as u16));
+        MSG_QUEUES.lock().insert(msqid, Arc::new(queue));
+        Ok(msqid)
+    })?;
+    Ok(result)
+}
+
+/// Get a MsgQueue by msqid.
+pub fn get_msg_queue(msqid: i32) -> Option<Arc<MsgQueue>> {
+    MSG_QUEUES.lock().get(&msqid).cloned()
+}
+
+/// 删除消息队列，丢弃其中的消息并唤醒所有等待者
+pub fn remove_msg_queue(msqid: i32) {
+    ipc_remove(&KEY_TO_MSQID, msqid);
+    if let Some(queue) = MSG_QUEUES.lock().remove(&msqid) {
+        let mut inner = queue.inner.lock();
+        inner.removed = true;
+        inner.messages.clear();
+        drop(inner);
+        queue.changed();
+    }
+}
+
+impl MsgQueue {
+    fn new(perm: IpcPerm) -> Self {
+        Self {
+            inner: SpinNoIrq::new(MsgQueueInner {
+                info: MsgQueueInfo {
+                    perm,
+                    stime: 0,
+                    rtime: 0,
+                    ctime: current_time().as_secs() as usize,
+                    cbytes: 0,
+                    qnum: 0,
+                    qbytes: MSGMNB,
+                    lspid: 0,
+                    lrpid: 0,
+                },
+                messages: VecDeque::new(),
+                removed: false,
+            }),
+            version: AtomicUsize::new(0),
+            wq: WaitQueue::new(),
+        }
+    }
+
+    /// 队列的状态
+    pub fn info(&self) -> MsgQueueInfo {
+        self.inner.lock().info
+    }
+
+    /// IPC_SET：修改所有者、访问权限与队列的容量
+    pub fn set_info(&self, uid: u32, gid: u32, mode: u16, qbytes: usize) {
+        let mut inner = self.inner.lock();
+        inner.info.perm.set(uid, gid, mode);
+        inner.info.qbytes = qbytes;
+        inner.info.ctime = current_time().as_secs() as usize;
+        drop(inner);
+        // 容量可能变大，等待发送的任务可以继续
+        self.changed();
+    }
+
+    /// 尝试将一条消息放入队列，队列已满时返回 false
+    ///
+    /// 消息长于队列的容量时永远无法放入，返回 EINVAL
+    pub fn try_send(&self, mtype: isize, data: &[u8], pid: u64) -> LinuxResult<bool> {
+        let mut inner = self.inner.lock();
+        if inner.removed {
+            return Err(LinuxError::EIDRM);
+        }
+        if data.len() > inner.info.qbytes {
+            return Err(LinuxError::EINVAL);
+        }
+        if inner.info.cbytes + data.len() > inner.info.qbytes {
+            return Ok(false);
+        }
+        inner.messages.push_back(Message {
+            mtype,
+            data: data.to_vec(),
+        });
+        inner.info.cbytes += data.len();
+        inner.info.qnum += 1;
+        inner.info.lspid = pid;
+        inner.info.stime = current_time().as_secs() as usize;
+        drop(inner);
+        self.changed();
+        Ok(true)
+    }
+
+    /// 尝试取出一条按照 `msgtyp` 选择的消息，没有这样的消息时返回 None
+    ///
+    /// - `msgtyp` 为 0 时取出第一条消息；
+    /// - 大于 0 时取出第一条类型为 `msgtyp` 的消息，给出 `MSG_EXCEPT` 时则是第一条类型不为 `msgtyp` 的消息；
+    /// - 小于 0 时取出类型不超过 `msgtyp` 的绝对值的消息中类型最小的第一条。
+    ///
+    /// 消息长于 `max_size` 时，给出 `MSG_NOERROR` 则由调用者截断，否则消息留在队列中并返回 E2BIG。
+    /// 返回的消息总是完整的，以便复制给用户失败时通过 [`MsgQueue::requeue`] 放回队列
+    pub fn try_recv(
+        &self,
+        msgtyp: isize,
+        max_size: usize,
+        flags: i32,
+        pid: u64,
+    ) -> LinuxResult<Option<Message>> {
+        let mut inner = self.inner.lock();
+        if inner.removed {
+            return Err(LinuxError::EIDRM);
+        }
+        let index = if msgtyp == 0 {
+            (!inner.messages.is_empty()).then_some(0)
+        } else if msgtyp > 0 {
+            let except = flags & MSG_EXCEPT != 0;
+            inner
+                .messages
+                .iter()
+                .position(|msg| (msg.mtype == msgtyp) != except)
+        } else {
+            // 消息的类型总是正数，`msgtyp` 为 isize::MIN 时不能直接取负
+            let max_type = msgtyp.unsigned_abs();
+            inner
+                .messages
+                .iter()
+                .enumerate()
+                .filter(|(_, msg)| msg.mtype.unsigned_abs() <= max_type)
+                .min_by_key(|(index, msg)| (msg.mtype, *index))
+                .map(|(index, _)| index)
+        };
+        let Some(index) = index else {
+            return Ok(None);
+        };
+        if inner.messages[index].data.len() > max_size && flags & MSG_NOERROR == 0 {
+            return Err(LinuxError::E2BIG);
+        }
+        let msg = inner.messages.remove(index).unwrap();
+        inner.info.cbytes -= msg.data.len();
+        inner.info.qnum -= 1;
+        inner.info.lrpid = pid;
+        inner.info.rtime = current_time().as_secs() as usize;
+        drop(inner);
+        self.changed();
+        Ok(Some(msg))
+    }
+
+    /// 将已经取出但无法复制给用户的消息放回队列头部，队列已经被删除时丢弃
+    ///
+    /// 被取出的消息是所选择的消息中的第一条，放回头部之后仍然会被同样的 `msgtyp` 首先选中。
+    /// 放回的消息可能使队列暂时超出容量
+    pub fn requeue(&self, msg: Message) {
+        let mut inner = self.inner.lock();
+        if inner.removed {
+            return;
+        }
+        inner.info.cbytes += msg.data.len();
+        inner.info.qnum += 1;
+        inner.messages.push_front(msg);
+        drop(inner);
+        self.changed();
+    }
+
+    /// 队列的版本，每次发送、接收、修改或者删除时递增。
+    ///
+    /// 操作无法完成时，等待者在尝试之前记录版本，等待到版本变化之后再次尝试
+    pub fn version(&self) -> usize {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// 队列发生了变化，唤醒所有等待者
+    fn changed(&self) {
+        self.version.fetch_add(1, Ordering::Release);
+        self.wq.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MsgQueue, MSG_EXCEPT, MSG_NOERROR};
+    use crate::ipc::IpcPerm;
+    use axerrno::LinuxError;
+
+    fn queue_with(types: &[isize]) -> MsgQueue {
+        let queue = MsgQueue::new(IpcPerm::new(0, 0, 0, 0o600));
+        for (index, &mtype) in types.iter().enumerate() {
+            assert!(queue.try_send(mtype, &[index as u8], 1).unwrap());
+        }
+        queue
+    }
+
+    /// 取出一条消息，返回其类型与它在发送时的序号
+    fn recv(queue: &MsgQueue, msgtyp: isize, flags: i32) -> Option<(isize, u8)> {
+        queue
+            .try_recv(msgtyp, 16, flags, 2)
+            .unwrap()
+            .map(|msg| (msg.mtype, msg.data[0]))
+    }
+
+    #[test]
+    fn test_recv_first() {
+        let queue = queue_with(&[3, 1, 2]);
+        assert_eq!(Some((3, 0)), recv(&queue, 0, 0));
+        assert_eq!(Some((1, 1)), recv(&queue, 0, 0));
+        assert_eq!(Some((2, 2)), recv(&queue, 0, 0));
+        assert_eq!(None, recv(&queue, 0, 0));
+    }
+
+    #[test]
+    fn test_recv_by_type() {
+        let queue = queue_with(&[3, 1, 2, 1]);
+        assert_eq!(Some((1, 1)), recv(&queue, 1, 0));
+        assert_eq!(Some((1, 3)), recv(&queue, 1, 0));
+        assert_eq!(None, recv(&queue, 1, 0));
+        assert_eq!(Some((2, 2)), recv(&queue, 3, MSG_EXCEPT));
+        assert_eq!(1, queue.info().qnum);
+    }
+
+    #[test]
+    fn test_recv_lowest_type() {
+        let queue = queue_with(&[3, 2, 1, 2, 1]);
+        // 类型不超过 2 的消息中类型最小的第一条
+        assert_eq!(Some((1, 2)), recv(&queue, -2, 0));
+        assert_eq!(Some((1, 4)), recv(&queue, -2, 0));
+        assert_eq!(Some((2, 1)), recv(&queue, -2, 0));
+        assert_eq!(Some((2, 3)), recv(&queue, -2, 0));
+        assert_eq!(None, recv(&queue, -2, 0));
+        assert_eq!(Some((3, 0)), recv(&queue, -3, 0));
+        // 绝对值无法用 isize 表示时同样选择所有消息
+        let queue = queue_with(&[2, 1]);
+        assert_eq!(Some((1, 1)), recv(&queue, isize::MIN, 0));
+    }
+
+    #[test]
+    fn test_recv_too_long() {
+        let queue = MsgQueue::new(IpcPerm::new(0, 0, 0, 0o600));
+        assert!(queue.try_send(1, &[0; 8], 1).unwrap());
+        assert_eq!(Some(LinuxError::E2BIG), queue.try_recv(0, 4, 0, 2).err());
+        // 消息仍然留在队列中，给出 MSG_NOERROR 时返回完整的消息
+        assert_eq!(1, queue.info().qnum);
+        let msg = queue.try_recv(0, 4, MSG_NOERROR, 2).unwrap().unwrap();
+        assert_eq!(8, msg.data.len());
+        assert_eq!(0, queue.info().cbytes);
+    }
+
+    #[test]
+    fn test_send_too_long() {
+        let queue = MsgQueue::new(IpcPerm::new(0, 0, 0, 0o600));
+        let info = queue.info();
+        queue.set_info(info.perm.uid, info.perm.gid, info.perm.mode, 4);
+        assert_eq!(
+            Some(LinuxError::EINVAL),
+            queue.try_send(1, &[0; 8], 1).err()
+        );
+        assert!(queue.try_send(1, &[0; 4], 1).unwrap());
+        // 队列已满但消息不超过容量时等待
+        assert!(!queue.try_send(1, &[0; 1], 1).unwrap());
+    }
+
+    #[test]
+    fn test_requeue() {
+        let queue = queue_with(&[1, 2]);
+        let msg = queue.try_recv(2, 16, 0, 2).unwrap().unwrap();
+        queue.requeue(msg);
+        assert_eq!(2, queue.info().qnum);
+        assert_eq!(Some((2, 1)), recv(&queue, 0, 0));
+    }
+}
@@ -0,0 +1,103 @@
+//! 供驱动程序 DMA 使用的用户页面固定。
+//!
+//! 固定的页面持有物理页的引用，在被释放之前不会被换出、丢弃或者被页缓存回收，即使用户已经解除了映射。
+//! 固定的页面计入 RLIMIT_MEMLOCK。
+//!
+//! 用于写入的固定会先完成写时复制，但之后的 fork 仍会让父进程在写入时复制页面，
+//! 使 DMA 写入的页面与用户看到的页面不再相同，因此 DMA 缓冲区应当使用 `MADV_DONTFORK`。
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use axalloc::PhysPage;
+use axhal::mem::PhysAddr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use sync::{Mutex, SpinNoIrq};
+
+/// 每个物理页被固定的次数，以页面的 `Arc` 指针区分。
+///
+/// 固定持有的引用不计入写时复制的共享判断，否则固定的页面会在写入时被复制，与设备看到的页面不再相同
+static PIN_COUNTS: SpinNoIrq<BTreeMap<usize, usize>> = SpinNoIrq::new(BTreeMap::new());
+
+fn page_key(page: &Arc<Mutex<PhysPage>>) -> usize {
+    Arc::as_ptr(page) as usize
+}
+
+/// 物理页被固定的次数
+pub(crate) fn pin_count(page: &Arc<Mutex<PhysPage>>) -> usize {
+    PIN_COUNTS.lock().get(&page_key(page)).copied().unwrap_or(0)
+}
+
+/// 物理页是否被固定，固定的页面不能被换出或者丢弃
+pub(crate) fn is_pinned(page: &Arc<Mutex<PhysPage>>) -> bool {
+    pin_count(page) > 0
+}
+
+/// 一段被固定的用户内存
+pub struct PinnedPages {
+    paddrs: Vec<PhysAddr>,
+    /// 持有物理页的引用，保证物理页在固定期间不被释放
+    _pages: Vec<Arc<Mutex<PhysPage>>>,
+    offset: usize,
+    len: usize,
+    /// 所属地址空间中被固定的页数
+    counter: Arc<AtomicUsize>,
+}
+
+impl PinnedPages {
+    pub(crate) fn new(
+        pages: Vec<(PhysAddr, Arc<Mutex<PhysPage>>)>,
+        offset: usize,
+        len: usize,
+        counter: Arc<AtomicUsize>,
+    ) -> Self {
+        let (paddrs, pages): (Vec<_>, Vec<_>) = pages.into_iter().unzip();
+        let mut pin_counts = PIN_COUNTS.lock();
+        for page in pages.iter() {
+            *pin_counts.entry(page_key(page)).or_insert(0) += 1;
+        }
+        drop(pin_counts);
+        Self {
+            paddrs,
+            _pages: pages,
+            offset,
+            len,
+            counter,
+        }
+    }
+
+    /// 每一页的物理地址
+    pub fn phys_pages(&self) -> &[PhysAddr] {
+        &self.paddrs
+    }
+
+    /// 固定的内存在第一页中的偏移
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// 固定的内存的长度
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// 固定的内存的长度是否为 0
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Drop for PinnedPages {
+    fn drop(&mut self) {
+        // 先减少固定次数再释放引用，期间页面只会被看作共享
+        let mut pin_counts = PIN_COUNTS.lock();
+        for page in self._pages.iter() {
+            let key = page_key(page);
+            if let Some(count) = pin_counts.get_mut(&key) {
+                *count -= 1;
+                if *count == 0 {
+                    pin_counts.remove(&key);
+                }
+            }
+        }
+        drop(pin_counts);
+        self.counter.fetch_sub(self.paddrs.len(), Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,492 @@
+//! System V 信号量集。
+//!
+//! 一次 semop 中的所有操作要么全部完成，要么都不执行：任何一个操作需要等待时不修改信号量，
+//! 由调用者在信号量集的等待队列上等待，信号量的值发生变化或者信号量集被删除时唤醒所有等待者后重新尝试。
+//!
+//! 带有 `SEM_UNDO` 的操作在所属进程的调整值中记录相反的修改，进程退出时由 [`exit_sem`] 应用这些调整值，
+//! 信号量的值被限制在 0 与 [`SEMVMX`] 之间。SETVAL 与 SETALL 会清除所有进程对被设置的信号量的调整值。
+//! fork 出的子进程不继承调整值。
+use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use axerrno::{AxError, LinuxError, LinuxResult};
+use axhal::time::current_time;
+use core::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+use sync::{SpinNoIrq, WaitQueue};
+
+use crate::ipc::{ipc_get, ipc_remove, IpcKeys, IpcPerm, IPC_NOWAIT};
+
+/// 一个信号量集中信号量的最大数量
+pub const SEMMSL: usize = 32000;
+/// 信号量的最大值
+pub const SEMVMX: i32 = 32767;
+/// 一次 semop 的最大操作数
+pub const SEMOPM: usize = 500;
+
+/// 进程退出时撤销这个操作
+pub const SEM_UNDO: i16 = 0x1000;
+
+static SEMID: AtomicI32 = AtomicI32::new(1);
+
+static SEM_SETS: SpinNoIrq<BTreeMap<i32, Arc<SemSet>>> = SpinNoIrq::new(BTreeMap::new());
+
+/// The map from key to semid. It's used to query semid from key.
+pub static KEY_TO_SEMID: IpcKeys = SpinNoIrq::new(BTreeMap::new());
+
+/// 每个进程的调整值，键为 (semid, 信号量的下标)
+static SEM_UNDOS: SpinNoIrq<BTreeMap<u64, BTreeMap<(i32, u16), i32>>> =
+    SpinNoIrq::new(BTreeMap::new());
+
+/// semop 的一个操作，与用户态的 `struct sembuf` 布局相同
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SemBuf {
+    /// 信号量的下标
+    pub sem_num: u16,
+    /// 大于 0 时增加信号量，小于 0 时等待信号量不小于其绝对值后减少，为 0 时等待信号量变为 0
+    pub sem_op: i16,
+    /// `IPC_NOWAIT` 与 `SEM_UNDO`
+    pub sem_flg: i16,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Sem {
+    val: i32,
+    /// 最近一次操作这个信号量的进程
+    pid: u64,
+    /// 等待信号量增加的任务数
+    ncnt: usize,
+    /// 等待信号量变为 0 的任务数
+    zcnt: usize,
+}
+
+/// 信号量集的状态，由 IPC_STAT 返回
+#[derive(Debug, Clone, Copy)]
+pub struct SemSetInfo {
+    pub perm: IpcPerm,
+    /// 最近一次 semop 的时间
+    pub otime: usize,
+    /// 最近一次修改的时间
+    pub ctime: usize,
+    /// 信号量的数量
+    pub nsems: usize,
+}
+
+struct SemSetInner {
+    perm: IpcPerm,
+    otime: usize,
+    ctime: usize,
+    sems: Vec<Sem>,
+    removed: bool,
+}
+
+/// 一个信号量集
+pub struct SemSet {
+    semid: i32,
+    inner: SpinNoIrq<SemSetInner>,
+    version: AtomicUsize,
+    /// 等待信号量的任务
+    pub wq: WaitQueue,
+}
+
+/// 按照 `key` 与 `flags` 查找或者创建含有 `nsems` 个信号量的信号量集，返回其标识符以及是否为新建的信号量集
+///
+/// 已经存在的信号量集中的信号量少于 `nsems` 时返回 EINVAL
+pub fn semget(key: i32, nsems: usize, flags: i32, uid: u32, gid: u32) -> LinuxResult<(i32, bool)> {
+    if nsems > SEMMSL {
+        return Err(LinuxError::EINVAL);
+    }
+    let (semid, created) = ipc_get(&KEY_TO_SEMID, key, flags, || {
+        if nsems == 0 {
+            return Err(AxError::InvalidInput);
+        }
+        let semid = SEMID.fetch_add(1, Ordering::Relaxed);
+        let set = SemSet::new(semid, IpcPerm::new(key, uid, gid, flags as u16), nsems);
+        SEM_SETS.lock().insert(semid, Arc::new(set));
+        Ok(semid)
+    })?;
+    if get_sem_set(semid).is_some_and(|set| set.nsems() < nsems) {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok((semid, created))
+}
+
+/// Get a SemSet by semid.
+pub fn get_sem_set(semid: i32) -> Option<Arc<SemSet>> {
+    SEM_SETS.lock().get(&semid).cloned()
+}
+
+/// 删除信号量集并唤醒所有等待者，各个进程对它的调整值在进程退出时被忽略
+pub fn remove_sem_set(semid: i32) {
+    ipc_remove(&KEY_TO_SEMID, semid);
+    if let Some(set) = SEM_SETS.lock().remove(&semid) {
+        set.inner.lock().removed = true;
+        set.changed();
+    }
+}
+
+/// 进程 `pid` 退出时应用它的所有调整值
+pub fn exit_sem(pid: u64) {
+    let Some(undos) = SEM_UNDOS.lock().remove(&pid) else {
+        return;
+    };
+    let mut sets: BTreeMap<i32, Vec<(u16, i32)>> = BTreeMap::new();
+    for ((semid, sem_num), adj) in undos {
+        sets.entry(semid).or_default().push((sem_num, adj));
+    }
+    for (semid, adjs) in sets {
+        let Some(set) = get_sem_set(semid) else {
+            continue;
+        };
+        let mut inner = set.inner.lock();
+        if inner.removed {
+            continue;
+        }
+        for (sem_num, adj) in adjs {
+            if let Some(sem) = inner.sems.get_mut(sem_num as usize) {
+                sem.val = (sem.val + adj).clamp(0, SEMVMX);
+                sem.pid = pid;
+            }
+        }
+        inner.ctime = current_time().as_secs() as usize;
+        drop(inner);
+        set.changed();
+    }
+}
+
+/// 清除所有进程对 `semid` 中下标属于 `sem_nums` 的信号量的调整值
+fn clear_undos(semid: i32, mut sem_nums: impl FnMut(u16) -> bool) {
+    for undos in SEM_UNDOS.lock().values_mut() {
+        undos.retain(|&(id, sem_num), _| id != semid || !sem_nums(sem_num));
+    }
+}
+
+impl SemSet {
+    fn new(semid: i32, perm: IpcPerm, nsems: usize) -> Self {
+        Self {
+            semid,
+            inner: SpinNoIrq::new(SemSetInner {
+                perm,
+                otime: 0,
+                ctime: current_time().as_secs() as usize,
+                sems: vec![Sem::default(); nsems],
+                removed: false,
+            }),
+            version: AtomicUsize::new(0),
+            wq: WaitQueue::new(),
+        }
+    }
+
+    /// 信号量的数量
+    pub fn nsems(&self) -> usize {
+        self.inner.lock().sems.len()
+    }
+
+    /// 信号量集的状态
+    pub fn info(&self) -> SemSetInfo {
+        let inner = self.inner.lock();
+        SemSetInfo {
+            perm: inner.perm,
+            otime: inner.otime,
+            ctime: inner.ctime,
+            nsems: inner.sems.len(),
+        }
+    }
+
+    /// IPC_SET：修改所有者与访问权限
+    pub fn set_perm(&self, uid: u32, gid: u32, mode: u16) {
+        let mut inner = self.inner.lock();
+        inner.perm.set(uid, gid, mode);
+        inner.ctime = current_time().as_secs() as usize;
+    }
+
+    /// 尝试原子地完成 `ops` 中的所有操作，返回 Some(i) 表示第 i 个操作需要等待，此时不修改任何信号量
+    ///
+    /// 需要等待的操作带有 `IPC_NOWAIT` 时返回 EAGAIN。信号量或者 `SEM_UNDO` 的调整值超出范围时返回 ERANGE
+    pub fn try_semop(&self, ops: &[SemBuf], pid: u64) -> LinuxResult<Option<usize>> {
+        let mut inner = self.inner.lock();
+        if inner.removed {
+            return Err(LinuxError::EIDRM);
+        }
+        if ops.iter().any(|op| op.sem_num as usize >= inner.sems.len()) {
+            return Err(LinuxError::EFBIG);
+        }
+        let mut vals: Vec<i32> = inner.sems.iter().map(|sem| sem.val).collect();
+        for (index, op) in ops.iter().enumerate() {
+            let val = &mut vals[op.sem_num as usize];
+            let blocked = match op.sem_op {
+                0 => *val != 0,
+                sem_op => *val + (sem_op as i32) < 0,
+            };
+            if blocked {
+                return if op.sem_flg & IPC_NOWAIT as i16 != 0 {
+                    Err(LinuxError::EAGAIN)
+                } else {
+                    Ok(Some(index))
+                };
+            }
+            *val += op.sem_op as i32;
+            if *val > SEMVMX {
+                return Err(LinuxError::ERANGE);
+            }
+        }
+
+        let mut all_undos = SEM_UNDOS.lock();
+        let mut adjs = BTreeMap::new();
+        for op in ops.iter().filter(|op| op.sem_flg & SEM_UNDO != 0) {
+            let key = (self.semid, op.sem_num);
+            let adj = adjs.entry(key).or_insert_with(|| {
+                all_undos
+                    .get(&pid)
+                    .and_then(|undos| undos.get(&key).copied())
+                    .unwrap_or(0)
+            });
+            *adj -= op.sem_op as i32;
+            if !(-SEMVMX..=SEMVMX).contains(adj) {
+                return Err(LinuxError::ERANGE);
+            }
+        }
+        if !adjs.is_empty() {
+            let undos = all_undos.entry(pid).or_default();
+            for (key, adj) in adjs {
+                if adj == 0 {
+                    undos.remove(&key);
+                } else {
+                    undos.insert(key, adj);
+                }
+            }
+            if undos.is_empty() {
+                all_undos.remove(&pid);
+            }
+        }
+        drop(all_undos);
+
+        for op in ops {
+            inner.sems[op.sem_num as usize].pid = pid;
+        }
+        for (sem, val) in inner.sems.iter_mut().zip(vals) {
+            sem.val = val;
+        }
+        inner.otime = current_time().as_secs() as usize;
+        drop(inner);
+        self.changed();
+        Ok(None)
+    }
+
+    /// 记录当前任务开始或者结束等待操作 `op`，用于 GETNCNT 与 GETZCNT
+    pub fn set_waiting(&self, op: &SemBuf, waiting: bool) {
+        let mut inner = self.inner.lock();
+        let Some(sem) = inner.sems.get_mut(op.sem_num as usize) else {
+            return;
+        };
+        let count = if op.sem_op == 0 {
+            &mut sem.zcnt
+        } else {
+            &mut sem.ncnt
+        };
+        if waiting {
+            *count += 1;
+        } else {
+            *count -= 1;
+        }
+    }
+
+    fn with_sem<T>(&self, sem_num: usize, f: impl FnOnce(&Sem) -> T) -> LinuxResult<T> {
+        let inner = self.inner.lock();
+        if inner.removed {
+            return Err(LinuxError::EIDRM);
+        }
+        inner.sems.get(sem_num).map(f).ok_or(LinuxError::EINVAL)
+    }
+
+    /// GETVAL：信号量的值
+    pub fn get_val(&self, sem_num: usize) -> LinuxResult<i32> {
+        self.with_sem(sem_num, |sem| sem.val)
+    }
+
+    /// GETPID：最近一次操作信号量的进程
+    pub fn get_pid(&self, sem_num: usize) -> LinuxResult<u64> {
+        self.with_sem(sem_num, |sem| sem.pid)
+    }
+
+    /// GETNCNT：等待信号量增加的任务数
+    pub fn get_ncnt(&self, sem_num: usize) -> LinuxResult<usize> {
+        self.with_sem(sem_num, |sem| sem.ncnt)
+    }
+
+    /// GETZCNT：等待信号量变为 0 的任务数
+    pub fn get_zcnt(&self, sem_num: usize) -> LinuxResult<usize> {
+        self.with_sem(sem_num, |sem| sem.zcnt)
+    }
+
+    /// GETALL：所有信号量的值
+    pub fn get_all(&self) -> Vec<u16> {
+        let inner = self.inner.lock();
+        inner.sems.iter().map(|sem| sem.val as u16).collect()
+    }
+
+    /// SETVAL：设置信号量的值，并清除所有进程对它的调整值
+    pub fn set_val(&self, sem_num: usize, val: i32, pid: u64) -> LinuxResult {
+        if !(0..=SEMVMX).contains(&val) {
+            return Err(LinuxError::ERANGE);
+        }
+        let mut inner = self.inner.lock();
+        if inner.removed {
+            return Err(LinuxError::EIDRM);
+        }
+        let sem = inner.sems.get_mut(sem_num).ok_or(LinuxError::EINVAL)?;
+        sem.val = val;
+        sem.pid = pid;
+        inner.ctime = current_time().as_secs() as usize;
+        clear_undos(self.semid, |num| num as usize == sem_num);
+        drop(inner);
+        self.changed();
+        Ok(())
+    }
+
+    /// SETALL：设置所有信号量的值，并清除所有进程对这个信号量集的调整值
+    pub fn set_all(&self, vals: &[u16], pid: u64) -> LinuxResult {
+        if vals.iter().any(|&val| val as i32 > SEMVMX) {
+            return Err(LinuxError::ERANGE);
+        }
+        let mut inner = self.inner.lock();
+        if inner.removed {
+            return Err(LinuxError::EIDRM);
+        }
+        if vals.len() != inner.sems.len() {
+            return Err(LinuxError::EINVAL);
+        }
+        for (sem, &val) in inner.sems.iter_mut().zip(vals) {
+            sem.val = val as i32;
+            sem.pid = pid;
+        }
+        inner.ctime = current_time().as_secs() as usize;
+        clear_undos(self.semid, |_| true);
+        drop(inner);
+        self.changed();
+        Ok(())
+    }
+
+    /// 信号量集的版本，每次信号量的值发生变化或者被删除时递增。
+    ///
+    /// 操作需要等待时，等待者在尝试之前记录版本，等待到版本变化之后再次尝试
+    pub fn version(&self) -> usize {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// 信号量发生了变化，唤醒所有等待者
+    fn changed(&self) {
+        self.version.fetch_add(1, Ordering::Release);
+        self.wq.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{exit_sem, SemBuf, SemSet, SEMVMX, SEM_SETS, SEM_UNDO, SEM_UNDOS};
+    use crate::ipc::{IpcPerm, IPC_NOWAIT};
+    use alloc::sync::Arc;
+    use axerrno::LinuxError;
+
+    fn op(sem_num: u16, sem_op: i16, sem_flg: i16) -> SemBuf {
+        SemBuf {
+            sem_num,
+            sem_op,
+            sem_flg,
+        }
+    }
+
+    /// 创建并登记一个信号量集。调整值是全局的，各个测试使用不同的 semid 与 pid
+    fn new_set(semid: i32, nsems: usize) -> Arc<SemSet> {
+        let set = Arc::new(SemSet::new(semid, IpcPerm::new(0, 0, 0, 0o600), nsems));
+        SEM_SETS.lock().insert(semid, set.clone());
+        set
+    }
+
+    /// 进程 `pid` 对信号量的调整值
+    fn undo_of(pid: u64, semid: i32, sem_num: u16) -> Option<i32> {
+        SEM_UNDOS
+            .lock()
+            .get(&pid)
+            .and_then(|undos| undos.get(&(semid, sem_num)).copied())
+    }
+
+    #[test]
+    fn test_semop_all_or_nothing() {
+        let set = new_set(1001, 2);
+        assert_eq!(Ok(None), set.try_semop(&[op(0, 2, 0)], 1001));
+        // 第二个操作需要等待，第一个操作也不生效
+        assert_eq!(
+            Ok(Some(1)),
+            set.try_semop(&[op(0, -1, 0), op(1, -1, 0)], 1001)
+        );
+        assert_eq!(
+            Err(LinuxError::EAGAIN),
+            set.try_semop(&[op(0, -1, 0), op(1, -1, IPC_NOWAIT as i16)], 1001)
+        );
+        assert_eq!(Ok(Some(0)), set.try_semop(&[op(0, 0, 0)], 1001));
+        assert_eq!(
+            Err(LinuxError::ERANGE),
+            set.try_semop(&[op(1, 1, 0), op(0, SEMVMX as i16, 0)], 1001)
+        );
+        assert_eq!(
+            Err(LinuxError::EFBIG),
+            set.try_semop(&[op(0, -1, 0), op(2, 1, 0)], 1001)
+        );
+        assert_eq!(Ok(2), set.get_val(0));
+        assert_eq!(Ok(0), set.get_val(1));
+    }
+
+    #[test]
+    fn test_semop_sees_earlier_ops() {
+        let set = new_set(1002, 1);
+        // 同一次 semop 中后面的操作看到前面的操作的结果
+        assert_eq!(Ok(None), set.try_semop(&[op(0, 1, 0), op(0, -1, 0)], 1002));
+        assert_eq!(
+            Ok(Some(1)),
+            set.try_semop(&[op(0, 1, 0), op(0, 0, 0)], 1002)
+        );
+        assert_eq!(Ok(0), set.get_val(0));
+    }
+
+    #[test]
+    fn test_semop_undo() {
+        let set = new_set(1003, 1);
+        assert_eq!(Ok(None), set.try_semop(&[op(0, 3, SEM_UNDO)], 1003));
+        assert_eq!(Some(-3), undo_of(1003, 1003, 0));
+        assert_eq!(Ok(None), set.try_semop(&[op(0, -1, SEM_UNDO)], 1003));
+        assert_eq!(Some(-2), undo_of(1003, 1003, 0));
+        // 不带 SEM_UNDO 的操作不记录调整值
+        assert_eq!(Ok(None), set.try_semop(&[op(0, 1, 0)], 1003));
+        assert_eq!(Some(-2), undo_of(1003, 1003, 0));
+        assert_eq!(Ok(3), set.get_val(0));
+
+        exit_sem(1003);
+        assert_eq!(Ok(1), set.get_val(0));
+        assert!(!SEM_UNDOS.lock().contains_key(&1003));
+    }
+
+    #[test]
+    fn test_semop_undo_cancels() {
+        let set = new_set(1004, 1);
+        assert_eq!(Ok(None), set.try_semop(&[op(0, 1, SEM_UNDO)], 1004));
+        assert_eq!(Ok(None), set.try_semop(&[op(0, -1, SEM_UNDO)], 1004));
+        // 调整值抵消为 0 时不再记录
+        assert!(!SEM_UNDOS.lock().contains_key(&1004));
+    }
+
+    #[test]
+    fn test_semop_undo_out_of_range() {
+        let set = new_set(1005, 1);
+        assert_eq!(
+            Ok(None),
+            set.try_semop(&[op(0, SEMVMX as i16, SEM_UNDO)], 1005)
+        );
+        assert_eq!(Ok(None), set.try_semop(&[op(0, -(SEMVMX as i16), 0)], 1005));
+        // 调整值超出范围时不修改信号量与调整值
+        assert_eq!(
+            Err(LinuxError::ERANGE),
+            set.try_semop(&[op(0, 1, SEM_UNDO)], 1005)
+        );
+        assert_eq!(Ok(0), set.get_val(0));
+        assert_eq!(Some(-SEMVMX), undo_of(1005, 1005, 0));
+    }
+}
@@ -0,0 +1,468 @@
+//! 匿名页面的交换。
+//!
+//! 交换空间是通过 async_fs 打开的块设备或者文件，按页划分为交换槽，第 0 页保留给 mkswap 写入的头部。
+//! 被换出的页面在页表中留下一个不存在的页表项：标志为空，物理地址部分保存交换槽号。
+//! 缺页时由发生缺页的任务异步地读回，读取期间其他协程可以继续运行。
+//!
+//! 页面回收使用时钟算法：每个地址空间记录最近被访问过的页面（发生过缺页或者被内核访问过），
+//! 并且测试页表项中的访问位，扫描时这些页面获得第二次机会。只有私有匿名映射中不被其他地址空间共享的页面会被换出，
+//! 因此不需要反向映射。
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use async_vfs::{AsyncVfsNodeOps, VfsNodeRef};
+use axerrno::{AxError, AxResult};
+use axhal::{
+    mem::{PhysAddr, VirtAddr, PAGE_SIZE_4K},
+    paging::{MappingFlags, PageSize, PageTable},
+};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use page_table_entry::GenericPTE;
+use sync::{Mutex, SpinNoIrq};
+
+use crate::MemorySet;
+
+/// 内存不足时一次换出的页数
+pub const SWAP_CLUSTER: usize = 32;
+
+/// 交换槽的分配状态
+struct SwapSlots {
+    /// 每个交换槽的引用计数，fork 之后父子进程共享同一个交换槽。第 0 个交换槽保留
+    counts: Vec<u32>,
+    free: usize,
+    /// 下一次从这里开始查找空闲的交换槽
+    next: usize,
+}
+
+impl SwapSlots {
+    /// 含有 `pages` 个交换槽，其中第 0 个保留
+    fn new(pages: usize) -> Self {
+        let mut counts = vec![0; pages];
+        counts[0] = u32::MAX;
+        Self {
+            counts,
+            free: pages - 1,
+            next: 1,
+        }
+    }
+
+    /// 可以使用的交换槽数
+    fn total(&self) -> usize {
+        self.counts.len() - 1
+    }
+
+    fn alloc(&mut self) -> Option<usize> {
+        if self.free == 0 {
+            return None;
+        }
+        let slot = (self.next..self.counts.len())
+            .chain(1..self.next)
+            .find(|&slot| self.counts[slot] == 0)?;
+        self.counts[slot] = 1;
+        self.free -= 1;
+        self.next = slot + 1;
+        Some(slot)
+    }
+
+    fn dup(&mut self, slot: usize) -> AxResult {
+        self.counts[slot] = self.counts[slot].checked_add(1).ok_or(AxError::NoMemory)?;
+        Ok(())
+    }
+
+    fn free(&mut self, slot: usize) {
+        self.counts[slot] -= 1;
+        if self.counts[slot] == 0 {
+            self.free += 1;
+        }
+    }
+}
+
+struct SwapSpace {
+    path: String,
+    node: VfsNodeRef,
+    slots: SwapSlots,
+    /// 正在 swapoff，不再分配新的交换槽
+    closing: bool,
+}
+
+static SWAP: SpinNoIrq<Option<SwapSpace>> = SpinNoIrq::new(None);
+
+/// mkswap 在第 0 页末尾写入的签名
+const SWAP_SIGNATURE: &[u8] = b"SWAPSPACE2";
+
+/// 头部中 `last_page` 字段的偏移，位于 1024 字节的引导块与 4 字节的版本号之后
+const SWAP_LAST_PAGE_OFFSET: usize = 1028;
+
+/// 检查 mkswap 写入的第 0 页，返回交换空间的页数（包括第 0 页）
+fn check_header(header: &[u8], pages: usize) -> AxResult<usize> {
+    if header.len() != PAGE_SIZE_4K || !header.ends_with(SWAP_SIGNATURE) {
+        return Err(AxError::InvalidInput);
+    }
+    let last_page = &header[SWAP_LAST_PAGE_OFFSET..SWAP_LAST_PAGE_OFFSET + 4];
+    let last_page = u32::from_ne_bytes(last_page.try_into().unwrap()) as usize;
+    let pages = pages.min(last_page.saturating_add(1));
+    if pages < 2 {
+        return Err(AxError::InvalidInput);
+    }
+    Ok(pages)
+}
+
+/// 参与页面回收的地址空间
+static MEMORY_SETS: SpinNoIrq<Vec<Weak<Mutex<MemorySet>>>> = SpinNoIrq::new(Vec::new());
+
+/// 下一次回收从第几个地址空间开始
+static NEXT_SET: AtomicUsize = AtomicUsize::new(0);
+
+/// 以 `path` 处的块设备或者文件作为交换空间，其第 0 页必须是 mkswap 写入的头部
+pub async fn swapon(path: &str) -> AxResult {
+    let node = async_fs::api::lookup(path).await?;
+    let attr = node.get_attr().await?;
+    if !attr.is_file() && !attr.file_type().is_block_device() {
+        return Err(AxError::InvalidInput);
+    }
+    let pages = (attr.size() / PAGE_SIZE_4K as u64) as usize;
+    if pages < 2 {
+        return Err(AxError::InvalidInput);
+    }
+    let mut header = vec![0; PAGE_SIZE_4K];
+    read_exact(&node, 0, &mut header).await?;
+    let pages = check_header(&header, pages)?;
+    let mut swap = SWAP.lock();
+    if swap.is_some() {
+        return Err(AxError::ResourceBusy);
+    }
+    *swap = Some(SwapSpace {
+        path: path.into(),
+        node,
+        slots: SwapSlots::new(pages),
+        closing: false,
+    });
+    info!("swapon {}: {} pages", path, pages - 1);
+    Ok(())
+}
+
+/// 停止使用 `path` 处的交换空间，先将所有被换出的页面换入
+pub async fn swapoff(path: &str) -> AxResult {
+    match SWAP.lock().as_mut() {
+        Some(swap) if swap.path == path && !swap.closing => swap.closing = true,
+        _ => return Err(AxError::InvalidInput),
+    }
+    for memory_set in memory_sets() {
+        if let Err(err) = memory_set.lock().await.swap_in_all().await {
+            if let Some(swap) = SWAP.lock().as_mut() {
+                swap.closing = false;
+            }
+            return Err(err);
+        }
+    }
+    let mut swap = SWAP.lock();
+    if let Some(space) = swap.as_ref() {
+        // 还有页面没有换入，只可能来自没有注册的地址空间
+        if space.slots.free != space.slots.total() {
+            warn!("swapoff {}: some swapped pages are not reachable", path);
+        }
+    }
+    *swap = None;
+    info!("swapoff {}", path);
+    Ok(())
+}
+
+/// 是否有可用的交换空间
+pub fn enabled() -> bool {
+    SWAP.lock().as_ref().is_some_and(|swap| !swap.closing)
+}
+
+/// 交换空间的总大小与空闲大小，单位为字节
+pub fn swap_info() -> (usize, usize) {
+    SWAP.lock().as_ref().map_or((0, 0), |swap| {
+        (
+            swap.slots.total() * PAGE_SIZE_4K,
+            swap.slots.free * PAGE_SIZE_4K,
+        )
+    })
+}
+
+/// 分配一个交换槽
+pub(crate) fn alloc_slot() -> Option<usize> {
+    let mut swap = SWAP.lock();
+    swap.as_mut().filter(|swap| !swap.closing)?.slots.alloc()
+}
+
+/// 增加交换槽的引用计数，fork 时子进程共享父进程被换出的页面。引用计数已满时返回 [`AxError::NoMemory`]
+pub(crate) fn dup_slot(slot: usize) -> AxResult {
+    match SWAP.lock().as_mut() {
+        Some(swap) => swap.slots.dup(slot),
+        None => Ok(()),
+    }
+}
+
+/// 减少交换槽的引用计数，计数为 0 时释放交换槽
+pub(crate) fn free_slot(slot: usize) {
+    if let Some(swap) = SWAP.lock().as_mut() {
+        swap.slots.free(slot);
+    }
+}
+
+fn swap_node() -> AxResult<VfsNodeRef> {
+    SWAP.lock()
+        .as_ref()
+        .map(|swap| swap.node.clone())
+        .ok_or(AxError::NotFound)
+}
+
+/// 将一页写入交换槽 `slot`
+pub(crate) async fn write_slot(slot: usize, buf: &[u8]) -> AxResult {
+    let node = swap_node()?;
+    let offset = (slot * PAGE_SIZE_4K) as u64;
+    let mut written = 0;
+    while written < buf.len() {
+        let n = node
+            .write_at(offset + written as u64, &buf[written..])
+            .await?;
+        if n == 0 {
+            return Err(AxError::Io);
+        }
+        written += n;
+    }
+    Ok(())
+}
+
+/// 从交换槽 `slot` 读出一页
+pub(crate) async fn read_slot(slot: usize, buf: &mut [u8]) -> AxResult {
+    let node = swap_node()?;
+    read_exact(&node, (slot * PAGE_SIZE_4K) as u64, buf).await
+}
+
+async fn read_exact(node: &VfsNodeRef, offset: u64, buf: &mut [u8]) -> AxResult {
+    let mut read = 0;
+    while read < buf.len() {
+        let n = node.read_at(offset + read as u64, &mut buf[read..]).await?;
+        if n == 0 {
+            return Err(AxError::Io);
+        }
+        read += n;
+    }
+    Ok(())
+}
+
+/// 将 `vaddr` 的页表项设置为指向交换槽 `slot` 的换出项
+pub(crate) fn set_swap_entry(page_table: &mut PageTable, vaddr: VirtAddr, slot: usize) {
+    let paddr = PhysAddr::from(slot * PAGE_SIZE_4K);
+    if page_table
+        .update(vaddr, Some(paddr), Some(MappingFlags::empty()))
+        .is_err()
+    {
+        page_table
+            .map_fault(vaddr, PageSize::Size4K, MappingFlags::empty())
+            .unwrap();
+        page_table
+            .update(vaddr, Some(paddr), Some(MappingFlags::empty()))
+            .unwrap();
+    }
+}
+
+/// 页表项中由硬件在访问页面时设置的访问位与脏位
+#[cfg(target_arch = "x86_64")]
+const PTE_ACCESSED: usize = 1 << 5;
+#[cfg(target_arch = "x86_64")]
+const PTE_DIRTY: usize = 1 << 6;
+#[cfg(target_arch = "riscv64")]
+const PTE_ACCESSED: usize = 1 << 6;
+#[cfg(target_arch = "riscv64")]
+const PTE_DIRTY: usize = 1 << 7;
+
+/// 测试并清除 `vaddr` 的页表项中的访问位，返回页面在上一次清除之后是否被用户访问过
+///
+/// 清除之后刷新该地址的 TLB，使下一次访问重新设置访问位。
+/// aarch64 上清除访问标志会引发访问标志异常，因此不清除，只依赖缺页与内核访问的记录
+pub(crate) fn test_and_clear_accessed(page_table: &mut PageTable, vaddr: VirtAddr) -> bool {
+    #[cfg(any(target_arch = "x86_64", target_arch = "riscv64"))]
+    {
+        let Ok((entry, _)) = page_table.get_entry_mut(vaddr) else {
+            return false;
+        };
+        if !entry.is_present() || entry.bits() & PTE_ACCESSED == 0 {
+            return false;
+        }
+        // 各个架构的页表项都是对一个机器字的封装
+        let bits = entry as *mut _ as *mut usize;
+        unsafe { bits.write_volatile(bits.read_volatile() & !PTE_ACCESSED) };
+        axhal::arch::flush_tlb(Some(vaddr));
+        true
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "riscv64")))]
+    {
+        let _ = (page_table, vaddr);
+        false
+    }
+}
+
+/// 设置 `vaddr` 的页表项中的访问位，`write` 时同时设置脏位，并刷新该地址的 TLB
+///
+/// 不由硬件维护访问位与脏位的 hart 在这两位未设置时会触发缺页，
+/// 因此页面已经映射时的缺页需要由软件设置，否则 [`test_and_clear_accessed`] 之后会一直缺页
+pub(crate) fn mark_accessed(page_table: &mut PageTable, vaddr: VirtAddr, write: bool) {
+    #[cfg(any(target_arch = "x86_64", target_arch = "riscv64"))]
+    {
+        let Ok((entry, _)) = page_table.get_entry_mut(vaddr) else {
+            return;
+        };
+        if !entry.is_present() {
+            return;
+        }
+        let set = if write {
+            PTE_ACCESSED | PTE_DIRTY
+        } else {
+            PTE_ACCESSED
+        };
+        let bits = entry as *mut _ as *mut usize;
+        unsafe { bits.write_volatile(bits.read_volatile() | set) };
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "riscv64")))]
+    let _ = (page_table, write);
+    axhal::arch::flush_tlb(Some(vaddr));
+}
+
+/// 若 `vaddr` 的页表项是换出项，返回其中的交换槽号
+pub(crate) fn swap_entry(page_table: &PageTable, vaddr: VirtAddr) -> Option<usize> {
+    let (entry, _) = page_table.get_entry_mut(vaddr).ok()?;
+    if entry.is_present() || !entry.flags().is_empty() {
+        return None;
+    }
+    let slot = entry.paddr().as_usize() / PAGE_SIZE_4K;
+    (slot != 0).then_some(slot)
+}
+
+/// 将地址空间加入页面回收的范围，进程创建时调用
+pub fn register(memory_set: &Arc<Mutex<MemorySet>>) {
+    let mut sets = MEMORY_SETS.lock();
+    sets.retain(|set| set.strong_count() > 0);
+    if !sets
+        .iter()
+        .any(|set| set.as_ptr() == Arc::as_ptr(memory_set))
+    {
+        sets.push(Arc::downgrade(memory_set));
+    }
+}
+
+/// 所有仍然存在的地址空间，从上一次回收停下的位置开始轮转
+fn memory_sets() -> Vec<Arc<Mutex<MemorySet>>> {
+    let mut sets: Vec<_> = MEMORY_SETS
+        .lock()
+        .iter()
+        .filter_map(|set| set.upgrade())
+        .collect();
+    if !sets.is_empty() {
+        let start = NEXT_SET.fetch_add(1, Ordering::Relaxed) % sets.len();
+        sets.rotate_left(start);
+    }
+    sets
+}
+
+/// 回收至多 `target` 个页面，返回回收的页数
+///
+/// 先回收页缓存中没有被映射的页面，再换出或者丢弃各个地址空间中的匿名页面。
+/// 已经被加锁的地址空间（例如正在处理缺页的地址空间）会被跳过
+pub async fn reclaim(target: usize) -> usize {
+    let mut reclaimed = async_fs::page_cache::reclaim(target).await;
+    if reclaimed >= target {
+        return reclaimed;
+    }
+    for memory_set in memory_sets() {
+        let Some(mut memory_set) = memory_set.try_lock() else {
+            continue;
+        };
+        reclaimed += memory_set.swap_out(target - reclaimed).await;
+        if reclaimed >= target {
+            break;
+        }
+    }
+    debug!("reclaimed {} pages", reclaimed);
+    reclaimed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_header, SwapSlots, SWAP_LAST_PAGE_OFFSET, SWAP_SIGNATURE};
+    use alloc::{vec, vec::Vec};
+    use axerrno::AxError;
+    use axhal::mem::PAGE_SIZE_4K;
+
+    fn header(last_page: u32) -> Vec<u8> {
+        let mut header = vec![0; PAGE_SIZE_4K];
+        header[SWAP_LAST_PAGE_OFFSET..SWAP_LAST_PAGE_OFFSET + 4]
+            .copy_from_slice(&last_page.to_ne_bytes());
+        header[PAGE_SIZE_4K - SWAP_SIGNATURE.len()..].copy_from_slice(SWAP_SIGNATURE);
+        header
+    }
+
+    #[test]
+    fn test_check_header() {
+        assert_eq!(Ok(8), check_header(&header(7), 8));
+        // last_page 之后的部分不使用
+        assert_eq!(Ok(4), check_header(&header(3), 8));
+        assert_eq!(Ok(8), check_header(&header(100), 8));
+        assert_eq!(Err(AxError::InvalidInput), check_header(&header(0), 8));
+        // 没有 mkswap 的签名
+        let mut raw = header(7);
+        raw[PAGE_SIZE_4K - 1] = 0;
+        assert_eq!(Err(AxError::InvalidInput), check_header(&raw, 8));
+        assert_eq!(
+            Err(AxError::InvalidInput),
+            check_header(&vec![0; PAGE_SIZE_4K], 8)
+        );
+    }
+
+    #[test]
+    fn test_alloc_skips_header() {
+        let mut slots = SwapSlots::new(4);
+        assert_eq!(3, slots.total());
+        assert_eq!(Some(1), slots.alloc());
+        assert_eq!(Some(2), slots.alloc());
+        assert_eq!(Some(3), slots.alloc());
+        assert_eq!(None, slots.alloc());
+        assert_eq!(0, slots.free);
+    }
+
+    #[test]
+    fn test_alloc_wraps_around() {
+        let mut slots = SwapSlots::new(4);
+        for _ in 0..3 {
+            slots.alloc();
+        }
+        slots.free(2);
+        assert_eq!(Some(2), slots.alloc());
+        slots.free(1);
+        slots.free(3);
+        // 从上一次分配的位置之后开始查找
+        assert_eq!(Some(3), slots.alloc());
+        assert_eq!(Some(1), slots.alloc());
+    }
+
+    #[test]
+    fn test_shared_slot() {
+        let mut slots = SwapSlots::new(2);
+        let slot = slots.alloc().unwrap();
+        slots.dup(slot).unwrap();
+        slots.free(slot);
+        // 仍然被另一个地址空间引用
+        assert_eq!(0, slots.free);
+        assert_eq!(None, slots.alloc());
+        slots.free(slot);
+        assert_eq!(1, slots.free);
+        assert_eq!(Some(slot), slots.alloc());
+    }
+
+    #[test]
+    fn test_dup_overflow() {
+        let mut slots = SwapSlots::new(2);
+        let slot = slots.alloc().unwrap();
+        slots.counts[slot] = u32::MAX - 1;
+        slots.dup(slot).unwrap();
+        assert_eq!(Err(AxError::NoMemory), slots.dup(slot));
+        assert_eq!(u32::MAX, slots.counts[slot]);
+    }
+}
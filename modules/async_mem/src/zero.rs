@@ -0,0 +1,33 @@
+//! 匿名映射共享的零页。
+//!
+//! 私有匿名映射中尚未分配的页面发生读缺页时，只读地映射同一个全局的零页，而不是分配并清零一个新的物理页。
+//! 零页与其他物理页一样记录在区域中，由于这里始终持有一份引用，它总是被视为共享的页面：
+//! 第一次写入时由写时复制换成私有的物理页，也不会被换出或者丢弃。
+//!
+//! 共享的匿名映射不使用零页，否则写入会被所有映射了零页的地址空间看到。
+use alloc::sync::Arc;
+use axalloc::PhysPage;
+use axhal::mem::{virt_to_phys, PhysAddr};
+use sync::{Mutex, SpinNoIrq};
+
+static ZERO_PAGE: SpinNoIrq<Option<(Arc<Mutex<PhysPage>>, PhysAddr)>> = SpinNoIrq::new(None);
+
+/// 获取零页与其物理地址，第一次调用时分配。分配失败时返回 `None`，调用者退回分配普通页面
+pub(crate) fn zero_page() -> Option<(Arc<Mutex<PhysPage>>, PhysAddr)> {
+    let mut zero = ZERO_PAGE.lock();
+    if zero.is_none() {
+        let mut page = PhysPage::alloc().ok()?;
+        page.fill(0);
+        let paddr = virt_to_phys(page.start_vaddr);
+        *zero = Some((Arc::new(Mutex::new(page)), paddr));
+    }
+    zero.clone()
+}
+
+/// `page` 是否为零页
+pub(crate) fn is_zero_page(page: &Arc<Mutex<PhysPage>>) -> bool {
+    ZERO_PAGE
+        .lock()
+        .as_ref()
+        .is_some_and(|(zero, _)| Arc::ptr_eq(zero, page))
+}
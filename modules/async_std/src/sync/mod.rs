@@ -9,7 +9,11 @@ pub use alloc::sync::{Arc, Weak};
 
 #[cfg(feature = "multitask")]
 #[doc(cfg(feature = "multitask"))]
-pub use sync::{Mutex, MutexGuard};
+pub use sync::{
+    broadcast, mpsc, oneshot, Barrier, BarrierWaitResult, Condvar, Mutex, MutexGuard,
+    OwnedSemaphorePermit, RwLock, RwLockReadGuard, RwLockUpgradableGuard, RwLockWriteGuard,
+    Semaphore, SemaphorePermit, WaitTimeoutResult,
+};
 
 #[cfg(not(feature = "multitask"))]
 #[doc(cfg(not(feature = "multitask")))]
@@ -6,17 +6,18 @@
 //!         1. open
 //!         2. release
 //!         3. get_attr
-//!         4. read_at
-//!         5. write_at
-//!         6. fsync
-//!         7. truncate
-//!         8. parent
-//!         9. lookup
-//!         10. create
-//!         11. remove
-//!         12. read_dir
-//!         13. rename
-//!         14. as_any
+//!         4. set_attr
+//!         5. read_at
+//!         6. write_at
+//!         7. fsync
+//!         8. truncate
+//!         9. parent
+//!         10. lookup
+//!         11. create
+//!         12. remove
+//!         13. read_dir
+//!         14. rename
+//!         15. as_any
 //!     2. VfsOps trait：定义了文件系统的接口
 //!         1. mount
 //!         2. format
@@ -111,6 +112,17 @@ pub trait VfsNodeOps: Send + Sync + Unpin {
         Poll::Ready(ax_err!(Unsupported))
     }
 
+    /// Set the permission mode and the owner of the node.
+    ///
+    /// Only the permission and the owner in `attr` are used.
+    fn poll_set_attr(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        _attr: VfsNodeAttr,
+    ) -> Poll<VfsResult> {
+        Poll::Ready(ax_err!(Unsupported))
+    }
+
     // file operations:
 
     /// Read data from the file at the given offset.
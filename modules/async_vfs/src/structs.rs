@@ -16,6 +16,10 @@ pub struct VfsNodeAttr {
     size: u64,
     /// Number of 512B blocks allocated.
     blocks: u64,
+    /// User ID of the owner.
+    uid: u32,
+    /// Group ID of the owner.
+    gid: u32,
 }
 
 bitflags::bitflags! {
@@ -198,13 +202,15 @@ impl VfsNodeType {
 
 impl VfsNodeAttr {
     /// Creates a new `VfsNodeAttr` with the given permission mode, type, size
-    /// and number of blocks.
+    /// and number of blocks, owned by root.
     pub const fn new(mode: VfsNodePerm, ty: VfsNodeType, size: u64, blocks: u64) -> Self {
         Self {
             mode,
             ty,
             size,
             blocks,
+            uid: 0,
+            gid: 0,
         }
     }
 
@@ -215,6 +221,8 @@ impl VfsNodeAttr {
             ty: VfsNodeType::File,
             size,
             blocks,
+            uid: 0,
+            gid: 0,
         }
     }
 
@@ -226,6 +234,8 @@ impl VfsNodeAttr {
             ty: VfsNodeType::Dir,
             size,
             blocks,
+            uid: 0,
+            gid: 0,
         }
     }
 
@@ -249,6 +259,22 @@ impl VfsNodeAttr {
         self.mode = perm
     }
 
+    /// Returns the user ID of the owner.
+    pub const fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// Returns the group ID of the owner.
+    pub const fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    /// Sets the owner of the node.
+    pub fn set_owner(&mut self, uid: u32, gid: u32) {
+        self.uid = uid;
+        self.gid = gid;
+    }
+
     /// Returns the type of the node.
     pub const fn file_type(&self) -> VfsNodeType {
         self.ty
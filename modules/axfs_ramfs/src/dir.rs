@@ -2,7 +2,7 @@ use alloc::collections::BTreeMap;
 use alloc::sync::{Arc, Weak};
 use alloc::{string::String, vec::Vec};
 
-use async_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use async_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType};
 use async_vfs::{VfsError, VfsResult};
 use core::{
     pin::Pin,
@@ -20,6 +20,9 @@ pub struct DirNode {
     this: Weak<DirNode>,
     parent: RwLock<Weak<dyn VfsNodeOps + Unpin + Send + Sync>>,
     children: RwLock<BTreeMap<String, VfsNodeRef>>,
+    perm: RwLock<VfsNodePerm>,
+    /// (uid, gid)
+    owner: RwLock<(u32, u32)>,
 }
 
 impl DirNode {
@@ -28,6 +31,8 @@ impl DirNode {
             this: this.clone(),
             parent: RwLock::new(parent.unwrap_or_else(|| Weak::<Self>::new())),
             children: RwLock::new(BTreeMap::new()),
+            perm: RwLock::new(VfsNodePerm::default_dir()),
+            owner: RwLock::new((0, 0)),
         })
     }
 
@@ -68,6 +73,16 @@ impl DirNode {
         Ok(())
     }
 
+    /// Adds an existing node with the given name to this directory.
+    pub fn add_node(&self, name: &str, node: VfsNodeRef) -> VfsResult {
+        let mut children = self.children.write();
+        if children.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        children.insert(name.into(), node);
+        Ok(())
+    }
+
     /// Removes a node by the given name in this directory.
     pub fn remove_node(&self, name: &str) -> VfsResult {
         let mut children = self.children.write();
@@ -84,7 +99,21 @@ impl DirNode {
 
 impl VfsNodeOps for DirNode {
     fn poll_get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
-        Poll::Ready(Ok(VfsNodeAttr::new_dir(4096, 0)))
+        let (uid, gid) = *self.owner.read();
+        let mut attr = VfsNodeAttr::new_dir(4096, 0);
+        attr.set_perm(*self.perm.read());
+        attr.set_owner(uid, gid);
+        Poll::Ready(Ok(attr))
+    }
+
+    fn poll_set_attr(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        attr: VfsNodeAttr,
+    ) -> Poll<VfsResult> {
+        *self.perm.write() = attr.perm();
+        *self.owner.write() = (attr.uid(), attr.gid());
+        Poll::Ready(Ok(()))
     }
 
     fn parent(&self) -> Option<VfsNodeRef> {
@@ -1,5 +1,5 @@
 use alloc::vec::Vec;
-use async_vfs::{impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsResult};
+use async_vfs::{impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsResult};
 use core::{
     pin::Pin,
     task::{Context, Poll},
@@ -11,6 +11,9 @@ use spin::RwLock;
 /// It implements [`axfs_vfs::VfsNodeOps`].
 pub struct FileNode {
     content: RwLock<Vec<u8>>,
+    perm: RwLock<VfsNodePerm>,
+    /// (uid, gid)
+    owner: RwLock<(u32, u32)>,
 }
 
 impl FileNode {
@@ -18,13 +21,29 @@ impl FileNode {
     pub const fn new() -> Self {
         Self {
             content: RwLock::new(Vec::new()),
+            perm: RwLock::new(VfsNodePerm::default_file()),
+            owner: RwLock::new((0, 0)),
         }
     }
 }
 
 impl VfsNodeOps for FileNode {
     fn poll_get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
-        Poll::Ready(Ok(VfsNodeAttr::new_file(self.content.read().len() as _, 0)))
+        let (uid, gid) = *self.owner.read();
+        let mut attr = VfsNodeAttr::new_file(self.content.read().len() as _, 0);
+        attr.set_perm(*self.perm.read());
+        attr.set_owner(uid, gid);
+        Poll::Ready(Ok(attr))
+    }
+
+    fn poll_set_attr(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        attr: VfsNodeAttr,
+    ) -> Poll<VfsResult> {
+        *self.perm.write() = attr.perm();
+        *self.owner.write() = (attr.uid(), attr.gid());
+        Poll::Ready(Ok(()))
     }
 
     fn poll_truncate(self: Pin<&Self>, _cx: &mut Context<'_>, size: u64) -> Poll<VfsResult> {
@@ -0,0 +1,58 @@
+use alloc::string::String;
+use async_vfs::{impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsResult};
+use axerrno::AxError;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// 内容在每次读取时重新生成的只读文件，用于 procfs 中随内核状态变化的文件。
+///
+/// 与 Linux 的 procfs 相同，文件的大小总是 0，读者需要一直读到返回 0 为止。
+pub struct GeneratedFile {
+    render: fn() -> String,
+}
+
+impl GeneratedFile {
+    /// Creates a file whose content is produced by `render` on every read.
+    pub const fn new(render: fn() -> String) -> Self {
+        Self { render }
+    }
+}
+
+impl VfsNodeOps for GeneratedFile {
+    fn poll_get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
+        let mut attr = VfsNodeAttr::new_file(0, 0);
+        attr.set_perm(VfsNodePerm::from_bits_truncate(0o444));
+        Poll::Ready(Ok(attr))
+    }
+
+    fn poll_read_at(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Poll<VfsResult<usize>> {
+        let content = (self.render)();
+        let content = content.as_bytes();
+        let start = content.len().min(offset as usize);
+        let end = content.len().min(start + buf.len());
+        buf[..end - start].copy_from_slice(&content[start..end]);
+        Poll::Ready(Ok(end - start))
+    }
+
+    fn poll_write_at(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        _offset: u64,
+        _buf: &[u8],
+    ) -> Poll<VfsResult<usize>> {
+        Poll::Ready(Err(AxError::PermissionDenied))
+    }
+
+    fn poll_truncate(self: Pin<&Self>, _cx: &mut Context<'_>, _size: u64) -> Poll<VfsResult> {
+        Poll::Ready(Err(AxError::PermissionDenied))
+    }
+
+    impl_vfs_non_dir_default! {}
+}
@@ -9,12 +9,14 @@ extern crate alloc;
 
 mod dir;
 mod file;
+mod generated;
 mod interrupts;
 #[cfg(test)]
 mod tests;
 
 pub use self::dir::DirNode;
 pub use self::file::FileNode;
+pub use self::generated::GeneratedFile;
 pub use self::interrupts::{Interrupts, INTERRUPT};
 use alloc::sync::Arc;
 use async_vfs::{VfsNodeOps, VfsNodeRef, VfsOps, VfsResult};
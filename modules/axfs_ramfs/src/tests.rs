@@ -152,3 +152,47 @@ fn test_ramfs() {
 
     let _ = Box::pin(test()).as_mut().poll(&mut cx);
 }
+
+async fn test_generated_file() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    static READS: AtomicUsize = AtomicUsize::new(0);
+    fn render() -> String {
+        format!("reads {}\n", READS.fetch_add(1, Ordering::Relaxed))
+    }
+
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir_node();
+    root.add_node("stat", Arc::new(GeneratedFile::new(render)))
+        .unwrap();
+    assert_eq!(
+        root.add_node("stat", Arc::new(GeneratedFile::new(render)))
+            .err(),
+        Some(VfsError::AlreadyExists)
+    );
+
+    let node = ramfs.root_dir().lookup("stat").unwrap();
+    assert_eq!(node.get_attr().await.unwrap().size(), 0);
+    let mut buf = [0; 16];
+    assert_eq!(node.read_at(0, &mut buf).await, Ok(8));
+    assert_eq!(&buf[..8], b"reads 0\n");
+    // 每次读取都重新生成内容
+    assert_eq!(node.read_at(6, &mut buf).await, Ok(2));
+    assert_eq!(&buf[..2], b"1\n");
+    assert_eq!(node.read_at(8, &mut buf).await, Ok(0));
+    assert_eq!(
+        node.write_at(0, b"x").await.err(),
+        Some(VfsError::PermissionDenied)
+    );
+}
+
+#[test]
+fn test_ramfs_generated_file() {
+    use core::future::Future;
+    let waker = core::task::Waker::noop();
+    let mut cx = Context::from_waker(&waker);
+
+    assert!(Box::pin(test_generated_file())
+        .as_mut()
+        .poll(&mut cx)
+        .is_ready());
+}
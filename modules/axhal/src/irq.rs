@@ -33,3 +33,27 @@ pub(crate) fn register_handler_common(irq_num: usize, handler: IrqHandler) -> bo
     warn!("register handler for IRQ {} failed", irq_num);
     false
 }
+
+/// IRQ 处理函数的嵌套深度，大于 0 表示当前 CPU 正处于中断上下文中
+#[percpu::def_percpu]
+static IRQ_NESTING: usize = 0;
+
+/// Marks the entry of an IRQ handler on the current CPU.
+///
+/// 需要在关闭抢占的情况下与 [`irq_exit`] 成对调用。
+#[inline]
+pub fn irq_enter() {
+    unsafe { IRQ_NESTING.write_current_raw(IRQ_NESTING.read_current_raw() + 1) };
+}
+
+/// Marks the exit of an IRQ handler on the current CPU.
+#[inline]
+pub fn irq_exit() {
+    unsafe { IRQ_NESTING.write_current_raw(IRQ_NESTING.read_current_raw() - 1) };
+}
+
+/// Returns whether the current CPU is running an IRQ handler.
+#[inline]
+pub fn in_irq() -> bool {
+    IRQ_NESTING.read_current() > 0
+}
@@ -1,9 +1,10 @@
 use crate::{
-    flags::WaitStatus, futex::futex_wake, send_signal_to_process, send_signal_to_thread,
-    CurrentExecutor, Executor, KERNEL_EXECUTOR, KERNEL_EXECUTOR_ID, KERNEL_SCHEDULER, PID2PC,
-    TID2TASK, UTRAP_HANDLER,
+    flags::{WaitOptions, WaitStatus},
+    futex::futex_wake,
+    send_signal_to_process, send_signal_to_thread, CurrentExecutor, Executor, KERNEL_EXECUTOR,
+    KERNEL_EXECUTOR_ID, KERNEL_SCHEDULER, PID2PC, TID2TASK, UTRAP_HANDLER,
 };
-use alloc::{boxed::Box, string::String, sync::Arc};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
 use axsignal::signal_no::SignalNo;
 use core::{future::Future, ops::Deref, pin::Pin};
 use spinlock::SpinNoIrq;
@@ -15,6 +16,7 @@ pub fn init(utrap_handler: fn() -> Pin<Box<dyn Future<Output = isize> + 'static>
     vdso::init();
     taskctx::init();
     UTRAP_HANDLER.init_by(utrap_handler);
+    async_fs::api::register_cred_provider(current_fs_cred);
     let mut scheduler = Scheduler::new();
     scheduler.init();
     KERNEL_SCHEDULER.init_by(Arc::new(SpinNoIrq::new(scheduler)));
@@ -35,6 +37,15 @@ pub fn init_secondary() {
     unsafe { CurrentExecutor::init_current(kexecutor) };
 }
 
+/// 当前进程访问文件系统时使用的凭证，没有当前进程时为 root
+fn current_fs_cred() -> async_fs::api::FsCred {
+    current_task_may_uninit()
+        .and_then(|task| PID2PC.get(&task.get_process_id()))
+        .map_or_else(async_fs::api::FsCred::root, |process| {
+            process.cred.lock().fs_cred()
+        })
+}
+
 pub fn current_task_may_uninit() -> Option<CurrentTask> {
     CurrentTask::try_get()
 }
@@ -45,14 +56,7 @@ pub fn current_task() -> CurrentTask {
 
 pub async fn current_executor() -> Arc<Executor> {
     let current_task = current_task();
-    let current_process = Arc::clone(
-        PID2PC
-            .lock()
-            .await
-            .get(&current_task.get_process_id())
-            .unwrap(),
-    );
-    current_process
+    PID2PC.get(&current_task.get_process_id()).unwrap()
 }
 
 /// Spawns a new task with the given parameters.
@@ -144,15 +148,16 @@ pub async fn exit(exit_code: isize) {
                 break;
             }
         }
-        TID2TASK.lock().await.remove(&curr_id);
+        TID2TASK.remove(&curr_id).await;
         current_executor.exit_main_task().await;
         current_executor.tasks.lock().await.clear();
 
         current_executor.fd_manager.fd_table.lock().await.clear();
 
         current_executor.signal_modules.lock().await.clear();
+        crate::posix_timer::delete_all_timers(&current_executor);
+        async_mem::sem::exit_sem(current_executor.pid());
 
-        let mut pid2pc = PID2PC.lock().await;
         let kernel_executor = &*KERNEL_EXECUTOR;
         // 将子进程交给idle进程
         // process.memory_set = Arc::clone(&kernel_process.memory_set);
@@ -164,11 +169,12 @@ pub async fn exit(exit_code: isize) {
                 .await
                 .push(Arc::clone(child));
         }
-        if let Some(parent_process) = pid2pc.get(&current_executor.get_parent()) {
+        // 跟踪者退出时，被跟踪的进程继续运行
+        crate::ptrace::detach_all_tracees(current_executor.pid()).await;
+        if let Some(parent_process) = PID2PC.get(&current_executor.get_parent()) {
             parent_process.set_vfork_block(false).await;
         }
-        pid2pc.remove(&current_executor.pid());
-        drop(pid2pc);
+        PID2PC.remove(&current_executor.pid()).await;
         // 在这里直接更换为内核页表，并且关闭中断
         axhal::arch::disable_irqs();
         unsafe {
@@ -178,7 +184,7 @@ pub async fn exit(exit_code: isize) {
         current_executor.set_zombie(true);
         drop(current_executor);
     } else {
-        TID2TASK.lock().await.remove(&curr_id);
+        TID2TASK.remove(&curr_id).await;
         // 从进程中删除当前线程
         let mut tasks = current_executor.tasks.lock().await;
         let len = tasks.len();
@@ -233,58 +239,113 @@ pub fn set_priority(prio: isize) -> bool {
 /// 若找到了则返回对应的pid
 /// 否则返回一个状态
 ///
-/// # Safety
+/// `pid` 的含义与 wait4 相同：大于 0 时等待对应的子进程，为 -1 时等待任意子进程，
+/// 为 0 时等待与当前进程同一进程组的子进程，小于 -1 时等待进程组 -pid 中的子进程。
+///
+/// `options` 指定是否同时报告被停止或者被恢复运行的子进程。
 ///
-/// 保证传入的 ptr 是有效的
-pub async unsafe fn wait_pid(pid: i32, exit_code_ptr: *mut i32) -> Result<u64, WaitStatus> {
+/// 状态在释放 children 锁之后才写入 `exit_code_ptr`，地址无效时子进程仍然被回收，并返回 [`WaitStatus::BadAddress`]
+pub async fn wait_pid(
+    pid: i32,
+    exit_code_ptr: *mut i32,
+    options: WaitOptions,
+) -> Result<u64, WaitStatus> {
     // 获取当前进程
     let curr_process = current_executor().await;
-    let mut exit_task_id: usize = 0;
+    let mut exit_task_id: Option<usize> = None;
     let mut answer_id: u64 = 0;
+    let mut answer_code: i32 = 0;
     let mut answer_status = WaitStatus::NotExist;
-    for (index, child) in curr_process.children.lock().await.iter().enumerate() {
-        if pid <= 0 {
-            if pid == 0 {
-                axlog::warn!("Don't support for process group.");
-            }
-            // 任意一个进程结束都可以的
+    let matches = |child: &Executor| match pid {
+        -1 => true,
+        0 => child.pgid() == curr_process.pgid(),
+        pid if pid < -1 => child.pgid() == (-pid) as u64,
+        pid => child.pid() == pid as u64,
+    };
+    let mut children = curr_process.children.lock().await;
+    for (index, child) in children.iter().enumerate() {
+        if !matches(child) {
+            continue;
+        }
+        answer_status = WaitStatus::Running;
+        let status = if let Some(exit_code) = child.get_code_if_exit() {
+            info!("wait pid _{}_ with code _{}_", child.pid(), exit_code);
+            exit_task_id = Some(index);
+            // 用于WEXITSTATUS设置编码
+            (exit_code as i32) << 8
+        } else if let Some(status) = child.take_ptrace_event(curr_process.pid()) {
+            info!("wait pid _{}_ with ptrace stop {:#x}", child.pid(), status);
+            status
+        } else if let Some(event) = child.take_job_event(options) {
+            info!("wait pid _{}_ with job event {:?}", child.pid(), event);
+            event.wait_status()
+        } else {
+            continue;
+        };
+        answer_status = WaitStatus::Exited;
+        answer_code = status;
+        answer_id = child.pid();
+        break;
+    }
+    // 若进程成功结束，需要将其从父进程的children中删除
+    if let Some(exit_task_id) = exit_task_id {
+        children.remove(exit_task_id);
+    }
+    drop(children);
+    // 跟踪者也可以 wait 到不是其子进程的被跟踪进程的停止
+    if answer_status != WaitStatus::Exited {
+        let tracees: Vec<_> = PID2PC
+            .read()
+            .values()
+            .filter(|process| {
+                process.tracer() == Some(curr_process.pid())
+                    && process.get_parent() != curr_process.pid()
+                    && matches(process)
+            })
+            .cloned()
+            .collect();
+        for tracee in tracees {
             answer_status = WaitStatus::Running;
-            if let Some(exit_code) = child.get_code_if_exit() {
+            if let Some(status) = tracee.take_ptrace_event(curr_process.pid()) {
                 answer_status = WaitStatus::Exited;
-                info!("wait pid _{}_ with code _{}_", child.pid(), exit_code);
-                exit_task_id = index;
-                if !exit_code_ptr.is_null() {
-                    unsafe {
-                        // 因为没有切换页表，所以可以直接填写
-                        *exit_code_ptr = (exit_code as i32) << 8;
-                    }
-                }
-                answer_id = child.pid();
+                answer_code = status;
+                answer_id = tracee.pid();
                 break;
             }
-        } else if child.pid() == pid as u64 {
-            // 找到了对应的进程
-            if let Some(exit_code) = child.get_code_if_exit() {
-                answer_status = WaitStatus::Exited;
-                info!("wait pid _{}_ with code _{:?}_", child.pid(), exit_code);
-                exit_task_id = index;
-                if !exit_code_ptr.is_null() {
-                    unsafe {
-                        *exit_code_ptr = (exit_code as i32) << 8;
-                        // 用于WEXITSTATUS设置编码
-                    }
-                }
-                answer_id = child.pid();
-            } else {
-                answer_status = WaitStatus::Running;
-            }
-            break;
         }
     }
-    // 若进程成功结束，需要将其从父进程的children中删除
     if answer_status == WaitStatus::Exited {
-        curr_process.children.lock().await.remove(exit_task_id);
+        if !exit_code_ptr.is_null()
+            && curr_process
+                .write_user(exit_code_ptr, answer_code)
+                .await
+                .is_err()
+        {
+            return Err(WaitStatus::BadAddress);
+        }
         return Ok(answer_id);
     }
     Err(answer_status)
 }
+
+/// 获取进程组 `pgid` 中所有进程的 id
+pub fn process_group_members(pgid: u64) -> Vec<u64> {
+    PID2PC
+        .read()
+        .values()
+        .filter(|process| process.pgid() == pgid && process.pid() != KERNEL_EXECUTOR_ID)
+        .map(|process| process.pid())
+        .collect()
+}
+
+/// 进程组 `pgid` 是否为孤儿进程组：组内每个进程的父进程要么在同一个进程组中，要么在其他会话中
+pub fn is_orphaned_process_group(pgid: u64) -> bool {
+    let processes = PID2PC.read();
+    processes
+        .values()
+        .filter(|process| process.pgid() == pgid && process.pid() != KERNEL_EXECUTOR_ID)
+        .all(|process| match processes.get(&process.get_parent()) {
+            Some(parent) => parent.pgid() == pgid || parent.sid() != process.sid(),
+            None => true,
+        })
+}
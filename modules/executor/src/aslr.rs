@@ -0,0 +1,100 @@
+//! 用户地址空间布局的随机化（ASLR）。
+//!
+//! exec 时随机选取 PIE 程序与动态链接器的加载基址、用户栈、用户堆与 mmap 的起始地址，
+//! 随机数来自 [`crate::random`]。进程的执行域带有 [`ADDR_NO_RANDOMIZE`] 时使用固定的布局。
+use crate::{random::random_below, rlimit::DEFAULT_STACK_LIMIT};
+use async_mem::STACK_GUARD_GAP;
+use axconfig::{MAX_USER_HEAP_SIZE, USER_HEAP_BASE, USER_STACK_TOP};
+use axhal::mem::{VirtAddr, PAGE_SIZE_4K};
+
+/// personality 中关闭地址空间随机化的标志
+pub const ADDR_NO_RANDOMIZE: u32 = 0x0040000;
+
+/// PIE 程序的加载基址
+pub const ELF_ET_DYN_BASE: usize = 0x400_0000;
+/// 动态链接器的加载基址
+pub const ELF_INTERP_BASE: usize = 0x2000_0000;
+/// 没有给出地址的 mmap 的起始地址
+pub const MMAP_BASE: usize = 0x1000_0000;
+
+/// 用户栈区域下方为栈向下扩展保留的地址空间：默认的 RLIMIT_STACK 加上栈与堆之间的保护间隔。
+///
+/// 调高 RLIMIT_STACK 之后栈仍然只能扩展到堆之上的保护间隔为止
+pub const STACK_GROWTH_RESERVE: usize = DEFAULT_STACK_LIMIT as usize + STACK_GUARD_GAP;
+
+/// PIE 程序、动态链接器与 mmap 起始地址随机偏移的最大页数
+const MMAP_RND_PAGES: usize = 0x1000;
+/// 用户栈向下随机偏移的最大页数
+const STACK_RND_PAGES: usize = 0x100;
+/// 用户堆与用户栈之间随机间隔的最大页数
+const BRK_RND_PAGES: usize = 0x100;
+
+/// 一次 exec 使用的地址空间布局
+#[derive(Debug, Clone, Copy)]
+pub struct AddressLayout {
+    /// PIE 程序的加载基址
+    pub exec_base: VirtAddr,
+    /// 动态链接器的加载基址
+    pub interp_base: VirtAddr,
+    /// mmap 的起始地址
+    pub mmap_base: VirtAddr,
+    /// 用户栈区域的起始地址，栈区域为 `[stack_start, stack_start + MAX_USER_STACK_SIZE)`，
+    /// 可以在 RLIMIT_STACK 之内向下扩展
+    pub stack_start: VirtAddr,
+    /// 用户堆的起始地址
+    pub heap_start: VirtAddr,
+}
+
+/// 用户堆放在栈扩展的范围的下方，返回栈区域从 `stack_start` 开始时用户堆最高的起始地址
+fn heap_start_below(stack_start: usize) -> usize {
+    stack_start - STACK_GROWTH_RESERVE - MAX_USER_HEAP_SIZE
+}
+
+/// 随机选取不超过 `pages` 页的偏移
+fn random_offset(pages: usize) -> usize {
+    random_below(pages) * PAGE_SIZE_4K
+}
+
+impl AddressLayout {
+    /// 生成一个新的布局，`randomize` 为 false 时返回固定的布局
+    pub fn new(randomize: bool) -> Self {
+        if !randomize {
+            return Self {
+                exec_base: ELF_ET_DYN_BASE.into(),
+                interp_base: ELF_INTERP_BASE.into(),
+                mmap_base: MMAP_BASE.into(),
+                stack_start: USER_STACK_TOP.into(),
+                // 配置中的堆紧挨着栈区域，需要下移为栈留出扩展的空间
+                heap_start: USER_HEAP_BASE.min(heap_start_below(USER_STACK_TOP)).into(),
+            };
+        }
+        // 栈区域之上紧接着信号跳板，因此栈只能向下偏移
+        let stack_start = USER_STACK_TOP - random_offset(STACK_RND_PAGES);
+        let heap_start = heap_start_below(stack_start) - random_offset(BRK_RND_PAGES);
+        Self {
+            exec_base: (ELF_ET_DYN_BASE + random_offset(MMAP_RND_PAGES)).into(),
+            interp_base: (ELF_INTERP_BASE + random_offset(MMAP_RND_PAGES)).into(),
+            mmap_base: (MMAP_BASE + random_offset(MMAP_RND_PAGES)).into(),
+            stack_start: stack_start.into(),
+            heap_start: heap_start.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AddressLayout, STACK_GROWTH_RESERVE};
+    use axconfig::MAX_USER_HEAP_SIZE;
+
+    #[test]
+    fn test_fixed_layout_leaves_room_for_stack() {
+        let layout = AddressLayout::new(false);
+        let heap_end = layout.heap_start + MAX_USER_HEAP_SIZE;
+        assert!(heap_end + STACK_GROWTH_RESERVE <= layout.stack_start);
+        assert!(layout.mmap_base < layout.heap_start);
+        // 固定布局每次都相同
+        let again = AddressLayout::new(false);
+        assert_eq!(layout.heap_start, again.heap_start);
+        assert_eq!(layout.stack_start, again.stack_start);
+    }
+}
@@ -0,0 +1,665 @@
+//! 可执行文件格式的识别（binfmt）。
+//!
+//! exec 时用已注册的格式依次匹配文件的开头：脚本与 misc 格式将参数改写为交给解释器执行，
+//! 改写之后重新匹配，直到得到可以直接加载的 ELF 文件。带有 PT_INTERP 的 ELF 文件还会打开其动态链接器，
+//! 与程序一起加载。这里只读取文件的开头（ELF 文件读取到程序头表为止），段的内容在缺页时才从文件中读取。
+//! 匹配顺序为用户注册的格式（后注册的优先）、`#!` 脚本、没有 `#!` 行的 `.sh` 脚本、ELF。
+//! 所有格式都不匹配时返回 ENOEXEC，改写次数超过 [`BINPRM_MAX_RECURSION`] 时返回 ELOOP。
+//!
+//! 用户通过 [`BINFMT_MISC_DIR`] 中的控制文件注册与删除 misc 格式，与 Linux 的 binfmt_misc 相同。
+extern crate alloc;
+use crate::link::real_path;
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use async_fs::api::File;
+use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::PAGE_SIZE_4K;
+use core::str::from_utf8;
+use sync::SpinNoIrq;
+use xmas_elf::{
+    header,
+    program::{ProgramHeader, Type},
+    ElfFile,
+};
+
+/// 匹配格式时可以读取的文件开头的长度，`#!` 行不能超过这个长度
+pub const BINPRM_BUF_SIZE: usize = 256;
+/// 解释器的最大嵌套层数
+pub const BINPRM_MAX_RECURSION: usize = 4;
+/// ELF 文件的程序头表必须位于文件开头的这个长度之内
+pub const ELF_MAX_HEADER_SIZE: usize = 0x10000;
+/// 动态链接器路径的最大长度
+const PATH_MAX: usize = 4096;
+
+/// 正在被 exec 的程序
+pub struct LinuxBinprm {
+    /// 要加载的文件
+    pub filename: String,
+    /// 传给 exec 的路径（AT_EXECFN），改写为交给解释器执行之后保持不变
+    pub execfn: String,
+    /// 传给程序的参数
+    pub args: Vec<String>,
+    /// 打开的文件
+    pub file: File,
+    /// 文件的开头，ELF 文件包括 ELF 头与程序头表
+    pub data: Vec<u8>,
+    /// 动态链接器，其 `args` 为空
+    pub interp: Option<Box<LinuxBinprm>>,
+}
+
+impl LinuxBinprm {
+    /// 文件开头用于匹配格式的部分
+    pub fn header(&self) -> &[u8] {
+        &self.data[..self.data.len().min(BINPRM_BUF_SIZE)]
+    }
+
+    /// 改为执行解释器 `interp`，`interp_args` 为解释器的参数（包括 argv[0]）
+    fn set_interpreter(&mut self, interp: String, interp_args: Vec<String>) {
+        self.filename = interp;
+        self.args = interp_args;
+    }
+}
+
+/// 格式匹配的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinfmtResult {
+    /// 不是这种格式，交给下一种格式匹配
+    NoMatch,
+    /// 可以直接加载的 ELF 文件
+    Loadable,
+    /// 已经将程序改写为交给解释器执行，需要重新匹配
+    Interpreter,
+}
+
+/// 可执行文件格式
+pub trait BinaryFormat: Send + Sync {
+    /// 格式的名称
+    fn name(&self) -> &str;
+
+    /// 匹配文件并在需要时改写 `bprm`，文件属于这种格式但内容错误时返回 ENOEXEC
+    fn check(&self, bprm: &mut LinuxBinprm) -> LinuxResult<BinfmtResult>;
+}
+
+/// ELF 文件
+pub struct ElfFormat;
+
+impl BinaryFormat for ElfFormat {
+    fn name(&self) -> &str {
+        "elf"
+    }
+
+    fn check(&self, bprm: &mut LinuxBinprm) -> LinuxResult<BinfmtResult> {
+        if !bprm.data.starts_with(b"\x7fELF") {
+            return Ok(BinfmtResult::NoMatch);
+        }
+        let elf = ElfFile::new(&bprm.data).map_err(|_| LinuxError::ENOEXEC)?;
+        check_load_segments(&elf)?;
+        Ok(BinfmtResult::Loadable)
+    }
+}
+
+/// 检查 ELF 文件的 PT_LOAD 段能否直接映射文件
+///
+/// 各段按地址升序排列且互不重叠，段在文件中的偏移与地址模页大小同余。
+/// 相邻两段共享的页面以一个映射同时读取两段的内容，因此它们的偏移与地址之差必须相同，且前一段不能带有 bss
+fn check_load_segments(elf: &ElfFile) -> LinuxResult<()> {
+    let mut prev: Option<ProgramHeader> = None;
+    for ph in elf
+        .program_iter()
+        .filter(|ph| ph.get_type() == Ok(Type::Load))
+    {
+        let page = PAGE_SIZE_4K as u64;
+        if ph.offset() % page != ph.virtual_addr() % page || ph.file_size() > ph.mem_size() {
+            return Err(LinuxError::ENOEXEC);
+        }
+        // 段的结尾在地址与文件中都不能溢出
+        segment_end(&ph)?;
+        ph.offset()
+            .checked_add(ph.file_size())
+            .ok_or(LinuxError::ENOEXEC)?;
+        if let Some(prev) = prev {
+            let prev_end = segment_end(&prev)?;
+            if ph.virtual_addr() < prev_end {
+                return Err(LinuxError::ENOEXEC);
+            }
+            let share_page = ph.virtual_addr() / page < prev_end.div_ceil(page);
+            let delta = |ph: &ProgramHeader| ph.virtual_addr().checked_sub(ph.offset());
+            if share_page
+                && (delta(&ph).is_none()
+                    || delta(&ph) != delta(&prev)
+                    || prev.file_size() != prev.mem_size())
+            {
+                return Err(LinuxError::ENOEXEC);
+            }
+        }
+        prev = Some(ph);
+    }
+    Ok(())
+}
+
+/// PT_LOAD 段在内存中的结尾地址，溢出时返回 ENOEXEC
+fn segment_end(ph: &ProgramHeader) -> LinuxResult<u64> {
+    ph.virtual_addr()
+        .checked_add(ph.mem_size())
+        .ok_or(LinuxError::ENOEXEC)
+}
+
+/// 返回 ELF 文件 PT_INTERP 段给出的动态链接器，没有该段时返回 None
+pub async fn elf_interp(bprm: &LinuxBinprm) -> LinuxResult<Option<String>> {
+    let elf = ElfFile::new(&bprm.data).map_err(|_| LinuxError::ENOEXEC)?;
+    let Some(interp) = elf
+        .program_iter()
+        .find(|ph| ph.get_type() == Ok(Type::Interp))
+    else {
+        return Ok(None);
+    };
+    if interp.file_size() as usize > PATH_MAX {
+        return Err(LinuxError::ENOEXEC);
+    }
+    let mut data = vec![0; interp.file_size() as usize];
+    if read_at(&bprm.file, interp.offset(), &mut data).await? != data.len() {
+        return Err(LinuxError::ENOEXEC);
+    }
+    // 去掉末尾的 '\0'
+    let interp = from_utf8(&data)
+        .map_err(|_| LinuxError::ENOEXEC)?
+        .trim_matches(char::from(0))
+        .to_string();
+    if interp.is_empty() {
+        return Err(LinuxError::ENOEXEC);
+    }
+    Ok(Some(interp))
+}
+
+/// 以 `#!` 开头的脚本
+///
+/// 第一行为 `#!interpreter [optional-arg]`，解释器之后的内容整体作为一个参数，
+/// 改写后的参数为 `interpreter [optional-arg] filename argv[1..]`
+pub struct ScriptFormat;
+
+impl BinaryFormat for ScriptFormat {
+    fn name(&self) -> &str {
+        "script"
+    }
+
+    fn check(&self, bprm: &mut LinuxBinprm) -> LinuxResult<BinfmtResult> {
+        let header = bprm.header();
+        if !header.starts_with(b"#!") {
+            return Ok(BinfmtResult::NoMatch);
+        }
+        // `#!` 行必须在 BINPRM_BUF_SIZE 之内结束，否则无法确定解释器与参数是否完整
+        let line = match header.iter().position(|&c| c == b'\n') {
+            Some(end) => &header[2..end],
+            None if bprm.data.len() < BINPRM_BUF_SIZE => &header[2..],
+            None => return Err(LinuxError::ENOEXEC),
+        };
+        let line = from_utf8(line)
+            .map_err(|_| LinuxError::ENOEXEC)?
+            .trim_matches([' ', '\t', '\r']);
+        let (interp, optional_arg) = match line.find([' ', '\t']) {
+            Some(pos) => (&line[..pos], line[pos..].trim_matches([' ', '\t'])),
+            None => (line, ""),
+        };
+        if interp.is_empty() {
+            return Err(LinuxError::ENOEXEC);
+        }
+        let mut args = vec![interp.to_string()];
+        if !optional_arg.is_empty() {
+            args.push(optional_arg.to_string());
+        }
+        let interp = interp.to_string();
+        args.push(bprm.filename.clone());
+        args.extend(bprm.args.drain(..).skip(1));
+        bprm.set_interpreter(interp, args);
+        Ok(BinfmtResult::Interpreter)
+    }
+}
+
+/// 没有 `#!` 行的 `.sh` 脚本，交给 busybox 的 sh 执行，改写后的参数为 `busybox sh filename argv[1..]`
+pub struct ShellScriptFormat;
+
+impl BinaryFormat for ShellScriptFormat {
+    fn name(&self) -> &str {
+        "sh"
+    }
+
+    fn check(&self, bprm: &mut LinuxBinprm) -> LinuxResult<BinfmtResult> {
+        if !bprm.filename.ends_with(".sh") || bprm.data.starts_with(b"\x7fELF") {
+            return Ok(BinfmtResult::NoMatch);
+        }
+        let mut args = vec![String::from("busybox"), String::from("sh")];
+        args.push(bprm.filename.clone());
+        args.extend(bprm.args.drain(..).skip(1));
+        bprm.set_interpreter(String::from("busybox"), args);
+        Ok(BinfmtResult::Interpreter)
+    }
+}
+
+/// misc 格式的匹配方式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MiscMatch {
+    /// 文件中 `offset` 处的内容与 `mask` 按位与之后等于 `magic`
+    Magic {
+        offset: usize,
+        magic: Vec<u8>,
+        mask: Vec<u8>,
+    },
+    /// 文件名的扩展名
+    Extension(String),
+}
+
+/// 用户注册的 misc 格式，使用 binfmt_misc 的注册格式 `:name:type:offset:magic:mask:interpreter:flags`
+///
+/// 改写后的参数为 `interpreter filename argv[1..]`，带有 P 标志时保留原来的 argv[0]，
+/// 即 `interpreter filename argv[0] argv[1..]`
+#[derive(Debug, Clone)]
+pub struct MiscFormat {
+    /// 格式的名称
+    pub name: String,
+    /// 匹配方式
+    pub matcher: MiscMatch,
+    /// 解释器
+    pub interpreter: String,
+    /// 是否保留原来的 argv[0]
+    pub preserve_argv0: bool,
+}
+
+impl MiscFormat {
+    /// 解析 binfmt_misc 的注册字符串，格式错误时返回 EINVAL
+    ///
+    /// 第一个字符为分隔符，magic 与 mask 中可以使用 `\xHH` 表示任意字节
+    pub fn parse(line: &str) -> LinuxResult<Self> {
+        let line = line.trim_end_matches('\n');
+        let delimiter = line.chars().next().ok_or(LinuxError::EINVAL)?;
+        let fields: Vec<&str> = line[delimiter.len_utf8()..].split(delimiter).collect();
+        if fields.len() < 6 || fields.len() > 7 {
+            return Err(LinuxError::EINVAL);
+        }
+        let name = fields[0];
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+            return Err(LinuxError::EINVAL);
+        }
+        let matcher = match fields[1] {
+            "M" => {
+                let offset = if fields[2].is_empty() {
+                    0
+                } else {
+                    fields[2].parse().map_err(|_| LinuxError::EINVAL)?
+                };
+                let magic = unescape(fields[3])?;
+                let mask = if fields[4].is_empty() {
+                    vec![0xff; magic.len()]
+                } else {
+                    unescape(fields[4])?
+                };
+                if magic.is_empty() || mask.len() != magic.len() {
+                    return Err(LinuxError::EINVAL);
+                }
+                if offset
+                    .checked_add(magic.len())
+                    .is_none_or(|end| end > BINPRM_BUF_SIZE)
+                {
+                    return Err(LinuxError::EINVAL);
+                }
+                MiscMatch::Magic {
+                    offset,
+                    magic,
+                    mask,
+                }
+            }
+            "E" => {
+                if fields[3].is_empty() || fields[3].contains('/') {
+                    return Err(LinuxError::EINVAL);
+                }
+                MiscMatch::Extension(fields[3].to_string())
+            }
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let interpreter = fields[5];
+        if interpreter.is_empty() {
+            return Err(LinuxError::EINVAL);
+        }
+        let flags = fields.get(6).copied().unwrap_or("");
+        // O、C、F 依赖打开的文件描述符和凭据的传递，目前不支持
+        if flags.chars().any(|flag| flag != 'P') {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(Self {
+            name: name.to_string(),
+            matcher,
+            interpreter: interpreter.to_string(),
+            preserve_argv0: flags.contains('P'),
+        })
+    }
+
+    /// 格式对应的控制文件的内容
+    fn describe(&self) -> String {
+        let flags = if self.preserve_argv0 { "P" } else { "" };
+        let mut info = format!(
+            "enabled\ninterpreter {}\nflags: {}\n",
+            self.interpreter, flags
+        );
+        match &self.matcher {
+            MiscMatch::Magic {
+                offset,
+                magic,
+                mask,
+            } => {
+                let hex = |bytes: &[u8]| {
+                    bytes
+                        .iter()
+                        .map(|b| format!("{:02x}", b))
+                        .collect::<String>()
+                };
+                info += &format!(
+                    "offset {}\nmagic {}\nmask {}\n",
+                    offset,
+                    hex(magic),
+                    hex(mask)
+                );
+            }
+            MiscMatch::Extension(ext) => info += &format!("extension .{}\n", ext),
+        }
+        info
+    }
+
+    fn matches(&self, bprm: &LinuxBinprm) -> bool {
+        match &self.matcher {
+            MiscMatch::Magic {
+                offset,
+                magic,
+                mask,
+            } => bprm
+                .header()
+                .get(*offset..offset + magic.len())
+                .is_some_and(|data| {
+                    data.iter()
+                        .zip(mask)
+                        .zip(magic)
+                        .all(|((byte, mask), magic)| byte & mask == *magic)
+                }),
+            MiscMatch::Extension(ext) => {
+                let name = bprm.filename.rsplit('/').next().unwrap_or("");
+                name.rsplit_once('.').is_some_and(|(_, e)| e == ext)
+            }
+        }
+    }
+}
+
+impl BinaryFormat for MiscFormat {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self, bprm: &mut LinuxBinprm) -> LinuxResult<BinfmtResult> {
+        if !self.matches(bprm) {
+            return Ok(BinfmtResult::NoMatch);
+        }
+        let skip = if self.preserve_argv0 { 0 } else { 1 };
+        let mut args = vec![self.interpreter.clone(), bprm.filename.clone()];
+        args.extend(bprm.args.drain(..).skip(skip));
+        bprm.set_interpreter(self.interpreter.clone(), args);
+        Ok(BinfmtResult::Interpreter)
+    }
+}
+
+/// 将 `\xHH` 转义为对应的字节
+fn unescape(s: &str) -> LinuxResult<Vec<u8>> {
+    let bytes = s.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'x') {
+            let hex = s.get(i + 2..i + 4).ok_or(LinuxError::EINVAL)?;
+            result.push(u8::from_str_radix(hex, 16).map_err(|_| LinuxError::EINVAL)?);
+            i += 4;
+        } else {
+            result.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Ok(result)
+}
+
+/// 用户注册的格式，后注册的在前
+static FORMATS: SpinNoIrq<Vec<Arc<dyn BinaryFormat>>> = SpinNoIrq::new(Vec::new());
+
+/// 注册一种可执行文件格式，名称相同的格式已经存在时返回 EEXIST
+pub fn register_format(format: Arc<dyn BinaryFormat>) -> LinuxResult<()> {
+    let mut formats = FORMATS.lock();
+    if formats.iter().any(|f| f.name() == format.name()) {
+        return Err(LinuxError::EEXIST);
+    }
+    formats.insert(0, format);
+    Ok(())
+}
+
+/// 按照 binfmt_misc 的注册字符串注册 misc 格式
+pub fn register_misc(line: &str) -> LinuxResult<()> {
+    register_format(Arc::new(MiscFormat::parse(line)?))
+}
+
+/// 删除名为 `name` 的格式，不存在时返回 ENOENT
+pub fn unregister_format(name: &str) -> LinuxResult<()> {
+    let mut formats = FORMATS.lock();
+    let index = formats
+        .iter()
+        .position(|f| f.name() == name)
+        .ok_or(LinuxError::ENOENT)?;
+    formats.remove(index);
+    Ok(())
+}
+
+/// binfmt_misc 的控制目录：向其中的 `register` 写入注册字符串注册 misc 格式，
+/// 每种注册的格式对应一个同名文件，向其中写入 `-1` 删除该格式
+pub const BINFMT_MISC_DIR: &str = "/proc/sys/fs/binfmt_misc";
+
+/// 处理对控制目录中的文件 `name` 的写入，是否允许写入由调用者检查
+///
+/// 注册字符串格式错误或者写入的内容无法识别时返回 EINVAL，格式已经存在时返回 EEXIST，不存在时返回 ENOENT
+pub async fn write_misc_control(name: &str, data: &[u8]) -> LinuxResult<()> {
+    let data = from_utf8(data).map_err(|_| LinuxError::EINVAL)?;
+    if name == "register" {
+        let format = MiscFormat::parse(data)?;
+        let path = format!("{}/{}", BINFMT_MISC_DIR, format.name);
+        let info = format.describe();
+        register_format(Arc::new(format))?;
+        if let Err(err) = async_fs::api::write(&path, info).await {
+            warn!("failed to create {}: {:?}", path, err);
+        }
+        return Ok(());
+    }
+    if data.trim_end_matches('\n') != "-1" {
+        return Err(LinuxError::EINVAL);
+    }
+    unregister_format(name)?;
+    let path = format!("{}/{}", BINFMT_MISC_DIR, name);
+    if let Err(err) = async_fs::api::remove_file(&path).await {
+        warn!("failed to remove {}: {:?}", path, err);
+    }
+    Ok(())
+}
+
+/// 用所有格式依次匹配 `bprm`
+fn search_binary_handler(bprm: &mut LinuxBinprm) -> LinuxResult<BinfmtResult> {
+    let formats = FORMATS.lock().clone();
+    let builtin: [&dyn BinaryFormat; 3] = [&ScriptFormat, &ShellScriptFormat, &ElfFormat];
+    for format in formats
+        .iter()
+        .map(|f| f.as_ref())
+        .chain(builtin.into_iter())
+    {
+        match format.check(bprm)? {
+            BinfmtResult::NoMatch => continue,
+            result => return Ok(result),
+        }
+    }
+    Err(LinuxError::ENOEXEC)
+}
+
+/// 从 `offset` 处读取文件，直到填满 `buf` 或者到达文件末尾
+async fn read_at(file: &File, offset: u64, buf: &mut [u8]) -> LinuxResult<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        let n = file
+            .read_at(offset + read as u64, &mut buf[read..])
+            .await
+            .map_err(|_| LinuxError::EIO)?;
+        if n == 0 {
+            break;
+        }
+        read += n;
+    }
+    Ok(read)
+}
+
+/// 打开 `name` 并读取其开头，ELF 文件读取到程序头表的末尾
+async fn open_binprm(name: String, args: Vec<String>) -> LinuxResult<LinuxBinprm> {
+    let file = File::open(name.as_str()).await.map_err(|_| {
+        info!("App not found: {}", name);
+        LinuxError::ENOENT
+    })?;
+    let mut data = vec![0; BINPRM_BUF_SIZE];
+    let len = read_at(&file, 0, &mut data).await?;
+    data.truncate(len);
+    let ph_end = header::parse_header(&data).ok().map(|header| {
+        let pt2 = &header.pt2;
+        pt2.ph_offset() as usize + pt2.ph_count() as usize * pt2.ph_entry_size() as usize
+    });
+    if let Some(ph_end) = ph_end {
+        if ph_end > ELF_MAX_HEADER_SIZE {
+            return Err(LinuxError::ENOEXEC);
+        }
+        if ph_end > data.len() {
+            data.resize(ph_end, 0);
+            if read_at(&file, 0, &mut data).await? != ph_end {
+                return Err(LinuxError::ENOEXEC);
+            }
+        }
+    }
+    Ok(LinuxBinprm {
+        execfn: name.clone(),
+        filename: name,
+        args,
+        file,
+        data,
+        interp: None,
+    })
+}
+
+/// 找到 `name` 最终需要加载的 ELF 文件，返回时 `filename` 与 `args` 已经按照解释器改写
+///
+/// 动态链接器不存在时返回 ENOENT，不是 ELF 文件或者自身还需要动态链接器时返回 ELIBBAD
+pub async fn resolve(mut name: String, mut args: Vec<String>) -> LinuxResult<LinuxBinprm> {
+    let execfn = name.clone();
+    for _ in 0..=BINPRM_MAX_RECURSION {
+        let mut bprm = open_binprm(name, args).await?;
+        bprm.execfn = execfn.clone();
+        match search_binary_handler(&mut bprm)? {
+            BinfmtResult::Loadable => {
+                if let Some(interp) = elf_interp(&bprm).await? {
+                    let mut interp = open_binprm(real_path(&interp).await, Vec::new()).await?;
+                    if ElfFormat.check(&mut interp).ok() != Some(BinfmtResult::Loadable)
+                        || !matches!(elf_interp(&interp).await, Ok(None))
+                    {
+                        return Err(LinuxError::ELIBBAD);
+                    }
+                    bprm.interp = Some(Box::new(interp));
+                }
+                return Ok(bprm);
+            }
+            _ => {
+                // 解释器可能是通过链接给出的，解析后的路径只用来打开文件，
+                // argv[0] 保持 `#!` 行或者注册时写下的原样
+                name = real_path(&bprm.filename).await;
+                args = bprm.args;
+            }
+        }
+    }
+    Err(LinuxError::ELOOP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MiscFormat, MiscMatch, BINPRM_BUF_SIZE};
+    use alloc::format;
+    use axerrno::LinuxError;
+
+    #[test]
+    fn test_parse_magic() {
+        let format =
+            MiscFormat::parse(":qemu:M:2:\\x7fEL:\\xff\\xff\\xfe:/usr/bin/qemu:P\n").unwrap();
+        assert_eq!("qemu", format.name);
+        assert_eq!("/usr/bin/qemu", format.interpreter);
+        assert!(format.preserve_argv0);
+        assert_eq!(
+            MiscMatch::Magic {
+                offset: 2,
+                magic: b"\x7fEL".to_vec(),
+                mask: [0xff, 0xff, 0xfe].to_vec(),
+            },
+            format.matcher
+        );
+    }
+
+    #[test]
+    fn test_parse_extension_and_default_mask() {
+        // 分隔符可以是任意字符
+        let format = MiscFormat::parse("|py|E||py||/usr/bin/python3|").unwrap();
+        assert_eq!(MiscMatch::Extension("py".into()), format.matcher);
+        assert!(!format.preserve_argv0);
+        // mask 为空时匹配 magic 的每一个字节
+        let format = MiscFormat::parse(":wasm:M::\\x00asm::/usr/bin/wasmtime:").unwrap();
+        assert_eq!(
+            MiscMatch::Magic {
+                offset: 0,
+                magic: b"\0asm".to_vec(),
+                mask: [0xff; 4].to_vec(),
+            },
+            format.matcher
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for line in [
+            "",
+            ":name:M::abc::",
+            ":name:M::abc::/bin/x:X",
+            ":name:M::abc::/bin/x:PO",
+            ":name:M::abc::/bin/x:C",
+            ":name:M::abc::/bin/x:F",
+            "::E::sh::/bin/sh:",
+            ":a/b:E::sh::/bin/sh:",
+            ":name:E::a/b::/bin/sh:",
+            ":name:Q::abc::/bin/x:",
+            ":name:M::abc:\\xff:/bin/x:",
+            ":name:M::\\x7:/bin/x:",
+            ":name:M:x:abc::/bin/x:",
+            ":name:E::sh::/bin/sh::",
+        ] {
+            assert_eq!(
+                Some(LinuxError::EINVAL),
+                MiscFormat::parse(line).err(),
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_magic_out_of_buffer() {
+        let last = format!(":name:M:{}:a::/bin/x:", BINPRM_BUF_SIZE - 1);
+        assert!(MiscFormat::parse(&last).is_ok());
+        let beyond = format!(":name:M:{}:a::/bin/x:", BINPRM_BUF_SIZE);
+        assert_eq!(Some(LinuxError::EINVAL), MiscFormat::parse(&beyond).err());
+        // offset 与 magic 的长度相加溢出
+        let overflow = format!(":name:M:{}:ab::/bin/x:", usize::MAX);
+        assert_eq!(Some(LinuxError::EINVAL), MiscFormat::parse(&overflow).err());
+    }
+}
@@ -0,0 +1,385 @@
+//! 进程因默认动作为 Core 的信号终止时，将其地址空间与各线程的寄存器写入 ELF 格式的 core 文件。
+//!
+//! core 文件包含一个 PT_NOTE 段（NT_PRPSINFO 与每个线程的 NT_PRSTATUS）以及每个内存区域对应的 PT_LOAD 段，
+//! 可以离线使用 gdb 检查。文件大小受 RLIMIT_CORE 的软上限限制，超出的部分被截断。
+extern crate alloc;
+use alloc::{format, string::String, vec::Vec};
+use async_fs::api::{File, Write};
+use axerrno::AxResult;
+use axhal::{mem::PAGE_SIZE_4K, paging::MappingFlags};
+use axsignal::signal_no::SignalNo;
+use core::mem::size_of;
+
+use crate::{current_task, ptrace::UserRegs, rlimit::RLIMIT_CORE, Executor};
+
+/// core 文件路径的默认格式
+const DEFAULT_CORE_PATTERN: &str = "core.%p";
+
+/// 保存 core 文件路径格式的 sysctl 文件，与 Linux 相同，root 可以写入新的格式。格式支持以下占位符：
+/// - `%p`：进程 id
+/// - `%e`：可执行文件名，最多 15 个字符
+/// - `%s`：导致终止的信号
+/// - `%t`：转储时的时间，单位为秒
+/// - `%%`：字符 `%`
+///
+/// 相对路径相对于进程的工作目录
+const CORE_PATTERN_PATH: &str = "/proc/sys/kernel/core_pattern";
+
+/// 设置 core 文件路径的格式，为空时恢复默认格式
+pub async fn set_core_pattern(pattern: &str) -> AxResult<()> {
+    async_fs::api::write(CORE_PATTERN_PATH, pattern).await
+}
+
+/// 当前 core 文件路径的格式，sysctl 文件不存在或者为空时使用默认格式
+pub async fn core_pattern() -> String {
+    let pattern = async_fs::api::read_to_string(CORE_PATTERN_PATH)
+        .await
+        .unwrap_or_default();
+    match pattern.trim_end_matches('\n') {
+        "" => String::from(DEFAULT_CORE_PATTERN),
+        pattern => String::from(pattern),
+    }
+}
+
+const ET_CORE: u16 = 4;
+const EM_RISCV: u16 = 243;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+const NT_PRPSINFO: u32 = 3;
+/// 注释的名称，包含结尾的 0
+const NOTE_NAME: &[u8] = b"CORE\0";
+
+#[repr(C)]
+struct ElfHeader {
+    ident: [u8; 16],
+    elf_type: u16,
+    machine: u16,
+    version: u32,
+    entry: u64,
+    phoff: u64,
+    shoff: u64,
+    flags: u32,
+    ehsize: u16,
+    phentsize: u16,
+    phnum: u16,
+    shentsize: u16,
+    shnum: u16,
+    shstrndx: u16,
+}
+
+#[repr(C)]
+struct ProgramHeader {
+    p_type: u32,
+    flags: u32,
+    offset: u64,
+    vaddr: u64,
+    paddr: u64,
+    filesz: u64,
+    memsz: u64,
+    align: u64,
+}
+
+#[repr(C)]
+struct NoteHeader {
+    namesz: u32,
+    descsz: u32,
+    n_type: u32,
+}
+
+/// 线程的状态，与 Linux 的 elf_prstatus 布局相同
+#[repr(C)]
+struct PrStatus {
+    si_signo: i32,
+    si_code: i32,
+    si_errno: i32,
+    cursig: i16,
+    sigpend: u64,
+    sighold: u64,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    utime: [i64; 2],
+    stime: [i64; 2],
+    cutime: [i64; 2],
+    cstime: [i64; 2],
+    regs: UserRegs,
+    fpvalid: i32,
+}
+
+/// 进程的信息，与 Linux 的 elf_prpsinfo 布局相同
+#[repr(C)]
+struct PrPsInfo {
+    state: u8,
+    sname: u8,
+    zomb: u8,
+    nice: i8,
+    flag: u64,
+    uid: u32,
+    gid: u32,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    fname: [u8; 16],
+    psargs: [u8; 80],
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+const fn align_up(size: usize, align: usize) -> usize {
+    (size + align - 1) & !(align - 1)
+}
+
+/// 将纳秒转换为 timeval
+fn timeval(nanos: usize) -> [i64; 2] {
+    [
+        (nanos / 1_000_000_000) as i64,
+        (nanos % 1_000_000_000 / 1000) as i64,
+    ]
+}
+
+/// 将 `src` 复制到 `dst` 中，超出的部分被截断，并保留结尾的 0
+fn copy_str(dst: &mut [u8], src: &str) {
+    let len = src.len().min(dst.len() - 1);
+    dst[..len].copy_from_slice(&src.as_bytes()[..len]);
+}
+
+/// 在注释段中追加一个注释
+fn push_note(notes: &mut Vec<u8>, n_type: u32, desc: &[u8]) {
+    let header = NoteHeader {
+        namesz: NOTE_NAME.len() as u32,
+        descsz: desc.len() as u32,
+        n_type,
+    };
+    notes.extend_from_slice(as_bytes(&header));
+    notes.extend_from_slice(NOTE_NAME);
+    notes.resize(align_up(notes.len(), 4), 0);
+    notes.extend_from_slice(desc);
+    notes.resize(align_up(notes.len(), 4), 0);
+}
+
+/// 按照 `pattern` 生成 core 文件的路径
+fn expand_pattern(pattern: &str, pid: u64, exe: &str, signal: SignalNo) -> String {
+    let mut path = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            path.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('p') => path.push_str(&format!("{}", pid)),
+            Some('e') => path.push_str(exe),
+            Some('s') => path.push_str(&format!("{}", signal as usize)),
+            Some('t') => path.push_str(&format!("{}", axhal::time::current_time().as_secs())),
+            Some('%') => path.push('%'),
+            // 与 Linux 相同，未知的占位符被丢弃
+            _ => {}
+        }
+    }
+    path
+}
+
+/// 写入 core 文件，超出 `limit` 的部分被丢弃
+struct CoreWriter {
+    file: File,
+    written: usize,
+    limit: usize,
+}
+
+impl CoreWriter {
+    async fn write(&mut self, buf: &[u8]) -> AxResult<()> {
+        let len = buf.len().min(self.limit - self.written);
+        if len > 0 {
+            self.file.write_all(&buf[..len]).await?;
+            self.written += len;
+        }
+        Ok(())
+    }
+}
+
+/// 为当前线程所在的进程 `process` 生成因信号 `signal` 终止的 core 文件
+///
+/// RLIMIT_CORE 的软上限小于一页时不生成 core 文件。被换出的页面与尚未读入的文件页面先读入，
+/// 从未访问过的匿名页面以 0 填充，不会因转储而分配内存。
+pub async fn dump_core(process: &Executor, signal: SignalNo) -> AxResult<()> {
+    let limit = process.get_rlimit(RLIMIT_CORE).rlim_cur;
+    if limit < PAGE_SIZE_4K as u64 {
+        return Ok(());
+    }
+    let pid = process.pid();
+    let file_path = process.get_file_path().await;
+    let exe = file_path.rsplit('/').next().unwrap_or_default();
+    let exe = &exe[..exe.len().min(15)];
+    let mut path = expand_pattern(&core_pattern().await, pid, exe, signal);
+    if !path.starts_with('/') {
+        let cwd = process.get_cwd().await;
+        path = if cwd.ends_with('/') {
+            format!("{}{}", cwd, path)
+        } else {
+            format!("{}/{}", cwd, path)
+        };
+    }
+
+    let cred = process.cred();
+    let mut psinfo = PrPsInfo {
+        state: 0,
+        sname: b'R',
+        zomb: 0,
+        nice: 0,
+        flag: 0,
+        uid: cred.uid,
+        gid: cred.gid,
+        pid: pid as i32,
+        ppid: process.get_parent() as i32,
+        pgrp: process.pgid() as i32,
+        sid: process.sid() as i32,
+        fname: [0; 16],
+        psargs: [0; 80],
+    };
+    copy_str(&mut psinfo.fname, exe);
+    copy_str(&mut psinfo.psargs, &file_path);
+    let mut notes = Vec::new();
+    push_note(&mut notes, NT_PRPSINFO, as_bytes(&psinfo));
+
+    // 触发信号的线程排在最前面，gdb 将第一个 NT_PRSTATUS 视为崩溃的线程
+    let current_tid = current_task().id().as_u64();
+    let mut tasks = process.tasks.lock().await.clone();
+    tasks.sort_by_key(|task| task.id().as_u64() != current_tid);
+    let signal_modules = process.signal_modules.lock().await;
+    for task in tasks.iter() {
+        let tid = task.id().as_u64();
+        let Some(tf) = task.utrap_frame() else {
+            continue;
+        };
+        let (sigpend, sighold) = signal_modules
+            .get(&tid)
+            .map(|module| (module.signal_set.pending, module.signal_set.mask))
+            .unwrap_or_default();
+        let cursig = if tid == current_tid { signal as i32 } else { 0 };
+        let (utime, stime) = task.time_stat_output();
+        let status = PrStatus {
+            si_signo: cursig,
+            si_code: 0,
+            si_errno: 0,
+            cursig: cursig as i16,
+            sigpend: sigpend as u64,
+            sighold: sighold as u64,
+            pid: tid as i32,
+            ppid: psinfo.ppid,
+            pgrp: psinfo.pgrp,
+            sid: psinfo.sid,
+            utime: timeval(utime),
+            stime: timeval(stime),
+            cutime: [0; 2],
+            cstime: [0; 2],
+            regs: UserRegs::from_trap_frame(tf),
+            fpvalid: 0,
+        };
+        push_note(&mut notes, NT_PRSTATUS, as_bytes(&status));
+    }
+    drop(signal_modules);
+
+    let mut memory_set = process.memory_set.lock().await;
+    // 写入页面时需要可变地访问地址空间，这里只记录各区域的范围与权限
+    let areas: Vec<(usize, usize, MappingFlags)> = memory_set
+        .areas()
+        .map(|area| (area.vaddr.as_usize(), area.size(), area.flags))
+        .collect();
+    let phnum = areas.len() + 1;
+    let notes_offset = size_of::<ElfHeader>() + phnum * size_of::<ProgramHeader>();
+    let mut headers = Vec::new();
+    let mut ident = [0u8; 16];
+    // ELFMAG、ELFCLASS64、ELFDATA2LSB、EV_CURRENT
+    ident[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+    let elf_header = ElfHeader {
+        ident,
+        elf_type: ET_CORE,
+        machine: EM_RISCV,
+        version: 1,
+        entry: 0,
+        phoff: size_of::<ElfHeader>() as u64,
+        shoff: 0,
+        flags: 0,
+        ehsize: size_of::<ElfHeader>() as u16,
+        phentsize: size_of::<ProgramHeader>() as u16,
+        phnum: phnum as u16,
+        shentsize: 0,
+        shnum: 0,
+        shstrndx: 0,
+    };
+    headers.extend_from_slice(as_bytes(&elf_header));
+    let note_header = ProgramHeader {
+        p_type: PT_NOTE,
+        flags: 0,
+        offset: notes_offset as u64,
+        vaddr: 0,
+        paddr: 0,
+        filesz: notes.len() as u64,
+        memsz: 0,
+        align: 0,
+    };
+    headers.extend_from_slice(as_bytes(&note_header));
+    // 内存区域的内容从页对齐的位置开始，不可读的区域不写入内容
+    let mut offset = align_up(notes_offset + notes.len(), PAGE_SIZE_4K);
+    for &(vaddr, size, area_flags) in areas.iter() {
+        let mut flags = 0;
+        if area_flags.contains(MappingFlags::READ) {
+            flags |= PF_R;
+        }
+        if area_flags.contains(MappingFlags::WRITE) {
+            flags |= PF_W;
+        }
+        if area_flags.contains(MappingFlags::EXECUTE) {
+            flags |= PF_X;
+        }
+        let filesz = if flags & PF_R != 0 { size } else { 0 };
+        let load_header = ProgramHeader {
+            p_type: PT_LOAD,
+            flags,
+            offset: offset as u64,
+            vaddr: vaddr as u64,
+            paddr: 0,
+            filesz: filesz as u64,
+            memsz: size as u64,
+            align: PAGE_SIZE_4K as u64,
+        };
+        headers.extend_from_slice(as_bytes(&load_header));
+        offset += filesz;
+    }
+
+    let mut writer = CoreWriter {
+        file: File::create(path.as_str()).await?,
+        written: 0,
+        limit: limit.min(usize::MAX as u64) as usize,
+    };
+    writer.write(&headers).await?;
+    writer.write(&notes).await?;
+    let zero_page = [0u8; PAGE_SIZE_4K];
+    let padding = align_up(notes_offset + notes.len(), PAGE_SIZE_4K) - notes_offset - notes.len();
+    writer.write(&zero_page[..padding]).await?;
+    let mut buf = [0u8; PAGE_SIZE_4K];
+    for (vaddr, size, flags) in areas {
+        if !flags.contains(MappingFlags::READ) {
+            continue;
+        }
+        for page in (vaddr..vaddr + size).step_by(PAGE_SIZE_4K) {
+            if writer.written >= writer.limit {
+                return Ok(());
+            }
+            // 无法读入的页面同样以 0 填充，保持各段在文件中的偏移不变
+            match memory_set.read_page_for_dump(page.into(), &mut buf).await {
+                Ok(true) => writer.write(&buf).await?,
+                _ => writer.write(&zero_page).await?,
+            }
+        }
+    }
+    Ok(())
+}
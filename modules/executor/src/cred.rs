@@ -0,0 +1,42 @@
+//! 进程的用户凭证，fork 时复制，exec 时保存有效 id。
+
+use alloc::vec::Vec;
+use async_fs::api::FsCred;
+
+/// 附加组的最大数量
+pub const NGROUPS_MAX: usize = 65536;
+
+/// 进程的用户凭证
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    /// 实际用户 id
+    pub uid: u32,
+    /// 有效用户 id，用于权限检查
+    pub euid: u32,
+    /// 保存的用户 id
+    pub suid: u32,
+    /// 实际组 id
+    pub gid: u32,
+    /// 有效组 id，用于权限检查
+    pub egid: u32,
+    /// 保存的组 id
+    pub sgid: u32,
+    /// 附加组
+    pub groups: Vec<u32>,
+}
+
+impl Credentials {
+    /// 有效用户是否为 root，root 可以任意修改凭证
+    pub fn is_privileged(&self) -> bool {
+        self.euid == 0
+    }
+
+    /// 访问文件系统时使用的凭证
+    pub fn fs_cred(&self) -> FsCred {
+        FsCred {
+            uid: self.euid,
+            gid: self.egid,
+            groups: self.groups.clone(),
+        }
+    }
+}
@@ -1,10 +1,20 @@
 use crate::{
+    aslr::ADDR_NO_RANDOMIZE,
+    binfmt,
+    cred::Credentials,
     current_task,
     fd_manager::{FdManager, FdTable},
-    flags::CloneFlags,
+    flags::{CloneFlags, JobEvent, WaitOptions},
     futex::FutexRobustList,
-    load_app,
-    stdio::{Stderr, Stdin, Stdout},
+    load_app, load_binprm,
+    posix_timer::PosixTimer,
+    ptrace::PtraceState,
+    rlimit::{
+        default_rlimits, RLimit, RLIMIT_AS, RLIMIT_CPU, RLIMIT_DATA, RLIMIT_NOFILE, RLIMIT_NPROC,
+        RLIMIT_STACK, RLIM_INFINITY, RLIM_NLIMITS,
+    },
+    sigqueue::SigQueue,
+    stdio::{Stderr, Stdin, Stdout, CONSOLE_TTY},
     SignalModule,
 };
 use alloc::{
@@ -18,33 +28,39 @@ use alloc::{
 };
 use async_fs::api::{FileIO, OpenFlags};
 use async_mem::MemorySet;
-use axerrno::{AxError, AxResult};
-use axhal::{mem::VirtAddr, time::current_time_nanos};
+use axerrno::{AxError, AxResult, LinuxResult};
+use axhal::{
+    mem::VirtAddr,
+    time::{current_time, current_time_nanos, TimeValue},
+};
 use axsignal::signal_no::SignalNo;
 use core::{
     cell::UnsafeCell,
     future::Future,
     pin::Pin,
-    sync::atomic::{AtomicBool, AtomicI32, AtomicIsize, AtomicU64, Ordering},
+    sync::atomic::{
+        AtomicBool, AtomicI32, AtomicIsize, AtomicU32, AtomicU64, AtomicUsize, Ordering,
+    },
 };
 use lazy_init::LazyInit;
-use spinlock::SpinNoIrq;
-use sync::Mutex;
+use sync::rcu::RcuMap;
+use sync::{Mutex, SpinNoIrq, WaitQueue};
 use task_api::yield_now;
 use taskctx::{BaseScheduler, Task, TaskInner, TaskRef, TrapFrame};
 use taskctx::{Scheduler, TaskId};
 
 const FD_LIMIT_ORIGIN: usize = 1025;
 pub const KERNEL_EXECUTOR_ID: u64 = 1;
-pub static TID2TASK: Mutex<BTreeMap<u64, TaskRef>> = Mutex::new(BTreeMap::new());
-pub static PID2PC: Mutex<BTreeMap<u64, Arc<Executor>>> = Mutex::new(BTreeMap::new());
+/// 任务与进程的注册表，读者无需加锁，写者复制后替换
+pub static TID2TASK: RcuMap<u64, TaskRef> = RcuMap::new();
+pub static PID2PC: RcuMap<u64, Arc<Executor>> = RcuMap::new();
 
 pub static UTRAP_HANDLER: LazyInit<fn() -> Pin<Box<dyn Future<Output = isize> + 'static>>> =
     LazyInit::new();
 
 pub static KERNEL_EXECUTOR: LazyInit<Arc<Executor>> = LazyInit::new();
 pub static KERNEL_PAGE_TABLE_TOKEN: LazyInit<usize> = LazyInit::new();
-pub static KERNEL_SCHEDULER: LazyInit<Arc<SpinNoIrq<Scheduler>>> = LazyInit::new();
+pub static KERNEL_SCHEDULER: LazyInit<Arc<spinlock::SpinNoIrq<Scheduler>>> = LazyInit::new();
 
 extern "C" {
     fn start_signal_trampoline();
@@ -53,6 +69,10 @@ extern "C" {
 pub struct Executor {
     pub pid: u64,
     pub parent: AtomicU64,
+    /// 进程组 id
+    pub pgid: AtomicU64,
+    /// 会话 id
+    pub sid: AtomicU64,
     /// 子进程
     pub children: Mutex<Vec<Arc<Executor>>>,
     // scheduler: Arc<SpinNoIrq<Scheduler>>,
@@ -62,6 +82,12 @@ pub struct Executor {
     pub is_zombie: AtomicBool,
     /// 退出状态码
     pub exit_code: AtomicIsize,
+    /// 是否被作业控制信号（SIGSTOP、SIGTSTP 等）停止
+    pub is_stopped: AtomicBool,
+    /// 尚未被父进程 wait 到的作业控制状态变化
+    pub job_event: SpinNoIrq<Option<JobEvent>>,
+    /// 进程被停止时，其中的线程在此等待恢复运行
+    pub stop_wq: WaitQueue,
 
     /// 地址空间
     pub memory_set: Arc<Mutex<MemorySet>>,
@@ -76,8 +102,26 @@ pub struct Executor {
     /// 信号处理模块
     /// 第一维代表TaskID，第二维代表对应的信号处理模块
     pub signal_modules: Mutex<BTreeMap<u64, SignalModule>>,
-    /// 栈大小
-    pub stack_size: AtomicU64,
+    /// 发送给进程的实时信号队列
+    pub rt_queue: SpinNoIrq<SigQueue>,
+    /// 向进程中的任意线程加入信号的次数，等待队列的条件通过它得知等待期间是否收到了信号
+    pub signal_events: AtomicUsize,
+    /// 资源限制
+    pub rlimits: SpinNoIrq<[RLimit; RLIM_NLIMITS]>,
+    /// 下一次因 RLIMIT_CPU 发送 SIGXCPU 时的 CPU 时间（秒），为 0 时表示软上限
+    pub next_xcpu_secs: AtomicU64,
+    /// 用户凭证
+    pub cred: SpinNoIrq<Credentials>,
+    /// 执行域（personality），fork 与 exec 时保留
+    pub personality: AtomicU32,
+    /// timer_create 创建的计时器，键为计时器 id
+    pub posix_timers: SpinNoIrq<BTreeMap<usize, Arc<PosixTimer>>>,
+    /// `posix_timers` 中的计时器数，处理信号时不用加锁就可以跳过没有计时器的进程
+    pub posix_timer_count: AtomicUsize,
+    /// 跟踪状态
+    pub ptrace: SpinNoIrq<PtraceState>,
+    /// 被跟踪的进程停止时，在此等待跟踪者使其继续运行
+    pub ptrace_wq: WaitQueue,
     pub main_task: Mutex<Option<TaskRef>>,
     pub tasks: Mutex<Vec<TaskRef>>,
 
@@ -92,6 +136,8 @@ unsafe impl Send for Executor {}
 
 impl Executor {
     /// 创建一个新的 Executor（进程）
+    ///
+    /// 新的进程单独组成一个进程组与会话，fork 时需要再设置为继承父进程的
     pub fn new(
         pid: u64,
         parent: u64,
@@ -103,21 +149,36 @@ impl Executor {
     ) -> Self {
         // let mut scheduler = Scheduler::new();
         // scheduler.init();
+        async_mem::swap::register(&memory_set);
         Self {
             pid,
             parent: AtomicU64::new(parent),
+            pgid: AtomicU64::new(pid),
+            sid: AtomicU64::new(pid),
             children: Mutex::new(Vec::new()),
             // scheduler: Arc::new(SpinNoIrq::new(scheduler)),
             fd_manager: FdManager::new(fd_table, cwd, mask, FD_LIMIT_ORIGIN),
             is_zombie: AtomicBool::new(false),
             exit_code: AtomicIsize::new(0),
+            is_stopped: AtomicBool::new(false),
+            job_event: SpinNoIrq::new(None),
+            stop_wq: WaitQueue::new(),
             memory_set,
             heap_bottom: AtomicU64::new(heap_bottom),
             heap_top: AtomicU64::new(heap_bottom),
             blocked_by_vfork: Mutex::new(false),
             file_path: Mutex::new(String::new()),
             signal_modules: Mutex::new(BTreeMap::new()),
-            stack_size: AtomicU64::new(axconfig::TASK_STACK_SIZE as _),
+            rt_queue: SpinNoIrq::new(SigQueue::default()),
+            signal_events: AtomicUsize::new(0),
+            rlimits: SpinNoIrq::new(default_rlimits(FD_LIMIT_ORIGIN as u64)),
+            next_xcpu_secs: AtomicU64::new(0),
+            cred: SpinNoIrq::new(Credentials::default()),
+            personality: AtomicU32::new(0),
+            posix_timers: SpinNoIrq::new(BTreeMap::new()),
+            posix_timer_count: AtomicUsize::new(0),
+            ptrace: SpinNoIrq::new(PtraceState::default()),
+            ptrace_wq: WaitQueue::new(),
             main_task: Mutex::new(None),
             tasks: Mutex::new(Vec::new()),
             robust_list: Mutex::new(BTreeMap::new()),
@@ -174,6 +235,31 @@ impl Executor {
         self.parent.store(parent, Ordering::Release)
     }
 
+    /// 获取进程组 id
+    pub fn pgid(&self) -> u64 {
+        self.pgid.load(Ordering::Acquire)
+    }
+
+    /// 设置进程组 id
+    pub fn set_pgid(&self, pgid: u64) {
+        self.pgid.store(pgid, Ordering::Release)
+    }
+
+    /// 获取会话 id
+    pub fn sid(&self) -> u64 {
+        self.sid.load(Ordering::Acquire)
+    }
+
+    /// 设置会话 id
+    pub fn set_sid(&self, sid: u64) {
+        self.sid.store(sid, Ordering::Release)
+    }
+
+    /// 是否为会话首进程
+    pub fn is_session_leader(&self) -> bool {
+        self.sid() == self.pid
+    }
+
     /// 获取 Executor（进程）退出码
     pub fn get_exit_code(&self) -> isize {
         self.exit_code.load(Ordering::Acquire)
@@ -214,14 +300,107 @@ impl Executor {
         self.heap_bottom.store(bottom, Ordering::Release)
     }
 
-    /// set stack size
-    pub fn set_stack_limit(&self, limit: u64) {
-        self.stack_size.store(limit, Ordering::Release)
-    }
-
     /// get stack size
     pub fn get_stack_limit(&self) -> u64 {
-        self.stack_size.load(Ordering::Acquire)
+        self.rlimits.lock()[RLIMIT_STACK].rlim_cur
+    }
+
+    /// 获取用户凭证
+    pub fn cred(&self) -> Credentials {
+        self.cred.lock().clone()
+    }
+
+    /// 获取执行域
+    pub fn personality(&self) -> u32 {
+        self.personality.load(Ordering::Acquire)
+    }
+
+    /// 设置执行域，返回原来的值
+    pub fn set_personality(&self, personality: u32) -> u32 {
+        self.personality.swap(personality, Ordering::AcqRel)
+    }
+
+    /// 获取资源 `resource` 的限制
+    pub fn get_rlimit(&self, resource: usize) -> RLimit {
+        self.rlimits.lock()[resource]
+    }
+
+    /// 设置资源 `resource` 的限制，并同步到对应的子系统中
+    ///
+    /// 不检查权限，提高硬上限与修改其他进程的限制是否允许由调用者检查
+    pub async fn set_rlimit(&self, resource: usize, limit: RLimit) -> AxResult<()> {
+        if resource >= RLIM_NLIMITS || limit.rlim_cur > limit.rlim_max {
+            return Err(AxError::InvalidInput);
+        }
+        self.rlimits.lock()[resource] = limit;
+        match resource {
+            RLIMIT_NOFILE => self.fd_manager.set_limit(limit.rlim_cur),
+            RLIMIT_AS | RLIMIT_DATA | RLIMIT_STACK => self.sync_memory_limits().await,
+            RLIMIT_CPU => self.next_xcpu_secs.store(0, Ordering::Release),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// 继承 `parent` 的所有资源限制
+    pub async fn inherit_rlimits(&self, parent: &Executor) {
+        let rlimits = *parent.rlimits.lock();
+        *self.rlimits.lock() = rlimits;
+        self.fd_manager.set_limit(rlimits[RLIMIT_NOFILE].rlim_cur);
+        self.sync_memory_limits().await;
+    }
+
+    /// 将 RLIMIT_AS、RLIMIT_DATA 与 RLIMIT_STACK 同步到地址空间中，由 mmap 与栈的扩展检查
+    async fn sync_memory_limits(&self) {
+        let (as_limit, data_limit, stack_limit) = {
+            let rlimits = self.rlimits.lock();
+            (
+                rlimits[RLIMIT_AS].rlim_cur,
+                rlimits[RLIMIT_DATA].rlim_cur,
+                rlimits[RLIMIT_STACK].rlim_cur,
+            )
+        };
+        self.memory_set.lock().await.set_limits(
+            as_limit.try_into().unwrap_or(usize::MAX),
+            data_limit.try_into().unwrap_or(usize::MAX),
+            stack_limit.try_into().unwrap_or(usize::MAX),
+        );
+    }
+
+    /// 进程中所有线程使用的 CPU 时间，单位为纳秒
+    pub async fn cpu_time_ns(&self) -> usize {
+        self.tasks
+            .lock()
+            .await
+            .iter()
+            .map(|task| {
+                let (utime, stime) = task.time_stat_output();
+                utime + stime
+            })
+            .sum()
+    }
+
+    /// 检查进程的 CPU 时间是否超过了 RLIMIT_CPU，返回需要发送给进程的信号
+    ///
+    /// 超过软上限后每经过一秒发送一次 SIGXCPU，超过硬上限时发送 SIGKILL
+    pub async fn check_cpu_limit(&self) -> Option<SignalNo> {
+        let limit = self.get_rlimit(RLIMIT_CPU);
+        if limit.rlim_cur == RLIM_INFINITY {
+            return None;
+        }
+        let secs = (self.cpu_time_ns().await / 1_000_000_000) as u64;
+        if secs >= limit.rlim_max {
+            return Some(SignalNo::SIGKILL);
+        }
+        let next = self
+            .next_xcpu_secs
+            .load(Ordering::Acquire)
+            .max(limit.rlim_cur);
+        if secs < next {
+            return None;
+        }
+        self.next_xcpu_secs.store(secs + 1, Ordering::Release);
+        Some(SignalNo::SIGXCPU)
     }
 
     /// 设置 Executor（进程）是否被 vfork 阻塞
@@ -254,6 +433,97 @@ impl Executor {
         None
     }
 
+    /// 判断进程是否被作业控制信号停止
+    pub fn is_stopped(&self) -> bool {
+        self.is_stopped.load(Ordering::Acquire)
+    }
+
+    /// 因信号 `signal` 停止进程，若进程之前未被停止则返回 true
+    pub fn stop(&self, signal: SignalNo) -> bool {
+        if self.is_stopped.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+        *self.job_event.lock() = Some(JobEvent::Stopped(signal));
+        true
+    }
+
+    /// 恢复被停止的进程，若进程之前被停止则返回 true
+    ///
+    /// `report` 表示是否需要让父进程通过 WCONTINUED 观察到这次恢复
+    pub fn resume(&self, report: bool) -> bool {
+        if !self.is_stopped.swap(false, Ordering::AcqRel) {
+            return false;
+        }
+        *self.job_event.lock() = report.then_some(JobEvent::Continued);
+        self.stop_wq.notify_all();
+        true
+    }
+
+    /// 若进程被停止，则等待其恢复运行
+    pub async fn wait_while_stopped(&self) {
+        if self.is_stopped() {
+            self.stop_wq.wait_until(|| !self.is_stopped()).await;
+        }
+    }
+
+    /// 在等待队列 `wq` 上等待 `condition` 成立，等待可以被信号打断
+    ///
+    /// 当前任务收到信号时返回 `Interrupted`，给出 `deadline` 且到达时返回 `Timeout`。
+    /// 返回 Ok 之后条件可能又不再成立，调用者需要重新检查。
+    /// 未使能 irq 时没有时钟中断，超时只在被唤醒时检查
+    pub async fn wait_interruptible<F>(
+        &self,
+        wq: &WaitQueue,
+        deadline: Option<TimeValue>,
+        condition: F,
+    ) -> AxResult
+    where
+        F: Fn() -> bool + Unpin,
+    {
+        let tid = current_task().id().as_u64();
+        loop {
+            // 先记录信号的计数再检查信号，之后加入的信号一定会使下面的等待结束
+            let events = self.signal_events.load(Ordering::Acquire);
+            if condition() {
+                return Ok(());
+            }
+            if self.have_signals().await.is_some() {
+                return Err(AxError::Interrupted);
+            }
+            if deadline.is_some_and(|deadline| current_time() >= deadline) {
+                return Err(AxError::Timeout);
+            }
+            // 信号会唤醒任务，但等待队列只会重新检查条件，因此条件中也要检查信号
+            let wake = || {
+                condition()
+                    || self.signal_events.load(Ordering::Acquire) != events
+                    || crate::posix_timer::pending_timer_signal(self, tid).is_some()
+            };
+            match deadline {
+                // 定时器只在返回 Pending 时设置，条件已经成立时不会改变任务的状态
+                #[cfg(feature = "irq")]
+                Some(deadline) => {
+                    wq.wait_timeout_until(deadline, wake).await;
+                }
+                _ => wq.wait_until(wake).await,
+            }
+        }
+    }
+
+    /// 取出一个尚未被报告的作业控制状态变化，`options` 指定需要报告的类型
+    pub fn take_job_event(&self, options: WaitOptions) -> Option<JobEvent> {
+        let mut job_event = self.job_event.lock();
+        match *job_event {
+            Some(JobEvent::Stopped(_)) if options.contains(WaitOptions::STOPPED) => {
+                job_event.take()
+            }
+            Some(JobEvent::Continued) if options.contains(WaitOptions::CONTINUED) => {
+                job_event.take()
+            }
+            _ => None,
+        }
+    }
+
     #[inline]
     /// Pick one task from Executor
     pub fn pick_next_task(&self) -> Option<TaskRef> {
@@ -349,6 +619,18 @@ impl Executor {
             .await
     }
 
+    /// 将 `value` 写入用户地址 `ptr` 处，地址无效时返回 [`AxError::BadAddress`]
+    ///
+    /// fork 之后私有页面被写保护，内核直接写入这样的页面会在内核态发生缺页，
+    /// 因此写入之前先分配页面并解除写时复制
+    pub async fn write_user<T>(&self, ptr: *mut T, value: T) -> AxResult<()> {
+        if ptr.is_null() || self.manual_alloc_type_for_lazy(ptr).await.is_err() {
+            return Err(AxError::BadAddress);
+        }
+        unsafe { ptr.write_unaligned(value) };
+        Ok(())
+    }
+
     /// 为进程分配一个文件描述符
     pub fn alloc_fd(&self, fd_table: &mut Vec<Option<Arc<dyn FileIO + Unpin>>>) -> AxResult<usize> {
         for (i, fd) in fd_table.iter().enumerate() {
@@ -374,7 +656,8 @@ impl Executor {
             .unwrap()
             .signal_set
             .find_signal()
-            .map_or_else(|| current_task.check_pending_signal(), Some)
+            .or_else(|| current_task.check_pending_signal())
+            .or_else(|| crate::posix_timer::pending_timer_signal(self, current_task.id().as_u64()))
     }
 
     /// Judge whether the signal request the interrupted syscall to restart
@@ -427,7 +710,7 @@ impl Executor {
             };
         }
         let (entry, user_stack_bottom, heap_bottom) =
-            if let Ok(ans) = load_app(path.clone(), args, envs, &mut memory_set).await {
+            if let Ok(ans) = load_app(path.clone(), args, envs, &mut memory_set, true).await {
                 ans
             } else {
                 error!("Failed to load app {}", path);
@@ -458,6 +741,7 @@ impl Executor {
             Arc::new(Mutex::new(String::from("/").into())),
             Arc::new(AtomicI32::new(0o022)),
         ));
+        new_executor.sync_memory_limits().await;
         if !path.starts_with('/') {
             //如果path不是绝对路径, 则加上当前工作目录
             let cwd = new_executor.get_cwd().await;
@@ -465,6 +749,8 @@ impl Executor {
             path = format!("{}{}", cwd, path);
         }
         new_executor.set_file_path(path.clone()).await;
+        // 内核直接创建的用户进程是新会话的首进程，并获得控制台作为控制终端
+        CONSOLE_TTY.set_controlling(new_executor.sid(), new_executor.pgid());
         let scheduler = KERNEL_SCHEDULER.clone();
         let fut = UTRAP_HANDLER();
         let pid = new_executor.pid();
@@ -482,9 +768,8 @@ impl Executor {
         new_executor.tasks.lock().await.push(new_task.clone());
         new_task.get_scheduler().lock().add_task(new_task.clone());
         TID2TASK
-            .lock()
-            .await
-            .insert(new_task.id().as_u64(), Arc::clone(&new_task));
+            .insert(new_task.id().as_u64(), Arc::clone(&new_task))
+            .await;
         new_task.set_leader(true);
         new_executor.set_main_task(new_task.clone()).await;
 
@@ -499,14 +784,12 @@ impl Executor {
             .await
             .insert(new_task.id().as_u64(), FutexRobustList::default());
         PID2PC
-            .lock()
-            .await
-            .insert(new_executor.pid(), Arc::clone(&new_executor));
+            .insert(new_executor.pid(), Arc::clone(&new_executor))
+            .await;
         // 记录内核 executor
         PID2PC
-            .lock()
-            .await
-            .insert(KERNEL_EXECUTOR_ID, KERNEL_EXECUTOR.clone());
+            .insert(KERNEL_EXECUTOR_ID, KERNEL_EXECUTOR.clone())
+            .await;
         // 将其作为内核进程的子进程
         KERNEL_EXECUTOR
             .children
@@ -518,6 +801,10 @@ impl Executor {
 
     /// 实现简易的clone系统调用
     /// 返回值为新产生的任务的id
+    ///
+    /// `stack` 由用户分配，内核不为其保留保护间隔：用 `MAP_GROWSDOWN` 映射的线程栈与主线程的栈一样向下扩展，
+    /// 其余的线程栈依靠用户库设置的保护页，溢出时访问保护页得到 SIGSEGV，
+    /// 信号栈帧也放不下时以 SIGSEGV 结束进程
     pub async fn clone_task(
         &self,
         flags: usize,
@@ -528,6 +815,12 @@ impl Executor {
         exit_signal: Option<SignalNo>,
     ) -> AxResult<u64> {
         let clone_flags = CloneFlags::from_bits((flags & !0x3f) as u32).unwrap();
+        // 实际用户的任务数不能超过 RLIMIT_NPROC，特权进程不受限制
+        let cred = self.cred();
+        let nproc_limit = self.get_rlimit(RLIMIT_NPROC).rlim_cur;
+        if !cred.is_privileged() && user_task_count(cred.uid).await as u64 >= nproc_limit {
+            return Err(AxError::WouldBlock);
+        }
         // 是否共享虚拟地址空间
         let new_memory_set = if clone_flags.contains(CloneFlags::CLONE_VM) {
             Arc::clone(&self.memory_set)
@@ -595,9 +888,8 @@ impl Executor {
 
         debug!("new task:{}", new_task.id_name());
         TID2TASK
-            .lock()
-            .await
-            .insert(new_task.id().as_u64(), Arc::clone(&new_task));
+            .insert(new_task.id().as_u64(), Arc::clone(&new_task))
+            .await;
         let new_handler = if clone_flags.contains(CloneFlags::CLONE_SIGHAND) {
             // let curr_id = current().id().as_u64();
             self.signal_modules
@@ -741,11 +1033,17 @@ impl Executor {
             ));
             // 复制当前工作文件夹
             new_process.set_cwd(self.get_cwd().await).await;
+            // 继承进程组与会话
+            new_process.set_pgid(self.pgid());
+            new_process.set_sid(self.sid());
+            // 继承资源限制
+            new_process.inherit_rlimits(self).await;
+            // 继承用户凭证
+            *new_process.cred.lock() = self.cred();
+            // 继承执行域
+            new_process.set_personality(self.personality());
             // 记录该进程，防止被回收
-            PID2PC
-                .lock()
-                .await
-                .insert(process_id, Arc::clone(&new_process));
+            PID2PC.insert(process_id, Arc::clone(&new_process)).await;
             new_task.set_leader(true);
             new_process.set_main_task(new_task.clone()).await;
             new_process.tasks.lock().await.push(Arc::clone(&new_task));
@@ -768,9 +1066,7 @@ impl Executor {
                 .insert(new_task.id().as_u64(), FutexRobustList::default());
             return_id = new_process.pid;
             PID2PC
-                .lock()
-                .await
-                .get_mut(&parent_id)
+                .get(&parent_id)
                 .unwrap()
                 .children
                 .lock()
@@ -821,7 +1117,20 @@ impl Executor {
     /// 将当前进程替换为指定的用户程序
     /// args为传入的参数
     /// 任务的统计时间会被重置
-    pub async fn exec(&self, name: String, args: Vec<String>, envs: &Vec<String>) -> AxResult<()> {
+    ///
+    /// 可执行文件的格式在释放原有资源之前检查，不能执行时返回 ENOENT、ENOEXEC 或 ELOOP，进程保持不变
+    pub async fn exec(
+        &self,
+        name: String,
+        args: Vec<String>,
+        envs: &Vec<String>,
+    ) -> LinuxResult<()> {
+        let args = if args.is_empty() {
+            vec![name.clone()]
+        } else {
+            args
+        };
+        let bprm = binfmt::resolve(name.clone(), args).await?;
         // 首先要处理原先进程的资源
         // 处理分配的页帧
         // 之后加入额外的东西之后再处理其他的包括信号等因素
@@ -849,6 +1158,12 @@ impl Executor {
 
         // 关闭 `CLOEXEC` 的文件
         self.fd_manager.close_on_exec().await;
+        // 保存有效 id
+        {
+            let mut cred = self.cred.lock();
+            cred.suid = cred.euid;
+            cred.sgid = cred.egid;
+        }
         let current_task = current_task();
         // 再考虑手动结束其他所有的task
         // 暂时不支持其他任务，因此直接注释掉下面的代码，并且目前的设计也不支持寻找其他的任务
@@ -864,7 +1179,7 @@ impl Executor {
                 }
                 tasks.push(task);
             } else {
-                TID2TASK.lock().await.remove(&task.id().as_u64());
+                TID2TASK.remove(&task.id().as_u64()).await;
                 panic!("currently not support exec when has another task ");
             }
         }
@@ -876,19 +1191,10 @@ impl Executor {
         current_task.set_name(name.split('/').last().unwrap());
         assert!(tasks.len() == 1);
         drop(tasks);
-        let args = if args.is_empty() {
-            vec![name.clone()]
-        } else {
-            args
-        };
-        let (entry, user_stack_bottom, heap_bottom) = if let Ok(ans) =
-            load_app(name.clone(), args, envs, &mut *self.memory_set.lock().await).await
-        {
-            ans
-        } else {
-            error!("Failed to load app {}", name);
-            return Err(AxError::NotFound);
-        };
+        // 设置了 ADDR_NO_RANDOMIZE 时使用固定的地址空间布局
+        let randomize = self.personality() & ADDR_NO_RANDOMIZE == 0;
+        let (entry, user_stack_bottom, heap_bottom) =
+            load_binprm(bprm, envs, &mut *self.memory_set.lock().await, randomize).await?;
         // 切换了地址空间， 需要切换token
         let page_table_token = if self.pid == KERNEL_EXECUTOR_ID {
             0
@@ -919,6 +1225,9 @@ impl Executor {
             // 重置信号处理模块
             // 此时只会留下一个线程
             self.signal_modules.lock().await.clear();
+            self.rt_queue.lock().clear();
+            // exec 之后不再保留之前创建的计时器
+            crate::posix_timer::delete_all_timers(self);
             self.signal_modules
                 .lock()
                 .await
@@ -948,10 +1257,12 @@ impl Executor {
 
         // release vfork for parent process
         {
-            let pid2pc = PID2PC.lock().await;
-            let parent_process = pid2pc.get(&self.get_parent()).unwrap();
+            let parent_process = PID2PC.get(&self.get_parent()).unwrap();
             parent_process.set_vfork_block(false).await;
-            drop(pid2pc);
+        }
+        // 被跟踪的进程在 exec 成功后停止，跟踪者可以在新程序运行之前设置断点
+        if self.is_traced() {
+            crate::ptrace::exec_stop(self).await;
         }
         Ok(())
     }
@@ -979,3 +1290,18 @@ impl Executor {
         ktask
     }
 }
+
+/// 实际用户 id 为 `uid` 的所有进程中的任务总数，用于检查 RLIMIT_NPROC
+async fn user_task_count(uid: u32) -> usize {
+    let processes: Vec<Arc<Executor>> = PID2PC
+        .read()
+        .values()
+        .filter(|process| process.cred().uid == uid)
+        .cloned()
+        .collect();
+    let mut count = 0;
+    for process in processes {
+        count += process.tasks.lock().await.len();
+    }
+    count
+}
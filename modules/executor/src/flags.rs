@@ -1,5 +1,6 @@
-//! clone 任务时指定的参数。
+//! clone 与 wait 任务时使用的参数和状态。
 
+use axsignal::signal_no::SignalNo;
 use bitflags::*;
 
 bitflags! {
@@ -56,4 +57,36 @@ pub enum WaitStatus {
     Running,
     /// 找不到对应的子任务
     NotExist,
+    /// 已经等待到子任务，但无法将状态写入用户给出的地址
+    BadAddress,
+}
+
+bitflags! {
+    /// sys_wait4 中除了退出之外，还需要报告的子进程状态变化
+    #[derive(Debug, Clone, Copy)]
+    pub struct WaitOptions: u32 {
+        /// 报告被停止的子进程（WUNTRACED）
+        const STOPPED = 1 << 1;
+        /// 报告被 SIGCONT 恢复运行的子进程（WCONTINUED）
+        const CONTINUED = 1 << 3;
+    }
+}
+
+/// 进程因作业控制产生的状态变化，等待被父进程 wait 到
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobEvent {
+    /// 被对应的信号停止
+    Stopped(SignalNo),
+    /// 被 SIGCONT 恢复运行
+    Continued,
+}
+
+impl JobEvent {
+    /// 按照 wait 的 status 格式编码
+    pub fn wait_status(&self) -> i32 {
+        match self {
+            JobEvent::Stopped(signal) => ((*signal as i32) << 8) | 0x7f,
+            JobEvent::Continued => 0xffff,
+        }
+    }
 }
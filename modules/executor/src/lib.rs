@@ -1,4 +1,4 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![feature(type_alias_impl_trait)]
 
 extern crate alloc;
@@ -14,8 +14,17 @@ mod loader;
 pub mod signal;
 mod stdio;
 
+pub mod aslr;
+pub mod binfmt;
+pub mod coredump;
+pub mod cred;
 pub mod flags;
-pub use loader::load_app;
+pub mod posix_timer;
+pub mod ptrace;
+pub mod random;
+pub mod rlimit;
+pub mod sigqueue;
+pub use loader::{load_app, load_binprm};
 
 pub use api::*;
 pub use current::CurrentExecutor;
@@ -24,6 +33,6 @@ pub type ExecutorRef = alloc::sync::Arc<Executor>;
 pub use executor::*;
 pub use fd_manager::*;
 pub use signal::*;
-pub use stdio::{Stderr, Stdin, Stdout};
+pub use stdio::{ConsoleTty, Stderr, Stdin, Stdout, CONSOLE_TTY};
 pub use taskctx::{BaseScheduler, TaskId, TaskRef};
 pub mod futex;
@@ -1,129 +1,354 @@
-use core::{ptr::copy_nonoverlapping, str::from_utf8};
-
-use crate::link::real_path;
-use alloc::{
-    string::{String, ToString},
-    vec,
-    vec::Vec,
+use crate::{
+    aslr::AddressLayout,
+    binfmt::{self, LinuxBinprm},
+    random::fill_random,
 };
-use async_mem::MemorySet;
-use axconfig::{MAX_USER_HEAP_SIZE, MAX_USER_STACK_SIZE, USER_HEAP_BASE, USER_STACK_TOP};
-use axerrno::{AxError, AxResult};
-use axhal::{mem::VirtAddr, paging::MappingFlags};
-use elf_parser::{
-    get_app_stack_region, get_auxv_vector, get_elf_entry, get_elf_segments, get_relocate_pairs,
+use alloc::{boxed::Box, collections::BTreeMap, string::String, vec, vec::Vec};
+use async_fs::api::File;
+use async_mem::{BackEndFile, MemBackend, MemorySet};
+use axconfig::{MAX_USER_HEAP_SIZE, MAX_USER_STACK_SIZE};
+use axerrno::{LinuxError, LinuxResult};
+use axhal::{
+    mem::{VirtAddr, PAGE_SIZE_4K},
+    paging::MappingFlags,
 };
-use xmas_elf::program::SegmentData;
+use xmas_elf::{header, program::Type, ElfFile};
+
+const AT_PHDR: u8 = 3;
+const AT_PHENT: u8 = 4;
+const AT_PHNUM: u8 = 5;
+const AT_PAGESZ: u8 = 6;
+const AT_BASE: u8 = 7;
+const AT_FLAGS: u8 = 8;
+const AT_ENTRY: u8 = 9;
+const AT_HWCAP: u8 = 16;
+const AT_CLKTCK: u8 = 17;
+const AT_SECURE: u8 = 23;
+const AT_RANDOM: u8 = 25;
+const AT_EXECFN: u8 = 31;
+const AT_SYSINFO_EHDR: u8 = 33;
 
 /// 返回应用程序入口，用户栈底，用户堆底
+///
+/// `randomize` 为 true 时随机化地址空间布局
 pub async fn load_app(
-    mut name: String,
-    mut args: Vec<String>,
+    name: String,
+    args: Vec<String>,
     envs: &Vec<String>,
     memory_set: &mut MemorySet,
-) -> AxResult<(VirtAddr, VirtAddr, VirtAddr)> {
-    loop {
-        if name.ends_with(".sh") {
-            args = [vec![String::from("busybox"), String::from("sh")], args].concat();
-            name = "busybox".to_string();
-            continue;
-        }
-        let elf_data = if let Ok(ans) = async_fs::api::read(name.as_str()).await {
-            ans
-        } else {
-            // exit(0)
-            info!("App not found: {}", name);
-            return Err(AxError::NotFound);
-        };
-        let elf = xmas_elf::ElfFile::new(&elf_data).expect("Error parsing app ELF file.");
-        if let Some(interp) = elf
-            .program_iter()
-            .find(|ph| ph.get_type() == Ok(xmas_elf::program::Type::Interp))
-        {
-            let interp = match interp.get_data(&elf) {
-                Ok(SegmentData::Undefined(data)) => data,
-                _ => panic!("Invalid data in Interp Elf Program Header"),
-            };
-
-            let interp_path = from_utf8(interp).expect("Interpreter path isn't valid UTF-8");
-            // remove trailing '\0'
-            let interp_path = interp_path.trim_matches(char::from(0)).to_string();
-            let real_interp_path = real_path(&interp_path).await;
-            args = [vec![real_interp_path.clone()], args].concat();
-            name = real_interp_path;
-            continue;
-        }
-        info!("load app args: {:?} name: {}", args, name);
-        let elf_base_addr = Some(0x400_0000);
-        warn!("The elf base addr may be different in different arch!");
-        // let (entry, segments, relocate_pairs) = parse_elf(&elf, elf_base_addr);
-        let entry = get_elf_entry(&elf, elf_base_addr);
-        let segments = get_elf_segments(&elf, elf_base_addr);
-        let relocate_pairs = get_relocate_pairs(&elf, elf_base_addr);
-        for segment in segments {
-            memory_set
-                .new_region(
-                    segment.vaddr,
-                    segment.size,
-                    false,
-                    segment.flags,
-                    segment.data.as_deref(),
-                    None,
-                )
-                .await;
-        }
+    randomize: bool,
+) -> LinuxResult<(VirtAddr, VirtAddr, VirtAddr)> {
+    let bprm = binfmt::resolve(name, args).await?;
+    load_binprm(bprm, envs, memory_set, randomize).await
+}
 
-        for relocate_pair in relocate_pairs {
-            let src: usize = relocate_pair.src.into();
-            let dst: usize = relocate_pair.dst.into();
-            let count = relocate_pair.count;
-            unsafe { copy_nonoverlapping(src.to_ne_bytes().as_ptr(), dst as *mut u8, count) }
-        }
+/// 以文件为后端的一段映射，对应一个 PT_LOAD 段或者其中的一部分
+struct FileSegment {
+    /// 起始地址，按页对齐
+    start: usize,
+    /// 结束地址，按页对齐
+    end: usize,
+    /// 起始地址对应的文件偏移，按页对齐
+    offset: u64,
+    /// 文件内容的结束偏移，之后的部分（bss）填 0
+    file_end: u64,
+    flags: MappingFlags,
+}
 
-        let vdso_base = vdso::VDSO_INFO.vdso2memoryset(memory_set).await;
+impl FileSegment {
+    /// 在页对齐的地址 `at` 处拆分，自身保留 `[start, at)`，返回 `[at, end)`
+    fn split_off(&mut self, at: usize) -> FileSegment {
+        let tail = FileSegment {
+            start: at,
+            end: self.end,
+            offset: self.offset + (at - self.start) as u64,
+            file_end: self.file_end,
+            flags: self.flags,
+        };
+        self.end = at;
+        tail
+    }
 
-        // Now map the stack and the heap
-        let heap_start = VirtAddr::from(USER_HEAP_BASE);
-        let heap_data = [0_u8].repeat(MAX_USER_HEAP_SIZE);
+    async fn map(self, file: &File, memory_set: &mut MemorySet) {
+        if self.start == self.end {
+            return;
+        }
+        let backend = MemBackend::new_with_len(
+            BackEndFile::new(Box::new(file.clone())),
+            self.offset,
+            self.file_end - self.offset,
+        )
+        .await;
+        debug!(
+            "[new region] elf segment: [{:#x}, {:#x}) offset: {:#x} {:?}",
+            self.start, self.end, self.offset, self.flags
+        );
         memory_set
             .new_region(
-                heap_start,
-                MAX_USER_HEAP_SIZE,
+                self.start.into(),
+                self.end - self.start,
                 false,
-                MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
-                Some(&heap_data),
+                self.flags,
                 None,
+                Some(backend),
             )
             .await;
+    }
+}
+
+/// 将 ELF 文件的 PT_LOAD 段映射到 `base` 处，非 PIE 的文件按照其中给出的地址映射，返回加载的偏移与入口
+///
+/// 段以文件为后端延迟映射，缺页时才从文件中读取。各段的布局已经在 resolve 中检查过，
+/// 加上加载偏移之后地址溢出时返回 ENOEXEC
+async fn map_elf(
+    bprm: &LinuxBinprm,
+    base: VirtAddr,
+    memory_set: &mut MemorySet,
+) -> LinuxResult<(usize, VirtAddr)> {
+    let elf = ElfFile::new(&bprm.data).unwrap();
+    let bias = match elf.header.pt2.type_().as_type() {
+        header::Type::SharedObject => base.as_usize(),
+        _ => 0,
+    };
+    let mut current: Option<FileSegment> = None;
+    for ph in elf
+        .program_iter()
+        .filter(|ph| ph.get_type() == Ok(Type::Load))
+    {
+        let vaddr = bias
+            .checked_add(ph.virtual_addr() as usize)
+            .ok_or(LinuxError::ENOEXEC)?;
+        let start = vaddr / PAGE_SIZE_4K * PAGE_SIZE_4K;
+        let end = vaddr
+            .checked_add(ph.mem_size() as usize)
+            .and_then(|end| end.checked_next_multiple_of(PAGE_SIZE_4K))
+            .ok_or(LinuxError::ENOEXEC)?;
+        let file_end = ph
+            .offset()
+            .checked_add(ph.file_size())
+            .ok_or(LinuxError::ENOEXEC)?;
+        let mut flags = MappingFlags::USER;
+        if ph.flags().is_read() {
+            flags |= MappingFlags::READ;
+        }
+        if ph.flags().is_write() {
+            flags |= MappingFlags::WRITE;
+        }
+        if ph.flags().is_execute() {
+            flags |= MappingFlags::EXECUTE;
+        }
+        let mut segment = FileSegment {
+            start,
+            end,
+            offset: ph.offset() - (vaddr - start) as u64,
+            file_end,
+            flags,
+        };
+        match current.take() {
+            // 与上一段落在同一页中：共享的页面单独映射，只有这一页带有两段权限的并集，
+            // 两段其余的页面保持各自的权限。两段在文件中连续，共享的页面从前一段的偏移开始读取
+            Some(mut prev) if start < prev.end => {
+                let mut shared = prev.split_off(start);
+                let rest = segment.split_off(shared.end);
+                shared.file_end = file_end;
+                shared.flags |= flags;
+                prev.map(&bprm.file, memory_set).await;
+                if rest.start == rest.end {
+                    // 这一段整个落在共享的页面中，共享的页面还可能与下一段共享
+                    current = Some(shared);
+                } else {
+                    shared.map(&bprm.file, memory_set).await;
+                    current = Some(rest);
+                }
+            }
+            prev => {
+                if let Some(prev) = prev {
+                    prev.map(&bprm.file, memory_set).await;
+                }
+                current = Some(segment);
+            }
+        }
+    }
+    if let Some(segment) = current {
+        segment.map(&bprm.file, memory_set).await;
+    }
+    let entry = bias
+        .checked_add(elf.header.pt2.entry_point() as usize)
+        .ok_or(LinuxError::ENOEXEC)?;
+    Ok((bias, entry.into()))
+}
+
+/// 程序头表被加载到的地址
+fn phdr_addr(elf: &ElfFile, bias: usize) -> usize {
+    if let Some(phdr) = elf
+        .program_iter()
+        .find(|ph| ph.get_type() == Ok(Type::Phdr))
+    {
+        return bias + phdr.virtual_addr() as usize;
+    }
+    let phoff = elf.header.pt2.ph_offset();
+    elf.program_iter()
+        .find(|ph| {
+            ph.get_type() == Ok(Type::Load)
+                && ph.offset() <= phoff
+                && phoff < ph.offset() + ph.file_size()
+        })
+        .map_or(0, |ph| {
+            bias + (ph.virtual_addr() + (phoff - ph.offset())) as usize
+        })
+}
+
+/// 将 [`binfmt::resolve`] 得到的 ELF 文件加载到地址空间中
+///
+/// 带有动态链接器时同时加载动态链接器，并从动态链接器的入口开始执行。
+/// 内核不做重定位，PIE 程序与动态链接器自行完成重定位
+///
+/// 返回应用程序入口，用户栈底，用户堆底
+pub async fn load_binprm(
+    bprm: LinuxBinprm,
+    envs: &Vec<String>,
+    memory_set: &mut MemorySet,
+    randomize: bool,
+) -> LinuxResult<(VirtAddr, VirtAddr, VirtAddr)> {
+    let layout = AddressLayout::new(randomize);
+    info!(
+        "load app args: {:?} name: {} layout: {:?}",
+        bprm.args, bprm.filename, layout
+    );
+    let (bias, entry) = map_elf(&bprm, layout.exec_base, memory_set).await?;
+
+    let elf = ElfFile::new(&bprm.data).unwrap();
+    let mut auxv = BTreeMap::new();
+    auxv.insert(AT_PHDR, phdr_addr(&elf, bias));
+    auxv.insert(AT_PHENT, elf.header.pt2.ph_entry_size() as usize);
+    auxv.insert(AT_PHNUM, elf.header.pt2.ph_count() as usize);
+    auxv.insert(AT_PAGESZ, PAGE_SIZE_4K);
+    auxv.insert(AT_FLAGS, 0);
+    auxv.insert(AT_ENTRY, entry.as_usize());
+    auxv.insert(AT_HWCAP, 0);
+    auxv.insert(AT_CLKTCK, 100);
+    auxv.insert(AT_SECURE, 0);
+    let entry = if let Some(interp) = bprm.interp.as_ref() {
         info!(
-            "[new region] user heap: [{:?}, {:?})",
-            heap_start,
-            heap_start + MAX_USER_HEAP_SIZE
+            "load interpreter {} at {:?}",
+            interp.filename, layout.interp_base
         );
+        let (interp_bias, interp_entry) = map_elf(interp, layout.interp_base, memory_set).await?;
+        auxv.insert(AT_BASE, interp_bias);
+        interp_entry
+    } else {
+        auxv.insert(AT_BASE, 0);
+        entry
+    };
 
-        let mut auxv = get_auxv_vector(&elf, elf_base_addr);
-        const AT_SYSINFO_EHDR: u8 = 33;
-        auxv.insert(AT_SYSINFO_EHDR, vdso_base.as_usize());
-        let stack_top = VirtAddr::from(USER_STACK_TOP);
-        let stack_size = MAX_USER_STACK_SIZE;
+    let vdso_base = vdso::VDSO_INFO.vdso2memoryset(memory_set).await;
+    auxv.insert(AT_SYSINFO_EHDR, vdso_base.as_usize());
+    memory_set.set_mmap_base(layout.mmap_base);
 
-        let (stack_data, stack_bottom) =
-            get_app_stack_region(args, envs, auxv, stack_top, stack_size);
-        memory_set
-            .new_region(
-                stack_top,
-                stack_size,
-                false,
-                MappingFlags::USER | MappingFlags::READ | MappingFlags::WRITE,
-                Some(&stack_data),
-                None,
-            )
-            .await;
-        info!(
-            "[new region] user stack: [{:?}, {:?})",
+    // Now map the stack and the heap
+    let heap_start = layout.heap_start;
+    let heap_data = [0_u8].repeat(MAX_USER_HEAP_SIZE);
+    memory_set
+        .new_region(
+            heap_start,
+            MAX_USER_HEAP_SIZE,
+            false,
+            MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
+            Some(&heap_data),
+            None,
+        )
+        .await;
+    info!(
+        "[new region] user heap: [{:?}, {:?})",
+        heap_start,
+        heap_start + MAX_USER_HEAP_SIZE
+    );
+
+    let stack_top = layout.stack_start;
+    let stack_size = MAX_USER_STACK_SIZE;
+
+    let (stack_data, stack_bottom) =
+        build_user_stack(&bprm.execfn, bprm.args, envs, auxv, stack_top, stack_size);
+    memory_set
+        .new_region(
             stack_top,
-            stack_top + stack_size
-        );
-        return Ok((entry, stack_bottom.into(), heap_start));
+            stack_size,
+            false,
+            MappingFlags::USER | MappingFlags::READ | MappingFlags::WRITE,
+            Some(&stack_data),
+            None,
+        )
+        .await;
+    // 栈溢出到栈区域下方时向下扩展
+    memory_set.set_grows_down(stack_top);
+    info!(
+        "[new region] user stack: [{:?}, {:?})",
+        stack_top,
+        stack_top + stack_size
+    );
+    Ok((entry, stack_bottom, heap_start))
+}
+
+/// 构造初始的用户栈，返回栈区域的内容与初始的栈指针
+///
+/// 从高地址到低地址依次为：参数与环境变量字符串、程序路径、AT_RANDOM 指向的 16 个随机字节、
+/// 辅助向量、环境变量指针、参数指针、argc。栈指针按 16 字节对齐
+fn build_user_stack(
+    execfn: &str,
+    args: Vec<String>,
+    envs: &[String],
+    mut auxv: BTreeMap<u8, usize>,
+    stack_start: VirtAddr,
+    stack_size: usize,
+) -> (Vec<u8>, VirtAddr) {
+    let mut data = vec![0_u8; stack_size];
+    let base = stack_start.as_usize();
+    let mut sp = base + stack_size;
+    let push_bytes = |data: &mut Vec<u8>, sp: &mut usize, bytes: &[u8]| {
+        *sp -= bytes.len();
+        let offset = *sp - base;
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        *sp
+    };
+    let push_str = |data: &mut Vec<u8>, sp: &mut usize, s: &str| {
+        push_bytes(data, sp, &[0]);
+        push_bytes(data, sp, s.as_bytes())
+    };
+
+    let envp: Vec<usize> = envs
+        .iter()
+        .map(|env| push_str(&mut data, &mut sp, env))
+        .collect();
+    let argv: Vec<usize> = args
+        .iter()
+        .map(|arg| push_str(&mut data, &mut sp, arg))
+        .collect();
+    auxv.insert(AT_EXECFN, push_str(&mut data, &mut sp, execfn));
+    let mut random = [0_u8; 16];
+    fill_random(&mut random);
+    sp &= !0xf;
+    auxv.insert(AT_RANDOM, push_bytes(&mut data, &mut sp, &random));
+
+    let mut words: Vec<usize> = Vec::new();
+    words.push(argv.len());
+    words.extend(argv.iter());
+    words.push(0);
+    words.extend(envp.iter());
+    words.push(0);
+    for (key, value) in auxv.iter() {
+        words.push(*key as usize);
+        words.push(*value);
+    }
+    // AT_NULL
+    words.push(0);
+    words.push(0);
+    // 压栈之后栈指针仍然按 16 字节对齐
+    sp -= words.len() * core::mem::size_of::<usize>();
+    sp &= !0xf;
+    let offset = sp - base;
+    for (i, word) in words.iter().enumerate() {
+        let offset = offset + i * core::mem::size_of::<usize>();
+        data[offset..offset + core::mem::size_of::<usize>()].copy_from_slice(&word.to_ne_bytes());
     }
+    (data, sp.into())
 }
@@ -0,0 +1,400 @@
+//! POSIX 间隔计时器（timer_create 系列）。
+//!
+//! CLOCK_REALTIME 与 CLOCK_MONOTONIC 的计时器在 task_api 的定时器链表中注册回调，
+//! 回调在时钟中断中只记录到期并唤醒目标线程；CPU 时间计时器没有确定的到期时刻，
+//! 在处理信号时与进程或线程已经使用的 CPU 时间比较。
+//! 到期的计时器在处理信号时以 [`SI_TIMER`] 发送信号，信号发送之前再次到期的次数记为 overrun。
+extern crate alloc;
+use crate::{
+    rlimit::RLIMIT_SIGPENDING, send_siginfo_to_thread, send_signal_to_process, sigqueue::SI_TIMER,
+    Executor, PID2PC, TID2TASK,
+};
+use alloc::{sync::Arc, vec::Vec};
+use axerrno::{AxError, AxResult};
+use axhal::time::current_time_nanos;
+use axsignal::{info::SigInfo, signal_no::SignalNo};
+use core::{sync::atomic::Ordering, time::Duration};
+use sync::SpinNoIrq;
+
+/// 计时器到期时发送信号
+pub const SIGEV_SIGNAL: i32 = 0;
+/// 计时器到期时不通知
+pub const SIGEV_NONE: i32 = 1;
+/// 计时器到期时向指定线程发送信号
+pub const SIGEV_THREAD_ID: i32 = 4;
+
+/// timer_settime 的 flags，表示给出的是绝对时间
+pub const TIMER_ABSTIME: usize = 1;
+
+/// overrun 的最大值
+pub const DELAYTIMER_MAX: usize = i32::MAX as usize;
+
+/// 计时器使用的时钟
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerClock {
+    /// 系统实际时间
+    Realtime,
+    /// 系统启动以来的时间
+    Monotonic,
+    /// 进程中所有线程使用的 CPU 时间
+    ProcessCputime,
+    /// 线程使用的 CPU 时间，参数为线程 id
+    ThreadCputime(u64),
+}
+
+impl TimerClock {
+    /// 是否为 CPU 时间时钟
+    pub fn is_cputime(&self) -> bool {
+        matches!(self, Self::ProcessCputime | Self::ThreadCputime(_))
+    }
+
+    /// 读取进程 `pid` 中的时钟的当前值，单位为纳秒
+    pub async fn now_ns(&self, pid: u64) -> usize {
+        match self {
+            Self::Realtime | Self::Monotonic => current_time_nanos() as usize,
+            Self::ProcessCputime => match PID2PC.get(&pid) {
+                Some(process) => process.cpu_time_ns().await,
+                None => 0,
+            },
+            Self::ThreadCputime(tid) => TID2TASK.get(tid).map_or(0, |task| {
+                let (utime, stime) = task.time_stat_output();
+                utime + stime
+            }),
+        }
+    }
+}
+
+/// 计时器到期时的通知方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerNotify {
+    /// 不通知，只能通过 timer_gettime 查询
+    None,
+    /// 向进程发送信号 `signum`，`value` 作为 SigInfo 中的 sigval
+    Signal { signum: usize, value: usize },
+    /// 向线程 `tid` 发送信号 `signum`
+    ThreadSignal {
+        tid: u64,
+        signum: usize,
+        value: usize,
+    },
+}
+
+/// 计时器的可变状态，时刻均按计时器的时钟计算
+#[derive(Debug, Default)]
+struct TimerState {
+    /// 下一次到期的时刻，单位为纳秒，为 0 表示计时器未启动
+    expires_ns: usize,
+    /// 到期后重新启动的间隔，为 0 表示只到期一次
+    interval_ns: usize,
+    /// 每次设置计时器时增加，用于忽略之前设置的回调
+    seq: u64,
+    /// 计时器已经到期，但信号尚未发送
+    pending: bool,
+    /// 信号尚未发送期间额外到期的次数
+    overrun: usize,
+    /// 最近一次发送的信号对应的 overrun
+    last_overrun: usize,
+}
+
+impl TimerState {
+    /// 距离下一次到期的时间
+    fn remaining_ns(&self, now_ns: usize) -> usize {
+        if self.expires_ns == 0 {
+            0
+        } else {
+            self.expires_ns.saturating_sub(now_ns).max(1)
+        }
+    }
+
+    /// 若计时器在 `now_ns` 时已经到期，则记录到期并计算下一次到期的时刻
+    fn expire(&mut self, now_ns: usize) -> bool {
+        if self.expires_ns == 0 || now_ns < self.expires_ns {
+            return false;
+        }
+        let mut overrun = 0;
+        if self.interval_ns > 0 {
+            // 错过的周期同样计入 overrun
+            let missed = (now_ns - self.expires_ns) / self.interval_ns;
+            overrun += missed;
+            self.expires_ns = self
+                .expires_ns
+                .saturating_add((missed + 1).saturating_mul(self.interval_ns));
+        } else {
+            self.expires_ns = 0;
+        }
+        if self.pending {
+            overrun += 1;
+        }
+        self.overrun = self.overrun.saturating_add(overrun);
+        self.pending = true;
+        true
+    }
+}
+
+/// 由 timer_create 创建的计时器
+pub struct PosixTimer {
+    /// 计时器 id，在进程内唯一
+    id: usize,
+    /// 所属进程
+    pid: u64,
+    /// 接收信号的线程，向进程发送的信号由主线程接收
+    target_tid: u64,
+    clock: TimerClock,
+    notify: TimerNotify,
+    state: SpinNoIrq<TimerState>,
+}
+
+impl PosixTimer {
+    /// 计时器 id
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// 计时器使用的时钟
+    pub fn clock(&self) -> TimerClock {
+        self.clock
+    }
+
+    /// 到期时需要发送的信号
+    fn signum(&self) -> Option<usize> {
+        match self.notify {
+            TimerNotify::None => None,
+            TimerNotify::Signal { signum, .. } | TimerNotify::ThreadSignal { signum, .. } => {
+                Some(signum)
+            }
+        }
+    }
+
+    /// 在定时器链表中取消与注册回调时使用的 key
+    fn alarm_key(self: &Arc<Self>) -> usize {
+        Arc::as_ptr(self) as usize
+    }
+
+    /// 按照当前状态在 task_api 的定时器链表中注册下一次到期的回调
+    fn schedule(self: &Arc<Self>, state: &TimerState) {
+        task_api::cancel_alarm_callback(self.alarm_key());
+        if self.clock.is_cputime() || state.expires_ns == 0 {
+            return;
+        }
+        let timer = Arc::downgrade(self);
+        let seq = state.seq;
+        task_api::set_alarm_callback(
+            Duration::from_nanos(state.expires_ns as u64),
+            self.alarm_key(),
+            move |now| {
+                if let Some(timer) = timer.upgrade() {
+                    timer.on_alarm(now.as_nanos() as usize, seq);
+                }
+            },
+        );
+    }
+
+    /// 定时器链表中的回调，在时钟中断中执行
+    fn on_alarm(self: &Arc<Self>, now_ns: usize, seq: u64) {
+        let mut state = self.state.lock();
+        if state.seq != seq || !state.expire(now_ns) {
+            return;
+        }
+        self.schedule(&state);
+        drop(state);
+        self.wakeup_target();
+    }
+
+    /// 唤醒接收信号的线程，使其尽快进入信号处理
+    fn wakeup_target(&self) {
+        if self.signum().is_none() {
+            return;
+        }
+        if let Some(task) = TID2TASK.get(&self.target_tid) {
+            if task.is_blocked() {
+                taskctx::wakeup_task(Arc::as_ptr(&task));
+            }
+        }
+    }
+
+    /// 设置计时器，`value_ns` 为 0 时停止计时器
+    ///
+    /// 返回设置之前的 (间隔, 剩余时间)，单位为纳秒
+    pub async fn settime(
+        self: &Arc<Self>,
+        value_ns: usize,
+        interval_ns: usize,
+        flags: usize,
+    ) -> (usize, usize) {
+        let now_ns = self.clock.now_ns(self.pid).await;
+        let mut state = self.state.lock();
+        let old = (state.interval_ns, state.remaining_ns(now_ns));
+        state.seq += 1;
+        state.overrun = 0;
+        state.interval_ns = interval_ns;
+        state.expires_ns = if value_ns == 0 {
+            0
+        } else if flags & TIMER_ABSTIME != 0 {
+            // 已经过去的绝对时间在下一次检查时立即到期
+            value_ns
+        } else {
+            now_ns.saturating_add(value_ns)
+        };
+        self.schedule(&state);
+        old
+    }
+
+    /// 返回计时器的 (间隔, 剩余时间)，单位为纳秒
+    pub async fn gettime(&self) -> (usize, usize) {
+        let now_ns = self.clock.now_ns(self.pid).await;
+        let state = self.state.lock();
+        (state.interval_ns, state.remaining_ns(now_ns))
+    }
+
+    /// 最近一次发送的信号对应的 overrun
+    pub fn overrun(&self) -> usize {
+        self.state.lock().last_overrun
+    }
+
+    /// 停止计时器并取消已经注册的回调
+    fn disarm(self: &Arc<Self>) {
+        let mut state = self.state.lock();
+        state.seq += 1;
+        state.expires_ns = 0;
+        state.pending = false;
+        task_api::cancel_alarm_callback(self.alarm_key());
+    }
+
+    /// 取出需要发送的信号
+    ///
+    /// SigInfo 中与 kill 发送的信号的 pid、uid 位于相同位置的是 Linux 中的 si_timerid 与 si_overrun
+    fn take_signal(&self) -> Option<(TimerNotify, SigInfo)> {
+        let mut state = self.state.lock();
+        if !state.pending {
+            return None;
+        }
+        state.pending = false;
+        state.last_overrun = core::mem::take(&mut state.overrun).min(DELAYTIMER_MAX);
+        let (signum, value) = match self.notify {
+            TimerNotify::None => return None,
+            TimerNotify::Signal { signum, value }
+            | TimerNotify::ThreadSignal { signum, value, .. } => (signum, value),
+        };
+        let info = SigInfo {
+            si_signo: signum as i32,
+            si_code: SI_TIMER,
+            pid: self.id as i32,
+            uid: state.last_overrun as u32,
+            si_val_int: value as i32,
+            ..Default::default()
+        };
+        Some((self.notify, info))
+    }
+}
+
+/// 创建计时器，返回计时器 id
+///
+/// `notify` 为 None 时向进程发送 SIGALRM，sigval 为计时器 id。
+/// 进程中的计时器数受 RLIMIT_SIGPENDING 限制，超过时返回 WouldBlock
+pub async fn create_timer(
+    process: &Executor,
+    clock: TimerClock,
+    notify: Option<TimerNotify>,
+) -> AxResult<usize> {
+    let target_tid = match notify {
+        Some(TimerNotify::ThreadSignal { tid, .. }) => tid,
+        _ => process
+            .get_main_task()
+            .await
+            .map_or(0, |task| task.id().as_u64()),
+    };
+    let limit = process.get_rlimit(RLIMIT_SIGPENDING).rlim_cur;
+    let mut timers = process.posix_timers.lock();
+    if timers.len() as u64 >= limit {
+        return Err(AxError::WouldBlock);
+    }
+    let id = (0..).find(|id| !timers.contains_key(id)).unwrap();
+    let notify = notify.unwrap_or(TimerNotify::Signal {
+        signum: SignalNo::SIGALRM as usize,
+        value: id,
+    });
+    timers.insert(
+        id,
+        Arc::new(PosixTimer {
+            id,
+            pid: process.pid(),
+            target_tid,
+            clock,
+            notify,
+            state: SpinNoIrq::new(TimerState::default()),
+        }),
+    );
+    process.posix_timer_count.fetch_add(1, Ordering::Release);
+    Ok(id)
+}
+
+/// 根据 id 查找进程中的计时器
+pub fn find_timer(process: &Executor, id: usize) -> Option<Arc<PosixTimer>> {
+    process.posix_timers.lock().get(&id).cloned()
+}
+
+/// 删除计时器，尚未发送的信号不再发送
+pub fn delete_timer(process: &Executor, id: usize) -> AxResult<()> {
+    let timer = process
+        .posix_timers
+        .lock()
+        .remove(&id)
+        .ok_or(AxError::InvalidInput)?;
+    process.posix_timer_count.fetch_sub(1, Ordering::Release);
+    timer.disarm();
+    Ok(())
+}
+
+/// 删除进程的所有计时器，在进程退出与 exec 时调用
+pub fn delete_all_timers(process: &Executor) {
+    let timers = core::mem::take(&mut *process.posix_timers.lock());
+    process
+        .posix_timer_count
+        .fetch_sub(timers.len(), Ordering::Release);
+    for timer in timers.values() {
+        timer.disarm();
+    }
+}
+
+/// 线程 `tid` 是否有到期的计时器需要向其发送信号，有则返回信号编号
+pub fn pending_timer_signal(process: &Executor, tid: u64) -> Option<usize> {
+    if process.posix_timer_count.load(Ordering::Acquire) == 0 {
+        return None;
+    }
+    process
+        .posix_timers
+        .lock()
+        .values()
+        .filter(|timer| timer.target_tid == tid && timer.state.lock().pending)
+        .find_map(|timer| timer.signum())
+}
+
+/// 检查 CPU 时间计时器是否到期，并发送所有到期的计时器的信号
+pub async fn deliver_timer_signals(process: &Executor) {
+    // 大多数进程没有计时器，不需要加锁
+    if process.posix_timer_count.load(Ordering::Acquire) == 0 {
+        return;
+    }
+    let timers: Vec<_> = process.posix_timers.lock().values().cloned().collect();
+    for timer in timers {
+        if timer.clock.is_cputime() {
+            let now_ns = timer.clock.now_ns(process.pid()).await;
+            timer.state.lock().expire(now_ns);
+        }
+        let Some((notify, info)) = timer.take_signal() else {
+            continue;
+        };
+        let result = match notify {
+            TimerNotify::ThreadSignal { tid, signum, .. } => {
+                send_siginfo_to_thread(tid as isize, signum as isize, Some(info)).await
+            }
+            _ => {
+                send_signal_to_process(process.pid() as isize, info.si_signo as isize, Some(info))
+                    .await
+            }
+        };
+        if let Err(err) = result {
+            warn!("Failed to send signal of timer {}: {:?}", timer.id, err);
+        }
+    }
+}
@@ -0,0 +1,603 @@
+//! 进程跟踪（ptrace），供调试器与 strace 等工具使用。
+//!
+//! 跟踪者以进程为单位记录，停止状态以线程为单位记录。被跟踪的线程在以下时刻停止，
+//! 等待跟踪者通过 wait 观察并使其继续运行：
+//! 1. 信号递送之前（signal-delivery-stop），跟踪者可以替换或丢弃这个信号；
+//! 2. 以 PTRACE_SYSCALL 继续运行后，进入与离开系统调用时（syscall-stop）；
+//! 3. exec 成功后，被跟踪的进程会收到 SIGTRAP，从而产生一次信号递送前的停止；
+//!    设置了 PTRACE_O_TRACEEXEC 时改为以 PTRACE_EVENT_EXEC 停止。
+extern crate alloc;
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use axerrno::{AxError, AxResult};
+use axsignal::signal_no::SignalNo;
+use taskctx::TrapFrame;
+
+use crate::{
+    current_executor, current_task, send_signal_to_process, send_signal_to_thread, Executor,
+    PID2PC, TID2TASK,
+};
+
+pub const PTRACE_TRACEME: usize = 0;
+pub const PTRACE_PEEKTEXT: usize = 1;
+pub const PTRACE_PEEKDATA: usize = 2;
+pub const PTRACE_POKETEXT: usize = 4;
+pub const PTRACE_POKEDATA: usize = 5;
+pub const PTRACE_CONT: usize = 7;
+pub const PTRACE_KILL: usize = 8;
+pub const PTRACE_SINGLESTEP: usize = 9;
+pub const PTRACE_GETREGS: usize = 12;
+pub const PTRACE_SETREGS: usize = 13;
+pub const PTRACE_ATTACH: usize = 16;
+pub const PTRACE_DETACH: usize = 17;
+pub const PTRACE_SYSCALL: usize = 24;
+pub const PTRACE_SETOPTIONS: usize = 0x4200;
+pub const PTRACE_GETREGSET: usize = 0x4204;
+pub const PTRACE_SETREGSET: usize = 0x4205;
+
+/// syscall-stop 时报告的信号为 SIGTRAP | 0x80，便于跟踪者区分
+pub const PTRACE_O_TRACESYSGOOD: usize = 1;
+pub const PTRACE_O_TRACEFORK: usize = 1 << 1;
+pub const PTRACE_O_TRACEVFORK: usize = 1 << 2;
+pub const PTRACE_O_TRACECLONE: usize = 1 << 3;
+/// exec 成功后以 PTRACE_EVENT_EXEC 停止，代替发送 SIGTRAP
+pub const PTRACE_O_TRACEEXEC: usize = 1 << 4;
+pub const PTRACE_O_TRACEVFORKDONE: usize = 1 << 5;
+pub const PTRACE_O_TRACEEXIT: usize = 1 << 6;
+pub const PTRACE_O_TRACESECCOMP: usize = 1 << 7;
+/// 跟踪者退出时向被跟踪的进程发送 SIGKILL
+pub const PTRACE_O_EXITKILL: usize = 1 << 20;
+
+/// 目前支持的选项，自动跟踪子进程等其余选项会被拒绝
+const PTRACE_O_SUPPORTED: usize = PTRACE_O_TRACESYSGOOD | PTRACE_O_TRACEEXEC | PTRACE_O_EXITKILL;
+
+pub const PTRACE_EVENT_EXEC: usize = 4;
+
+/// 被跟踪的线程停止的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PtraceStop {
+    /// 信号递送之前
+    Signal(SignalNo),
+    /// 进入系统调用时
+    SyscallEnter,
+    /// 离开系统调用时
+    SyscallExit,
+    /// PTRACE_EVENT_* 事件
+    Event(usize),
+}
+
+/// 跟踪者使被跟踪的线程继续运行的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResumeMode {
+    /// 运行到下一次信号递送
+    #[default]
+    Cont,
+    /// 同时在进入与离开系统调用时停止
+    Syscall,
+}
+
+/// 被跟踪进程中一个线程的跟踪状态
+#[derive(Debug, Default)]
+pub struct PtraceThread {
+    /// 当前的停止原因
+    pub stop: Option<PtraceStop>,
+    /// 当前的停止是否已经被跟踪者 wait 到
+    pub reported: bool,
+    /// 继续运行的方式
+    pub resume_mode: ResumeMode,
+    /// 继续运行时注入的信号，为 0 表示不注入
+    pub resume_signal: usize,
+}
+
+/// 进程的跟踪状态，跟踪者以进程为单位，停止状态以线程为单位
+#[derive(Debug, Default)]
+pub struct PtraceState {
+    /// 跟踪者的进程 id
+    pub tracer: Option<u64>,
+    /// PTRACE_SETOPTIONS 设置的选项
+    pub options: usize,
+    /// 各个线程的跟踪状态，以线程 id 为键
+    pub threads: BTreeMap<u64, PtraceThread>,
+}
+
+impl PtraceState {
+    /// 按照 wait 的 status 格式编码停止状态
+    fn wait_status(&self, stop: PtraceStop) -> i32 {
+        let signal = match stop {
+            PtraceStop::Signal(signal) => signal as i32,
+            PtraceStop::SyscallEnter | PtraceStop::SyscallExit => {
+                let mut signal = SignalNo::SIGTRAP as i32;
+                if self.options & PTRACE_O_TRACESYSGOOD != 0 {
+                    signal |= 0x80;
+                }
+                signal
+            }
+            PtraceStop::Event(event) => SignalNo::SIGTRAP as i32 | ((event as i32) << 8),
+        };
+        (signal << 8) | 0x7f
+    }
+
+    /// 由 `tracer` 开始跟踪，已经被跟踪时返回错误
+    fn attach(&mut self, tracer: u64) -> AxResult<()> {
+        if self.tracer.is_some() {
+            return Err(AxError::PermissionDenied);
+        }
+        self.tracer = Some(tracer);
+        self.options = 0;
+        Ok(())
+    }
+
+    /// 解除跟踪，处于停止状态的线程注入信号 `signal` 后继续运行
+    fn detach(&mut self, signal: usize) {
+        self.tracer = None;
+        self.options = 0;
+        for thread in self.threads.values_mut() {
+            let stopped = thread.stop.take().is_some();
+            *thread = PtraceThread {
+                resume_signal: if stopped { signal } else { 0 },
+                ..Default::default()
+            };
+        }
+    }
+
+    /// 若被 `tracer` 跟踪，返回处于停止状态的线程 id。
+    ///
+    /// `tid` 为 None 时返回任意一个停止的线程
+    fn stopped_thread(&self, tracer: u64, tid: Option<u64>) -> Option<u64> {
+        if self.tracer != Some(tracer) {
+            return None;
+        }
+        match tid {
+            Some(tid) => self.threads.get(&tid)?.stop.map(|_| tid),
+            None => self
+                .threads
+                .iter()
+                .find(|(_, thread)| thread.stop.is_some())
+                .map(|(tid, _)| *tid),
+        }
+    }
+
+    /// 设置 PTRACE_SETOPTIONS 的选项，含有不支持的选项时返回错误
+    fn set_options(&mut self, options: usize) -> AxResult<()> {
+        if options & !PTRACE_O_SUPPORTED != 0 {
+            return Err(AxError::InvalidInput);
+        }
+        self.options = options;
+        Ok(())
+    }
+
+    /// 使处于停止状态的线程 `tid` 继续运行，并注入信号 `signal`
+    fn resume(&mut self, tid: u64, mode: ResumeMode, signal: usize) -> AxResult<()> {
+        let thread = self.threads.get_mut(&tid).ok_or(AxError::NotFound)?;
+        if thread.stop.take().is_none() {
+            return Err(AxError::NotFound);
+        }
+        thread.resume_mode = mode;
+        thread.resume_signal = signal;
+        Ok(())
+    }
+
+    /// 使所有处于停止状态的线程继续运行，返回是否有线程被恢复
+    fn resume_all(&mut self) -> bool {
+        let mut resumed = false;
+        for thread in self.threads.values_mut() {
+            if thread.stop.take().is_some() {
+                thread.resume_signal = 0;
+                resumed = true;
+            }
+        }
+        resumed
+    }
+
+    /// 取出一个尚未被 `tracer` wait 到的停止状态
+    fn take_event(&mut self, tracer: u64) -> Option<i32> {
+        if self.tracer != Some(tracer) {
+            return None;
+        }
+        let stop = self
+            .threads
+            .values_mut()
+            .find(|thread| thread.stop.is_some() && !thread.reported)
+            .map(|thread| {
+                thread.reported = true;
+                thread.stop.unwrap()
+            })?;
+        Some(self.wait_status(stop))
+    }
+
+    /// 线程 `tid` 因 `stop` 停止，返回跟踪者的进程 id，没有被跟踪时不停止
+    fn stop(&mut self, tid: u64, stop: PtraceStop) -> Option<u64> {
+        let tracer = self.tracer?;
+        let thread = self.threads.entry(tid).or_default();
+        thread.stop = Some(stop);
+        thread.reported = false;
+        thread.resume_signal = 0;
+        Some(tracer)
+    }
+
+    /// 线程 `tid` 是否已经不处于停止状态
+    fn resumed(&self, tid: u64) -> bool {
+        self.threads
+            .get(&tid)
+            .map_or(true, |thread| thread.stop.is_none())
+    }
+
+    /// 线程 `tid` 继续运行之后取出跟踪者注入的信号，已经解除跟踪时清除其跟踪状态
+    fn finish_stop(&mut self, tid: u64) -> usize {
+        let signal = self
+            .threads
+            .get(&tid)
+            .map_or(0, |thread| thread.resume_signal);
+        if self.tracer.is_none() {
+            self.threads.remove(&tid);
+        }
+        signal
+    }
+
+    /// 线程 `tid` 是否以 PTRACE_SYSCALL 的方式继续运行
+    fn traces_syscall(&self, tid: u64) -> bool {
+        self.tracer.is_some()
+            && self
+                .threads
+                .get(&tid)
+                .is_some_and(|thread| thread.resume_mode == ResumeMode::Syscall)
+    }
+}
+
+impl Executor {
+    /// 跟踪者的进程 id
+    pub fn tracer(&self) -> Option<u64> {
+        self.ptrace.lock().tracer
+    }
+
+    /// 是否被跟踪
+    pub fn is_traced(&self) -> bool {
+        self.tracer().is_some()
+    }
+
+    /// 由 `tracer` 开始跟踪当前进程，已经被跟踪时返回错误
+    pub fn attach_tracer(&self, tracer: u64) -> AxResult<()> {
+        self.ptrace.lock().attach(tracer)
+    }
+
+    /// 解除跟踪，处于停止状态的线程注入信号 `signal` 后继续运行
+    pub fn detach_tracer(&self, signal: usize) {
+        self.ptrace.lock().detach(signal);
+        self.ptrace_wq.notify_all();
+    }
+
+    /// 若进程被 `tracer` 跟踪，返回处于停止状态的线程 id，`tid` 为 None 时返回任意一个停止的线程
+    pub fn ptrace_stopped_task(&self, tracer: u64, tid: Option<u64>) -> Option<u64> {
+        self.ptrace.lock().stopped_thread(tracer, tid)
+    }
+
+    /// 设置 PTRACE_SETOPTIONS 的选项，含有不支持的选项时返回错误
+    pub fn set_ptrace_options(&self, options: usize) -> AxResult<()> {
+        self.ptrace.lock().set_options(options)
+    }
+
+    /// 跟踪者使处于停止状态的线程 `tid` 继续运行，并注入信号 `signal`
+    pub fn ptrace_resume(&self, tid: u64, mode: ResumeMode, signal: usize) -> AxResult<()> {
+        self.ptrace.lock().resume(tid, mode, signal)?;
+        self.ptrace_wq.notify_all();
+        Ok(())
+    }
+
+    /// 收到 SIGKILL 时，处于跟踪停止状态的线程需要立即继续运行以便退出
+    pub(crate) fn ptrace_kill_resume(&self) {
+        if self.ptrace.lock().resume_all() {
+            self.ptrace_wq.notify_all();
+        }
+    }
+
+    /// 取出一个尚未被 `tracer` wait 到的跟踪停止状态
+    pub fn take_ptrace_event(&self, tracer: u64) -> Option<i32> {
+        self.ptrace.lock().take_event(tracer)
+    }
+
+    /// 当前线程因 `stop` 停止，直到跟踪者使其继续运行，返回跟踪者注入的信号
+    ///
+    /// 进程没有被跟踪时不会停止，返回 None
+    async fn ptrace_stop(&self, stop: PtraceStop) -> Option<usize> {
+        let tid = current_task().id().as_u64();
+        let tracer = self.ptrace.lock().stop(tid, stop)?;
+        let _ = send_signal_to_process(tracer as isize, SignalNo::SIGCHLD as isize, None).await;
+        self.ptrace_wq
+            .wait_until(|| self.ptrace.lock().resumed(tid))
+            .await;
+        Some(self.ptrace.lock().finish_stop(tid))
+    }
+}
+
+/// 信号递送之前的停止，返回跟踪者决定递送的信号，为 None 时丢弃这个信号
+///
+/// 进程没有被跟踪或者信号为 SIGKILL 时直接递送原信号
+pub async fn signal_delivery_stop(process: &Executor, signal: SignalNo) -> Option<SignalNo> {
+    if signal == SignalNo::SIGKILL {
+        return Some(signal);
+    }
+    match process.ptrace_stop(PtraceStop::Signal(signal)).await {
+        None => Some(signal),
+        Some(0) => None,
+        Some(injected) => Some(SignalNo::from(injected)),
+    }
+}
+
+/// 若当前线程以 PTRACE_SYSCALL 的方式被跟踪，则返回当前进程
+async fn syscall_tracee() -> Option<Arc<Executor>> {
+    // 异步系统调用在内核协程中执行，没有对应的用户态上下文，不产生停止
+    current_task().utrap_frame()?;
+    let process = current_executor().await;
+    let tid = current_task().id().as_u64();
+    let traced = process.ptrace.lock().traces_syscall(tid);
+    traced.then_some(process)
+}
+
+/// exec 成功后的停止：设置了 PTRACE_O_TRACEEXEC 时以 PTRACE_EVENT_EXEC 停止，否则向当前线程发送 SIGTRAP
+pub async fn exec_stop(process: &Executor) {
+    let options = process.ptrace.lock().options;
+    if options & PTRACE_O_TRACEEXEC != 0 {
+        process
+            .ptrace_stop(PtraceStop::Event(PTRACE_EVENT_EXEC))
+            .await;
+    } else if process.is_traced() {
+        let _ = send_signal_to_thread(
+            current_task().id().as_u64() as isize,
+            SignalNo::SIGTRAP as isize,
+        )
+        .await;
+    }
+}
+
+/// 进入系统调用时的停止，返回 true 时跟踪者可能修改了 trap 上下文中的系统调用号与参数
+///
+/// 与 Linux 相同，syscall-stop 之后注入的信号被忽略
+pub async fn syscall_enter_stop() -> bool {
+    match syscall_tracee().await {
+        Some(process) => process
+            .ptrace_stop(PtraceStop::SyscallEnter)
+            .await
+            .is_some(),
+        None => false,
+    }
+}
+
+/// 离开系统调用时的停止，跟踪者可以观察并修改返回值，返回最终的返回值
+pub async fn syscall_exit_stop(ret: isize) -> isize {
+    let Some(process) = syscall_tracee().await else {
+        return ret;
+    };
+    current_task()
+        .utrap_frame()
+        .unwrap()
+        .set_ret_code(ret as usize);
+    if process.ptrace_stop(PtraceStop::SyscallExit).await.is_none() {
+        return ret;
+    }
+    current_task().utrap_frame().unwrap().get_ret_code() as isize
+}
+
+/// 跟踪者 `tracer` 退出时，解除其对所有进程的跟踪，设置了 PTRACE_O_EXITKILL 的进程被杀死
+pub async fn detach_all_tracees(tracer: u64) {
+    let tracees: Vec<_> = PID2PC
+        .read()
+        .values()
+        .filter(|process| process.tracer() == Some(tracer))
+        .cloned()
+        .collect();
+    for tracee in tracees {
+        let exit_kill = tracee.ptrace.lock().options & PTRACE_O_EXITKILL != 0;
+        tracee.detach_tracer(0);
+        if exit_kill {
+            let _ = send_signal_to_process(tracee.pid() as isize, SignalNo::SIGKILL as isize, None)
+                .await;
+        }
+    }
+}
+
+/// PTRACE_GETREGS 等使用的寄存器，与 Linux 的 user_regs_struct 布局相同：pc 之后依次为 x1 到 x31
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UserRegs {
+    pub pc: usize,
+    pub regs: [usize; 31],
+}
+
+impl UserRegs {
+    /// 从 trap 上下文中读取寄存器
+    pub fn from_trap_frame(tf: &TrapFrame) -> Self {
+        // GeneralRegisters 按照 x1 到 x31 的顺序排列
+        let regs = unsafe { *(&tf.regs as *const _ as *const [usize; 31]) };
+        Self {
+            pc: tf.get_pc(),
+            regs,
+        }
+    }
+
+    /// 将寄存器写回 trap 上下文
+    pub fn write_to_trap_frame(&self, tf: &mut TrapFrame) {
+        unsafe {
+            *(&mut tf.regs as *mut _ as *mut [usize; 31]) = self.regs;
+        }
+        tf.set_pc(self.pc);
+    }
+}
+
+impl Executor {
+    /// 对被 `tracer` 跟踪且处于停止状态的线程 `tid` 的 trap 上下文执行 `f`
+    pub fn with_stopped_trap_frame<R>(
+        &self,
+        tracer: u64,
+        tid: u64,
+        f: impl FnOnce(&mut TrapFrame) -> R,
+    ) -> AxResult<R> {
+        let tid = self
+            .ptrace_stopped_task(tracer, Some(tid))
+            .ok_or(AxError::NotFound)?;
+        let task = TID2TASK.get(&tid).ok_or(AxError::NotFound)?;
+        let tf = task.utrap_frame().ok_or(AxError::NotFound)?;
+        Ok(f(tf))
+    }
+
+    /// 读写被 `tracer` 跟踪且处于停止状态的进程的地址空间
+    pub async fn ptrace_access_memory(
+        &self,
+        tracer: u64,
+        addr: usize,
+        buf: &mut [u8],
+        write: bool,
+    ) -> AxResult<()> {
+        if self.ptrace_stopped_task(tracer, None).is_none() {
+            return Err(AxError::NotFound);
+        }
+        self.memory_set
+            .lock()
+            .await
+            .access_foreign(addr.into(), buf, write)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        PtraceState, PtraceStop, ResumeMode, PTRACE_EVENT_EXEC, PTRACE_O_EXITKILL,
+        PTRACE_O_TRACECLONE, PTRACE_O_TRACEEXEC, PTRACE_O_TRACEFORK, PTRACE_O_TRACESYSGOOD,
+    };
+    use axerrno::AxError;
+    use axsignal::signal_no::SignalNo;
+
+    const TRACER: u64 = 1;
+
+    fn attached() -> PtraceState {
+        let mut state = PtraceState::default();
+        state.attach(TRACER).unwrap();
+        state
+    }
+
+    #[test]
+    fn test_attach_stop_cont() {
+        let mut state = attached();
+        assert_eq!(Err(AxError::PermissionDenied), state.attach(2));
+        assert_eq!(None, state.stopped_thread(TRACER, None));
+        assert_eq!(
+            Some(TRACER),
+            state.stop(10, PtraceStop::Signal(SignalNo::SIGSTOP))
+        );
+        assert!(!state.resumed(10));
+        // 停止状态只被 wait 到一次，其他进程不能观察
+        assert_eq!(None, state.take_event(2));
+        assert_eq!(
+            Some(((SignalNo::SIGSTOP as i32) << 8) | 0x7f),
+            state.take_event(TRACER)
+        );
+        assert_eq!(None, state.take_event(TRACER));
+        assert_eq!(Some(10), state.stopped_thread(TRACER, None));
+        assert_eq!(None, state.stopped_thread(2, None));
+        assert_eq!(
+            Ok(()),
+            state.resume(10, ResumeMode::Cont, SignalNo::SIGUSR1 as usize)
+        );
+        assert!(state.resumed(10));
+        assert_eq!(SignalNo::SIGUSR1 as usize, state.finish_stop(10));
+        assert_eq!(None, state.stopped_thread(TRACER, None));
+        // 没有停止的线程不能继续运行
+        assert_eq!(
+            Err(AxError::NotFound),
+            state.resume(10, ResumeMode::Cont, 0)
+        );
+    }
+
+    #[test]
+    fn test_stop_per_thread() {
+        let mut state = attached();
+        state.stop(10, PtraceStop::Signal(SignalNo::SIGSTOP));
+        state.stop(11, PtraceStop::Signal(SignalNo::SIGUSR2));
+        assert!(state.take_event(TRACER).is_some());
+        assert!(state.take_event(TRACER).is_some());
+        assert_eq!(None, state.take_event(TRACER));
+        state.resume(11, ResumeMode::Syscall, 0).unwrap();
+        // 只有线程 11 继续运行，并且在系统调用处停止
+        assert!(state.resumed(11));
+        assert!(!state.resumed(10));
+        assert!(state.traces_syscall(11));
+        assert!(!state.traces_syscall(10));
+        assert_eq!(Some(10), state.stopped_thread(TRACER, Some(10)));
+        assert_eq!(None, state.stopped_thread(TRACER, Some(11)));
+        assert_eq!(Some(10), state.stopped_thread(TRACER, None));
+    }
+
+    #[test]
+    fn test_syscall_and_event_status() {
+        let mut state = attached();
+        state.stop(10, PtraceStop::SyscallEnter);
+        assert_eq!(
+            Some(((SignalNo::SIGTRAP as i32) << 8) | 0x7f),
+            state.take_event(TRACER)
+        );
+        state.set_options(PTRACE_O_TRACESYSGOOD).unwrap();
+        state.stop(10, PtraceStop::SyscallExit);
+        assert_eq!(
+            Some(((SignalNo::SIGTRAP as i32 | 0x80) << 8) | 0x7f),
+            state.take_event(TRACER)
+        );
+        state.stop(10, PtraceStop::Event(PTRACE_EVENT_EXEC));
+        assert_eq!(
+            Some(((SignalNo::SIGTRAP as i32 | ((PTRACE_EVENT_EXEC as i32) << 8)) << 8) | 0x7f),
+            state.take_event(TRACER)
+        );
+    }
+
+    #[test]
+    fn test_set_options() {
+        let mut state = attached();
+        let options = PTRACE_O_TRACESYSGOOD | PTRACE_O_TRACEEXEC | PTRACE_O_EXITKILL;
+        assert_eq!(Ok(()), state.set_options(options));
+        assert_eq!(options, state.options);
+        // 不支持的选项被拒绝，原有的选项保持不变
+        for unsupported in [PTRACE_O_TRACEFORK, PTRACE_O_TRACECLONE] {
+            assert_eq!(
+                Err(AxError::InvalidInput),
+                state.set_options(PTRACE_O_TRACESYSGOOD | unsupported)
+            );
+        }
+        assert_eq!(options, state.options);
+    }
+
+    #[test]
+    fn test_detach_resumes_stopped_threads() {
+        let mut state = attached();
+        state.set_options(PTRACE_O_TRACESYSGOOD).unwrap();
+        state.stop(10, PtraceStop::Signal(SignalNo::SIGSTOP));
+        state.stop(11, PtraceStop::SyscallEnter);
+        state.resume(11, ResumeMode::Syscall, 0).unwrap();
+        state.detach(SignalNo::SIGCONT as usize);
+        assert_eq!(None, state.tracer);
+        assert_eq!(0, state.options);
+        assert!(state.resumed(10));
+        assert!(!state.traces_syscall(11));
+        assert_eq!(SignalNo::SIGCONT as usize, state.finish_stop(10));
+        assert_eq!(0, state.finish_stop(11));
+        // 解除跟踪之后线程的状态被清除，可以重新被跟踪
+        assert!(state.threads.is_empty());
+        assert_eq!(Ok(()), state.attach(2));
+    }
+
+    #[test]
+    fn test_untraced_does_not_stop() {
+        let mut state = PtraceState::default();
+        assert_eq!(None, state.stop(10, PtraceStop::Signal(SignalNo::SIGSTOP)));
+        assert!(state.resumed(10));
+        assert!(!state.resume_all());
+    }
+
+    #[test]
+    fn test_kill_resumes_all_threads() {
+        let mut state = attached();
+        state.stop(10, PtraceStop::Signal(SignalNo::SIGSTOP));
+        state.stop(11, PtraceStop::SyscallEnter);
+        assert!(state.resume_all());
+        assert!(state.resumed(10) && state.resumed(11));
+        assert_eq!(0, state.finish_stop(10));
+        assert!(!state.resume_all());
+    }
+}
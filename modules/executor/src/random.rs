@@ -0,0 +1,160 @@
+//! 内核使用的密码学安全伪随机数生成器（ChaCha20）。
+//!
+//! 第一次使用时以多次读取时钟计数得到的抖动作为种子，并混入硬件随机数生成器（x86_64 的 RDRAND、
+//! aarch64 的 RNDR）的输出与引导时设备树的内容（QEMU 与 U-Boot 在 `/chosen` 中放入 `rng-seed`）。
+//! 之后每次取出随机数时都会混入当前的时钟计数。
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+use sync::SpinNoIrq;
+
+static KERNEL_RNG: SpinNoIrq<Option<ChaCha20Rng>> = SpinNoIrq::new(None);
+
+/// 从时钟计数中收集种子
+fn collect_seed() -> [u8; 32] {
+    let mut seed = [0u8; 32];
+    for (i, chunk) in seed.chunks_mut(8).enumerate() {
+        let mut value = axhal::time::current_ticks();
+        // 相邻两次读取之间的间隔受缓存与中断影响，取其低位作为抖动
+        for round in 0..64 {
+            let ticks = axhal::time::current_ticks();
+            value =
+                value.rotate_left(7) ^ ticks.wrapping_mul(0x9e37_79b9_7f4a_7c15 + round + i as u64);
+        }
+        chunk.copy_from_slice(&value.to_ne_bytes());
+    }
+    seed
+}
+
+/// 硬件随机数生成器给出的种子，没有硬件随机数生成器时返回 None
+#[cfg(target_arch = "x86_64")]
+fn hardware_seed() -> Option<[u8; 32]> {
+    use core::arch::x86_64::{__cpuid, _rdrand64_step};
+
+    #[target_feature(enable = "rdrand")]
+    unsafe fn rdrand() -> Option<u64> {
+        let mut value = 0;
+        // 硬件暂时没有可用的随机数时重试
+        for _ in 0..10 {
+            if _rdrand64_step(&mut value) == 1 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    // CPUID.01H:ECX[30] 表示支持 RDRAND
+    if unsafe { __cpuid(1) }.ecx & (1 << 30) == 0 {
+        return None;
+    }
+    let mut seed = [0u8; 32];
+    for chunk in seed.chunks_mut(8) {
+        chunk.copy_from_slice(&unsafe { rdrand() }?.to_ne_bytes());
+    }
+    Some(seed)
+}
+
+/// 硬件随机数生成器给出的种子，没有硬件随机数生成器时返回 None
+#[cfg(target_arch = "aarch64")]
+fn hardware_seed() -> Option<[u8; 32]> {
+    let isar0: u64;
+    unsafe { core::arch::asm!("mrs {}, ID_AA64ISAR0_EL1", out(reg) isar0) };
+    // ID_AA64ISAR0_EL1.RNDR 不为 0 时支持 RNDR
+    if (isar0 >> 60) & 0xf == 0 {
+        return None;
+    }
+    let mut seed = [0u8; 32];
+    for chunk in seed.chunks_mut(8) {
+        let mut value = None;
+        for _ in 0..10 {
+            let (bits, ok): (u64, u64);
+            // RNDR 成功时清除 NZCV，失败时设置 Z
+            unsafe {
+                core::arch::asm!(
+                    "mrs {bits}, s3_3_c2_c4_0",
+                    "cset {ok}, ne",
+                    bits = out(reg) bits,
+                    ok = out(reg) ok,
+                    options(nomem, nostack),
+                )
+            };
+            if ok == 1 {
+                value = Some(bits);
+                break;
+            }
+        }
+        chunk.copy_from_slice(&value?.to_ne_bytes());
+    }
+    Some(seed)
+}
+
+/// 硬件随机数生成器给出的种子，没有硬件随机数生成器时返回 None
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn hardware_seed() -> Option<[u8; 32]> {
+    None
+}
+
+/// 引导时由固件传入的设备树，其中可能带有引导程序生成的随机种子
+#[cfg(not(target_arch = "x86_64"))]
+fn boot_entropy() -> &'static [u8] {
+    let Some(fdt) = of::get_fdt_ptr() else {
+        return &[];
+    };
+    let fdt = fdt as usize as *const u8;
+    // 设备树头部的第二个字段是以大端序保存的总长度
+    let size = u32::from_be(unsafe { (fdt as *const u32).add(1).read_unaligned() });
+    unsafe { core::slice::from_raw_parts(fdt, size as usize) }
+}
+
+/// x86_64 的平台不使用设备树
+#[cfg(target_arch = "x86_64")]
+fn boot_entropy() -> &'static [u8] {
+    &[]
+}
+
+/// 将 `data` 混入种子：依次与每 32 字节异或后，用 ChaCha20 的输出替换种子
+fn mix_seed(seed: &mut [u8; 32], data: &[u8]) {
+    for chunk in data.chunks(32) {
+        for (s, d) in seed.iter_mut().zip(chunk) {
+            *s ^= d;
+        }
+        ChaCha20Rng::from_seed(*seed).fill_bytes(seed);
+    }
+}
+
+/// 第一次使用时的种子
+fn initial_seed() -> [u8; 32] {
+    let mut seed = collect_seed();
+    if let Some(hardware) = hardware_seed() {
+        mix_seed(&mut seed, &hardware);
+    }
+    mix_seed(&mut seed, boot_entropy());
+    seed
+}
+
+/// 用随机字节填满 `buf`
+pub fn fill_random(buf: &mut [u8]) {
+    let mut rng = KERNEL_RNG.lock();
+    let rng = rng.get_or_insert_with(|| ChaCha20Rng::from_seed(initial_seed()));
+    // 混入当前时钟计数，使得种子泄露之后的输出仍然难以预测
+    let stream = rng.get_stream();
+    rng.set_stream(stream ^ axhal::time::current_ticks());
+    rng.fill_bytes(buf);
+}
+
+/// 返回一个随机的 u64
+pub fn random_u64() -> u64 {
+    let mut buf = [0u8; 8];
+    fill_random(&mut buf);
+    u64::from_ne_bytes(buf)
+}
+
+/// 返回 `[0, bound)` 内的随机数，`bound` 为 0 时返回 0
+pub fn random_below(bound: usize) -> usize {
+    if bound == 0 {
+        0
+    } else {
+        (random_u64() % bound as u64) as usize
+    }
+}
@@ -0,0 +1,63 @@
+//! 进程的资源限制（rlimit），通过 prlimit64 查询与设置，fork 与 exec 时保持不变。
+
+/// 表示没有限制
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+/// CPU 时间，单位为秒，超过软上限时发送 SIGXCPU，超过硬上限时发送 SIGKILL
+pub const RLIMIT_CPU: usize = 0;
+/// 可以写入的文件的最大大小，超出时发送 SIGXFSZ
+pub const RLIMIT_FSIZE: usize = 1;
+/// 数据段（私有可写的映射）的最大大小
+pub const RLIMIT_DATA: usize = 2;
+/// 用户栈的最大大小，栈区域向下扩展时检查
+pub const RLIMIT_STACK: usize = 3;
+/// core 文件的最大大小
+pub const RLIMIT_CORE: usize = 4;
+/// 常驻内存的最大大小
+pub const RLIMIT_RSS: usize = 5;
+/// 可以创建的任务数
+pub const RLIMIT_NPROC: usize = 6;
+/// 可以打开的 fd 数
+pub const RLIMIT_NOFILE: usize = 7;
+/// 可以锁定的内存大小
+pub const RLIMIT_MEMLOCK: usize = 8;
+/// 用户地址空间的最大大小
+pub const RLIMIT_AS: usize = 9;
+/// 可以排队的实时信号数
+pub const RLIMIT_SIGPENDING: usize = 11;
+/// 资源的种类数
+pub const RLIM_NLIMITS: usize = 16;
+
+/// sys_prlimit64 使用的数组
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RLimit {
+    /// 软上限
+    pub rlim_cur: u64,
+    /// 硬上限
+    pub rlim_max: u64,
+}
+
+impl RLimit {
+    /// 软上限与硬上限相同的限制
+    pub const fn new(limit: u64) -> Self {
+        Self {
+            rlim_cur: limit,
+            rlim_max: limit,
+        }
+    }
+}
+
+/// 用户栈默认的最大大小，与 Linux 相同
+pub const DEFAULT_STACK_LIMIT: u64 = 8 * 1024 * 1024;
+
+/// 新建的进程使用的资源限制
+pub fn default_rlimits(fd_limit: u64) -> [RLimit; RLIM_NLIMITS] {
+    let mut rlimits = [RLimit::new(RLIM_INFINITY); RLIM_NLIMITS];
+    rlimits[RLIMIT_STACK].rlim_cur = DEFAULT_STACK_LIMIT;
+    rlimits[RLIMIT_CORE].rlim_cur = 0;
+    rlimits[RLIMIT_NOFILE] = RLimit::new(fd_limit);
+    rlimits[RLIMIT_MEMLOCK] = RLimit::new(64 * 1024);
+    rlimits[RLIMIT_SIGPENDING] = RLimit::new(4096);
+    rlimits
+}
@@ -1,7 +1,11 @@
 //! 负责处理进程中与信号相关的内容
 extern crate alloc;
-use crate::KERNEL_EXECUTOR_ID;
-use alloc::sync::Arc;
+use crate::{
+    rlimit::RLIMIT_SIGPENDING,
+    sigqueue::{is_rt_signal, SigQueue, SI_TKILL, SI_USER},
+    KERNEL_EXECUTOR_ID,
+};
+use alloc::{collections::btree_map::BTreeMap, sync::Arc};
 use axerrno::{AxError, AxResult};
 use axhal::cpu::this_cpu_id;
 use axlog::{info, warn};
@@ -12,6 +16,7 @@ use axsignal::{
     ucontext::{SignalStack, SignalUserContext},
     SignalHandler, SignalSet,
 };
+use core::sync::atomic::Ordering;
 use sync::Mutex;
 use taskctx::TrapFrame;
 
@@ -29,6 +34,8 @@ pub struct SignalModule {
     exit_signal: Option<SignalNo>,
     /// Alternative signal stack
     pub alternate_stack: SignalStack,
+    /// 发送给线程的实时信号队列
+    pub rt_queue: SigQueue,
 }
 
 impl SignalModule {
@@ -46,6 +53,7 @@ impl SignalModule {
             signal_set,
             exit_signal: None,
             alternate_stack: SignalStack::default(),
+            rt_queue: SigQueue::default(),
         }
     }
 
@@ -80,7 +88,9 @@ impl SignalModule {
 
 const USER_SIGNAL_PROTECT: usize = 512;
 
-use crate::{current_executor, current_task, exit, PID2PC, TID2TASK};
+use crate::{
+    current_executor, current_task, exit, process_group_members, ptrace, Executor, PID2PC, TID2TASK,
+};
 
 /// 将保存的trap上下文填入内核栈中
 ///
@@ -165,15 +175,45 @@ pub async fn handle_signals() {
         // 内核进程不处理信号
         return;
     }
+    if let Some(signal) = process.check_cpu_limit().await {
+        send_signal_to_process(process.pid() as isize, signal as isize, None)
+            .await
+            .unwrap_or_else(|err| {
+                warn!("send signal failed: {:?}", err);
+            });
+    }
+    crate::posix_timer::deliver_timer_signals(&process).await;
+    // 进程被停止时，其中的线程在返回用户态之前等待其恢复运行
+    process.wait_while_stopped().await;
     let mut signal_modules = process.signal_modules.lock().await;
 
     let signal_module = signal_modules.get_mut(&current_task.id().as_u64()).unwrap();
-    let signal_set = &mut signal_module.signal_set;
-    let sig_num = if let Some(sig_num) = signal_set.get_one_signal() {
+    let sig_num = if let Some(sig_num) = signal_module.signal_set.get_one_signal() {
         sig_num
     } else {
         return;
     };
+    let mut sig_info = take_sig_info(&process, signal_module, sig_num, current_task.is_leader());
+    drop(signal_modules);
+    // 被跟踪的进程在信号递送之前停止，由跟踪者决定递送哪个信号
+    let sig_num = if process.is_traced() {
+        match ptrace::signal_delivery_stop(&process, SignalNo::from(sig_num)).await {
+            Some(signal) if signal as usize == sig_num => sig_num,
+            Some(signal) => {
+                // 跟踪者替换了信号，原信号的 SigInfo 不再适用
+                sig_info = None;
+                signal as usize
+            }
+            None => return,
+        }
+    } else {
+        sig_num
+    };
+    let mut signal_modules = process.signal_modules.lock().await;
+    let Some(signal_module) = signal_modules.get_mut(&current_task.id().as_u64()) else {
+        return;
+    };
+    let signal_set = &mut signal_module.signal_set;
     info!(
         "cpu: {}, task: {}, handler signal: {}",
         this_cpu_id(),
@@ -219,12 +259,25 @@ pub async fn handle_signals() {
                 terminate_process(signal, None).await;
             }
             SignalDefault::Stop => {
-                unimplemented!();
+                // 停止整个进程，直到收到 SIGCONT 或 SIGKILL
+                load_trap_for_signal().await;
+                if process.stop(signal) {
+                    notify_parent_job_change(&process).await;
+                }
+                process.wait_while_stopped().await;
             }
             SignalDefault::Cont => {
-                unimplemented!();
+                // 进程在 SIGCONT 产生时已经恢复运行，此时相当于忽略
+                load_trap_for_signal().await;
             }
             SignalDefault::Core => {
+                if let Err(err) = crate::coredump::dump_core(&process, signal).await {
+                    warn!(
+                        "Failed to dump core of process {}: {:?}",
+                        process.pid(),
+                        err
+                    );
+                }
                 terminate_process(signal, None).await;
             }
         }
@@ -257,6 +310,29 @@ pub async fn handle_signals() {
     };
 
     info!("use stack: {:#x}", sp);
+    // 先分配信号栈帧所在的页面，栈溢出导致放不下栈帧时，以 SIGSEGV 的默认处理结束进程
+    let frame_size = core::mem::size_of::<SigInfo>()
+        + core::mem::size_of::<SignalUserContext>()
+        + core::mem::size_of::<usize>()
+        + 0x20;
+    if process
+        .manual_alloc_range_for_lazy((sp - frame_size).into(), sp.into())
+        .await
+        .is_err()
+    {
+        warn!("Failed to alloc memory for signal user stack at {:#x}", sp);
+        drop(signal_handler);
+        drop(signal_modules);
+        if let Err(err) = crate::coredump::dump_core(&process, SignalNo::SIGSEGV).await {
+            warn!(
+                "Failed to dump core of process {}: {:?}",
+                process.pid(),
+                err
+            );
+        }
+        terminate_process(SignalNo::SIGSEGV, None).await;
+        return;
+    }
     let restorer = if let Some(addr) = action.get_storer() {
         addr
     } else {
@@ -279,27 +355,12 @@ pub async fn handle_signals() {
     if action.sa_flags.contains(SigActionFlags::SA_SIGINFO) {
         // current_task.set_siginfo(true);
         signal_module.sig_info = true;
-        let sp_base = (((sp - core::mem::size_of::<SigInfo>()) & !0xf)
-            - core::mem::size_of::<SignalUserContext>())
-            & !0xf;
-
-        // TODO: 统一为访问用户空间的操作封装函数
-        process
-            .manual_alloc_range_for_lazy(sp_base.into(), sp.into())
-            .await
-            .expect("Failed to alloc memory for signal user stack");
-
         // 注意16字节对齐
         sp = (sp - core::mem::size_of::<SigInfo>()) & !0xf;
-        let info = if let Some(info) = signal_set.info.get(&(sig_num - 1)) {
-            info!("test SigInfo: {:?}", info.0.si_val_int);
-            info.0
-        } else {
-            SigInfo {
-                si_signo: sig_num as i32,
-                ..Default::default()
-            }
-        };
+        let info = sig_info.unwrap_or(SigInfo {
+            si_signo: sig_num as i32,
+            ..Default::default()
+        });
         unsafe {
             *(sp as *mut SigInfo) = info;
         }
@@ -346,31 +407,135 @@ pub async fn signal_return() -> isize {
     }
 }
 
-/// 发送信号到指定的进程
+/// 进程因作业控制停止或恢复运行时，向父进程发送 SIGCHLD
+async fn notify_parent_job_change(process: &Executor) {
+    let parent = process.get_parent();
+    if parent == KERNEL_EXECUTOR_ID {
+        return;
+    }
+    if let Some(parent) = PID2PC.get(&parent) {
+        let _ = add_signal_to_process(&parent, SignalNo::SIGCHLD as isize, None).await;
+    }
+}
+
+/// SIGCONT 与 SIGKILL 在产生时就恢复被停止的进程，而不是等到被处理时
 ///
-/// 默认发送到该进程下的主线程
-pub async fn send_signal_to_process(
-    pid: isize,
+/// 只有 SIGCONT 引起的恢复需要报告给父进程
+async fn resume_for_signal(process: &Executor, signum: isize) {
+    if signum == SignalNo::SIGKILL as isize {
+        process.ptrace_kill_resume();
+    }
+    let report = signum == SignalNo::SIGCONT as isize;
+    if (report || signum == SignalNo::SIGKILL as isize) && process.resume(report) && report {
+        notify_parent_job_change(process).await;
+    }
+}
+
+/// 没有给出 SigInfo 时，按照由当前进程调用 kill 发送的方式生成
+fn user_sig_info(signum: usize) -> SigInfo {
+    let pid = current_task().get_process_id();
+    SigInfo {
+        si_signo: signum as i32,
+        si_code: SI_USER,
+        pid: pid as i32,
+        uid: PID2PC.get(&pid).map_or(0, |process| process.cred().uid),
+        ..Default::default()
+    }
+}
+
+/// 判断实时信号能否排队
+///
+/// 进程中排队的信号数达到 RLIMIT_SIGPENDING 时，由 kill 发送的信号只设置未决位，
+/// 其余方式发送的信号返回 WouldBlock
+fn may_queue(
+    process: &Executor,
+    signal_modules: &BTreeMap<u64, SignalModule>,
+    info: &SigInfo,
+) -> AxResult<bool> {
+    let queued = process.rt_queue.lock().len()
+        + signal_modules
+            .values()
+            .map(|module| module.rt_queue.len())
+            .sum::<usize>();
+    if (queued as u64) < process.get_rlimit(RLIMIT_SIGPENDING).rlim_cur {
+        Ok(true)
+    } else if info.si_code == SI_USER {
+        Ok(false)
+    } else {
+        Err(AxError::WouldBlock)
+    }
+}
+
+/// 取出将要递送的信号 `signum` 的 SigInfo
+///
+/// 实时信号先从线程的队列中取出，主线程还会从进程的队列中取出，队列中仍有实例时重新设置未决位
+fn take_sig_info(
+    process: &Executor,
+    signal_module: &mut SignalModule,
+    signum: usize,
+    is_leader: bool,
+) -> Option<SigInfo> {
+    if !is_rt_signal(signum) {
+        return signal_module
+            .signal_set
+            .info
+            .get(&(signum - 1))
+            .map(|info| info.0);
+    }
+    let mut process_queue = process.rt_queue.lock();
+    let mut info = signal_module.rt_queue.pop(signum);
+    if info.is_none() && is_leader {
+        info = process_queue.pop(signum);
+    }
+    if signal_module.rt_queue.contains(signum) || (is_leader && process_queue.contains(signum)) {
+        signal_module.signal_set.try_add_signal(signum, None);
+    }
+    info
+}
+
+/// 将信号加入进程主线程的未决信号集，实时信号同时在进程中排队
+async fn add_signal_to_process(
+    process: &Executor,
     signum: isize,
     info: Option<SigInfo>,
 ) -> AxResult<()> {
-    let mut pid2pc = PID2PC.lock().await;
-    if !pid2pc.contains_key(&(pid as u64)) {
-        return Err(axerrno::AxError::NotFound);
-    }
-    let process = pid2pc.get_mut(&(pid as u64)).unwrap();
     let main_task = process.get_main_task().await;
     if let Some(main_task) = main_task {
         let mut signal_modules = process.signal_modules.lock().await;
+        let signum = signum as usize;
+        let info = if is_rt_signal(signum) {
+            let info = info.unwrap_or_else(|| user_sig_info(signum));
+            if may_queue(process, &signal_modules, &info)? {
+                process.rt_queue.lock().push(signum, info);
+            }
+            None
+        } else {
+            info
+        };
         let signal_module = signal_modules.get_mut(&main_task.id().as_u64()).unwrap();
-        signal_module
-            .signal_set
-            .try_add_signal(signum as usize, info);
+        signal_module.signal_set.try_add_signal(signum, info);
+        process.signal_events.fetch_add(1, Ordering::Release);
         // 如果这个时候对应的线程是处于休眠状态的，则唤醒之，进入信号处理阶段
         if main_task.is_blocked() {
             taskctx::wakeup_task(Arc::as_ptr(&main_task));
         }
     }
+    Ok(())
+}
+
+/// 发送信号到指定的进程
+///
+/// 默认发送到该进程下的主线程
+pub async fn send_signal_to_process(
+    pid: isize,
+    signum: isize,
+    info: Option<SigInfo>,
+) -> AxResult<()> {
+    let Some(process) = PID2PC.get(&(pid as u64)) else {
+        return Err(axerrno::AxError::NotFound);
+    };
+    resume_for_signal(&process, signum).await;
+    add_signal_to_process(&process, signum, info).await?;
     // let mut now_id: Option<u64> = None;
     // for task in process.tasks.lock().iter_mut() {
     //     if task.is_leader() {
@@ -395,31 +560,64 @@ pub async fn send_signal_to_process(
     Ok(())
 }
 
+/// 发送信号到进程组中的所有进程
+pub async fn send_signal_to_process_group(
+    pgid: u64,
+    signum: isize,
+    info: Option<SigInfo>,
+) -> AxResult<()> {
+    let members = process_group_members(pgid);
+    if members.is_empty() {
+        return Err(AxError::NotFound);
+    }
+    for pid in members {
+        // 进程可能在此期间已经退出，不影响发送给其他进程
+        let _ = send_signal_to_process(pid as isize, signum, info).await;
+    }
+    Ok(())
+}
+
 /// 发送信号到指定的线程
 pub async fn send_signal_to_thread(tid: isize, signum: isize) -> AxResult<()> {
-    let tid2task = TID2TASK.lock().await;
-    let task = if let Some(task) = tid2task.get(&(tid as u64)) {
-        Arc::clone(task)
-    } else {
+    send_siginfo_to_thread(tid, signum, None).await
+}
+
+/// 发送带有 SigInfo 的信号到指定的线程，实时信号在线程中排队
+pub async fn send_siginfo_to_thread(
+    tid: isize,
+    signum: isize,
+    info: Option<SigInfo>,
+) -> AxResult<()> {
+    let Some(task) = TID2TASK.get(&(tid as u64)) else {
         return Err(AxError::NotFound);
     };
-    drop(tid2task);
     let pid = task.get_process_id();
-    let pid2pc = PID2PC.lock().await;
-    let process = if let Some(process) = pid2pc.get(&pid) {
-        Arc::clone(process)
-    } else {
+    let Some(process) = PID2PC.get(&pid) else {
         return Err(AxError::NotFound);
     };
-    drop(pid2pc);
+    resume_for_signal(&process, signum).await;
     let mut signal_modules = process.signal_modules.lock().await;
     if !signal_modules.contains_key(&(tid as u64)) {
         return Err(axerrno::AxError::NotFound);
     }
+    let signum = signum as usize;
+    let info = if is_rt_signal(signum) {
+        let info = info.unwrap_or_else(|| SigInfo {
+            si_code: SI_TKILL,
+            ..user_sig_info(signum)
+        });
+        let queue = may_queue(&process, &signal_modules, &info)?;
+        let signal_module = signal_modules.get_mut(&(tid as u64)).unwrap();
+        if queue {
+            signal_module.rt_queue.push(signum, info);
+        }
+        None
+    } else {
+        info
+    };
     let signal_module = signal_modules.get_mut(&(tid as u64)).unwrap();
-    signal_module
-        .signal_set
-        .try_add_signal(signum as usize, None);
+    signal_module.signal_set.try_add_signal(signum, info);
+    process.signal_events.fetch_add(1, Ordering::Release);
     // 如果这个时候对应的线程是处于休眠状态的，则唤醒之，进入信号处理阶段
     if task.is_blocked() {
         taskctx::wakeup_task(Arc::as_ptr(&task));
@@ -0,0 +1,77 @@
+//! 实时信号的排队。
+//!
+//! 未决信号集只能记录每个信号是否存在，标准信号的多个实例会合并为一个，
+//! 而编号不小于 [`SIGRTMIN`] 的实时信号的每个实例都需要连同其 [`SigInfo`] 一起排队递送。
+//! 发送给线程的实时信号在线程的信号模块中排队，发送给进程的实时信号在进程中排队，
+//! 对应的未决信号集中的位表示队列中至少存在一个实例。
+extern crate alloc;
+use alloc::collections::{btree_map::BTreeMap, VecDeque};
+use axsignal::info::SigInfo;
+
+/// 第一个实时信号
+pub const SIGRTMIN: usize = 32;
+/// 最后一个实时信号
+pub const SIGRTMAX: usize = 64;
+
+/// 由 kill 发送
+pub const SI_USER: i32 = 0;
+/// 由内核发送
+pub const SI_KERNEL: i32 = 0x80;
+/// 由 sigqueue 发送
+pub const SI_QUEUE: i32 = -1;
+/// 由 POSIX 计时器到期发送
+pub const SI_TIMER: i32 = -2;
+/// 由 tkill 或 tgkill 发送
+pub const SI_TKILL: i32 = -6;
+
+/// 是否为需要排队的实时信号
+pub fn is_rt_signal(signum: usize) -> bool {
+    (SIGRTMIN..=SIGRTMAX).contains(&signum)
+}
+
+/// 实时信号的队列，同一信号按照发送的顺序递送
+#[derive(Debug, Default)]
+pub struct SigQueue {
+    queues: BTreeMap<usize, VecDeque<SigInfo>>,
+    len: usize,
+}
+
+impl SigQueue {
+    /// 将信号 `signum` 的一个实例加入队列
+    pub fn push(&mut self, signum: usize, info: SigInfo) {
+        self.queues.entry(signum).or_default().push_back(info);
+        self.len += 1;
+    }
+
+    /// 取出信号 `signum` 最早加入的实例
+    pub fn pop(&mut self, signum: usize) -> Option<SigInfo> {
+        let queue = self.queues.get_mut(&signum)?;
+        let info = queue.pop_front()?;
+        if queue.is_empty() {
+            self.queues.remove(&signum);
+        }
+        self.len -= 1;
+        Some(info)
+    }
+
+    /// 队列中是否存在信号 `signum` 的实例
+    pub fn contains(&self, signum: usize) -> bool {
+        self.queues.contains_key(&signum)
+    }
+
+    /// 队列中所有信号的实例数
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// 队列是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 清空队列
+    pub fn clear(&mut self) {
+        self.queues.clear();
+        self.len = 0;
+    }
+}
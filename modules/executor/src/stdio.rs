@@ -10,11 +10,59 @@ use core::{
     cell::UnsafeCell,
     future::Future,
     pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
     task::{ready, Context, Poll},
 };
 use sync::Mutex;
 extern crate alloc;
 use alloc::string::String;
+
+use crate::Executor;
+
+/// 控制台作为控制终端的状态
+///
+/// 目前只有一个控制台，由内核直接创建的用户进程所在的会话持有
+pub struct ConsoleTty {
+    /// 以控制台为控制终端的会话 id，为 0 时表示没有
+    session: AtomicU64,
+    /// 前台进程组 id
+    foreground_pgid: AtomicU64,
+}
+
+/// 控制台终端
+pub static CONSOLE_TTY: ConsoleTty = ConsoleTty {
+    session: AtomicU64::new(0),
+    foreground_pgid: AtomicU64::new(0),
+};
+
+impl ConsoleTty {
+    /// 将控制台设置为会话 `sid` 的控制终端，并以 `pgid` 作为前台进程组
+    pub fn set_controlling(&self, sid: u64, pgid: u64) {
+        self.foreground_pgid.store(pgid, Ordering::Release);
+        self.session.store(sid, Ordering::Release);
+    }
+
+    /// 控制台是否为 `process` 的控制终端
+    pub fn is_controlling(&self, process: &Executor) -> bool {
+        let session = self.session.load(Ordering::Acquire);
+        session != 0 && session == process.sid()
+    }
+
+    /// 获取前台进程组 id
+    pub fn foreground_pgid(&self) -> u64 {
+        self.foreground_pgid.load(Ordering::Acquire)
+    }
+
+    /// 设置前台进程组 id，调用者需要保证该进程组属于持有控制台的会话
+    pub fn set_foreground_pgid(&self, pgid: u64) {
+        self.foreground_pgid.store(pgid, Ordering::Release);
+    }
+
+    /// `process` 是否属于控制台的后台进程组，后台进程组读取控制台时会收到 SIGTTIN
+    pub fn is_background(&self, process: &Executor) -> bool {
+        self.is_controlling(process) && process.pgid() != self.foreground_pgid()
+    }
+}
 /// stdin file for getting chars from console
 pub struct Stdin {
     pub flags: Mutex<OpenFlags>,
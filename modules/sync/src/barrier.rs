@@ -0,0 +1,210 @@
+//! 基于 [`WaitQueue`] 实现的屏障，允许多个任务同步地开始某项计算。
+
+use crate::{SpinNoIrq, WaitQueue};
+use core::fmt;
+use core::ops::Deref;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A barrier enables multiple tasks to synchronize the beginning
+/// of some computation, similar to
+/// [`std::sync::Barrier`](https://doc.rust-lang.org/std/sync/struct.Barrier.html).
+pub struct Barrier {
+    lock: SpinNoIrq<BarrierState>,
+    wq: WaitQueue,
+    num_tasks: usize,
+}
+
+struct BarrierState {
+    count: usize,
+    /// 每当所有任务都到达屏障后加一，用于区分不同轮次的等待
+    generation_id: usize,
+}
+
+/// A `BarrierWaitResult` is returned by [`Barrier::wait()`] when all tasks
+/// in the [`Barrier`] have rendezvoused.
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// Returns `true` if this task is the "leader task" for the call to
+    /// [`Barrier::wait()`].
+    ///
+    /// Only one task will have `true` returned from their result, all other
+    /// tasks will have `false` returned.
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl fmt::Debug for BarrierWaitResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BarrierWaitResult")
+            .field("is_leader", &self.is_leader())
+            .finish()
+    }
+}
+
+impl Barrier {
+    /// Creates a new barrier that can block a given number of tasks.
+    ///
+    /// A barrier will block `n`-1 tasks which call [`Barrier::wait()`] and
+    /// then wake up all tasks at once when the `n`th task calls `wait()`.
+    pub const fn new(n: usize) -> Self {
+        Self {
+            lock: SpinNoIrq::new(BarrierState {
+                count: 0,
+                generation_id: 0,
+            }),
+            wq: WaitQueue::new(),
+            num_tasks: n,
+        }
+    }
+
+    /// Blocks the current task until all tasks have rendezvoused here.
+    ///
+    /// 最后一个到达的任务会唤醒其他所有任务，并成为 leader。
+    /// 在使能 `thread` feature 时，该函数会阻塞当前线程，返回的 future 可以直接解引用得到结果；
+    /// 否则在返回的 future 第一次被 poll 时才算作到达屏障。
+    pub fn wait(&self) -> BarrierWaitFuture<'_> {
+        #[cfg(feature = "thread")]
+        {
+            let (local_gen, is_leader) = self.arrive();
+            if !is_leader {
+                self.wq
+                    .wait_until(|| self.lock.lock().generation_id != local_gen);
+            }
+            return BarrierWaitFuture {
+                barrier: self,
+                local_gen: Some(local_gen),
+                res: Some(BarrierWaitResult(is_leader)),
+            };
+        }
+        #[cfg(not(feature = "thread"))]
+        BarrierWaitFuture {
+            barrier: self,
+            local_gen: None,
+            res: None,
+        }
+    }
+
+    /// 记录一个任务到达屏障，返回到达时的轮次以及该任务是否是最后一个到达的
+    fn arrive(&self) -> (usize, bool) {
+        let mut state = self.lock.lock();
+        let local_gen = state.generation_id;
+        state.count += 1;
+        if state.count < self.num_tasks {
+            return (local_gen, false);
+        }
+        state.count = 0;
+        state.generation_id = state.generation_id.wrapping_add(1);
+        drop(state);
+        self.wq.notify_all();
+        (local_gen, true)
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Barrier")
+            .field("num_tasks", &self.num_tasks)
+            .finish()
+    }
+}
+
+/// [`Barrier::wait`] 返回的 future
+pub struct BarrierWaitFuture<'a> {
+    barrier: &'a Barrier,
+    /// 到达屏障时的轮次，尚未到达时为 None
+    local_gen: Option<usize>,
+    res: Option<BarrierWaitResult>,
+}
+
+impl<'a> Future for BarrierWaitFuture<'a> {
+    type Output = BarrierWaitResult;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Some(res) = this.res.take() {
+            return Poll::Ready(res);
+        }
+        let barrier = this.barrier;
+        let local_gen = match this.local_gen {
+            Some(local_gen) => local_gen,
+            None => {
+                // 创建之后从未被 poll 的 future 不会计入到达的任务数
+                let (local_gen, is_leader) = barrier.arrive();
+                if is_leader {
+                    return Poll::Ready(BarrierWaitResult(true));
+                }
+                this.local_gen = Some(local_gen);
+                local_gen
+            }
+        };
+        core::task::ready!(Pin::new(
+            &mut barrier
+                .wq
+                .wait_until(|| barrier.lock.lock().generation_id != local_gen)
+        )
+        .poll(_cx));
+        Poll::Ready(BarrierWaitResult(false))
+    }
+}
+
+impl<'a> Deref for BarrierWaitFuture<'a> {
+    type Target = BarrierWaitResult;
+
+    fn deref(&self) -> &Self::Target {
+        self.res
+            .as_ref()
+            .expect("res is none, you should use .await to get the result")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Barrier;
+    use crate::test_utils::TestTask;
+    use core::pin::pin;
+    use core::task::Poll;
+
+    #[test]
+    fn test_generation_reuse() {
+        let barrier = Barrier::new(2);
+        let (waiter, leader) = (TestTask::new(), TestTask::new());
+        for _ in 0..3 {
+            // 创建之后没有被 poll 的 future 不算作到达
+            let _unpolled = barrier.wait();
+            let mut wait = pin!(barrier.wait());
+            assert!(waiter.poll(wait.as_mut()).is_pending());
+            match leader.poll_once(barrier.wait()) {
+                Poll::Ready(res) => assert!(res.is_leader()),
+                Poll::Pending => panic!("the last task should not wait"),
+            }
+            assert!(waiter.woken());
+            match waiter.poll(wait) {
+                Poll::Ready(res) => assert!(!res.is_leader()),
+                Poll::Pending => panic!("the barrier should be released"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_next_generation_waits() {
+        let barrier = Barrier::new(2);
+        let (first, second) = (TestTask::new(), TestTask::new());
+        let mut wait = pin!(barrier.wait());
+        assert!(first.poll(wait.as_mut()).is_pending());
+        assert!(second.poll_once(barrier.wait()).is_ready());
+        // 新一轮中先到达的任务需要等待，不会被上一轮的唤醒放行
+        let mut next = pin!(barrier.wait());
+        assert!(second.poll(next.as_mut()).is_pending());
+        assert!(first.poll(wait).is_ready());
+        assert!(!second.woken());
+        assert!(first.poll_once(barrier.wait()).is_ready());
+        assert!(second.woken());
+        assert!(second.poll(next).is_ready());
+    }
+}
@@ -0,0 +1,356 @@
+//! 多生产者、多消费者的广播通道。
+//!
+//! 每个发送的值都会被所有的接收者收到。通道内部是一个容量固定的环形缓冲区，
+//! 当接收者落后太多、未读取的值已经被覆盖时，接收操作会返回 [`RecvError::Lagged`]，
+//! 并从缓冲区中最早的值继续读取。
+
+use super::poll_blocking;
+use crate::{SpinNoIrq, WaitQueue};
+use alloc::{collections::VecDeque, sync::Arc};
+use async_io::AsyncStream;
+use core::fmt;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use task_api::WaitWakerNode;
+
+/// Error returned by [`Sender::send`] when there are no active receivers.
+/// It contains the value that failed to be sent.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+/// Error returned by [`Receiver::recv`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvError {
+    /// All senders have been dropped and there are no remaining values.
+    Closed,
+    /// The receiver lagged too far behind, and the given number of values
+    /// have been skipped.
+    Lagged(u64),
+}
+
+/// Error returned by [`Receiver::try_recv`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryRecvError {
+    /// There are no new values currently.
+    Empty,
+    /// All senders have been dropped and there are no remaining values.
+    Closed,
+    /// The receiver lagged too far behind, and the given number of values
+    /// have been skipped.
+    Lagged(u64),
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("SendError { .. }")
+    }
+}
+
+struct Tail<T> {
+    buffer: VecDeque<T>,
+    /// `buffer[0]` 对应的序号
+    head_pos: u64,
+    tx_count: usize,
+    rx_count: usize,
+}
+
+impl<T> Tail<T> {
+    /// 下一个发送的值对应的序号
+    fn next_pos(&self) -> u64 {
+        self.head_pos + self.buffer.len() as u64
+    }
+}
+
+impl<T: Clone> Tail<T> {
+    /// 从序号 `next` 处尝试读取，返回 None 表示暂时没有新的值
+    fn try_read(&self, next: &mut u64) -> Option<Result<T, RecvError>> {
+        if *next < self.head_pos {
+            let lagged = self.head_pos - *next;
+            *next = self.head_pos;
+            return Some(Err(RecvError::Lagged(lagged)));
+        }
+        if *next < self.next_pos() {
+            let value = self.buffer[(*next - self.head_pos) as usize].clone();
+            *next += 1;
+            return Some(Ok(value));
+        }
+        if self.tx_count == 0 {
+            return Some(Err(RecvError::Closed));
+        }
+        None
+    }
+}
+
+struct Shared<T> {
+    tail: SpinNoIrq<Tail<T>>,
+    cap: usize,
+    wq: WaitQueue,
+}
+
+/// Creates a broadcast channel which can hold at most `cap` values.
+///
+/// # Panics
+///
+/// Panics if `cap` is zero.
+pub fn channel<T: Clone>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(cap > 0, "broadcast channel requires cap > 0");
+    let shared = Arc::new(Shared {
+        tail: SpinNoIrq::new(Tail {
+            buffer: VecDeque::with_capacity(cap),
+            head_pos: 0,
+            tx_count: 1,
+            rx_count: 1,
+        }),
+        cap,
+        wq: WaitQueue::new(),
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver {
+            shared,
+            next: 0,
+            waker_node: None,
+        },
+    )
+}
+
+/// The sending half of a broadcast channel.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// The receiving half of a broadcast channel.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    /// 下一个要读取的值对应的序号
+    next: u64,
+    waker_node: Option<Arc<WaitWakerNode>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends a value to all active receivers, returning the number of
+    /// receivers that will see it.
+    pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+        let mut tail = self.shared.tail.lock();
+        if tail.rx_count == 0 {
+            return Err(SendError(value));
+        }
+        if tail.buffer.len() == self.shared.cap {
+            tail.buffer.pop_front();
+            tail.head_pos += 1;
+        }
+        tail.buffer.push_back(value);
+        let rx_count = tail.rx_count;
+        drop(tail);
+        self.shared.wq.notify_all();
+        Ok(rx_count)
+    }
+
+    /// Creates a new receiver that will receive values sent after this call.
+    pub fn subscribe(&self) -> Receiver<T> {
+        new_receiver(&self.shared)
+    }
+
+    /// Returns the number of active receivers.
+    pub fn receiver_count(&self) -> usize {
+        self.shared.tail.lock().rx_count
+    }
+}
+
+fn new_receiver<T>(shared: &Arc<Shared<T>>) -> Receiver<T> {
+    let mut tail = shared.tail.lock();
+    tail.rx_count += 1;
+    let next = tail.next_pos();
+    drop(tail);
+    Receiver {
+        shared: shared.clone(),
+        next,
+        waker_node: None,
+    }
+}
+
+impl<T: Clone> Receiver<T> {
+    /// Receives the next value for this receiver.
+    pub fn recv(&mut self) -> RecvFuture<'_, T> {
+        RecvFuture { rx: self }
+    }
+
+    /// Attempts to receive the next value without waiting.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        match self.shared.tail.lock().try_read(&mut self.next) {
+            Some(Ok(value)) => Ok(value),
+            Some(Err(RecvError::Lagged(n))) => Err(TryRecvError::Lagged(n)),
+            Some(Err(RecvError::Closed)) => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    fn poll_recv_inner(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        if let Some(node) = self.waker_node.take() {
+            self.shared.wq.unregister(&node);
+        }
+        // 在持有锁时注册 waker，避免与发送者之间丢失唤醒
+        let tail = self.shared.tail.lock();
+        match tail.try_read(&mut self.next) {
+            Some(res) => Poll::Ready(res),
+            None => {
+                self.waker_node = Some(self.shared.wq.register(cx.waker()));
+                Poll::Pending
+            }
+        }
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        poll_blocking(cx, |cx| self.poll_recv_inner(cx))
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Creates a new receiver that will receive values sent after this call.
+    pub fn resubscribe(&self) -> Self {
+        new_receiver(&self.shared)
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.tail.lock().tx_count += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut tail = self.shared.tail.lock();
+        tail.tx_count -= 1;
+        let last = tail.tx_count == 0;
+        drop(tail);
+        if last {
+            // 唤醒所有的接收者，让其感知到所有的发送者都已经退出
+            self.shared.wq.notify_all();
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        if let Some(node) = self.waker_node.take() {
+            self.shared.wq.unregister(&node);
+        }
+        self.shared.tail.lock().rx_count -= 1;
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Sender { .. }")
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Receiver { .. }")
+    }
+}
+
+/// 作为 Stream 使用时，[`RecvError::Lagged`] 会作为一项输出，
+/// 所有的发送者退出后 Stream 结束。
+impl<T: Clone> AsyncStream for Receiver<T> {
+    type Item = Result<T, RecvError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match core::task::ready!(self.get_mut().poll_recv(cx)) {
+            Err(RecvError::Closed) => Poll::Ready(None),
+            res => Poll::Ready(Some(res)),
+        }
+    }
+}
+
+/// [`Receiver::recv`] 返回的 future
+pub struct RecvFuture<'a, T> {
+    rx: &'a mut Receiver<T>,
+}
+
+impl<'a, T: Clone> Future for RecvFuture<'a, T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.get_mut().rx.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel, RecvError, TryRecvError};
+    use crate::test_utils::TestTask;
+    use core::pin::pin;
+    use core::task::Poll;
+
+    #[test]
+    fn test_all_receivers_woken() {
+        let (tx, mut rx1) = channel(2);
+        let mut rx2 = tx.subscribe();
+        let (task1, task2) = (TestTask::new(), TestTask::new());
+        let mut recv1 = pin!(rx1.recv());
+        let mut recv2 = pin!(rx2.recv());
+        assert!(task1.poll(recv1.as_mut()).is_pending());
+        assert!(task2.poll(recv2.as_mut()).is_pending());
+        assert_eq!(Ok(2), tx.send(1));
+        assert!(task1.woken());
+        assert!(task2.woken());
+        assert_eq!(Poll::Ready(Ok(1)), task1.poll(recv1));
+        assert_eq!(Poll::Ready(Ok(1)), task2.poll(recv2));
+    }
+
+    #[test]
+    fn test_lagged() {
+        let (tx, mut rx) = channel(2);
+        for value in 0..5 {
+            tx.send(value).unwrap();
+        }
+        // 只保留最新的两个值，落后的接收者跳过之前的值
+        assert_eq!(Err(TryRecvError::Lagged(3)), rx.try_recv());
+        assert_eq!(Ok(3), rx.try_recv());
+        assert_eq!(Ok(4), rx.try_recv());
+        assert_eq!(Err(TryRecvError::Empty), rx.try_recv());
+        // 新的接收者只能收到之后发送的值
+        let mut late = rx.resubscribe();
+        tx.send(5).unwrap();
+        assert_eq!(Ok(5), late.try_recv());
+        assert_eq!(Ok(5), rx.try_recv());
+    }
+
+    #[test]
+    fn test_closed() {
+        let (tx, mut rx) = channel(1);
+        let tx2 = tx.clone();
+        tx.send(1).unwrap();
+        drop(tx);
+        let task = TestTask::new();
+        {
+            let mut recv = pin!(rx.recv());
+            assert_eq!(Poll::Ready(Ok(1)), task.poll(recv.as_mut()));
+        }
+        {
+            let mut recv = pin!(rx.recv());
+            assert!(task.poll(recv.as_mut()).is_pending());
+            // 最后一个发送者退出时唤醒接收者
+            drop(tx2);
+            assert!(task.woken());
+            assert_eq!(Poll::Ready(Err(RecvError::Closed)), task.poll(recv));
+        }
+        assert_eq!(Err(TryRecvError::Closed), rx.try_recv());
+
+        let (tx, rx) = channel(1);
+        assert_eq!(1, tx.receiver_count());
+        drop(rx);
+        assert!(tx.send(1).is_err());
+    }
+}
@@ -0,0 +1,39 @@
+//! 基于 [`WaitQueue`](crate::WaitQueue) 实现的异步通道。
+//!
+//! - [`mpsc`]: 多生产者、单消费者的通道，支持有界与无界两种形式。
+//! - [`oneshot`]: 只能发送一个值的通道。
+//! - [`broadcast`]: 多生产者、多消费者的广播通道，每个接收者都会收到所有的值。
+//!
+//! 接收者在没有数据时会将 waker 注册到等待队列中并让权，而不是通过 `yield_now` 轮询。
+//! 在使能 `thread` feature 时，对返回的 future 进行 poll 会直接阻塞当前线程，直到操作完成。
+
+pub mod broadcast;
+pub mod mpsc;
+pub mod oneshot;
+
+use core::task::{Context, Poll};
+#[cfg(feature = "thread")]
+use task_api::block_current;
+
+/// 在 `thread` 模式下阻塞当前线程，直到 `poll` 返回 `Poll::Ready`；
+/// 在协程模式下直接返回 `poll` 的结果。
+///
+/// `poll` 返回 `Poll::Pending` 之前需要将 `cx` 中的 waker 注册到等待队列中。
+#[inline]
+fn poll_blocking<R>(
+    cx: &mut Context<'_>,
+    mut poll: impl FnMut(&mut Context<'_>) -> Poll<R>,
+) -> Poll<R> {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "thread")] {
+            loop {
+                if let Poll::Ready(res) = poll(cx) {
+                    return Poll::Ready(res);
+                }
+                block_current();
+            }
+        } else if #[cfg(not(feature = "thread"))] {
+            poll(cx)
+        }
+    }
+}
@@ -0,0 +1,496 @@
+//! 多生产者、单消费者的 FIFO 通道。
+//!
+//! [`channel`] 创建有界的通道，缓冲区满时 [`Sender::send`] 会等待接收者取走数据；
+//! [`unbounded_channel`] 创建无界的通道，发送操作不会等待。
+
+use super::poll_blocking;
+use crate::{SpinNoIrq, WaitQueue};
+use alloc::{collections::VecDeque, sync::Arc};
+use async_io::AsyncStream;
+use core::fmt;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use task_api::WaitWakerNode;
+
+/// Error returned by [`Sender::send`] and [`UnboundedSender::send`] when the
+/// receiver has been dropped or closed. It contains the value that failed to be sent.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+/// Error returned by [`Sender::try_send`].
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum TrySendError<T> {
+    /// The channel is full.
+    Full(T),
+    /// The receiver has been dropped or closed.
+    Closed(T),
+}
+
+/// Error returned by [`Receiver::try_recv`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryRecvError {
+    /// The channel is currently empty.
+    Empty,
+    /// All senders have been dropped and the channel is empty.
+    Disconnected,
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("SendError { .. }")
+    }
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.pad("Full(..)"),
+            TrySendError::Closed(_) => f.pad("Closed(..)"),
+        }
+    }
+}
+
+struct ChanState<T> {
+    queue: VecDeque<T>,
+    tx_count: usize,
+    rx_closed: bool,
+}
+
+struct Chan<T> {
+    state: SpinNoIrq<ChanState<T>>,
+    /// 为 None 时表示无界通道
+    cap: Option<usize>,
+    /// 接收者在这里等待数据
+    recv_wq: WaitQueue,
+    /// 有界通道的发送者在这里等待空位
+    send_wq: WaitQueue,
+}
+
+impl<T> Chan<T> {
+    fn new(cap: Option<usize>) -> Arc<Self> {
+        Arc::new(Self {
+            state: SpinNoIrq::new(ChanState {
+                queue: VecDeque::new(),
+                tx_count: 1,
+                rx_closed: false,
+            }),
+            cap,
+            recv_wq: WaitQueue::new(),
+            send_wq: WaitQueue::new(),
+        })
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.lock().rx_closed
+    }
+
+    fn add_sender(&self) {
+        self.state.lock().tx_count += 1;
+    }
+
+    fn drop_sender(&self) {
+        let mut state = self.state.lock();
+        state.tx_count -= 1;
+        let last = state.tx_count == 0;
+        drop(state);
+        if last {
+            // 唤醒接收者，让其感知到所有的发送者都已经退出
+            self.recv_wq.notify_all();
+        }
+    }
+
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let mut state = self.state.lock();
+        if state.rx_closed {
+            return Err(TrySendError::Closed(value));
+        }
+        if self.cap.is_some_and(|cap| state.queue.len() >= cap) {
+            return Err(TrySendError::Full(value));
+        }
+        state.queue.push_back(value);
+        drop(state);
+        self.recv_wq.notify_one();
+        Ok(())
+    }
+
+    /// 在持有通道锁时注册 waker，避免与发送者之间丢失唤醒
+    fn poll_send(
+        &self,
+        value: &mut Option<T>,
+        waker_node: &mut Option<Arc<WaitWakerNode>>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), SendError<T>>> {
+        if let Some(node) = waker_node.take() {
+            self.send_wq.unregister(&node);
+        }
+        let mut state = self.state.lock();
+        if state.rx_closed {
+            return Poll::Ready(Err(SendError(value.take().unwrap())));
+        }
+        if self.cap.is_some_and(|cap| state.queue.len() >= cap) {
+            *waker_node = Some(self.send_wq.register(cx.waker()));
+            return Poll::Pending;
+        }
+        state.queue.push_back(value.take().unwrap());
+        drop(state);
+        self.recv_wq.notify_one();
+        Poll::Ready(Ok(()))
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.state.lock();
+        match state.queue.pop_front() {
+            Some(value) => {
+                drop(state);
+                if self.cap.is_some() {
+                    self.send_wq.notify_one();
+                }
+                Ok(value)
+            }
+            None if state.tx_count == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    fn poll_recv(
+        &self,
+        waker_node: &mut Option<Arc<WaitWakerNode>>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<T>> {
+        if let Some(node) = waker_node.take() {
+            self.recv_wq.unregister(&node);
+        }
+        let mut state = self.state.lock();
+        match state.queue.pop_front() {
+            Some(value) => {
+                drop(state);
+                if self.cap.is_some() {
+                    self.send_wq.notify_one();
+                }
+                Poll::Ready(Some(value))
+            }
+            None if state.tx_count == 0 => Poll::Ready(None),
+            None => {
+                *waker_node = Some(self.recv_wq.register(cx.waker()));
+                Poll::Pending
+            }
+        }
+    }
+
+    fn close(&self) {
+        self.state.lock().rx_closed = true;
+        // 唤醒所有等待空位的发送者，让其感知到接收者已经关闭
+        self.send_wq.notify_all();
+    }
+}
+
+/// Creates a bounded mpsc channel with the given capacity.
+///
+/// # Panics
+///
+/// Panics if `cap` is zero.
+pub fn channel<T>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(cap > 0, "mpsc bounded channel requires cap > 0");
+    let chan = Chan::new(Some(cap));
+    (
+        Sender { chan: chan.clone() },
+        Receiver {
+            chan,
+            waker_node: None,
+        },
+    )
+}
+
+/// Creates an unbounded mpsc channel.
+pub fn unbounded_channel<T>() -> (UnboundedSender<T>, Receiver<T>) {
+    let chan = Chan::new(None);
+    (
+        UnboundedSender { chan: chan.clone() },
+        Receiver {
+            chan,
+            waker_node: None,
+        },
+    )
+}
+
+/// The sending half of a bounded channel, created by [`channel`].
+pub struct Sender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+/// The sending half of an unbounded channel, created by [`unbounded_channel`].
+pub struct UnboundedSender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+/// The receiving half of a channel.
+pub struct Receiver<T> {
+    chan: Arc<Chan<T>>,
+    waker_node: Option<Arc<WaitWakerNode>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends a value, waiting until there is capacity.
+    ///
+    /// 返回的 future 在接收者已经关闭时输出 [`SendError`]，其中包含未发送的值。
+    pub fn send(&self, value: T) -> SendFuture<'_, T> {
+        SendFuture {
+            chan: &self.chan,
+            value: Some(value),
+            waker_node: None,
+        }
+    }
+
+    /// Attempts to send a value without waiting.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.chan.try_send(value)
+    }
+
+    /// Returns `true` if the receiver has been dropped or closed.
+    pub fn is_closed(&self) -> bool {
+        self.chan.is_closed()
+    }
+}
+
+impl<T> UnboundedSender<T> {
+    /// Sends a value without waiting.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.chan.try_send(value).map_err(|err| match err {
+            TrySendError::Full(value) | TrySendError::Closed(value) => SendError(value),
+        })
+    }
+
+    /// Returns `true` if the receiver has been dropped or closed.
+    pub fn is_closed(&self) -> bool {
+        self.chan.is_closed()
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Receives the next value, or `None` if all senders have been dropped
+    /// and there are no remaining values in the channel.
+    pub fn recv(&mut self) -> RecvFuture<'_, T> {
+        RecvFuture { rx: self }
+    }
+
+    /// Attempts to receive the next value without waiting.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.chan.try_recv()
+    }
+
+    /// Closes the receiving half without dropping it.
+    ///
+    /// 之后的发送操作都会失败，但已经在通道中的数据仍然可以被接收。
+    pub fn close(&mut self) {
+        self.chan.close();
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let Self { chan, waker_node } = self;
+        poll_blocking(cx, |cx| chan.poll_recv(waker_node, cx))
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.add_sender();
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Clone for UnboundedSender<T> {
+    fn clone(&self) -> Self {
+        self.chan.add_sender();
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.chan.drop_sender();
+    }
+}
+
+impl<T> Drop for UnboundedSender<T> {
+    fn drop(&mut self) {
+        self.chan.drop_sender();
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        if let Some(node) = self.waker_node.take() {
+            self.chan.recv_wq.unregister(&node);
+        }
+        self.chan.close();
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Sender { .. }")
+    }
+}
+
+impl<T> fmt::Debug for UnboundedSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("UnboundedSender { .. }")
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Receiver { .. }")
+    }
+}
+
+impl<T> AsyncStream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+/// [`Sender::send`] 返回的 future
+pub struct SendFuture<'a, T> {
+    chan: &'a Chan<T>,
+    value: Option<T>,
+    waker_node: Option<Arc<WaitWakerNode>>,
+}
+
+impl<'a, T> Unpin for SendFuture<'a, T> {}
+
+impl<'a, T> Future for SendFuture<'a, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self {
+            chan,
+            value,
+            waker_node,
+        } = self.get_mut();
+        poll_blocking(cx, |cx| chan.poll_send(value, waker_node, cx))
+    }
+}
+
+impl<'a, T> Drop for SendFuture<'a, T> {
+    fn drop(&mut self) {
+        if let Some(node) = self.waker_node.take() {
+            if !self.chan.send_wq.unregister(&node) {
+                // 已经被唤醒但没有使用空位，将唤醒传递给其他等待的发送者
+                self.chan.send_wq.notify_one();
+            }
+        }
+    }
+}
+
+/// [`Receiver::recv`] 返回的 future
+pub struct RecvFuture<'a, T> {
+    rx: &'a mut Receiver<T>,
+}
+
+impl<'a, T> Future for RecvFuture<'a, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.get_mut().rx.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel, unbounded_channel, SendError, TryRecvError, TrySendError};
+    use crate::test_utils::TestTask;
+    use core::pin::pin;
+    use core::task::Poll;
+
+    #[test]
+    fn test_bounded_send_waits() {
+        let (tx, mut rx) = channel(1);
+        tx.try_send(1).unwrap();
+        assert!(matches!(tx.try_send(2), Err(TrySendError::Full(2))));
+        let task = TestTask::new();
+        let mut send = pin!(tx.send(2));
+        assert!(task.poll(send.as_mut()).is_pending());
+        assert_eq!(Ok(1), rx.try_recv());
+        assert!(task.woken());
+        assert!(matches!(task.poll(send), Poll::Ready(Ok(()))));
+        assert_eq!(Ok(2), rx.try_recv());
+    }
+
+    #[test]
+    fn test_cancelled_sender_passes_wakeup() {
+        let (tx, mut rx) = channel(1);
+        tx.try_send(0).unwrap();
+        let (first, second) = (TestTask::new(), TestTask::new());
+        let mut send = pin!(tx.send(2));
+        {
+            let cancelled = pin!(tx.send(1));
+            assert!(first.poll(cancelled).is_pending());
+            assert!(second.poll(send.as_mut()).is_pending());
+            // 取出一个值只唤醒第一个等待的发送者
+            assert_eq!(Ok(0), rx.try_recv());
+            assert!(first.woken());
+            assert!(!second.woken());
+        }
+        // 被唤醒的发送者放弃发送，空位交给下一个等待的发送者
+        assert!(second.woken());
+        assert!(matches!(second.poll(send), Poll::Ready(Ok(()))));
+        assert_eq!(Ok(2), rx.try_recv());
+    }
+
+    #[test]
+    fn test_recv_disconnected() {
+        let (tx, mut rx) = unbounded_channel();
+        let task = TestTask::new();
+        {
+            let mut recv = pin!(rx.recv());
+            assert!(task.poll(recv.as_mut()).is_pending());
+            tx.send(1).unwrap();
+            assert!(task.woken());
+            assert_eq!(Poll::Ready(Some(1)), task.poll(recv));
+        }
+        let tx2 = tx.clone();
+        {
+            let mut recv = pin!(rx.recv());
+            assert!(task.poll(recv.as_mut()).is_pending());
+            drop(tx);
+            assert!(!task.woken());
+            // 最后一个发送者退出时唤醒接收者
+            drop(tx2);
+            assert!(task.woken());
+            assert_eq!(Poll::Ready(None), task.poll(recv));
+        }
+        assert_eq!(Err(TryRecvError::Disconnected), rx.try_recv());
+    }
+
+    #[test]
+    fn test_close() {
+        let (tx, mut rx) = channel(1);
+        tx.try_send(1).unwrap();
+        let task = TestTask::new();
+        let mut send = pin!(tx.send(2));
+        assert!(task.poll(send.as_mut()).is_pending());
+        rx.close();
+        // 等待空位的发送者被唤醒并取回未发送的值
+        assert!(task.woken());
+        assert!(matches!(task.poll(send), Poll::Ready(Err(SendError(2)))));
+        assert!(tx.is_closed());
+        assert!(matches!(tx.try_send(3), Err(TrySendError::Closed(3))));
+        // 关闭之前发送的值仍然可以接收
+        assert_eq!(Ok(1), rx.try_recv());
+        assert_eq!(Err(TryRecvError::Empty), rx.try_recv());
+
+        let (tx, rx) = unbounded_channel();
+        drop(rx);
+        assert!(matches!(tx.send(1), Err(SendError(1))));
+    }
+}
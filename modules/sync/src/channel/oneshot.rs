@@ -0,0 +1,220 @@
+//! 只能发送一个值的通道，通常用于等待另一个任务的计算结果。
+//!
+//! [`Receiver`] 本身实现了 [`Future`]，可以直接 `.await` 等待结果。
+
+use super::poll_blocking;
+use crate::{SpinNoIrq, WaitQueue};
+use alloc::sync::Arc;
+use core::fmt;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use task_api::WaitWakerNode;
+
+/// Error returned by awaiting the [`Receiver`] when the sender has been
+/// dropped without sending a value.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RecvError;
+
+/// Error returned by [`Receiver::try_recv`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryRecvError {
+    /// The value has not been sent yet.
+    Empty,
+    /// The sender has been dropped without sending a value,
+    /// or the value has already been received.
+    Closed,
+}
+
+struct State<T> {
+    value: Option<T>,
+    /// 发送者已经发送了值或者已经被 drop
+    tx_done: bool,
+    rx_closed: bool,
+}
+
+struct Inner<T> {
+    state: SpinNoIrq<State<T>>,
+    wq: WaitQueue,
+}
+
+/// Creates a new oneshot channel.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(Inner {
+        state: SpinNoIrq::new(State {
+            value: None,
+            tx_done: false,
+            rx_closed: false,
+        }),
+        wq: WaitQueue::new(),
+    });
+    (
+        Sender {
+            inner: Some(inner.clone()),
+        },
+        Receiver {
+            inner,
+            waker_node: None,
+        },
+    )
+}
+
+/// The sending half of a oneshot channel.
+pub struct Sender<T> {
+    inner: Option<Arc<Inner<T>>>,
+}
+
+/// The receiving half of a oneshot channel.
+pub struct Receiver<T> {
+    inner: Arc<Inner<T>>,
+    waker_node: Option<Arc<WaitWakerNode>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends a value to the receiver.
+    ///
+    /// 如果接收者已经被 drop 或者关闭，则返回 `Err` 并归还该值。
+    pub fn send(mut self, value: T) -> Result<(), T> {
+        let inner = self.inner.take().unwrap();
+        let mut state = inner.state.lock();
+        if state.rx_closed {
+            state.tx_done = true;
+            return Err(value);
+        }
+        state.value = Some(value);
+        state.tx_done = true;
+        drop(state);
+        inner.wq.notify_all();
+        Ok(())
+    }
+
+    /// Returns `true` if the receiver has been dropped or closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.as_ref().unwrap().state.lock().rx_closed
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Attempts to receive the value without waiting.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut state = self.inner.state.lock();
+        match state.value.take() {
+            Some(value) => Ok(value),
+            None if state.tx_done => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Prevents the sender from sending a value.
+    ///
+    /// 如果值已经被发送，仍然可以通过 `try_recv` 或 `.await` 取出。
+    pub fn close(&mut self) {
+        self.inner.state.lock().rx_closed = true;
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        let Self { inner, waker_node } = self;
+        if let Some(node) = waker_node.take() {
+            inner.wq.unregister(&node);
+        }
+        // 在持有锁时注册 waker，避免与发送者之间丢失唤醒
+        let mut state = inner.state.lock();
+        match state.value.take() {
+            Some(value) => Poll::Ready(Ok(value)),
+            None if state.tx_done => Poll::Ready(Err(RecvError)),
+            None => {
+                *waker_node = Some(inner.wq.register(cx.waker()));
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            inner.state.lock().tx_done = true;
+            // 唤醒接收者，让其感知到发送者已经退出
+            inner.wq.notify_all();
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        if let Some(node) = self.waker_node.take() {
+            self.inner.wq.unregister(&node);
+        }
+        self.inner.state.lock().rx_closed = true;
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Sender { .. }")
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Receiver { .. }")
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        poll_blocking(cx, |cx| this.poll_recv(cx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel, RecvError, TryRecvError};
+    use crate::test_utils::TestTask;
+    use core::pin::pin;
+    use core::task::Poll;
+
+    #[test]
+    fn test_send() {
+        let (tx, mut rx) = channel();
+        assert_eq!(Err(TryRecvError::Empty), rx.try_recv());
+        let task = TestTask::new();
+        let mut rx = pin!(rx);
+        assert!(task.poll(rx.as_mut()).is_pending());
+        tx.send(1).unwrap();
+        assert!(task.woken());
+        assert_eq!(Poll::Ready(Ok(1)), task.poll(rx.as_mut()));
+        // 值只能被取出一次
+        assert_eq!(Err(TryRecvError::Closed), rx.try_recv());
+    }
+
+    #[test]
+    fn test_sender_dropped() {
+        let (tx, rx) = channel::<i32>();
+        let task = TestTask::new();
+        let mut rx = pin!(rx);
+        assert!(task.poll(rx.as_mut()).is_pending());
+        drop(tx);
+        assert!(task.woken());
+        assert_eq!(Poll::Ready(Err(RecvError)), task.poll(rx));
+    }
+
+    #[test]
+    fn test_close() {
+        let (tx, mut rx) = channel();
+        assert!(!tx.is_closed());
+        rx.close();
+        assert!(tx.is_closed());
+        assert_eq!(Err(1), tx.send(1));
+        assert_eq!(Err(TryRecvError::Closed), rx.try_recv());
+
+        let (tx, rx) = channel();
+        drop(rx);
+        assert_eq!(Err(1), tx.send(1));
+    }
+}
@@ -0,0 +1,268 @@
+//! 与 [`Mutex`] 配合使用的条件变量。
+//!
+//! 条件变量允许出现虚假唤醒，调用者需要在循环中重新检查条件。
+
+use crate::{Mutex, MutexGuard, WaitQueue};
+use alloc::sync::Arc;
+use core::fmt;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use task_api::WaitWakerNode;
+#[cfg(feature = "thread")]
+use task_api::{block_current, current_task};
+
+#[cfg(feature = "irq")]
+use axhal::time::{current_time, TimeValue};
+#[cfg(feature = "irq")]
+use core::time::Duration;
+#[cfg(feature = "irq")]
+use task_api::{cancel_alarm, set_alarm_wakeup};
+
+/// A Condition Variable, similar to
+/// [`std::sync::Condvar`](https://doc.rust-lang.org/std/sync/struct.Condvar.html).
+///
+/// The waiting task will release the [`MutexGuard`] and be put into the wait
+/// queue. When it is notified, it will re-acquire the lock before returning.
+pub struct Condvar {
+    wq: WaitQueue,
+}
+
+/// A type indicating whether a timed wait on a condition variable returned
+/// due to a time out or not.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct WaitTimeoutResult(bool);
+
+impl WaitTimeoutResult {
+    /// Returns `true` if the wait was known to have timed out.
+    pub fn timed_out(&self) -> bool {
+        self.0
+    }
+}
+
+impl Condvar {
+    /// Creates a new condition variable.
+    pub const fn new() -> Self {
+        Self {
+            wq: WaitQueue::new(),
+        }
+    }
+
+    /// Wakes up one task blocked on this condvar.
+    pub fn notify_one(&self) -> bool {
+        self.wq.notify_one()
+    }
+
+    /// Wakes up all tasks blocked on this condvar.
+    pub fn notify_all(&self) {
+        self.wq.notify_all()
+    }
+
+    /// 释放 guard 并等待通知，被唤醒后重新获取锁
+    ///
+    /// 在使能 `thread` feature 时，该函数会阻塞当前线程，直接返回已经获取到锁的 future；
+    /// 否则需要使用 `.await` 才能真正地等待。
+    pub fn wait<'a, 'b, T: ?Sized>(
+        &'a self,
+        guard: MutexGuard<'b, T>,
+    ) -> CondvarWaitFuture<'a, 'b, T> {
+        assert!(
+            guard.is_acquired(),
+            "data is none, you should use .await to get the data"
+        );
+        let mutex = guard.mutex();
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "thread")] {
+                // 先注册再释放锁，避免在两者之间丢失唤醒
+                let waker_node = self.wq.register(&current_task().waker());
+                drop(guard);
+                block_current();
+                self.wq.unregister(&waker_node);
+                CondvarWaitFuture {
+                    cv: self,
+                    mutex,
+                    guard: Some(mutex.lock()),
+                    waker_node: None,
+                }
+            } else if #[cfg(not(feature = "thread"))] {
+                CondvarWaitFuture {
+                    cv: self,
+                    mutex,
+                    guard: Some(guard),
+                    waker_node: None,
+                }
+            }
+        }
+    }
+
+    /// 释放 guard 并等待通知或超时，被唤醒后重新获取锁
+    #[cfg(feature = "irq")]
+    pub fn wait_timeout<'a, 'b, T: ?Sized>(
+        &'a self,
+        guard: MutexGuard<'b, T>,
+        dur: Duration,
+    ) -> CondvarWaitTimeoutFuture<'a, 'b, T> {
+        assert!(
+            guard.is_acquired(),
+            "data is none, you should use .await to get the data"
+        );
+        let deadline = current_time() + dur;
+        let mutex = guard.mutex();
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "thread")] {
+                let waker = current_task().waker();
+                let waker_node = self.wq.register(&waker);
+                set_alarm_wakeup(deadline, waker.clone());
+                drop(guard);
+                block_current();
+
+                cancel_alarm(&waker);
+                self.wq.unregister(&waker_node);
+                CondvarWaitTimeoutFuture {
+                    inner: CondvarWaitFuture {
+                        cv: self,
+                        mutex,
+                        guard: Some(mutex.lock()),
+                        waker_node: None,
+                    },
+                    deadline,
+                    res: Some(WaitTimeoutResult(current_time() >= deadline)),
+                }
+            } else if #[cfg(not(feature = "thread"))] {
+                CondvarWaitTimeoutFuture {
+                    inner: CondvarWaitFuture {
+                        cv: self,
+                        mutex,
+                        guard: Some(guard),
+                        waker_node: None,
+                    },
+                    deadline,
+                    res: None,
+                }
+            }
+        }
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Condvar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Condvar { .. }")
+    }
+}
+
+/// [`Condvar::wait`] 返回的 future，输出重新获取到的 [`MutexGuard`]
+pub struct CondvarWaitFuture<'a, 'b, T: ?Sized> {
+    cv: &'a Condvar,
+    /// 协程模式下，被唤醒后需要通过它重新获取锁
+    #[cfg_attr(feature = "thread", allow(dead_code))]
+    mutex: &'b Mutex<T>,
+    /// 最初传入的 guard，或者被唤醒后重新获取锁的 guard
+    guard: Option<MutexGuard<'b, T>>,
+    /// 已经注册到等待队列中的节点，为 Some 时表示正在等待通知
+    waker_node: Option<(Arc<WaitWakerNode>, core::task::Waker)>,
+}
+
+impl<'a, 'b, T: ?Sized> CondvarWaitFuture<'a, 'b, T> {
+    /// 协程模式下的等待过程：注册并释放锁 -> 被唤醒后注销 -> 重新获取锁
+    fn poll_wait(&mut self, cx: &mut Context<'_>) -> Poll<MutexGuard<'b, T>> {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "thread")] {
+                let _ = cx;
+                Poll::Ready(self.guard.take().unwrap())
+            } else if #[cfg(not(feature = "thread"))] {
+                if let Some(guard) = self.guard.as_ref() {
+                    if guard.is_acquired() {
+                        // 先注册再释放锁，避免在两者之间丢失唤醒
+                        let waker = cx.waker().clone();
+                        let waker_node = self.cv.wq.register(&waker);
+                        self.waker_node = Some((waker_node, waker));
+                        self.guard = None;
+                        return Poll::Pending;
+                    }
+                } else if let Some((waker_node, _)) = self.waker_node.take() {
+                    self.cv.wq.unregister(&waker_node);
+                    self.guard = Some(self.mutex.lock());
+                }
+                let guard = core::task::ready!(Pin::new(self.guard.as_mut().unwrap()).poll(cx));
+                self.guard = None;
+                Poll::Ready(guard)
+            }
+        }
+    }
+}
+
+impl<'a, 'b, T: ?Sized> Future for CondvarWaitFuture<'a, 'b, T> {
+    type Output = MutexGuard<'b, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.get_mut().poll_wait(cx)
+    }
+}
+
+impl<'a, 'b, T: ?Sized> Drop for CondvarWaitFuture<'a, 'b, T> {
+    fn drop(&mut self) {
+        if let Some((waker_node, _)) = self.waker_node.take() {
+            self.cv.wq.unregister(&waker_node);
+        }
+    }
+}
+
+/// [`Condvar::wait_timeout`] 返回的 future，输出重新获取到的 [`MutexGuard`] 以及是否超时
+#[cfg(feature = "irq")]
+pub struct CondvarWaitTimeoutFuture<'a, 'b, T: ?Sized> {
+    inner: CondvarWaitFuture<'a, 'b, T>,
+    deadline: TimeValue,
+    res: Option<WaitTimeoutResult>,
+}
+
+#[cfg(feature = "irq")]
+impl<'a, 'b, T: ?Sized> Future for CondvarWaitTimeoutFuture<'a, 'b, T> {
+    type Output = (MutexGuard<'b, T>, WaitTimeoutResult);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self {
+            inner,
+            deadline,
+            res,
+        } = self.get_mut();
+        #[cfg(not(feature = "thread"))]
+        {
+            let registering = inner.waker_node.is_none() && res.is_none();
+            let waking = inner.waker_node.is_some();
+            if waking {
+                let (_, waker) = inner.waker_node.as_ref().unwrap();
+                cancel_alarm(waker);
+                *res = Some(WaitTimeoutResult(current_time() >= *deadline));
+            }
+            let poll = inner.poll_wait(cx);
+            if registering && poll.is_pending() && inner.waker_node.is_some() {
+                set_alarm_wakeup(*deadline, cx.waker().clone());
+            }
+            let guard = core::task::ready!(poll);
+            Poll::Ready((guard, res.unwrap()))
+        }
+        #[cfg(feature = "thread")]
+        {
+            let _ = deadline;
+            let guard = core::task::ready!(inner.poll_wait(cx));
+            Poll::Ready((guard, res.unwrap()))
+        }
+    }
+}
+
+#[cfg(feature = "irq")]
+impl<'a, 'b, T: ?Sized> Drop for CondvarWaitTimeoutFuture<'a, 'b, T> {
+    fn drop(&mut self) {
+        if let Some((_, waker)) = self.inner.waker_node.as_ref() {
+            cancel_alarm(waker);
+        }
+    }
+}
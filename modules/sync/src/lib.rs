@@ -1,14 +1,50 @@
 //! 以协程的方式实现同步原语、以及任务调度模块中的 WaitQueue、TimerQueue
 //! 目前支持的原语：
 //! - [`Mutex`]: A mutual exclusion primitive.
+//! - [`RwLock`]: Provides a mutual exclusion mechanism which allows multiple readers at the same time,
+//!   while allowing only one writer at a time.
+//! - [`Semaphore`]: A counting semaphore.
+//! - [`Condvar`]: A condition variable used together with [`Mutex`].
+//! - [`Barrier`]: Enables multiple tasks to synchronize the beginning of some computation.
+//! - [`mpsc`], [`oneshot`], [`broadcast`]: Asynchronous channels for passing messages between tasks.
+//! - [`SpinNoIrq`]: `spinlock::SpinNoIrq` that can be checked by lockdep.
+//! - [`rcu`]: Read-copy-update for read-mostly data, such as the task and process registries.
+//!
+//! 开启 `lockdep` feature 后，[`Mutex`] 与 [`SpinNoIrq`] 的获取顺序会被记录并检查，见 `lockdep` 模块。
 
 #![cfg_attr(not(test), no_std)]
 #![feature(ptr_metadata)]
 
 extern crate alloc;
 
+mod barrier;
+pub use barrier::*;
+
+mod channel;
+pub use channel::*;
+
+mod condvar;
+pub use condvar::*;
+
+#[cfg(feature = "lockdep")]
+pub mod lockdep;
+
 mod mutex;
 pub use mutex::*;
 
+pub mod rcu;
+
+mod rwlock;
+pub use rwlock::*;
+
+mod semaphore;
+pub use semaphore::*;
+
+mod spin;
+pub use spin::*;
+
+#[cfg(test)]
+mod test_utils;
+
 mod wait_queue;
 pub use wait_queue::*;
@@ -0,0 +1,252 @@
+//! 锁依赖检查（lockdep），通过 `lockdep` feature 开启。
+//!
+//! 每个锁在创建时根据创建的代码位置分配一个锁类别 [`LockClass`]，
+//! 同一位置创建的所有锁（例如每个进程的 `memory_set`）属于同一个类别。
+//! 每个任务（或者中断上下文）持有锁的顺序被记录下来，形成类别之间的依赖图：
+//! - 如果先持有 A 再获取 B，同时在其他地方先持有 B 再获取 A，则报告可能的 ABBA 死锁；
+//! - 如果某个类别的锁在 `.await` 让权时仍被持有，同时又会在中断上下文中被获取，
+//!   则报告可能的死锁（中断处理函数会一直等待已经让权的任务释放锁）；
+//! - 自旋锁在 `.await` 让权时仍被持有同样会被报告。
+//!
+//! 检查只会输出错误日志，不会影响程序的执行，每个问题只报告一次。
+//! 同一类别的锁之间的嵌套（例如 fork 时同时持有父子进程的 `memory_set`）不会被检查。
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use core::fmt;
+use core::panic::Location;
+use spinlock::SpinNoIrq;
+
+/// 锁的类别，以创建锁的代码位置区分
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LockClass(&'static Location<'static>);
+
+impl LockClass {
+    /// 以调用者的代码位置作为锁的类别
+    #[track_caller]
+    pub const fn caller() -> Self {
+        Self(Location::caller())
+    }
+}
+
+impl fmt::Display for LockClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.0.file(), self.0.line())
+    }
+}
+
+impl fmt::Debug for LockClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// 锁的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    /// [`crate::Mutex`]，等待时会让权
+    Mutex,
+    /// [`crate::SpinNoIrq`]，等待时自旋，持有期间关闭中断
+    SpinNoIrq,
+}
+
+struct HeldLock {
+    class: LockClass,
+    kind: LockKind,
+    addr: usize,
+}
+
+#[derive(Default)]
+struct ClassInfo {
+    /// 持有该类别的锁时获取过的其他类别
+    after: BTreeSet<LockClass>,
+    /// 是否在中断上下文中被获取过
+    irq_used: bool,
+    /// 是否在 `.await` 让权时被持有过
+    held_across_await: bool,
+}
+
+/// 出现过的问题，用于保证每个问题只报告一次
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Report {
+    Inversion(LockClass, LockClass),
+    IrqAwait(LockClass),
+    SpinAwait(LockClass),
+}
+
+struct LockDep {
+    classes: BTreeMap<LockClass, ClassInfo>,
+    /// 每个上下文（任务或者中断）当前持有的锁，按照获取的顺序排列
+    held: BTreeMap<usize, Vec<HeldLock>>,
+    reported: BTreeSet<Report>,
+}
+
+/// lockdep 本身的状态使用未被检查的 SpinNoIrq 保护
+static LOCKDEP: SpinNoIrq<LockDep> = SpinNoIrq::new(LockDep {
+    classes: BTreeMap::new(),
+    held: BTreeMap::new(),
+    reported: BTreeSet::new(),
+});
+
+/// 获取当前上下文的标识：中断上下文以及没有当前任务时使用 CPU 区分，否则使用任务的指针
+fn current_context() -> (usize, bool) {
+    #[cfg(feature = "irq")]
+    if axhal::irq::in_irq() {
+        return (usize::MAX - axhal::cpu::this_cpu_id(), true);
+    }
+    let task_ptr = task_api::current_task_ptr::<task_api::Task>() as usize;
+    if task_ptr == 0 {
+        (usize::MAX - axhal::cpu::this_cpu_id(), false)
+    } else {
+        (task_ptr, false)
+    }
+}
+
+impl LockDep {
+    /// 依赖图中是否存在从 `from` 到 `to` 的路径
+    fn reachable(&self, from: LockClass, to: LockClass) -> bool {
+        let mut visited = BTreeSet::new();
+        let mut stack = Vec::from([from]);
+        while let Some(class) = stack.pop() {
+            if class == to {
+                return true;
+            }
+            if !visited.insert(class) {
+                continue;
+            }
+            if let Some(info) = self.classes.get(&class) {
+                stack.extend(info.after.iter().copied());
+            }
+        }
+        false
+    }
+
+    fn report_once(&mut self, report: Report) -> bool {
+        self.reported.insert(report)
+    }
+
+    fn report_irq_await(&mut self, class: LockClass) {
+        if self.report_once(Report::IrqAwait(class)) {
+            log::error!(
+                "lockdep: lock {} is held across .await and also taken in IRQ context, possible deadlock",
+                class
+            );
+        }
+    }
+}
+
+fn dump_held(held: &[HeldLock]) {
+    for (i, lock) in held.iter().enumerate() {
+        log::error!(
+            "  #{}: {:?} {} ({:#x})",
+            i,
+            lock.kind,
+            lock.class,
+            lock.addr
+        );
+    }
+}
+
+/// 记录当前上下文获取了一个锁，并检查获取顺序
+pub fn lock_acquire(class: LockClass, kind: LockKind, addr: usize) {
+    let (ctx, in_irq) = current_context();
+    let mut lockdep = LOCKDEP.lock();
+    let held_classes: Vec<LockClass> = lockdep
+        .held
+        .get(&ctx)
+        .map(|held| held.iter().map(|lock| lock.class).collect())
+        .unwrap_or_default();
+    for prev in held_classes {
+        if prev == class {
+            continue;
+        }
+        let is_new_edge = !lockdep
+            .classes
+            .get(&prev)
+            .is_some_and(|info| info.after.contains(&class));
+        if !is_new_edge {
+            continue;
+        }
+        if lockdep.reachable(class, prev) && lockdep.report_once(Report::Inversion(prev, class)) {
+            log::error!(
+                "lockdep: possible ABBA deadlock: acquiring {} while holding {}, \
+                 but {} was previously acquired while holding {}",
+                class,
+                prev,
+                prev,
+                class
+            );
+            log::error!("lockdep: locks held by the current context:");
+            if let Some(held) = lockdep.held.get(&ctx) {
+                dump_held(held);
+            }
+        }
+        lockdep.classes.entry(prev).or_default().after.insert(class);
+    }
+    let info = lockdep.classes.entry(class).or_default();
+    if in_irq {
+        info.irq_used = true;
+    }
+    if info.irq_used && info.held_across_await {
+        lockdep.report_irq_await(class);
+    }
+    lockdep
+        .held
+        .entry(ctx)
+        .or_default()
+        .push(HeldLock { class, kind, addr });
+}
+
+/// 记录一个锁被释放
+pub fn lock_release(addr: usize) {
+    let (ctx, _) = current_context();
+    let mut lockdep = LOCKDEP.lock();
+    // 协程中的锁可能被转移到其他的任务中释放，因此当前上下文中找不到时需要查找所有的上下文
+    let owner = if lockdep
+        .held
+        .get(&ctx)
+        .is_some_and(|held| held.iter().any(|lock| lock.addr == addr))
+    {
+        Some(ctx)
+    } else {
+        lockdep
+            .held
+            .iter()
+            .find(|(_, held)| held.iter().any(|lock| lock.addr == addr))
+            .map(|(ctx, _)| *ctx)
+    };
+    let Some(owner) = owner else {
+        return;
+    };
+    let held = lockdep.held.get_mut(&owner).unwrap();
+    let pos = held.iter().rposition(|lock| lock.addr == addr).unwrap();
+    held.remove(pos);
+    if held.is_empty() {
+        lockdep.held.remove(&owner);
+    }
+}
+
+/// 当前任务在 `.await` 处让权时调用，检查任务是否仍持有锁
+pub fn check_held_across_await() {
+    let (ctx, _) = current_context();
+    let mut lockdep = LOCKDEP.lock();
+    let held: Vec<(LockClass, LockKind)> = match lockdep.held.get(&ctx) {
+        Some(held) => held.iter().map(|lock| (lock.class, lock.kind)).collect(),
+        None => return,
+    };
+    for (class, kind) in held {
+        if kind == LockKind::SpinNoIrq && lockdep.report_once(Report::SpinAwait(class)) {
+            log::error!("lockdep: spinlock {} is held across .await", class);
+            if let Some(held) = lockdep.held.get(&ctx) {
+                dump_held(held);
+            }
+        }
+        let info = lockdep.classes.entry(class).or_default();
+        info.held_across_await = true;
+        if info.irq_used {
+            lockdep.report_irq_await(class);
+        }
+    }
+}
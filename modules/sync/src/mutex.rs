@@ -28,6 +28,8 @@ use task_api::current_task;
 pub struct Mutex<T: ?Sized> {
     wq: WaitQueue,
     owner_task: AtomicUsize,
+    #[cfg(feature = "lockdep")]
+    class: crate::lockdep::LockClass,
     data: UnsafeCell<T>,
 }
 
@@ -51,10 +53,13 @@ unsafe impl<'a, T: ?Sized + 'a> Send for MutexGuard<'a, T> {}
 impl<T> Mutex<T> {
     /// Creates a new [`Mutex`] wrapping the supplied data.
     #[inline(always)]
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub const fn new(data: T) -> Self {
         Self {
             wq: WaitQueue::new(),
             owner_task: AtomicUsize::new(0),
+            #[cfg(feature = "lockdep")]
+            class: crate::lockdep::LockClass::caller(),
             data: UnsafeCell::new(data),
         }
     }
@@ -81,6 +86,17 @@ impl<T: ?Sized> Mutex<T> {
         self.owner_task.load(Ordering::Relaxed) != 0
     }
 
+    /// 记录锁被当前上下文获取，用于 lockdep 检查
+    #[inline(always)]
+    fn lockdep_acquire(&self) {
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::lock_acquire(
+            self.class,
+            crate::lockdep::LockKind::Mutex,
+            self as *const Self as *const () as usize,
+        );
+    }
+
     /// Locks the [`Mutex`] and returns a guard that permits access to the inner data.
     ///
     /// The returned value may be dereferenced for data access
@@ -109,6 +125,7 @@ impl<T: ?Sized> Mutex<T> {
                         }
                     }
                 }
+                self.lockdep_acquire();
                 return MutexGuard {
                     lock: self,
                     data: Some(self.data.get()),
@@ -134,6 +151,7 @@ impl<T: ?Sized> Mutex<T> {
             .compare_exchange(0, current_task, Ordering::Acquire, Ordering::Relaxed)
             .is_ok()
         {
+            self.lockdep_acquire();
             Some(MutexGuard {
                 lock: self,
                 data: Some(self.data.get()),
@@ -154,6 +172,8 @@ impl<T: ?Sized> Mutex<T> {
         let curr = current_task();
         let waker = curr.waker();
         let current_task = waker.data() as usize;
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::lock_release(self as *const Self as *const () as usize);
         let owner_task = self.owner_task.swap(0, Ordering::Release);
         assert_eq!(
             owner_task,
@@ -181,8 +201,21 @@ impl<T: ?Sized> Mutex<T> {
     }
 }
 
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    /// 获取 guard 对应的 [`Mutex`]，用于 [`Condvar`](crate::Condvar) 在等待时释放并重新获取锁
+    pub(crate) fn mutex(&self) -> &'a Mutex<T> {
+        self.lock
+    }
+
+    /// 当前 guard 是否已经持有锁（即已经获取到了数据）
+    pub(crate) fn is_acquired(&self) -> bool {
+        self.data.is_some()
+    }
+}
+
 impl<T: ?Sized + Default> Default for Mutex<T> {
     #[inline(always)]
+    #[cfg_attr(feature = "lockdep", track_caller)]
     fn default() -> Self {
         Self::new(Default::default())
     }
@@ -266,6 +299,7 @@ impl<'a, T: ?Sized + 'a> Future for MutexGuard<'a, T> {
                         Ordering::Relaxed,
                     ) {
                         Ok(_) => {
+                            lock.lockdep_acquire();
                             return Poll::Ready(MutexGuard {
                                 lock,
                                 data: Some(lock.data.get()),
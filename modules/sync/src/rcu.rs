@@ -0,0 +1,390 @@
+//! RCU（Read-Copy-Update）同步机制，适用于读多写少的数据。
+//!
+//! 读者通过 [`rcu_read_lock`] 进入读临界区，期间关闭抢占，不需要获取任何锁；
+//! 读临界区内不能 `.await` 或者阻塞。
+//!
+//! 写者复制一份数据并修改，随后原子地替换指针，再通过 [`synchronize_rcu`] 等待一个宽限期
+//! （grace period），保证所有在替换之前进入读临界区的读者都已经退出，之后才释放旧的数据。
+//! 不方便等待的写者可以通过 [`call_rcu`] 把释放操作推迟到宽限期结束之后，[`RcuMap`] 即是如此。
+//!
+//! 宽限期的判断依赖于调度器的静止状态（quiescent state）：CPU 在两次任务调度之间
+//! 不可能处于读临界区中，因此 trampoline 在每次调度时调用 [`rcu_quiescent_state`]。
+//! 当所有在线的 CPU 都在宽限期开始之后经过了一次静止状态，宽限期结束，
+//! 此时由报告静止状态的 CPU 执行宽限期已经结束的回调。
+
+use crate::{Mutex, SpinNoIrq, WaitQueue};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, VecDeque},
+    vec::Vec,
+};
+use core::borrow::Borrow;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+
+/// 支持的最大 CPU 数量
+const MAX_CPUS: usize = 64;
+
+/// 最近一次开始的宽限期的编号
+static GP_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+/// 每个 CPU 最近一次经过静止状态时看到的宽限期编号
+static CPU_QS: [AtomicUsize; MAX_CPUS] = [const { AtomicUsize::new(0) }; MAX_CPUS];
+
+/// 经过过静止状态的 CPU，只有这些 CPU 参与宽限期的判断
+static CPU_ONLINE: AtomicU64 = AtomicU64::new(0);
+
+/// 正在等待宽限期结束的任务数量
+static GP_WAITERS: AtomicUsize = AtomicUsize::new(0);
+
+static GP_WQ: WaitQueue = WaitQueue::new();
+
+/// 宽限期结束后执行的回调
+type RcuCallback = Box<dyn FnOnce() + Send>;
+
+/// 等待执行的回调及其宽限期，按宽限期升序排列
+static CALLBACKS: SpinNoIrq<VecDeque<(usize, RcuCallback)>> = SpinNoIrq::new(VecDeque::new());
+
+/// 等待执行的回调数量，调度时据此跳过对 `CALLBACKS` 加锁
+static PENDING_CALLBACKS: AtomicUsize = AtomicUsize::new(0);
+
+/// 报告当前 CPU 经过了一个静止状态，由调度器在两次任务调度之间调用。
+pub fn rcu_quiescent_state() {
+    report_quiescent_state(axhal::cpu::this_cpu_id());
+}
+
+fn report_quiescent_state(cpu_id: usize) {
+    assert!(cpu_id < MAX_CPUS, "rcu: cpu {} is out of range", cpu_id);
+    let mask = 1u64 << cpu_id;
+    if CPU_ONLINE.load(Ordering::Relaxed) & mask == 0 {
+        CPU_ONLINE.fetch_or(mask, Ordering::SeqCst);
+    }
+    CPU_QS[cpu_id].store(GP_SEQ.load(Ordering::SeqCst), Ordering::SeqCst);
+    if GP_WAITERS.load(Ordering::Acquire) != 0 {
+        GP_WQ.notify_all();
+    }
+    if PENDING_CALLBACKS.load(Ordering::Acquire) != 0 {
+        rcu_process_callbacks();
+    }
+}
+
+/// 执行宽限期已经结束的回调，回调在锁外执行
+fn rcu_process_callbacks() {
+    let mut ready = Vec::new();
+    let mut callbacks = CALLBACKS.lock();
+    while callbacks.front().is_some_and(|(gp, _)| gp_completed(*gp)) {
+        ready.extend(callbacks.pop_front().map(|(_, f)| f));
+    }
+    PENDING_CALLBACKS.store(callbacks.len(), Ordering::Release);
+    drop(callbacks);
+    for f in ready {
+        f();
+    }
+}
+
+/// 在一个宽限期结束后执行 `f`，不等待宽限期结束。
+///
+/// 宽限期在调用时开始，`f` 由之后报告静止状态的 CPU 执行，此时所有在调用之前进入读临界区的读者都已经退出。
+pub fn call_rcu(f: impl FnOnce() + Send + 'static) {
+    let mut callbacks = CALLBACKS.lock();
+    // 在锁内开始宽限期，保证队列按宽限期升序排列
+    let gp = start_gp();
+    callbacks.push_back((gp, Box::new(f)));
+    PENDING_CALLBACKS.store(callbacks.len(), Ordering::Release);
+}
+
+/// 宽限期 `gp` 是否已经结束
+fn gp_completed(gp: usize) -> bool {
+    let online = CPU_ONLINE.load(Ordering::SeqCst);
+    (0..MAX_CPUS)
+        .filter(|cpu_id| online & (1 << cpu_id) != 0)
+        .all(|cpu_id| CPU_QS[cpu_id].load(Ordering::SeqCst) >= gp)
+}
+
+/// 开始一个新的宽限期并返回其编号，不等待其结束
+fn start_gp() -> usize {
+    GP_SEQ.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+/// 等待一个宽限期结束，此时所有在调用之前进入读临界区的读者都已经退出。
+///
+/// 在使能 `thread` feature 时会阻塞当前线程。
+pub async fn synchronize_rcu() {
+    let gp = start_gp();
+    if gp_completed(gp) {
+        return;
+    }
+    GP_WAITERS.fetch_add(1, Ordering::AcqRel);
+    GP_WQ.wait_until(|| gp_completed(gp)).await;
+    GP_WAITERS.fetch_sub(1, Ordering::AcqRel);
+    // 在此之前通过 call_rcu 注册的回调也都可以执行了
+    rcu_process_callbacks();
+}
+
+/// RCU 读临界区的 guard，存在期间关闭抢占。
+///
+/// 持有期间不能 `.await`：任务让权时 trampoline 会报告静止状态，宽限期可能在读者仍在访问数据时结束。
+/// 该类型没有实现 `Send`，只能阻止其被要求 `Send` 的 future 跨越 `.await` 持有，并不能完全避免这种情况。
+pub struct RcuReadGuard {
+    _guard: kernel_guard::NoPreempt,
+    _not_send: PhantomData<*const ()>,
+}
+
+/// 进入 RCU 读临界区
+pub fn rcu_read_lock() -> RcuReadGuard {
+    RcuReadGuard {
+        _guard: kernel_guard::NoPreempt::new(),
+        _not_send: PhantomData,
+    }
+}
+
+/// 受 RCU 保护的数据。
+///
+/// 读者通过 [`RcuCell::read`] 无锁地访问数据，写者之间需要通过外部的锁互斥，
+/// 并通过 [`RcuCell::replace`] 替换数据。
+pub struct RcuCell<T> {
+    /// 初始数据，`ptr` 为空时使用，以便支持 const 初始化
+    init: T,
+    ptr: AtomicPtr<T>,
+}
+
+unsafe impl<T: Send + Sync> Sync for RcuCell<T> {}
+unsafe impl<T: Send> Send for RcuCell<T> {}
+
+impl<T> RcuCell<T> {
+    /// Creates a new [`RcuCell`] with the given initial data.
+    pub const fn new(data: T) -> Self {
+        Self {
+            init: data,
+            ptr: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// 在读临界区中访问数据，返回的引用不能超过 `guard` 的生命周期
+    pub fn read<'a>(&'a self, _guard: &'a RcuReadGuard) -> &'a T {
+        let ptr = self.ptr.load(Ordering::Acquire);
+        if ptr.is_null() {
+            &self.init
+        } else {
+            unsafe { &*ptr }
+        }
+    }
+
+    /// 替换数据，并在宽限期结束后释放旧的数据。
+    ///
+    /// 调用者需要保证写者之间互斥。若返回的 future 在宽限期结束前被 drop，旧的数据会被泄漏。
+    pub async fn replace(&self, data: T) {
+        let new = Box::into_raw(Box::new(data));
+        let old = self.ptr.swap(new, Ordering::AcqRel);
+        synchronize_rcu().await;
+        if !old.is_null() {
+            drop(unsafe { Box::from_raw(old) });
+        }
+    }
+}
+
+impl<T> Drop for RcuCell<T> {
+    fn drop(&mut self) {
+        let ptr = *self.ptr.get_mut();
+        if !ptr.is_null() {
+            drop(unsafe { Box::from_raw(ptr) });
+        }
+    }
+}
+
+/// 等待执行的回调超过这个数量时，写者等待宽限期结束再返回，避免读者长时间不退出时旧表不断累积
+const MAX_PENDING_CALLBACKS: usize = 64;
+
+/// 受 RCU 保护的 [`BTreeMap`]，读者不需要加锁，写者复制整个表后替换。
+///
+/// 适用于任务、进程注册表这类读远多于写的数据。写者不等待宽限期结束，
+/// 替换下来的旧表通过 [`call_rcu`] 在宽限期结束之后释放。
+pub struct RcuMap<K, V> {
+    cell: RcuCell<BTreeMap<K, V>>,
+    /// 写者之间的互斥锁
+    writer: Mutex<()>,
+}
+
+/// [`RcuMap::read`] 返回的 guard，可以直接作为 [`BTreeMap`] 使用
+pub struct RcuMapReadGuard<'a, K, V> {
+    map: &'a BTreeMap<K, V>,
+    _guard: RcuReadGuard,
+}
+
+impl<K, V> RcuMap<K, V> {
+    /// Creates an empty [`RcuMap`].
+    pub const fn new() -> Self {
+        Self {
+            cell: RcuCell::new(BTreeMap::new()),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// 进入读临界区并返回整个表，guard 不能在 `.await` 时被持有
+    pub fn read(&self) -> RcuMapReadGuard<'_, K, V> {
+        let guard = rcu_read_lock();
+        let map = self.cell.read(&guard) as *const BTreeMap<K, V>;
+        RcuMapReadGuard {
+            // 在 guard 存在期间，旧的表不会被释放
+            map: unsafe { &*map },
+            _guard: guard,
+        }
+    }
+}
+
+impl<K: Ord, V> RcuMap<K, V> {
+    /// Returns `true` if the map contains a value for the specified key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.read().contains_key(key)
+    }
+}
+
+impl<K: Ord, V: Clone> RcuMap<K, V> {
+    /// Returns a clone of the value corresponding to the key.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.read().get(key).cloned()
+    }
+}
+
+impl<K: Ord + Clone + Send + 'static, V: Clone + Send + 'static> RcuMap<K, V> {
+    /// 复制整个表并通过 `f` 修改，替换后旧的表在宽限期结束之后释放。
+    ///
+    /// 通常不等待宽限期，只有等待释放的旧表过多时才等待
+    pub async fn update<R>(&self, f: impl FnOnce(&mut BTreeMap<K, V>) -> R) -> R {
+        let writer = self.writer.lock().await;
+        let mut map = self.read().clone();
+        let res = f(&mut map);
+        let new = Box::into_raw(Box::new(map));
+        let old = self.cell.ptr.swap(new, Ordering::AcqRel);
+        if !old.is_null() {
+            let old = unsafe { Box::from_raw(old) };
+            call_rcu(move || drop(old));
+        }
+        // 替换完成后即可允许其他写者修改
+        drop(writer);
+        if PENDING_CALLBACKS.load(Ordering::Acquire) > MAX_PENDING_CALLBACKS {
+            synchronize_rcu().await;
+        }
+        res
+    }
+
+    /// Inserts a key-value pair into the map, returning the old value.
+    pub async fn insert(&self, key: K, value: V) -> Option<V> {
+        self.update(|map| map.insert(key, value)).await
+    }
+
+    /// Removes a key from the map, returning the value at the key.
+    pub async fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.update(|map| map.remove(key)).await
+    }
+}
+
+impl<K, V> Default for RcuMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for RcuMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.read().iter()).finish()
+    }
+}
+
+impl<'a, K, V> Deref for RcuMapReadGuard<'a, K, V> {
+    type Target = BTreeMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        self.map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{call_rcu, report_quiescent_state, synchronize_rcu, RcuMap};
+    use crate::test_utils::TestTask;
+    use alloc::sync::Arc;
+    use core::pin::pin;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use core::task::Poll;
+    use std::sync::Mutex;
+
+    /// 宽限期的状态是全局的，测试之间需要串行执行
+    static GP_LOCK: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn test_call_rcu_after_grace_period() {
+        let _lock = GP_LOCK.lock().unwrap();
+        report_quiescent_state(0);
+        report_quiescent_state(1);
+        let called = Arc::new(AtomicBool::new(false));
+        let flag = called.clone();
+        call_rcu(move || flag.store(true, Ordering::SeqCst));
+        // 只有 CPU 0 经过了静止状态，宽限期还没有结束
+        report_quiescent_state(0);
+        assert!(!called.load(Ordering::SeqCst));
+        report_quiescent_state(1);
+        assert!(called.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_synchronize_rcu_waits_for_grace_period() {
+        let _lock = GP_LOCK.lock().unwrap();
+        report_quiescent_state(0);
+        report_quiescent_state(1);
+        let called = Arc::new(AtomicBool::new(false));
+        let flag = called.clone();
+        call_rcu(move || flag.store(true, Ordering::SeqCst));
+        let task = TestTask::new();
+        let mut sync = pin!(synchronize_rcu());
+        assert!(task.poll(sync.as_mut()).is_pending());
+        report_quiescent_state(0);
+        report_quiescent_state(1);
+        assert!(called.load(Ordering::SeqCst));
+        assert!(task.woken());
+        assert!(task.poll(sync).is_ready());
+    }
+
+    #[test]
+    fn test_rcu_map_reclaim() {
+        let _lock = GP_LOCK.lock().unwrap();
+        report_quiescent_state(0);
+        report_quiescent_state(1);
+        let task = TestTask::new();
+        let map = RcuMap::new();
+        let value = Arc::new(());
+        assert_eq!(
+            Poll::Ready(None),
+            task.poll_once(map.insert(1, value.clone()))
+        );
+        // 替换之前进入读临界区的读者仍然看到旧表
+        let reader = map.read();
+        assert_eq!(
+            Poll::Ready(Some(value.clone())),
+            task.poll_once(map.remove(&1))
+        );
+        assert!(reader.contains_key(&1));
+        assert!(!map.contains_key(&1));
+        // 旧表在宽限期结束之前没有被释放
+        report_quiescent_state(0);
+        assert_eq!(2, Arc::strong_count(&value));
+        drop(reader);
+        report_quiescent_state(1);
+        assert_eq!(1, Arc::strong_count(&value));
+    }
+}
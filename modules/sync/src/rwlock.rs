@@ -0,0 +1,765 @@
+//! 可以在 async 函数以及线程环境中使用的读写锁实现。
+//!
+//! 接口的统一方式与 [`Mutex`](crate::Mutex) 相同：使能 `thread` feature 时，
+//! `read`、`write`、`upgradeable_read` 会以线程的形式阻塞，直接获取到内部的数据；
+//! 没有使能 `thread` feature 时，这些函数只返回不含数据的 guard，需要使用 `.await` 才能获取锁。
+//!
+//! 该读写锁是写者优先的：只要存在等待的写者，新的读者就不能获取锁，避免写者饿死。
+//! 可升级的读锁（upgradeable read）与普通读者共存，但同一时刻只能存在一个，
+//! 持有它时新的读者同样无法获取锁，从而保证升级为写锁的过程不会被饿死。
+
+use crate::WaitQueue;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+const WRITER: usize = 1;
+const UPGRADED: usize = 1 << 1;
+const READER: usize = 1 << 2;
+
+/// A reader-writer lock, similar to
+/// [`std::sync::RwLock`](https://doc.rust-lang.org/std/sync/struct.RwLock.html).
+///
+/// This lock allows a number of readers or at most one writer at any point in
+/// time. It also supports one upgradeable reader, which can be upgraded to a
+/// writer without releasing the lock.
+///
+/// The lock is writer-preferring: when a writer is waiting, new readers will
+/// be put into the wait queue until the writer releases the lock.
+pub struct RwLock<T: ?Sized> {
+    wq: WaitQueue,
+    /// `WRITER`、`UPGRADED` 标志以及读者数量
+    state: AtomicUsize,
+    /// 正在等待的写者数量
+    writer_waiting: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+// Same unsafe impls as `std::sync::RwLock`
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+/// A guard that provides immutable data access.
+///
+/// When the guard falls out of scope it will decrement the read count,
+/// potentially releasing the lock.
+pub struct RwLockReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+    data: Option<*const T>,
+}
+
+/// A guard that provides mutable data access.
+///
+/// When the guard falls out of scope it will release the lock.
+pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+    data: Option<*mut T>,
+    /// 由 [`RwLockUpgradableGuard`] 升级而来，此时已经持有 `UPGRADED` 标志
+    upgrading: bool,
+    /// 是否已经计入了等待的写者数量
+    waiting: bool,
+}
+
+/// A guard that provides immutable data access but can be upgraded to
+/// [`RwLockWriteGuard`].
+///
+/// When the guard falls out of scope it will release the lock.
+pub struct RwLockUpgradableGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+    data: Option<*const T>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Send for RwLockReadGuard<'a, T> {}
+unsafe impl<'a, T: ?Sized + Send + Sync + 'a> Send for RwLockWriteGuard<'a, T> {}
+unsafe impl<'a, T: ?Sized + Sync + 'a> Send for RwLockUpgradableGuard<'a, T> {}
+
+impl<T> RwLock<T> {
+    /// Creates a new [`RwLock`] wrapping the supplied data.
+    #[inline(always)]
+    pub const fn new(data: T) -> Self {
+        Self {
+            wq: WaitQueue::new(),
+            state: AtomicUsize::new(0),
+            writer_waiting: AtomicUsize::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Consumes this [`RwLock`] and unwraps the underlying data.
+    #[inline(always)]
+    pub fn into_inner(self) -> T {
+        // We know statically that there are no outstanding references to
+        // `self` so there's no need to lock.
+        let RwLock { data, .. } = self;
+        data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Returns the number of readers that currently hold the lock.
+    ///
+    /// # Safety
+    ///
+    /// This function provides no synchronization guarantees and so its result should be considered 'out of date'
+    /// the instant it is called. Do not use it for synchronization purposes. However, it may be useful as a heuristic.
+    #[inline(always)]
+    pub fn reader_count(&self) -> usize {
+        self.state.load(Ordering::Relaxed) / READER
+    }
+
+    /// Returns `true` if a writer currently holds the lock.
+    ///
+    /// # Safety
+    ///
+    /// This function provides no synchronization guarantees and so its result should be considered 'out of date'
+    /// the instant it is called. Do not use it for synchronization purposes. However, it may be useful as a heuristic.
+    #[inline(always)]
+    pub fn is_write_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER != 0
+    }
+
+    /// 读者能否获取锁：没有写者、没有可升级的读者，并且没有等待的写者
+    fn can_read(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & (WRITER | UPGRADED) == 0
+            && self.writer_waiting.load(Ordering::Relaxed) == 0
+    }
+
+    /// 写者能否获取锁。若是由可升级的读锁升级而来，则只需要等待其他的读者退出
+    fn can_write(&self, upgrading: bool) -> bool {
+        let expected = if upgrading { UPGRADED } else { 0 };
+        self.state.load(Ordering::Relaxed) == expected
+    }
+
+    fn try_acquire_reader(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & (WRITER | UPGRADED) != 0 || self.writer_waiting.load(Ordering::Relaxed) != 0
+            {
+                return false;
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state + READER,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(new_state) => state = new_state,
+            }
+        }
+    }
+
+    fn try_acquire_upgradeable(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & (WRITER | UPGRADED) != 0 || self.writer_waiting.load(Ordering::Relaxed) != 0
+            {
+                return false;
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state | UPGRADED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(new_state) => state = new_state,
+            }
+        }
+    }
+
+    fn try_acquire_writer(&self, upgrading: bool) -> bool {
+        // The reason for using a strong compare_exchange is explained here:
+        // https://github.com/Amanieu/parking_lot/pull/207#issuecomment-575869107
+        let expected = if upgrading { UPGRADED } else { 0 };
+        self.state
+            .compare_exchange(expected, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// 释放读锁，当最后一个读者退出时，唤醒等待的写者
+    fn release_reader(&self) {
+        let prev = self.state.fetch_sub(READER, Ordering::Release);
+        if prev / READER == 1 {
+            self.wq.notify_all();
+        }
+    }
+
+    fn release_upgradeable(&self) {
+        self.state.fetch_and(!UPGRADED, Ordering::Release);
+        self.wq.notify_all();
+    }
+
+    fn release_writer(&self) {
+        self.state
+            .fetch_and(!(WRITER | UPGRADED), Ordering::Release);
+        self.wq.notify_all();
+    }
+
+    /// Locks this [`RwLock`] with shared read access.
+    ///
+    /// The returned value may be dereferenced for data access
+    /// and the lock will be dropped when the guard falls out of scope.
+    pub fn read(&self) -> RwLockReadGuard<T> {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "thread")] {
+                while !self.try_acquire_reader() {
+                    self.wq.wait_until(|| self.can_read());
+                }
+                return RwLockReadGuard {
+                    lock: self,
+                    data: Some(self.data.get()),
+                };
+            } else if #[cfg(not(feature = "thread"))] {
+                return RwLockReadGuard {
+                    lock: self,
+                    data: None,
+                }
+            }
+        }
+    }
+
+    /// Locks this [`RwLock`] with exclusive write access.
+    ///
+    /// The returned value may be dereferenced for data access
+    /// and the lock will be dropped when the guard falls out of scope.
+    pub fn write(&self) -> RwLockWriteGuard<T> {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "thread")] {
+                self.writer_waiting.fetch_add(1, Ordering::Relaxed);
+                while !self.try_acquire_writer(false) {
+                    self.wq.wait_until(|| self.can_write(false));
+                }
+                self.writer_waiting.fetch_sub(1, Ordering::Relaxed);
+                return RwLockWriteGuard {
+                    lock: self,
+                    data: Some(self.data.get()),
+                    upgrading: false,
+                    waiting: false,
+                };
+            } else if #[cfg(not(feature = "thread"))] {
+                return RwLockWriteGuard {
+                    lock: self,
+                    data: None,
+                    upgrading: false,
+                    waiting: false,
+                }
+            }
+        }
+    }
+
+    /// Obtain a readable lock guard that can later be upgraded to a writable
+    /// lock guard.
+    ///
+    /// Only one upgradeable guard can exist at the same time, and new readers
+    /// will not get the lock while it is held.
+    pub fn upgradeable_read(&self) -> RwLockUpgradableGuard<T> {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "thread")] {
+                while !self.try_acquire_upgradeable() {
+                    self.wq.wait_until(|| self.can_read());
+                }
+                return RwLockUpgradableGuard {
+                    lock: self,
+                    data: Some(self.data.get()),
+                };
+            } else if #[cfg(not(feature = "thread"))] {
+                return RwLockUpgradableGuard {
+                    lock: self,
+                    data: None,
+                }
+            }
+        }
+    }
+
+    /// Attempt to acquire this lock with shared read access.
+    #[inline(always)]
+    pub fn try_read(&self) -> Option<RwLockReadGuard<T>> {
+        if self.try_acquire_reader() {
+            Some(RwLockReadGuard {
+                lock: self,
+                data: Some(self.data.get()),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Attempt to lock this [`RwLock`] with exclusive write access.
+    #[inline(always)]
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<T>> {
+        if self.try_acquire_writer(false) {
+            Some(RwLockWriteGuard {
+                lock: self,
+                data: Some(self.data.get()),
+                upgrading: false,
+                waiting: false,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Tries to obtain an upgradeable lock guard.
+    #[inline(always)]
+    pub fn try_upgradeable_read(&self) -> Option<RwLockUpgradableGuard<T>> {
+        if self.try_acquire_upgradeable() {
+            Some(RwLockUpgradableGuard {
+                lock: self,
+                data: Some(self.data.get()),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the [`RwLock`] mutably, and a mutable reference is guaranteed to be exclusive in
+    /// Rust, no actual locking needs to take place -- the mutable borrow statically guarantees no locks exist. As
+    /// such, this is a 'zero-cost' operation.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut T {
+        // We know statically that there are no other references to `self`, so
+        // there's no need to lock the inner lock.
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<T: ?Sized + Default> Default for RwLock<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_read() {
+            Some(guard) => write!(f, "RwLock {{ data: ")
+                .and_then(|()| (*guard).fmt(f))
+                .and_then(|()| write!(f, "}}")),
+            None => write!(f, "RwLock {{ <locked> }}"),
+        }
+    }
+}
+
+impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+    /// Downgrades the writable lock guard to a readable, shared lock guard.
+    pub fn downgrade(mut self) -> RwLockReadGuard<'a, T> {
+        assert!(
+            self.data.take().is_some(),
+            "data is none, you should use .await to get the data"
+        );
+        let lock = self.lock;
+        // 先加入读者再释放写者，保证中间不会有其他写者插入
+        lock.state.fetch_add(READER, Ordering::Acquire);
+        lock.release_writer();
+        RwLockReadGuard {
+            lock,
+            data: Some(lock.data.get()),
+        }
+    }
+
+    /// Downgrades the writable lock guard to an upgradable, shared lock guard.
+    pub fn downgrade_to_upgradeable(mut self) -> RwLockUpgradableGuard<'a, T> {
+        assert!(
+            self.data.take().is_some(),
+            "data is none, you should use .await to get the data"
+        );
+        let lock = self.lock;
+        lock.state.store(UPGRADED, Ordering::Release);
+        lock.wq.notify_all();
+        RwLockUpgradableGuard {
+            lock,
+            data: Some(lock.data.get()),
+        }
+    }
+}
+
+impl<'a, T: ?Sized> RwLockUpgradableGuard<'a, T> {
+    /// Upgrades an upgradeable lock guard to a writable lock guard.
+    ///
+    /// 与 `lock` 相同，使能 `thread` feature 时会阻塞直到其他读者全部退出；
+    /// 否则需要对返回的 guard 使用 `.await` 才能获取到数据。
+    pub fn upgrade(mut self) -> RwLockWriteGuard<'a, T> {
+        assert!(
+            self.data.take().is_some(),
+            "data is none, you should use .await to get the data"
+        );
+        let lock = self.lock;
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "thread")] {
+                while !lock.try_acquire_writer(true) {
+                    lock.wq.wait_until(|| lock.can_write(true));
+                }
+                return RwLockWriteGuard {
+                    lock,
+                    data: Some(lock.data.get()),
+                    upgrading: false,
+                    waiting: false,
+                };
+            } else if #[cfg(not(feature = "thread"))] {
+                return RwLockWriteGuard {
+                    lock,
+                    data: None,
+                    upgrading: true,
+                    waiting: false,
+                }
+            }
+        }
+    }
+
+    /// Tries to upgrade an upgradeable lock guard to a writable lock guard.
+    pub fn try_upgrade(mut self) -> Result<RwLockWriteGuard<'a, T>, Self> {
+        assert!(
+            self.data.is_some(),
+            "data is none, you should use .await to get the data"
+        );
+        let lock = self.lock;
+        if lock.try_acquire_writer(true) {
+            self.data.take();
+            Ok(RwLockWriteGuard {
+                lock,
+                data: Some(lock.data.get()),
+                upgrading: false,
+                waiting: false,
+            })
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Downgrades the upgradeable lock guard to a readable, shared lock guard.
+    pub fn downgrade(mut self) -> RwLockReadGuard<'a, T> {
+        assert!(
+            self.data.take().is_some(),
+            "data is none, you should use .await to get the data"
+        );
+        let lock = self.lock;
+        lock.state.fetch_add(READER, Ordering::Acquire);
+        lock.release_upgradeable();
+        RwLockReadGuard {
+            lock,
+            data: Some(lock.data.get()),
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        unsafe {
+            match self.data {
+                Some(data) => &*data,
+                None => panic!("data is none, you should use .await to get the data"),
+            }
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockUpgradableGuard<'a, T> {
+    type Target = T;
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        unsafe {
+            match self.data {
+                Some(data) => &*data,
+                None => panic!("data is none, you should use .await to get the data"),
+            }
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        // We know statically that only we are referencing data
+        unsafe {
+            match self.data {
+                Some(data) => &*data,
+                None => panic!("data is none, you should use .await to get the data"),
+            }
+        }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        // We know statically that only we are referencing data
+        unsafe {
+            match self.data {
+                Some(data) => &mut *data,
+                None => panic!("data is none, you should use .await to get the data"),
+            }
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for RwLockUpgradableGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
+    /// The dropping of the [`RwLockReadGuard`] will decrement the read count.
+    fn drop(&mut self) {
+        if self.data.is_some() {
+            self.lock.release_reader();
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockUpgradableGuard<'a, T> {
+    /// The dropping of the [`RwLockUpgradableGuard`] will release the lock it was created from.
+    fn drop(&mut self) {
+        if self.data.is_some() {
+            self.lock.release_upgradeable();
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
+    /// The dropping of the [`RwLockWriteGuard`] will release the lock it was created from.
+    ///
+    /// 若还没有获取到锁就被 drop，需要撤销等待写者的计数，
+    /// 对于升级得到的 guard 还需要释放原本持有的 `UPGRADED` 标志
+    fn drop(&mut self) {
+        if self.data.is_some() {
+            self.lock.release_writer();
+            return;
+        }
+        if self.waiting {
+            self.lock.writer_waiting.fetch_sub(1, Ordering::Relaxed);
+            self.lock.wq.notify_all();
+        }
+        if self.upgrading {
+            self.lock.release_upgradeable();
+        }
+    }
+}
+
+/// 这里要实现所有权的转移，否则会导致连续两次 drop，重复释放锁
+impl<'a, T: ?Sized + 'a> Future for RwLockReadGuard<'a, T> {
+    type Output = Self;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { lock, data } = self.get_mut();
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "thread")] {
+                assert!(data.is_some());
+                Poll::Ready(RwLockReadGuard {
+                    lock,
+                    data: data.take(),
+                })
+            } else if #[cfg(not(feature = "thread"))] {
+                assert!(data.is_none());
+                loop {
+                    if lock.try_acquire_reader() {
+                        return Poll::Ready(RwLockReadGuard {
+                            lock,
+                            data: Some(lock.data.get()),
+                        });
+                    }
+                    // 当前任务让权，并将 cx 注册到等待队列上
+                    core::task::ready!(Pin::new(&mut lock.wq.wait_until(|| lock.can_read())).poll(_cx));
+                }
+            }
+        }
+    }
+}
+
+/// 这里要实现所有权的转移，否则会导致连续两次 drop，重复释放锁
+impl<'a, T: ?Sized + 'a> Future for RwLockUpgradableGuard<'a, T> {
+    type Output = Self;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { lock, data } = self.get_mut();
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "thread")] {
+                assert!(data.is_some());
+                Poll::Ready(RwLockUpgradableGuard {
+                    lock,
+                    data: data.take(),
+                })
+            } else if #[cfg(not(feature = "thread"))] {
+                assert!(data.is_none());
+                loop {
+                    if lock.try_acquire_upgradeable() {
+                        return Poll::Ready(RwLockUpgradableGuard {
+                            lock,
+                            data: Some(lock.data.get()),
+                        });
+                    }
+                    core::task::ready!(Pin::new(&mut lock.wq.wait_until(|| lock.can_read())).poll(_cx));
+                }
+            }
+        }
+    }
+}
+
+/// 这里要实现所有权的转移，否则会导致连续两次 drop，重复释放锁
+///
+/// 在协程模式下，第一次获取失败时会将自己计入等待的写者，从而阻止新的读者进入
+impl<'a, T: ?Sized + 'a> Future for RwLockWriteGuard<'a, T> {
+    type Output = Self;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self {
+            lock,
+            data,
+            upgrading,
+            waiting,
+        } = self.get_mut();
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "thread")] {
+                assert!(data.is_some());
+                let _ = (upgrading, waiting);
+                Poll::Ready(RwLockWriteGuard {
+                    lock,
+                    data: data.take(),
+                    upgrading: false,
+                    waiting: false,
+                })
+            } else if #[cfg(not(feature = "thread"))] {
+                assert!(data.is_none());
+                loop {
+                    if lock.try_acquire_writer(*upgrading) {
+                        if *waiting {
+                            lock.writer_waiting.fetch_sub(1, Ordering::Relaxed);
+                        }
+                        // UPGRADED 标志已经被 WRITER 替换，旧的 guard 不能再释放它
+                        *upgrading = false;
+                        *waiting = false;
+                        return Poll::Ready(RwLockWriteGuard {
+                            lock,
+                            data: Some(lock.data.get()),
+                            upgrading: false,
+                            waiting: false,
+                        });
+                    }
+                    if !*upgrading && !*waiting {
+                        lock.writer_waiting.fetch_add(1, Ordering::Relaxed);
+                        *waiting = true;
+                    }
+                    let upgrading = *upgrading;
+                    core::task::ready!(Pin::new(&mut lock.wq.wait_until(|| lock.can_write(upgrading))).poll(_cx));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RwLock;
+    use crate::test_utils::TestTask;
+    use core::pin::pin;
+    use core::task::Poll;
+
+    #[test]
+    fn test_try_lock() {
+        let lock = RwLock::new(0);
+        let r1 = lock.try_read().unwrap();
+        let r2 = lock.try_read().unwrap();
+        assert_eq!(2, lock.reader_count());
+        assert!(lock.try_write().is_none());
+        drop((r1, r2));
+        let mut guard = lock.try_write().unwrap();
+        *guard = 1;
+        assert!(lock.is_write_locked());
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_upgradeable_read().is_none());
+        drop(guard);
+        assert_eq!(1, *lock.try_read().unwrap());
+    }
+
+    #[test]
+    fn test_writer_preferred() {
+        let lock = RwLock::new(0);
+        let reader = lock.try_read().unwrap();
+        let (writer_task, reader_task) = (TestTask::new(), TestTask::new());
+        let mut write = pin!(lock.write());
+        assert!(writer_task.poll(write.as_mut()).is_pending());
+        // 有写者在等待时，新的读者不能获取锁
+        let mut read = pin!(lock.read());
+        assert!(reader_task.poll(read.as_mut()).is_pending());
+        assert!(lock.try_read().is_none());
+
+        drop(reader);
+        assert!(writer_task.woken());
+        let Poll::Ready(mut guard) = writer_task.poll(write) else {
+            panic!("the writer should get the lock after the last reader left");
+        };
+        *guard = 1;
+        // 读者同样被唤醒，但需要继续等待写者释放锁
+        assert!(reader_task.woken());
+        assert!(reader_task.poll(read.as_mut()).is_pending());
+        drop(guard);
+        assert!(reader_task.woken());
+        match reader_task.poll(read) {
+            Poll::Ready(guard) => assert_eq!(1, *guard),
+            Poll::Pending => panic!("the reader should get the lock after the writer left"),
+        }
+    }
+
+    #[test]
+    fn test_cancelled_writer() {
+        let lock = RwLock::new(());
+        let _reader = lock.try_read().unwrap();
+        let task = TestTask::new();
+        {
+            let write = pin!(lock.write());
+            assert!(task.poll(write).is_pending());
+            assert!(lock.try_read().is_none());
+        }
+        // 放弃等待的写者不再阻止新的读者
+        assert!(task.woken());
+        assert!(lock.try_read().is_some());
+    }
+
+    #[test]
+    fn test_upgrade_waits_for_readers() {
+        let lock = RwLock::new(0);
+        let upgradeable = lock.try_upgradeable_read().unwrap();
+        let reader = lock.try_read().unwrap();
+        // 持有可升级的读锁时，新的读者与其他可升级的读者都不能获取锁
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_upgradeable_read().is_none());
+
+        let task = TestTask::new();
+        let mut write = pin!(upgradeable.upgrade());
+        assert!(task.poll(write.as_mut()).is_pending());
+        drop(reader);
+        assert!(task.woken());
+        let Poll::Ready(mut guard) = task.poll(write) else {
+            panic!("the upgrade should finish after the other reader left");
+        };
+        *guard = 1;
+        let reader = guard.downgrade();
+        assert_eq!(1, *reader);
+        assert!(lock.try_upgradeable_read().is_some());
+    }
+}
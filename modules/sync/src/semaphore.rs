@@ -0,0 +1,352 @@
+//! 基于 [`WaitQueue`] 实现的计数信号量。
+//!
+//! 与 [`Mutex`](crate::Mutex) 一样，`acquire` 在使能 `thread` feature 时以线程的形式阻塞，
+//! 直接返回已经获取到许可的 [`SemaphorePermit`]；否则需要使用 `.await` 才能获取到许可。
+
+use crate::WaitQueue;
+use alloc::sync::Arc;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A counting semaphore.
+///
+/// A semaphore maintains a set of permits. Permits are used to synchronize
+/// access to a shared resource. When there are not enough permits, the
+/// current task will be put into the wait queue until other tasks release
+/// their permits.
+pub struct Semaphore {
+    wq: WaitQueue,
+    permits: AtomicUsize,
+}
+
+/// A permit from the semaphore.
+///
+/// The permits will be returned to the semaphore when the permit is dropped,
+/// unless [`SemaphorePermit::forget`] is called.
+pub struct SemaphorePermit<'a> {
+    sem: &'a Semaphore,
+    permits: usize,
+    /// 是否已经获取到了许可，只有获取到许可的 permit 在 drop 时才会归还
+    acquired: bool,
+}
+
+/// An owned permit from the semaphore.
+///
+/// It is the same as [`SemaphorePermit`] but holds an [`Arc`] to the
+/// semaphore, so it can be moved to other tasks freely.
+pub struct OwnedSemaphorePermit {
+    sem: Arc<Semaphore>,
+    permits: usize,
+    acquired: bool,
+}
+
+impl Semaphore {
+    /// Creates a new semaphore with the given number of permits.
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            wq: WaitQueue::new(),
+            permits: AtomicUsize::new(permits),
+        }
+    }
+
+    /// Returns the current number of available permits.
+    pub fn available_permits(&self) -> usize {
+        self.permits.load(Ordering::Acquire)
+    }
+
+    /// Adds `n` new permits to the semaphore and wakes up the waiting tasks.
+    pub fn add_permits(&self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        self.permits.fetch_add(n, Ordering::Release);
+        // 等待的任务需要的许可数量可能不同，因此唤醒所有的任务重新检查
+        self.wq.notify_all();
+    }
+
+    fn try_acquire_inner(&self, n: usize) -> bool {
+        let mut permits = self.permits.load(Ordering::Relaxed);
+        loop {
+            if permits < n {
+                return false;
+            }
+            match self.permits.compare_exchange_weak(
+                permits,
+                permits - n,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(new_permits) => permits = new_permits,
+            }
+        }
+    }
+
+    #[cfg(feature = "thread")]
+    fn acquire_blocking(&self, n: usize) {
+        while !self.try_acquire_inner(n) {
+            self.wq.wait_until(|| self.available_permits() >= n);
+        }
+    }
+
+    /// 在协程中等待获取 `n` 个许可，获取成功时返回 `Poll::Ready`
+    #[cfg(not(feature = "thread"))]
+    fn poll_acquire(&self, n: usize, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            if self.try_acquire_inner(n) {
+                return Poll::Ready(());
+            }
+            // 当前任务让权，并将 cx 注册到等待队列上
+            core::task::ready!(
+                Pin::new(&mut self.wq.wait_until(|| self.available_permits() >= n)).poll(cx)
+            );
+        }
+    }
+
+    /// Acquires a permit from the semaphore.
+    pub fn acquire(&self) -> SemaphorePermit<'_> {
+        self.acquire_many(1)
+    }
+
+    /// Acquires `n` permits from the semaphore.
+    pub fn acquire_many(&self, n: usize) -> SemaphorePermit<'_> {
+        #[cfg(feature = "thread")]
+        self.acquire_blocking(n);
+        SemaphorePermit {
+            sem: self,
+            permits: n,
+            acquired: cfg!(feature = "thread"),
+        }
+    }
+
+    /// Tries to acquire a permit from the semaphore without waiting.
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        self.try_acquire_many(1)
+    }
+
+    /// Tries to acquire `n` permits from the semaphore without waiting.
+    pub fn try_acquire_many(&self, n: usize) -> Option<SemaphorePermit<'_>> {
+        if self.try_acquire_inner(n) {
+            Some(SemaphorePermit {
+                sem: self,
+                permits: n,
+                acquired: true,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Acquires a permit from the semaphore, returning an owned permit.
+    pub fn acquire_owned(self: Arc<Self>) -> OwnedSemaphorePermit {
+        self.acquire_many_owned(1)
+    }
+
+    /// Acquires `n` permits from the semaphore, returning an owned permit.
+    pub fn acquire_many_owned(self: Arc<Self>, n: usize) -> OwnedSemaphorePermit {
+        #[cfg(feature = "thread")]
+        self.acquire_blocking(n);
+        OwnedSemaphorePermit {
+            sem: self,
+            permits: n,
+            acquired: cfg!(feature = "thread"),
+        }
+    }
+
+    /// Tries to acquire a permit from the semaphore without waiting, returning
+    /// an owned permit.
+    pub fn try_acquire_owned(self: Arc<Self>) -> Option<OwnedSemaphorePermit> {
+        if self.try_acquire_inner(1) {
+            Some(OwnedSemaphorePermit {
+                sem: self,
+                permits: 1,
+                acquired: true,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Semaphore")
+            .field("permits", &self.available_permits())
+            .finish()
+    }
+}
+
+impl<'a> SemaphorePermit<'a> {
+    /// Forgets the permit **without** releasing it back to the semaphore.
+    pub fn forget(mut self) {
+        assert!(self.acquired, "you should use .await to get the permit");
+        self.acquired = false;
+    }
+
+    /// Returns the number of permits held by `self`.
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+}
+
+impl OwnedSemaphorePermit {
+    /// Forgets the permit **without** releasing it back to the semaphore.
+    pub fn forget(mut self) {
+        assert!(self.acquired, "you should use .await to get the permit");
+        self.acquired = false;
+    }
+
+    /// Returns the number of permits held by `self`.
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Returns the [`Semaphore`] from which this permit was acquired.
+    pub fn semaphore(&self) -> &Arc<Semaphore> {
+        &self.sem
+    }
+}
+
+impl<'a> Drop for SemaphorePermit<'a> {
+    fn drop(&mut self) {
+        if self.acquired {
+            self.sem.add_permits(self.permits);
+        }
+    }
+}
+
+impl Drop for OwnedSemaphorePermit {
+    fn drop(&mut self) {
+        if self.acquired {
+            self.sem.add_permits(self.permits);
+        }
+    }
+}
+
+impl<'a> fmt::Debug for SemaphorePermit<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SemaphorePermit")
+            .field("permits", &self.permits)
+            .field("acquired", &self.acquired)
+            .finish()
+    }
+}
+
+impl fmt::Debug for OwnedSemaphorePermit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OwnedSemaphorePermit")
+            .field("permits", &self.permits)
+            .field("acquired", &self.acquired)
+            .finish()
+    }
+}
+
+/// 这里要实现所有权的转移，否则会导致连续两次 drop，重复归还许可
+impl<'a> Future for SemaphorePermit<'a> {
+    type Output = Self;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "thread")] {
+                assert!(this.acquired);
+            } else if #[cfg(not(feature = "thread"))] {
+                assert!(!this.acquired);
+                core::task::ready!(this.sem.poll_acquire(this.permits, _cx));
+            }
+        }
+        this.acquired = false;
+        Poll::Ready(SemaphorePermit {
+            sem: this.sem,
+            permits: this.permits,
+            acquired: true,
+        })
+    }
+}
+
+/// 这里要实现所有权的转移，否则会导致连续两次 drop，重复归还许可
+impl Future for OwnedSemaphorePermit {
+    type Output = Self;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "thread")] {
+                assert!(this.acquired);
+            } else if #[cfg(not(feature = "thread"))] {
+                assert!(!this.acquired);
+                core::task::ready!(this.sem.poll_acquire(this.permits, _cx));
+            }
+        }
+        this.acquired = false;
+        Poll::Ready(OwnedSemaphorePermit {
+            sem: this.sem.clone(),
+            permits: this.permits,
+            acquired: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Semaphore;
+    use crate::test_utils::TestTask;
+    use alloc::sync::Arc;
+    use core::pin::pin;
+    use core::task::Poll;
+
+    #[test]
+    fn test_try_acquire() {
+        let sem = Semaphore::new(2);
+        let permit = sem.try_acquire_many(2).unwrap();
+        assert_eq!(0, sem.available_permits());
+        assert!(sem.try_acquire().is_none());
+        drop(permit);
+        assert_eq!(2, sem.available_permits());
+        // 被遗忘的许可不会归还
+        sem.try_acquire().unwrap().forget();
+        assert_eq!(1, sem.available_permits());
+
+        let sem = Arc::new(Semaphore::new(1));
+        let permit = sem.clone().try_acquire_owned().unwrap();
+        assert!(sem.clone().try_acquire_owned().is_none());
+        drop(permit);
+        assert_eq!(1, sem.available_permits());
+    }
+
+    #[test]
+    fn test_wakeup_order() {
+        let sem = Semaphore::new(0);
+        let (many_task, one_task) = (TestTask::new(), TestTask::new());
+        let mut many = pin!(sem.acquire_many(2));
+        let mut one = pin!(sem.acquire());
+        assert!(many_task.poll(many.as_mut()).is_pending());
+        assert!(one_task.poll(one.as_mut()).is_pending());
+
+        // 所有等待的任务都会被唤醒，许可不足的任务重新进入等待
+        sem.add_permits(1);
+        assert!(many_task.woken());
+        assert!(one_task.woken());
+        assert!(many_task.poll(many.as_mut()).is_pending());
+        let Poll::Ready(permit) = one_task.poll(one) else {
+            panic!("one permit is available");
+        };
+        assert_eq!(0, sem.available_permits());
+
+        sem.add_permits(1);
+        assert!(many_task.woken());
+        assert!(many_task.poll(many.as_mut()).is_pending());
+        drop(permit);
+        assert!(many_task.woken());
+        match many_task.poll(many) {
+            Poll::Ready(permit) => assert_eq!(2, permit.num_permits()),
+            Poll::Pending => panic!("two permits are available"),
+        }
+        assert_eq!(0, sem.available_permits());
+    }
+}
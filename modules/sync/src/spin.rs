@@ -0,0 +1,129 @@
+//! 可以被 lockdep 检查的 [`SpinNoIrq`]。
+//!
+//! 未开启 `lockdep` feature 时，直接使用 `spinlock` 中的实现；
+//! 开启后，在加锁与解锁时记录锁的获取顺序。
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "lockdep")] {
+        use crate::lockdep::{lock_acquire, lock_release, LockClass, LockKind};
+        use core::fmt;
+        use core::ops::{Deref, DerefMut};
+
+        /// A spin lock that disables kernel preemption and local IRQs while
+        /// trying to lock, and re-enables it after unlocking.
+        ///
+        /// 与 `spinlock::SpinNoIrq` 的接口一致，额外记录了锁的类别。
+        pub struct SpinNoIrq<T: ?Sized> {
+            class: LockClass,
+            inner: spinlock::SpinNoIrq<T>,
+        }
+
+        /// A guard that provides mutable data access for [`SpinNoIrq`].
+        pub struct SpinNoIrqGuard<'a, T: ?Sized + 'a> {
+            inner: spinlock::SpinNoIrqGuard<'a, T>,
+            addr: usize,
+        }
+
+        impl<T> SpinNoIrq<T> {
+            /// Creates a new [`SpinNoIrq`] wrapping the supplied data.
+            #[inline(always)]
+            #[track_caller]
+            pub const fn new(data: T) -> Self {
+                Self {
+                    class: LockClass::caller(),
+                    inner: spinlock::SpinNoIrq::new(data),
+                }
+            }
+
+            /// Consumes this [`SpinNoIrq`] and unwraps the underlying data.
+            #[inline(always)]
+            pub fn into_inner(self) -> T {
+                self.inner.into_inner()
+            }
+        }
+
+        impl<T: ?Sized> SpinNoIrq<T> {
+            fn addr(&self) -> usize {
+                self as *const Self as *const () as usize
+            }
+
+            /// Locks the [`SpinNoIrq`] and returns a guard that permits access to the inner data.
+            #[inline(always)]
+            pub fn lock(&self) -> SpinNoIrqGuard<T> {
+                let inner = self.inner.lock();
+                lock_acquire(self.class, LockKind::SpinNoIrq, self.addr());
+                SpinNoIrqGuard {
+                    inner,
+                    addr: self.addr(),
+                }
+            }
+
+            /// Try to lock this [`SpinNoIrq`], returning a lock guard if successful.
+            #[inline(always)]
+            pub fn try_lock(&self) -> Option<SpinNoIrqGuard<T>> {
+                let inner = self.inner.try_lock()?;
+                lock_acquire(self.class, LockKind::SpinNoIrq, self.addr());
+                Some(SpinNoIrqGuard {
+                    inner,
+                    addr: self.addr(),
+                })
+            }
+
+            /// Returns `true` if the lock is currently held.
+            #[inline(always)]
+            pub fn is_locked(&self) -> bool {
+                self.inner.is_locked()
+            }
+
+            /// Returns a mutable reference to the underlying data.
+            #[inline(always)]
+            pub fn get_mut(&mut self) -> &mut T {
+                self.inner.get_mut()
+            }
+        }
+
+        impl<T: ?Sized + Default> Default for SpinNoIrq<T> {
+            #[track_caller]
+            fn default() -> Self {
+                Self::new(Default::default())
+            }
+        }
+
+        impl<T: ?Sized + fmt::Debug> fmt::Debug for SpinNoIrq<T> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Debug::fmt(&self.inner, f)
+            }
+        }
+
+        impl<'a, T: ?Sized> Deref for SpinNoIrqGuard<'a, T> {
+            type Target = T;
+
+            #[inline(always)]
+            fn deref(&self) -> &T {
+                &self.inner
+            }
+        }
+
+        impl<'a, T: ?Sized> DerefMut for SpinNoIrqGuard<'a, T> {
+            #[inline(always)]
+            fn deref_mut(&mut self) -> &mut T {
+                &mut self.inner
+            }
+        }
+
+        impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for SpinNoIrqGuard<'a, T> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Debug::fmt(&**self, f)
+            }
+        }
+
+        impl<'a, T: ?Sized> Drop for SpinNoIrqGuard<'a, T> {
+            /// 先记录释放，随后 `inner` 被 drop 时真正解锁
+            fn drop(&mut self) {
+                lock_release(self.addr);
+            }
+        }
+    } else {
+        pub use spinlock::{SpinNoIrq, SpinNoIrqGuard};
+    }
+}
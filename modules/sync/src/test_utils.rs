@@ -0,0 +1,61 @@
+//! 测试中使用的任务。
+//!
+//! 等待队列注册 waker 时会把 waker 中的指针当作任务修改其状态，因此测试不能使用空的 waker，
+//! 需要构造真实的任务，并通过任务的状态判断它是否被唤醒。
+
+use alloc::{boxed::Box, string::ToString, sync::Arc};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use spinlock::SpinNoIrq;
+use task_api::{wakeup_task, Scheduler, Task, TaskInner, TaskState};
+
+const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake, drop);
+
+unsafe fn clone(p: *const ()) -> RawWaker {
+    RawWaker::new(p, &VTABLE)
+}
+
+unsafe fn wake(p: *const ()) {
+    wakeup_task(p as *const Task)
+}
+
+unsafe fn drop(_p: *const ()) {}
+
+/// 用于 poll future 的任务
+///
+/// 每次 poll 之前任务处于运行状态，返回 Pending 时若注册到了等待队列中则处于 Blocking 状态，
+/// 被唤醒后变为 Waked。被唤醒之前不要再次 poll，否则同一个任务会在队列中注册两次
+pub(crate) struct TestTask {
+    task: Arc<Task>,
+}
+
+impl TestTask {
+    pub(crate) fn new() -> Self {
+        let scheduler = Arc::new(SpinNoIrq::new(Scheduler::new()));
+        let inner = TaskInner::new("test".to_string(), 0, scheduler, 0, Box::pin(async { 0 }));
+        Self {
+            task: Arc::new(Task::new(inner)),
+        }
+    }
+
+    fn waker(&self) -> Waker {
+        unsafe { Waker::from_raw(RawWaker::new(Arc::as_ptr(&self.task) as _, &VTABLE)) }
+    }
+
+    /// 以该任务的身份 poll 一次 `fut`
+    pub(crate) fn poll<F: Future + ?Sized>(&self, fut: Pin<&mut F>) -> Poll<F::Output> {
+        self.task.set_state(TaskState::Running);
+        fut.poll(&mut Context::from_waker(&self.waker()))
+    }
+
+    /// 以该任务的身份 poll 一次新创建的 `fut`
+    pub(crate) fn poll_once<F: Future>(&self, fut: F) -> Poll<F::Output> {
+        self.poll(core::pin::pin!(fut))
+    }
+
+    /// 上一次 poll 返回 Pending 之后是否被唤醒
+    pub(crate) fn woken(&self) -> bool {
+        self.task.state() == TaskState::Waked
+    }
+}
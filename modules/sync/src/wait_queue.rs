@@ -1,8 +1,8 @@
+use crate::SpinNoIrq;
 use alloc::sync::Arc;
 use core::future::Future;
 use core::pin::Pin;
 use core::task::{Context, Poll, Waker};
-use spinlock::SpinNoIrq;
 #[cfg(feature = "thread")]
 use task_api::{block_current, current_task};
 use task_api::{cancel_alarm, set_alarm_wakeup, WaitTaskList, WaitWakerNode};
@@ -144,6 +144,22 @@ impl WaitQueue {
         }
     }
 
+    /// 将 waker 注册到等待队列中，返回对应的节点，等待结束后需要通过 [`WaitQueue::unregister`] 移除。
+    ///
+    /// 用于需要在注册之后、真正让权之前释放其他锁的同步原语（如 [`Condvar`](crate::Condvar)），
+    /// 避免在释放锁与注册之间丢失唤醒。
+    pub(crate) fn register(&self, waker: &Waker) -> Arc<WaitWakerNode> {
+        let waker_node = Arc::new(WaitWakerNode::new(waker.clone()));
+        self.queue.lock().prepare_to_wait(waker_node.clone());
+        waker_node
+    }
+
+    /// 将 [`WaitQueue::register`] 注册的节点从等待队列中移除，
+    /// 返回 false 表示节点已经不在队列中（已经被唤醒）
+    pub(crate) fn unregister(&self, waker_node: &Arc<WaitWakerNode>) -> bool {
+        self.queue.lock().remove(waker_node).is_some()
+    }
+
     /// Wake up the given task in the wait queue.
     pub fn notify_task(&self, waker: &Waker) -> bool {
         self.queue.lock().notify_task(waker)
@@ -80,6 +80,32 @@ pub struct ITimerVal {
     pub it_value: TimeVal,
 }
 
+/// sys_timer_settime / sys_timer_gettime 指定的类型
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ITimerSpec {
+    /// The cycle of the timer
+    pub it_interval: TimeSecs,
+    /// The remaining time of the timer
+    pub it_value: TimeSecs,
+}
+
+/// sys_timer_create 指定的到期通知方式
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SigEvent {
+    /// 随信号传递的值
+    pub sigev_value: usize,
+    /// 到期时发送的信号
+    pub sigev_signo: i32,
+    /// 通知方式
+    pub sigev_notify: i32,
+    /// SIGEV_THREAD_ID 时接收信号的线程
+    pub sigev_notify_thread_id: i32,
+    /// 补齐到 64 字节
+    pub _pad: [i32; 11],
+}
+
 /// sys_nanosleep指定的结构体类型
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default)]
@@ -108,6 +134,14 @@ impl TimeSecs {
         self.tv_sec * NSEC_PER_SEC + self.tv_nsec
     }
 
+    /// create a TimeSecs from nano seconds
+    pub fn from_nanos(nanos: usize) -> Self {
+        TimeSecs {
+            tv_sec: nanos / NSEC_PER_SEC,
+            tv_nsec: nanos % NSEC_PER_SEC,
+        }
+    }
+
     /// turn the TimeSecs to cpu ticks, which is related to cpu frequency
     pub fn get_ticks(&self) -> usize {
         self.tv_sec * axconfig::TIMER_FREQUENCY + (nanos_to_ticks(self.tv_nsec as u64) as usize)
@@ -174,10 +208,16 @@ bitflags! {
         const MAP_FIXED = 1 << 4;
         /// 不映射到实际文件
         const MAP_ANONYMOUS = 1 << 5;
+        /// 向下增长的栈区域，访问其下方的地址时自动扩展
+        const MAP_GROWSDOWN = 0x100;
+        /// 锁定映射的内存，如同对其调用了 mlock
+        const MAP_LOCKED = 0x2000;
         /// 映射时不保留空间，即可能在实际使用mmp出来的内存时内存溢出
         const MAP_NORESERVE = 1 << 14;
         /// Allocation is for a stack.
         const MAP_STACK = 0x20000;
+        /// 使用 2 MiB 大页映射匿名内存
+        const MAP_HUGETLB = 0x40000;
     }
 }
 
@@ -258,21 +298,8 @@ impl SigMaskFlag {
     }
 }
 
-/// sys_prlimit64 使用的数组
-#[repr(C)]
-pub struct RLimit {
-    /// 软上限
-    pub rlim_cur: u64,
-    /// 硬上限
-    pub rlim_max: u64,
-}
-// sys_prlimit64 使用的选项
-/// 用户栈大小
-pub const RLIMIT_STACK: i32 = 3;
-/// 可以打开的 fd 数
-pub const RLIMIT_NOFILE: i32 = 7;
-/// 用户地址空间的最大大小
-pub const RLIMIT_AS: i32 = 9;
+// sys_prlimit64 使用的数组与选项
+pub use executor::rlimit::*;
 
 /// robust list
 #[allow(unused)]
@@ -413,6 +440,10 @@ numeric_enum_macro::numeric_enum! {
         CLOCK_REALTIME = 0,
         /// monotonic clock
         CLOCK_MONOTONIC = 1,
+        /// CPU time consumed by all threads in the process
+        CLOCK_PROCESS_CPUTIME_ID = 2,
+        /// CPU time consumed by the calling thread
+        CLOCK_THREAD_CPUTIME_ID = 3,
     }
 }
 
@@ -702,3 +733,77 @@ pub struct CloneArgs {
     /// 该信息 Starry 暂未支持
     pub cgroup: u64,
 }
+
+/// System V IPC 对象的所有者与权限，即 `struct ipc64_perm`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IpcPerm64 {
+    /// 创建对象时使用的键
+    pub key: i32,
+    /// 所有者的用户 id
+    pub uid: u32,
+    /// 所有者的组 id
+    pub gid: u32,
+    /// 创建者的用户 id
+    pub cuid: u32,
+    /// 创建者的组 id
+    pub cgid: u32,
+    /// 访问权限，x86_64 上为 16 位，其他架构上为 32 位，这里都只使用低 16 位
+    pub mode: u16,
+    /// 补齐到 32 位
+    pub _pad1: u16,
+    /// 序列号，Starry 暂未使用
+    pub seq: u16,
+    /// 补齐
+    pub _pad2: u16,
+    /// 保留
+    pub _unused: [usize; 2],
+}
+
+/// msgctl 使用的结构体，即 `struct msqid64_ds`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsqidDs {
+    /// 所有者与权限
+    pub msg_perm: IpcPerm64,
+    /// 最近一次发送的时间
+    pub msg_stime: isize,
+    /// 最近一次接收的时间
+    pub msg_rtime: isize,
+    /// 最近一次修改的时间
+    pub msg_ctime: isize,
+    /// 队列中消息的总字节数
+    pub msg_cbytes: usize,
+    /// 队列中的消息数
+    pub msg_qnum: usize,
+    /// 队列的容量
+    pub msg_qbytes: usize,
+    /// 最近一次发送的进程
+    pub msg_lspid: i32,
+    /// 最近一次接收的进程
+    pub msg_lrpid: i32,
+    /// 保留
+    pub _unused: [usize; 2],
+}
+
+/// semctl 使用的结构体，即 `struct semid64_ds`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SemidDs {
+    /// 所有者与权限
+    pub sem_perm: IpcPerm64,
+    /// 最近一次 semop 的时间
+    pub sem_otime: isize,
+    /// x86_64 上的保留字段
+    #[cfg(target_arch = "x86_64")]
+    pub _unused1: usize,
+    /// 最近一次修改的时间
+    pub sem_ctime: isize,
+    /// x86_64 上的保留字段
+    #[cfg(target_arch = "x86_64")]
+    pub _unused2: usize,
+    /// 信号量的数量
+    pub sem_nsems: usize,
+    /// 保留
+    pub _unused: [usize; 2],
+}
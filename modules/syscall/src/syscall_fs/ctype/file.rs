@@ -17,7 +17,7 @@ use core::{
 
 use axlog::debug;
 
-use crate::{new_file, normal_file_mode, StMode, TimeSecs};
+use crate::{new_file, StMode, TimeSecs};
 use executor::link::get_link_count;
 use sync::Mutex;
 
@@ -141,10 +141,10 @@ impl FileIO for FileDesc {
             let kstat = Kstat {
                 st_dev: 1,
                 st_ino: inode_number,
-                st_mode: normal_file_mode(StMode::S_IFREG).bits() | 0o777,
+                st_mode: StMode::S_IFREG.bits() | attr.perm().bits() as u32,
                 st_nlink: get_link_count(&(self.path.as_str().to_string())).await as _,
-                st_uid: 0,
-                st_gid: 0,
+                st_uid: attr.uid(),
+                st_gid: attr.gid(),
                 st_rdev: 0,
                 _pad0: 0,
                 st_size: attr.size(),
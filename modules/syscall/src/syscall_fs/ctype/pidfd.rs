@@ -91,13 +91,10 @@ impl FileIO for PidFd {
 pub async fn new_pidfd(pid: u64, mut flags: OpenFlags) -> SyscallResult {
     // It is set to close the file descriptor on exec
     flags |= OpenFlags::CLOEXEC;
-    let pid2fd = PID2PC.lock().await;
-
-    let pidfd = pid2fd
+    let pidfd = PID2PC
         .get(&pid)
-        .map(|target_process| PidFd::new(Arc::clone(target_process), flags))
+        .map(|target_process| PidFd::new(target_process, flags))
         .ok_or(SyscallError::EINVAL)?;
-    drop(pid2fd);
     let process = current_executor().await;
     let mut fd_table = process.fd_manager.fd_table.lock().await;
     let fd = process
@@ -1,13 +1,15 @@
 //! 对文件系统的管理,包括目录项的创建、文件权限设置等内容
 use alloc::string::ToString;
 use async_fs::api::{
-    remove_dir, remove_file, rename, AsyncFileIO, ConsoleWinSize, OpenFlags, Permissions, FIOCLEX,
-    FIONBIO, TCGETS, TIOCGPGRP, TIOCGWINSZ, TIOCSPGRP,
+    remove_dir, remove_file, rename, AccessMode, AsyncFileIO, ConsoleWinSize, FileIOType,
+    OpenFlags, Permissions, FIOCLEX, FIONBIO, TCGETS, TIOCGPGRP, TIOCGWINSZ, TIOCSPGRP,
 };
 use async_io::Stream;
 use axlog::{debug, error, info};
+use axsignal::signal_no::SignalNo;
 use core::ptr::{self, copy_nonoverlapping};
 
+use super::io::{is_signal_blocked, is_signal_ignored};
 use crate::{
     syscall_fs::{
         ctype::{file::new_fd, pidfd::new_pidfd, FileDesc},
@@ -15,10 +17,12 @@ use crate::{
     },
     DirEnt, DirEntType, Fcntl64Cmd, RenameFlags, SyscallError, SyscallResult, TimeSecs,
 };
+use axerrno::AxError;
 use axhal::mem::VirtAddr;
 use executor::{
     current_executor,
     link::{FilePath, AT_FDCWD},
+    process_group_members, send_signal_to_process_group, CONSOLE_TTY, PID2PC,
 };
 
 extern crate alloc;
@@ -471,11 +475,43 @@ pub async fn syscall_ioctl(args: [usize; 6]) -> SyscallResult {
             }
             Ok(0)
         }
-        TCGETS | TIOCSPGRP => Ok(0),
-        TIOCGPGRP => {
-            unsafe {
-                *(argp as *mut u32) = 0;
+        TCGETS => Ok(0),
+        TIOCGPGRP | TIOCSPGRP => {
+            // 只有控制终端支持前台进程组的查询与设置
+            if !matches!(
+                file.get_type().await,
+                FileIOType::Stdin | FileIOType::Stdout | FileIOType::Stderr
+            ) || !CONSOLE_TTY.is_controlling(&process)
+            {
+                return Err(SyscallError::ENOTTY);
+            }
+            if request == TIOCGPGRP {
+                unsafe {
+                    *(argp as *mut u32) = CONSOLE_TTY.foreground_pgid() as u32;
+                }
+                return Ok(0);
+            }
+            let pgid = unsafe { *(argp as *const u32) } as u64;
+            // 新的前台进程组必须存在，且与当前进程属于同一个会话
+            let in_session = process_group_members(pgid).first().is_some_and(|pid| {
+                PID2PC
+                    .get(pid)
+                    .is_some_and(|member| member.sid() == process.sid())
+            });
+            if !in_session {
+                return Err(SyscallError::EPERM);
+            }
+            // 后台进程组修改前台进程组时，除非忽略或屏蔽了 SIGTTOU，否则向整个进程组发送 SIGTTOU
+            if CONSOLE_TTY.is_background(&process)
+                && !is_signal_ignored(&process, SignalNo::SIGTTOU).await
+                && !is_signal_blocked(&process, SignalNo::SIGTTOU).await
+            {
+                let _ =
+                    send_signal_to_process_group(process.pgid(), SignalNo::SIGTTOU as isize, None)
+                        .await;
+                return Err(SyscallError::ERESTART);
             }
+            CONSOLE_TTY.set_foreground_pgid(pgid);
             Ok(0)
         }
         FIONBIO => {
@@ -509,13 +545,60 @@ pub async fn syscall_fchmodat(args: [usize; 6]) -> SyscallResult {
     let path = args[1] as *const u8;
     let mode = args[2];
     let file_path = solve_path(dir_fd, Some(path), false).await?;
-    async_fs::api::metadata(file_path.path())
-        .await
-        .map(|mut metadata| {
-            metadata.set_permissions(Permissions::from_bits_truncate(mode as u16));
-            Ok(0)
-        })
-        .unwrap_or_else(|_| Err(SyscallError::ENOENT))
+    // 只有文件的所有者或 root 可以修改权限
+    match async_fs::api::set_permissions(
+        file_path.path(),
+        Permissions::from_bits_truncate(mode as u16),
+    )
+    .await
+    {
+        Ok(()) => Ok(0),
+        // 文件系统不支持修改权限时同样拒绝，而不是假装成功
+        Err(AxError::PermissionDenied | AxError::Unsupported) => Err(SyscallError::EPERM),
+        Err(_) => Err(SyscallError::ENOENT),
+    }
+}
+
+/// 54
+/// 修改文件的所有者与组，`owner` 或 `group` 为 -1 时保持不变
+/// 路径的解析方式与 fchmodat 相同
+/// # Arguments
+/// * `dir_fd`: usize, 目录的文件描述符
+/// * `path`: *const u8, 文件的路径
+/// * `owner`: u32, 新的所有者
+/// * `group`: u32, 新的组
+pub async fn syscall_fchownat(args: [usize; 6]) -> SyscallResult {
+    let dir_fd = args[0];
+    let path = args[1] as *const u8;
+    let file_path = solve_path(dir_fd, Some(path), false).await?;
+    change_owner(&file_path, args[2] as u32, args[3] as u32).await
+}
+
+/// 93
+/// 修改文件描述符 `fd` 所指的文件的所有者与组
+/// # Arguments
+/// * `fd`: usize, 文件描述符
+/// * `owner`: u32, 新的所有者
+/// * `group`: u32, 新的组
+#[cfg(target_arch = "x86_64")]
+pub async fn syscall_fchown(args: [usize; 6]) -> SyscallResult {
+    let file_path = solve_path(args[0], None, false).await?;
+    change_owner(&file_path, args[1] as u32, args[2] as u32).await
+}
+
+async fn change_owner(file_path: &FilePath, owner: u32, group: u32) -> SyscallResult {
+    match async_fs::api::set_owner(
+        file_path.path(),
+        (owner != u32::MAX).then_some(owner),
+        (group != u32::MAX).then_some(group),
+    )
+    .await
+    {
+        Ok(()) => Ok(0),
+        // 文件系统不支持修改所有者时同样拒绝
+        Err(AxError::PermissionDenied | AxError::Unsupported) => Err(SyscallError::EPERM),
+        Err(_) => Err(SyscallError::ENOENT),
+    }
 }
 
 /// 48
@@ -536,38 +619,18 @@ pub async fn syscall_faccessat(args: [usize; 6]) -> SyscallResult {
     let dir_fd = args[0];
     let path = args[1] as *const u8;
     let mode = args[2];
-    // todo: 有问题,实际上需要考虑当前进程对应的用户UID和文件拥有者之间的关系
-    // 现在一律当作root用户处理
     let file_path = solve_path(dir_fd, Some(path), false).await?;
     axlog::info!("syscall_faccessat file_path : {:?}", file_path);
-    if let Ok(metadata) = async_fs::api::metadata(file_path.path()).await {
-        if mode == 0 {
-            //F_OK
-            // 文件存在返回0,不存在返回-1
-            if async_fs::api::path_exists(file_path.path()).await {
-                Ok(0)
-            } else {
-                Err(SyscallError::ENOENT)
-            }
-        } else {
-            // 逐位对比
-            let mut ret = true;
-            if mode & 1 != 0 {
-                // X_OK
-                ret &= metadata.permissions().contains(Permissions::OWNER_EXEC)
-            }
-            if mode & 2 != 0 {
-                // W_OK
-                ret &= metadata.permissions().contains(Permissions::OWNER_WRITE)
-            }
-            if mode & 4 != 0 {
-                // R_OK
-                ret &= metadata.permissions().contains(Permissions::OWNER_READ)
-            }
-            Ok(ret as isize - 1)
-        }
-    } else {
-        Err(SyscallError::ENOENT)
+    // F_OK 只检查文件是否存在，其余按照当前进程的有效用户检查权限
+    match async_fs::api::access(
+        file_path.path(),
+        AccessMode::from_bits_truncate(mode as u32),
+    )
+    .await
+    {
+        Ok(()) => Ok(0),
+        Err(AxError::PermissionDenied) => Err(SyscallError::EACCES),
+        Err(_) => Err(SyscallError::ENOENT),
     }
 }
 
@@ -582,8 +645,6 @@ pub fn syscall_access(args: [usize; 6]) -> SyscallResult {
     let path = args[0];
     let mode = args[1];
     let temp_args = [AT_FDCWD, path, mode, 0, 0, 0];
-    // todo: 有问题,实际上需要考虑当前进程对应的用户UID和文件拥有者之间的关系
-    // 现在一律当作root用户处理
     syscall_faccessat(temp_args)
 }
 
@@ -1,15 +1,20 @@
 //! 负责与 IO 相关的系统调用
 extern crate alloc;
-use crate::syscall_fs::solve_path;
+use crate::syscall_fs::{solve_path, FileDesc};
 // use crate::syscall_net::Socket;
 use crate::{IoVec, SyscallError, SyscallResult};
 use alloc::{string::ToString, sync::Arc, vec};
-use async_fs::api::{AsyncFileIO, FileIOType, OpenFlags};
+use async_fs::api::{AsyncFileIO, FileIO, FileIOType, OpenFlags};
 use async_io::SeekFrom;
 use axerrno::AxError;
 use axlog::{debug, info};
-use executor::current_executor;
+use axsignal::{action::SIG_IGN, signal_no::SignalNo};
+use executor::binfmt::{self, BINFMT_MISC_DIR};
 use executor::link::{create_link, real_path};
+use executor::{
+    current_executor, current_task, is_orphaned_process_group, rlimit::RLIMIT_FSIZE,
+    send_signal_to_process_group, send_signal_to_thread, Executor, CONSOLE_TTY,
+};
 
 use crate::syscall_fs::ctype::{
     dir::new_dir,
@@ -17,6 +22,76 @@ use crate::syscall_fs::ctype::{
     file::{new_fd, new_inode},
     pipe::make_pipe,
 };
+/// 当前线程是否忽略了信号 `signal`
+pub(super) async fn is_signal_ignored(process: &Executor, signal: SignalNo) -> bool {
+    let signal_modules = process.signal_modules.lock().await;
+    match signal_modules.get(&current_task().id().as_u64()) {
+        Some(signal_module) => {
+            signal_module
+                .signal_handler
+                .lock()
+                .await
+                .get_action(signal as usize)
+                .sa_handler
+                == SIG_IGN
+        }
+        None => false,
+    }
+}
+
+/// 当前线程是否屏蔽了信号 `signal`
+pub(super) async fn is_signal_blocked(process: &Executor, signal: SignalNo) -> bool {
+    let signal_modules = process.signal_modules.lock().await;
+    signal_modules
+        .get(&current_task().id().as_u64())
+        .is_some_and(|signal_module| {
+            signal_module.signal_set.mask & (1 << (signal as usize - 1)) != 0
+        })
+}
+
+/// 检查向普通文件写入 `count` 字节时是否会超过 RLIMIT_FSIZE，返回允许写入的字节数
+///
+/// `offset` 为 None 时从文件当前的读写位置写入，以 O_APPEND 打开的文件则从文件末尾写入。若写入位置已经达到上限，
+/// 则向当前线程发送 SIGXFSZ 并返回 EFBIG
+async fn check_file_size_limit(
+    process: &Executor,
+    file: &Arc<dyn FileIO + Unpin>,
+    offset: Option<u64>,
+    count: usize,
+) -> Result<usize, SyscallError> {
+    let limit = process.get_rlimit(RLIMIT_FSIZE).rlim_cur;
+    if count == 0 || file.get_type().await != FileIOType::FileDesc {
+        return Ok(count);
+    }
+    let offset = match offset {
+        Some(offset) => offset,
+        None if file.get_status().await.contains(OpenFlags::APPEND) => {
+            file.get_stat()
+                .await
+                .map_err(|_| SyscallError::EINVAL)?
+                .st_size
+        }
+        None => file
+            .seek(SeekFrom::Current(0))
+            .await
+            .map_err(|_| SyscallError::EINVAL)?,
+    };
+    if offset >= limit {
+        return Err(file_size_exceeded().await);
+    }
+    Ok(count.min((limit - offset) as usize))
+}
+
+/// 文件大小超过 RLIMIT_FSIZE 时向当前线程发送 SIGXFSZ，返回 EFBIG
+async fn file_size_exceeded() -> SyscallError {
+    let _ = send_signal_to_thread(
+        current_task().id().as_u64() as isize,
+        SignalNo::SIGXFSZ as isize,
+    )
+    .await;
+    SyscallError::EFBIG
+}
+
 /// 功能:从一个文件描述符中读取；
 /// # Arguments
 /// * `fd`: usize, 要读取文件的文件描述符。
@@ -56,6 +131,19 @@ pub async fn syscall_read(args: [usize; 6]) -> SyscallResult {
         axlog::error!("fd is a dir");
         return Err(SyscallError::EISDIR);
     }
+    // 后台进程组读取控制终端时，向整个进程组发送 SIGTTIN。
+    // 信号被忽略或者屏蔽时不会停止进程，孤儿进程组停止后也没有进程会让它继续，此时直接返回 EIO
+    if file.get_type().await == FileIOType::Stdin && CONSOLE_TTY.is_background(&process) {
+        if is_signal_ignored(&process, SignalNo::SIGTTIN).await
+            || is_signal_blocked(&process, SignalNo::SIGTTIN).await
+            || is_orphaned_process_group(process.pgid())
+        {
+            return Err(SyscallError::EIO);
+        }
+        let _ =
+            send_signal_to_process_group(process.pgid(), SignalNo::SIGTTIN as isize, None).await;
+        return Err(SyscallError::ERESTART);
+    }
     if !file.readable().await {
         // 1. nonblocking socket
         //
@@ -148,6 +236,23 @@ pub async fn syscall_write(args: [usize; 6]) -> SyscallResult {
         }
     }
 
+    // binfmt_misc 的控制文件，写入时注册或者删除可执行文件格式，只有特权进程可以写入
+    if let Some(name) = file
+        .as_any()
+        .downcast_ref::<FileDesc>()
+        .and_then(|file| file.path.strip_prefix(BINFMT_MISC_DIR))
+        .and_then(|name| name.strip_prefix('/'))
+    {
+        if !process.cred.lock().is_privileged() {
+            return Err(SyscallError::EPERM);
+        }
+        binfmt::write_misc_control(name, buf).await?;
+        return Ok(buf.len() as isize);
+    }
+
+    let count = check_file_size_limit(&process, &file, None, buf.len()).await?;
+    let buf = &buf[..count];
+
     // for sockets:
     // Sockets are "writable" when:
     // - connected and have space in tx buffer to write
@@ -209,8 +314,15 @@ pub async fn syscall_writev(args: [usize; 6]) -> SyscallResult {
         }
         let temp_args = [fd, io.base as usize, io.len, 0, 0, 0];
         match syscall_write(temp_args).await {
-            len if len.is_ok() => write_len += len.unwrap(),
-
+            Ok(len) => {
+                write_len += len;
+                // 只写入了一部分（例如达到了 RLIMIT_FSIZE）时不再写入之后的缓冲区
+                if (len as usize) < io.len {
+                    break;
+                }
+            }
+            // 已经写入了数据时返回写入的长度，之后的写入再报告错误
+            Err(_) if write_len > 0 => break,
             err => return err,
         }
     }
@@ -564,6 +676,7 @@ pub async fn syscall_pwrite64(args: [usize; 6]) -> SyscallResult {
     let file = process.fd_manager.fd_table.lock().await[fd]
         .clone()
         .unwrap();
+    let count = check_file_size_limit(&process, &file, Some(offset as u64), count).await?;
 
     let old_offset = file.seek(SeekFrom::Current(0)).await.unwrap();
 
@@ -877,18 +990,22 @@ pub async fn syscall_ftruncate64(args: [usize; 6]) -> SyscallResult {
     let len = args[1];
     let process = current_executor().await;
     info!("fd: {}, len: {}", fd, len);
-    let fd_table = process.fd_manager.fd_table.lock().await;
-    if fd >= fd_table.len() {
-        return Err(SyscallError::EINVAL);
+    let file = match process.fd_manager.fd_table.lock().await.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return Err(SyscallError::EINVAL),
+    };
+    // 与写入相同，普通文件不能被扩展到超过 RLIMIT_FSIZE，缩小文件则不受限制
+    if file.get_type().await == FileIOType::FileDesc
+        && len as u64 > process.get_rlimit(RLIMIT_FSIZE).rlim_cur
+        && file
+            .get_stat()
+            .await
+            .is_ok_and(|stat| len as u64 > stat.st_size)
+    {
+        return Err(file_size_exceeded().await);
     }
-    if fd_table[fd].is_none() {
+    if file.truncate(len).await.is_err() {
         return Err(SyscallError::EINVAL);
     }
-
-    if let Some(file) = fd_table[fd].as_ref() {
-        if file.truncate(len).await.is_err() {
-            return Err(SyscallError::EINVAL);
-        }
-    }
     Ok(0)
 }
@@ -32,12 +32,14 @@ pub async fn syscall_fstat(args: [usize; 6]) -> SyscallResult {
     }
     let file = fd_table[fd].clone().unwrap();
 
+    drop(fd_table);
     match file.get_stat().await {
         Ok(stat) => {
-            unsafe {
-                *kst = stat;
-                info!("stat: {:?}", stat);
-            }
+            info!("stat: {:?}", stat);
+            process
+                .write_user(kst, stat)
+                .await
+                .map_err(|_| SyscallError::EFAULT)?;
             Ok(0)
         }
         Err(e) => {
@@ -64,6 +66,10 @@ pub async fn syscall_fstatat(args: [usize; 6]) -> SyscallResult {
         // 去尝试检查 STDOUT 的属性。这里暂时先特判，以后再改成真正的 stdout 的属性
         let path = unsafe { raw_ptr_to_ref_str(path) };
         if path.is_empty() && dir_fd == 1 {
+            let process = current_executor().await;
+            if process.manual_alloc_type_for_lazy(kst).await.is_err() {
+                return Err(SyscallError::EFAULT);
+            }
             unsafe {
                 (*kst).st_mode = 0o20000 | 0o220u32;
                 (*kst).st_ino = 1;
@@ -78,11 +84,15 @@ pub async fn syscall_fstatat(args: [usize; 6]) -> SyscallResult {
         return Err(SyscallError::ENOENT);
     }
     match get_stat_in_fs(&file_path).await {
-        Ok(stat) => unsafe {
-            *kst = stat;
+        Ok(stat) => {
             info!("stat: {:?}", stat);
+            current_executor()
+                .await
+                .write_user(kst, stat)
+                .await
+                .map_err(|_| SyscallError::EFAULT)?;
             Ok(0)
-        },
+        }
         Err(error_no) => {
             debug!("get stat error: {:?}", error_no);
             Err(error_no)
@@ -124,9 +134,11 @@ pub async fn syscall_statfs(args: [usize; 6]) -> SyscallResult {
     let _file_path = solve_path(AT_FDCWD, Some(path), false).await?;
     axlog::warn!("Only support fs_stat for root");
 
-    unsafe {
-        *stat = get_fs_stat();
-    }
+    current_executor()
+        .await
+        .write_user(stat, get_fs_stat())
+        .await
+        .map_err(|_| SyscallError::EFAULT)?;
 
     Ok(0)
 }
@@ -150,9 +162,11 @@ pub async fn syscall_statx(args: [usize; 6]) -> SyscallResult {
         if file_path.equal_to(&p) {
             // 目前只支持访问根目录文件系统的信息
             axlog::warn!("Only support fs_stat for root");
-            unsafe {
-                *stat = FsStatx::new();
-            }
+            current_executor()
+                .await
+                .write_user(stat, FsStatx::new())
+                .await
+                .map_err(|_| SyscallError::EFAULT)?;
         }
     }
     Ok(0)
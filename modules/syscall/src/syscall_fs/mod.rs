@@ -17,6 +17,8 @@ pub async fn new_file(path: &str, flags: &OpenFlags) -> AxResult<File> {
     let mut file = File::options();
     file.read(flags.readable());
     file.write(flags.writable());
+    // 只读打开时 O_APPEND 不应使文件可写
+    file.append(flags.writable() && flags.contains(OpenFlags::APPEND));
     file.create(flags.creatable());
     file.create_new(flags.new_creatable());
     file.open(path).await
@@ -72,7 +74,10 @@ pub async fn fs_syscall(syscall_id: fs_syscall_id::FsSyscallId, args: [usize; 6]
         PSELECT6 => syscall_pselect6(args).await,
         STATX => syscall_statx(args).await,
         PIDFD_OPEN => syscall_pidfd_open(args).await,
-        FCHOWN => Ok(0),
+        #[cfg(not(target_arch = "x86_64"))]
+        FCHOWN => syscall_fchownat(args).await,
+        #[cfg(target_arch = "x86_64")]
+        FCHOWN => syscall_fchown(args).await,
         #[cfg(not(target_arch = "x86_64"))]
         EVENTFD => syscall_eventfd(args).await,
         // #[cfg(target_arch = "x86_64")]
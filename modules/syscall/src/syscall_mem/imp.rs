@@ -1,15 +1,32 @@
-use crate::{syscall_fs::FileDesc, MMAPFlags, MREMAPFlags, SyscallError, SyscallResult, MMAPPROT};
+use crate::{
+    syscall_fs::{solve_path, FileDesc},
+    MMAPFlags, MREMAPFlags, SyscallError, SyscallResult, MMAPPROT,
+};
 extern crate alloc;
 
-use async_mem::{BackEndFile, MemorySet};
+use async_mem::{
+    ipc::{ipc_get, IPC_PRIVATE},
+    Advice, BackEndFile, MemorySet,
+};
 use axerrno::AxError;
 use axhal::{arch::flush_tlb, mem::VirtAddr, paging::MappingFlags};
 use axlog::info;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use bitflags::bitflags;
-use executor::current_executor;
+use executor::{
+    current_executor,
+    link::AT_FDCWD,
+    rlimit::{RLIMIT_DATA, RLIMIT_MEMLOCK},
+    Executor,
+};
 
 const MAX_HEAP_SIZE: usize = 0x20000;
+
+/// 同时在后台进行的 `MADV_WILLNEED` 预读任务数的上限，达到上限时不再发起新的预读
+const MAX_PREFETCH_TASKS: usize = 4;
+static PREFETCH_TASKS: AtomicUsize = AtomicUsize::new(0);
+
 /// 修改用户堆大小，
 ///
 /// - 如输入 brk 为 0 ，则返回堆顶地址
@@ -22,7 +39,28 @@ pub async fn syscall_brk(args: [usize; 6]) -> SyscallResult {
     let curr_process = current_executor().await;
     let mut return_val: isize = curr_process.get_heap_top() as isize;
     let heap_bottom = curr_process.get_heap_bottom() as usize;
-    if brk != 0 && brk >= heap_bottom && brk <= heap_bottom + MAX_HEAP_SIZE {
+    // 堆的大小不能超过 RLIMIT_DATA
+    let data_limit = curr_process.get_rlimit(RLIMIT_DATA).rlim_cur;
+    if brk != 0
+        && brk >= heap_bottom
+        && brk <= heap_bottom + MAX_HEAP_SIZE
+        && (brk - heap_bottom) as u64 <= data_limit
+    {
+        // mlockall(MCL_FUTURE) 之后堆扩展的部分也被锁定，锁定失败时堆顶保持不变
+        let top = return_val as usize;
+        if brk > top {
+            let limit = memlock_limit(&curr_process);
+            let locked = curr_process
+                .memory_set
+                .lock()
+                .await
+                .lock_new_region(top.into(), brk - top, limit)
+                .await;
+            flush_tlb(None);
+            if locked.is_err() {
+                return Ok(return_val);
+            }
+        }
         curr_process.set_heap_top(brk as u64);
         return_val = brk as isize;
     }
@@ -57,7 +95,18 @@ pub async fn syscall_mmap(args: [usize; 6]) -> SyscallResult {
     }
     let process = current_executor().await;
     let shared = flags.contains(MMAPFlags::MAP_SHARED);
-    let result = if flags.contains(MMAPFlags::MAP_ANONYMOUS) {
+    let result = if flags.contains(MMAPFlags::MAP_HUGETLB) {
+        // 只支持匿名映射使用大页
+        if !flags.contains(MMAPFlags::MAP_ANONYMOUS) || offset != 0 {
+            return Err(SyscallError::EINVAL);
+        }
+        process
+            .memory_set
+            .lock()
+            .await
+            .mmap_huge(start.into(), len, prot.into(), shared, fixed)
+            .await
+    } else if flags.contains(MMAPFlags::MAP_ANONYMOUS) {
         // no file
         if offset != 0 {
             return Err(SyscallError::EINVAL);
@@ -99,6 +148,40 @@ pub async fn syscall_mmap(args: [usize; 6]) -> SyscallResult {
             .await
     };
 
+    // 只有匿名映射可以向下增长
+    if let Ok(addr) = result {
+        if flags.contains(MMAPFlags::MAP_GROWSDOWN) && flags.contains(MMAPFlags::MAP_ANONYMOUS) {
+            process.memory_set.lock().await.set_grows_down(addr.into());
+        }
+    }
+    let result = match result {
+        Ok(addr) if flags.contains(MMAPFlags::MAP_LOCKED) => {
+            let limit = memlock_limit(&process);
+            let mut memory_set = process.memory_set.lock().await;
+            match memory_set.mlock(addr.into(), len, true, limit).await {
+                Ok(()) => Ok(addr),
+                Err(_) => {
+                    memory_set.munmap(addr.into(), len).await;
+                    flush_tlb(None);
+                    return Err(SyscallError::EAGAIN);
+                }
+            }
+        }
+        // mlockall(MCL_FUTURE) 之后新建的映射也被锁定
+        Ok(addr) => {
+            let limit = memlock_limit(&process);
+            let mut memory_set = process.memory_set.lock().await;
+            match memory_set.lock_new_region(addr.into(), len, limit).await {
+                Ok(()) => Ok(addr),
+                Err(err) => {
+                    memory_set.munmap(addr.into(), len).await;
+                    Err(err)
+                }
+            }
+        }
+        result => result,
+    };
+
     flush_tlb(None);
     // info!("val: {}", unsafe { *(addr as *const usize) });
     match result {
@@ -142,6 +225,122 @@ pub async fn syscall_msync(args: [usize; 6]) -> SyscallResult {
     Ok(0)
 }
 
+/// 以 `path` 处的块设备或者文件作为交换空间，只有特权进程可以调用
+/// # Arguments
+/// * `path` - *const u8
+/// * `flags` - usize, 交换空间的优先级等，目前忽略
+pub async fn syscall_swapon(args: [usize; 6]) -> SyscallResult {
+    let path = args[0] as *const u8;
+    if !current_executor().await.cred.lock().is_privileged() {
+        return Err(SyscallError::EPERM);
+    }
+    let path = solve_path(AT_FDCWD, Some(path), false).await?;
+    async_mem::swap::swapon(path.path()).await?;
+    Ok(0)
+}
+
+/// 停止使用 `path` 处的交换空间，被换出的页面会先被换入
+/// # Arguments
+/// * `path` - *const u8
+pub async fn syscall_swapoff(args: [usize; 6]) -> SyscallResult {
+    let path = args[0] as *const u8;
+    if !current_executor().await.cred.lock().is_privileged() {
+        return Err(SyscallError::EPERM);
+    }
+    let path = solve_path(AT_FDCWD, Some(path), false).await?;
+    async_mem::swap::swapoff(path.path()).await?;
+    Ok(0)
+}
+
+const MADV_NORMAL: i32 = 0;
+const MADV_RANDOM: i32 = 1;
+const MADV_SEQUENTIAL: i32 = 2;
+const MADV_WILLNEED: i32 = 3;
+const MADV_DONTNEED: i32 = 4;
+const MADV_FREE: i32 = 8;
+const MADV_REMOVE: i32 = 9;
+const MADV_DONTFORK: i32 = 10;
+const MADV_DOFORK: i32 = 11;
+const MADV_MERGEABLE: i32 = 12;
+const MADV_UNMERGEABLE: i32 = 13;
+const MADV_HUGEPAGE: i32 = 14;
+const MADV_NOHUGEPAGE: i32 = 15;
+const MADV_DONTDUMP: i32 = 16;
+const MADV_DODUMP: i32 = 17;
+
+/// 将 madvise 的 `advice` 转换为 [`Advice`]，直接忽略的建议返回 None，未知的建议返回 EINVAL
+fn parse_advice(advice: i32) -> Result<Option<Advice>, SyscallError> {
+    Ok(Some(match advice {
+        MADV_NORMAL => Advice::Normal,
+        MADV_RANDOM => Advice::Random,
+        MADV_SEQUENTIAL => Advice::Sequential,
+        MADV_WILLNEED => Advice::WillNeed,
+        MADV_DONTNEED => Advice::DontNeed,
+        MADV_FREE => Advice::Free,
+        MADV_DONTFORK => Advice::DontFork,
+        MADV_DOFORK => Advice::DoFork,
+        MADV_HUGEPAGE => Advice::HugePage,
+        MADV_NOHUGEPAGE => Advice::NoHugePage,
+        MADV_REMOVE | MADV_MERGEABLE | MADV_UNMERGEABLE | MADV_DONTDUMP | MADV_DODUMP => {
+            return Ok(None)
+        }
+        _ => return Err(SyscallError::EINVAL),
+    }))
+}
+
+/// 为新的预读任务占用一个名额，后台的预读任务已经达到上限时返回 false
+fn start_prefetch_task() -> bool {
+    PREFETCH_TASKS
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |tasks| {
+            (tasks < MAX_PREFETCH_TASKS).then_some(tasks + 1)
+        })
+        .is_ok()
+}
+
+/// 给出对 `[start, start + len)` 的使用建议
+///
+/// `MADV_WILLNEED` 在后台预读文件映射的内容，不等待读取完成。
+/// `MADV_REMOVE`、`MADV_MERGEABLE` 等建议目前直接忽略。
+/// 范围中有没有映射的部分时返回 ENOMEM，丢弃被锁定的映射时返回 EINVAL
+/// # Arguments
+/// * `start` - usize
+/// * `len` - usize
+/// * `advice` - i32
+pub async fn syscall_madvise(args: [usize; 6]) -> SyscallResult {
+    let start = VirtAddr::from(args[0]);
+    let len = args[1];
+    let advice = args[2] as i32;
+    if !start.is_aligned_4k() || args[0].checked_add(len).is_none() {
+        return Err(SyscallError::EINVAL);
+    }
+    let Some(advice) = parse_advice(advice)? else {
+        return Ok(0);
+    };
+    let process = current_executor().await;
+    let mut memory_set = process.memory_set.lock().await;
+    if advice == Advice::WillNeed {
+        let prefetch = memory_set.prefetch(start, len).await;
+        drop(memory_set);
+        let prefetch = prefetch.map_err(|_| SyscallError::ENOMEM)?;
+        // 预读只是建议，后台任务过多时直接放弃
+        if !prefetch.is_empty() && start_prefetch_task() {
+            executor::spawn(move || async move {
+                for prefetch in prefetch {
+                    prefetch.run().await;
+                }
+                PREFETCH_TASKS.fetch_sub(1, Ordering::AcqRel);
+                0
+            });
+        }
+        return Ok(0);
+    }
+    match memory_set.madvise(start, len, advice).await {
+        Ok(()) => Ok(0),
+        Err(AxError::NoMemory) => Err(SyscallError::ENOMEM),
+        Err(_) => Err(SyscallError::EINVAL),
+    }
+}
+
 /// # Arguments
 /// * `start` - usize
 /// * `len` - usize
@@ -249,8 +448,6 @@ pub async fn syscall_mremap(args: [usize; 6]) -> SyscallResult {
     flush_tlb(None);
     Ok(new_addr)
 }
-const IPC_PRIVATE: i32 = 0;
-
 bitflags! {
     #[derive(Debug)]
     struct ShmFlags: i32 {
@@ -275,7 +472,7 @@ pub async fn syscall_shmget(args: [usize; 6]) -> SyscallResult {
     let pid = current_executor().await.pid();
 
     // 9 bits for permission
-    let mode: u16 = (flags as u16) & ((1 << 10) - 1);
+    let mode: u16 = (flags as u16) & ((1 << 9) - 1);
 
     let Some(flags) = ShmFlags::from_bits(flags - mode as i32) else {
         // return -1;
@@ -296,31 +493,13 @@ pub async fn syscall_shmget(args: [usize; 6]) -> SyscallResult {
 
         Ok(shmid as isize)
     } else {
-        let mut key_map = async_mem::KEY_TO_SHMID.lock();
-
-        match key_map.get(&key) {
-            Some(shmid) => {
-                if flags.contains(ShmFlags::IPC_CREAT) && flags.contains(ShmFlags::IPC_EXCL) {
-                    Err(SyscallError::EEXIST)
-                } else {
-                    Ok(*shmid as isize)
-                }
-            }
-            None => {
-                if flags.contains(ShmFlags::IPC_CREAT) {
-                    let Ok((shmid, mem)) = MemorySet::create_shared_mem(key, size, pid, 0, 0, mode)
-                    else {
-                        return Err(SyscallError::EINVAL);
-                    };
-
-                    key_map.insert(key, shmid);
-                    MemorySet::add_shared_mem(shmid, mem);
-                    Ok(shmid as isize)
-                } else {
-                    Err(SyscallError::ENOENT)
-                }
-            }
-        }
+        let (shmid, _) = ipc_get(&async_mem::KEY_TO_SHMID, key, flags.bits(), || {
+            let (shmid, mem) = MemorySet::create_shared_mem(key, size, pid, 0, 0, mode)
+                .map_err(|_| AxError::InvalidInput)?;
+            MemorySet::add_shared_mem(shmid, mem);
+            Ok(shmid)
+        })?;
+        Ok(shmid as isize)
     }
 }
 
@@ -398,8 +577,153 @@ pub async fn syscall_shmat(args: [usize; 6]) -> SyscallResult {
     Ok(addr.as_usize() as isize)
 }
 
-/// # mlock
-#[cfg(target_arch = "x86_64")]
-pub fn syscall_mlock(_args: [usize; 6]) -> SyscallResult {
+/// 当前进程可以锁定的内存大小，特权进程不受 RLIMIT_MEMLOCK 的限制
+fn memlock_limit(process: &Executor) -> usize {
+    if process.cred.lock().is_privileged() {
+        usize::MAX
+    } else {
+        process
+            .get_rlimit(RLIMIT_MEMLOCK)
+            .rlim_cur
+            .try_into()
+            .unwrap_or(usize::MAX)
+    }
+}
+
+async fn mlock(start: usize, len: usize, populate: bool) -> SyscallResult {
+    let process = current_executor().await;
+    let limit = memlock_limit(&process);
+    let result = process
+        .memory_set
+        .lock()
+        .await
+        .mlock(start.into(), len, populate, limit)
+        .await;
+    flush_tlb(None);
+    match result {
+        Ok(()) => Ok(0),
+        // 超出 RLIMIT_MEMLOCK 或者范围中有没有映射的部分
+        Err(AxError::NoMemory) => Err(SyscallError::ENOMEM),
+        Err(_) => Err(SyscallError::EAGAIN),
+    }
+}
+
+/// 锁定 `[start, start + len)` 中的页面，页面会被预先分配，之后不会被换出
+/// # Arguments
+/// * `start` - usize
+/// * `len` - usize
+pub async fn syscall_mlock(args: [usize; 6]) -> SyscallResult {
+    mlock(args[0], args[1], true).await
+}
+
+const MLOCK_ONFAULT: usize = 1;
+
+/// 与 mlock 相同，但 `MLOCK_ONFAULT` 时页面在第一次访问时才分配
+/// # Arguments
+/// * `start` - usize
+/// * `len` - usize
+/// * `flags` - usize
+pub async fn syscall_mlock2(args: [usize; 6]) -> SyscallResult {
+    let flags = args[2];
+    if flags & !MLOCK_ONFAULT != 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    mlock(args[0], args[1], flags & MLOCK_ONFAULT == 0).await
+}
+
+/// 解除 `[start, start + len)` 中页面的锁定
+/// # Arguments
+/// * `start` - usize
+/// * `len` - usize
+pub async fn syscall_munlock(args: [usize; 6]) -> SyscallResult {
+    let (start, len) = (args[0], args[1]);
+    current_executor()
+        .await
+        .memory_set
+        .lock()
+        .await
+        .munlock(start.into(), len)
+        .await
+        .map_err(|_| SyscallError::ENOMEM)?;
+    Ok(0)
+}
+
+const MCL_CURRENT: usize = 1;
+const MCL_FUTURE: usize = 2;
+const MCL_ONFAULT: usize = 4;
+
+/// 锁定地址空间中现有的（`MCL_CURRENT`）或者之后新建的（`MCL_FUTURE`）映射
+/// # Arguments
+/// * `flags` - usize
+pub async fn syscall_mlockall(args: [usize; 6]) -> SyscallResult {
+    let flags = args[0];
+    if flags & !(MCL_CURRENT | MCL_FUTURE | MCL_ONFAULT) != 0
+        || flags & (MCL_CURRENT | MCL_FUTURE) == 0
+    {
+        return Err(SyscallError::EINVAL);
+    }
+    let process = current_executor().await;
+    let limit = memlock_limit(&process);
+    let result = process
+        .memory_set
+        .lock()
+        .await
+        .mlockall(
+            flags & MCL_CURRENT != 0,
+            flags & MCL_FUTURE != 0,
+            flags & MCL_ONFAULT == 0,
+            limit,
+        )
+        .await;
+    flush_tlb(None);
+    match result {
+        Ok(()) => Ok(0),
+        Err(AxError::NoMemory) => Err(SyscallError::ENOMEM),
+        Err(_) => Err(SyscallError::EAGAIN),
+    }
+}
+
+/// 解除地址空间中所有映射的锁定，之后新建的映射也不再被锁定
+pub async fn syscall_munlockall(_args: [usize; 6]) -> SyscallResult {
+    current_executor()
+        .await
+        .memory_set
+        .lock()
+        .await
+        .munlockall();
     Ok(0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_advice, start_prefetch_task, MADV_DODUMP, MADV_DONTNEED, MADV_FREE, MADV_REMOVE,
+        MADV_WILLNEED, MAX_PREFETCH_TASKS, PREFETCH_TASKS,
+    };
+    use crate::SyscallError;
+    use async_mem::Advice;
+    use core::sync::atomic::Ordering;
+
+    #[test]
+    fn test_parse_advice() {
+        assert_eq!(Ok(Some(Advice::WillNeed)), parse_advice(MADV_WILLNEED));
+        assert_eq!(Ok(Some(Advice::DontNeed)), parse_advice(MADV_DONTNEED));
+        assert_eq!(Ok(Some(Advice::Free)), parse_advice(MADV_FREE));
+        // 不支持的建议直接忽略
+        assert_eq!(Ok(None), parse_advice(MADV_REMOVE));
+        assert_eq!(Ok(None), parse_advice(MADV_DODUMP));
+        assert_eq!(Err(SyscallError::EINVAL), parse_advice(5));
+        assert_eq!(Err(SyscallError::EINVAL), parse_advice(-1));
+    }
+
+    #[test]
+    fn test_prefetch_task_limit() {
+        for _ in 0..MAX_PREFETCH_TASKS {
+            assert!(start_prefetch_task());
+        }
+        assert!(!start_prefetch_task());
+        PREFETCH_TASKS.fetch_sub(1, Ordering::AcqRel);
+        assert!(start_prefetch_task());
+        PREFETCH_TASKS.store(0, Ordering::Release);
+    }
+}
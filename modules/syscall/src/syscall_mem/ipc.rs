@@ -0,0 +1,455 @@
+//! System V 消息队列与信号量相关的系统调用
+//!
+//! 对象本身位于 async_mem 的 `msg` 与 `sem` 模块中，与共享内存共用 `ipc` 模块中的键空间。
+//! 无法立即完成的操作在对象的等待队列上等待，可以被信号打断。
+extern crate alloc;
+
+use alloc::vec::Vec;
+use async_mem::{
+    ipc::{IpcPerm, IPC_NOWAIT, IPC_RMID, IPC_SET, IPC_STAT},
+    msg::{self, MSGMAX, MSGMNB},
+    sem::{self, SemBuf, SEMOPM},
+};
+use axerrno::AxError;
+use axhal::time::current_time;
+use core::time::Duration;
+use executor::{cred::Credentials, current_executor, Executor};
+
+use crate::{IpcPerm64, MsqidDs, SemidDs, SyscallError, SyscallResult, TimeSecs};
+
+/// 旧版本的 glibc 在 cmd 中加入的标志，表示使用 64 位的结构体
+const IPC_64: i32 = 0x100;
+
+const GETPID: i32 = 11;
+const GETVAL: i32 = 12;
+const GETALL: i32 = 13;
+const GETNCNT: i32 = 14;
+const GETZCNT: i32 = 15;
+const SETVAL: i32 = 16;
+const SETALL: i32 = 17;
+
+/// 读权限
+const IPC_READ: u16 = 0o4;
+/// 写权限，消息队列的发送与信号量的修改需要这一权限
+const IPC_WRITE: u16 = 0o2;
+
+/// `flags` 中的访问权限请求的权限，用于检查 msgget 与 semget 得到的已有对象
+fn requested_access(flags: i32) -> u16 {
+    ((flags >> 6) | (flags >> 3) | flags) as u16 & 0o7
+}
+
+/// 当前进程对 `perm` 是否具有 `access` 权限
+fn check_access(cred: &Credentials, perm: &IpcPerm, access: u16) -> Result<(), SyscallError> {
+    if perm.permitted(cred.euid, cred.egid, &cred.groups, access) {
+        Ok(())
+    } else {
+        Err(SyscallError::EACCES)
+    }
+}
+
+/// 当前进程能否修改或者删除 `perm` 对应的对象
+fn check_owner(cred: &Credentials, perm: &IpcPerm) -> Result<(), SyscallError> {
+    if perm.is_owner(cred.euid) {
+        Ok(())
+    } else {
+        Err(SyscallError::EPERM)
+    }
+}
+
+impl From<&IpcPerm> for IpcPerm64 {
+    fn from(perm: &IpcPerm) -> Self {
+        Self {
+            key: perm.key,
+            uid: perm.uid,
+            gid: perm.gid,
+            cuid: perm.cuid,
+            cgid: perm.cgid,
+            mode: perm.mode,
+            ..Default::default()
+        }
+    }
+}
+
+/// 从用户地址 `ptr` 处读取一个 `T`
+async fn read_user<T: Copy>(process: &Executor, ptr: usize) -> Result<T, SyscallError> {
+    let ptr = ptr as *const T;
+    if ptr.is_null() || process.manual_alloc_type_for_lazy(ptr).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    Ok(unsafe { ptr.read_unaligned() })
+}
+
+/// 将 `value` 写入用户地址 `ptr` 处
+async fn write_user<T: Copy>(process: &Executor, ptr: usize, value: T) -> SyscallResult {
+    let ptr = ptr as *mut T;
+    if ptr.is_null() || process.manual_alloc_type_for_lazy(ptr).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    unsafe { ptr.write_unaligned(value) };
+    Ok(0)
+}
+
+/// 检查用户地址范围 `[start, start + len)` 是否可以访问
+async fn check_user_range(process: &Executor, start: usize, len: usize) -> SyscallResult {
+    let end = start.checked_add(len).ok_or(SyscallError::EFAULT)?;
+    if start == 0
+        || (len > 0
+            && process
+                .manual_alloc_range_for_lazy(start.into(), end.into())
+                .await
+                .is_err())
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    Ok(0)
+}
+
+/// 获取或者创建一个消息队列，返回其标识符
+/// # Arguments
+/// * `key` - i32
+/// * `flags` - i32
+pub async fn syscall_msgget(args: [usize; 6]) -> SyscallResult {
+    let key = args[0] as i32;
+    let flags = args[1] as i32;
+    let cred = current_executor().await.cred();
+    let (msqid, created) = msg::msgget(key, flags, cred.euid, cred.egid)?;
+    if !created {
+        let queue = msg::get_msg_queue(msqid).ok_or(SyscallError::EIDRM)?;
+        check_access(&cred, &queue.info().perm, requested_access(flags))?;
+    }
+    Ok(msqid as isize)
+}
+
+/// 向消息队列发送一条消息，队列已满时等待
+/// # Arguments
+/// * `msqid` - i32
+/// * `msgp` - *const msgbuf，开头为 long 类型的消息类型，之后为消息的内容
+/// * `msgsz` - usize，消息内容的长度
+/// * `flags` - i32
+pub async fn syscall_msgsnd(args: [usize; 6]) -> SyscallResult {
+    let msqid = args[0] as i32;
+    let msgp = args[1];
+    let msgsz = args[2];
+    let flags = args[3] as i32;
+    if msqid < 0 || msgsz > MSGMAX {
+        return Err(SyscallError::EINVAL);
+    }
+    let process = current_executor().await;
+    let mtype: isize = read_user(&process, msgp).await?;
+    if mtype < 1 {
+        return Err(SyscallError::EINVAL);
+    }
+    let text = msgp + core::mem::size_of::<isize>();
+    check_user_range(&process, text, msgsz).await?;
+    let data = unsafe { core::slice::from_raw_parts(text as *const u8, msgsz) }.to_vec();
+
+    let queue = msg::get_msg_queue(msqid).ok_or(SyscallError::EINVAL)?;
+    check_access(&process.cred(), &queue.info().perm, IPC_WRITE)?;
+    loop {
+        let version = queue.version();
+        if queue.try_send(mtype, &data, process.pid())? {
+            return Ok(0);
+        }
+        if flags & IPC_NOWAIT != 0 {
+            return Err(SyscallError::EAGAIN);
+        }
+        process
+            .wait_interruptible(&queue.wq, None, || queue.version() != version)
+            .await
+            .map_err(|_| SyscallError::EINTR)?;
+    }
+}
+
+/// 从消息队列接收一条消息，没有符合条件的消息时等待，返回消息内容的长度
+/// # Arguments
+/// * `msqid` - i32
+/// * `msgp` - *mut msgbuf
+/// * `msgsz` - usize，缓冲区中消息内容的长度
+/// * `msgtyp` - isize，选择消息的方式见 [`msg::MsgQueue::try_recv`]
+/// * `flags` - i32
+pub async fn syscall_msgrcv(args: [usize; 6]) -> SyscallResult {
+    let msqid = args[0] as i32;
+    let msgp = args[1];
+    let msgsz = args[2];
+    let msgtyp = args[3] as isize;
+    let flags = args[4] as i32;
+    if msqid < 0 || (msgsz as isize) < 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let process = current_executor().await;
+    check_user_range(&process, msgp, core::mem::size_of::<isize>() + msgsz).await?;
+
+    let queue = msg::get_msg_queue(msqid).ok_or(SyscallError::EINVAL)?;
+    check_access(&process.cred(), &queue.info().perm, IPC_READ)?;
+    let message = loop {
+        let version = queue.version();
+        if let Some(message) = queue.try_recv(msgtyp, msgsz, flags, process.pid())? {
+            break message;
+        }
+        if flags & IPC_NOWAIT != 0 {
+            return Err(SyscallError::ENOMSG);
+        }
+        process
+            .wait_interruptible(&queue.wq, None, || queue.version() != version)
+            .await
+            .map_err(|_| SyscallError::EINTR)?;
+    };
+    // 缓冲区在等待之前已经检查过，但等待期间可能被解除映射，此时将消息放回队列，不让消息丢失
+    let len = message.data.len().min(msgsz);
+    if check_user_range(&process, msgp, core::mem::size_of::<isize>() + len)
+        .await
+        .is_err()
+    {
+        queue.requeue(message);
+        return Err(SyscallError::EFAULT);
+    }
+    let text = msgp + core::mem::size_of::<isize>();
+    unsafe {
+        (msgp as *mut isize).write_unaligned(message.mtype);
+        core::slice::from_raw_parts_mut(text as *mut u8, len).copy_from_slice(&message.data[..len]);
+    }
+    Ok(len as isize)
+}
+
+/// 消息队列的控制操作：IPC_STAT、IPC_SET 与 IPC_RMID
+/// # Arguments
+/// * `msqid` - i32
+/// * `cmd` - i32
+/// * `buf` - *mut msqid64_ds
+pub async fn syscall_msgctl(args: [usize; 6]) -> SyscallResult {
+    let msqid = args[0] as i32;
+    let cmd = args[1] as i32 & !IPC_64;
+    let buf = args[2];
+    if msqid < 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let process = current_executor().await;
+    let cred = process.cred();
+    let queue = msg::get_msg_queue(msqid).ok_or(SyscallError::EINVAL)?;
+    let info = queue.info();
+    match cmd {
+        IPC_STAT => {
+            check_access(&cred, &info.perm, IPC_READ)?;
+            let ds = MsqidDs {
+                msg_perm: IpcPerm64::from(&info.perm),
+                msg_stime: info.stime as isize,
+                msg_rtime: info.rtime as isize,
+                msg_ctime: info.ctime as isize,
+                msg_cbytes: info.cbytes,
+                msg_qnum: info.qnum,
+                msg_qbytes: info.qbytes,
+                msg_lspid: info.lspid as i32,
+                msg_lrpid: info.lrpid as i32,
+                ..Default::default()
+            };
+            write_user(&process, buf, ds).await
+        }
+        IPC_SET => {
+            let ds: MsqidDs = read_user(&process, buf).await?;
+            check_owner(&cred, &info.perm)?;
+            if ds.msg_qbytes > MSGMNB && !cred.is_privileged() {
+                return Err(SyscallError::EPERM);
+            }
+            queue.set_info(
+                ds.msg_perm.uid,
+                ds.msg_perm.gid,
+                ds.msg_perm.mode,
+                ds.msg_qbytes,
+            );
+            Ok(0)
+        }
+        IPC_RMID => {
+            check_owner(&cred, &info.perm)?;
+            msg::remove_msg_queue(msqid);
+            Ok(0)
+        }
+        _ => Err(SyscallError::EINVAL),
+    }
+}
+
+/// 获取或者创建一个信号量集，返回其标识符
+/// # Arguments
+/// * `key` - i32
+/// * `nsems` - i32
+/// * `flags` - i32
+pub async fn syscall_semget(args: [usize; 6]) -> SyscallResult {
+    let key = args[0] as i32;
+    let nsems = args[1] as i32;
+    let flags = args[2] as i32;
+    if nsems < 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let cred = current_executor().await.cred();
+    let (semid, created) = sem::semget(key, nsems as usize, flags, cred.euid, cred.egid)?;
+    if !created {
+        let set = sem::get_sem_set(semid).ok_or(SyscallError::EIDRM)?;
+        check_access(&cred, &set.info().perm, requested_access(flags))?;
+    }
+    Ok(semid as isize)
+}
+
+/// 对信号量集原子地执行一组操作
+/// # Arguments
+/// * `semid` - i32
+/// * `sops` - *const sembuf
+/// * `nsops` - usize
+pub async fn syscall_semop(args: [usize; 6]) -> SyscallResult {
+    semtimedop(args[0] as i32, args[1], args[2], None).await
+}
+
+/// 与 semop 相同，但等待的时间不超过 `timeout`，超时返回 EAGAIN
+/// # Arguments
+/// * `semid` - i32
+/// * `sops` - *const sembuf
+/// * `nsops` - usize
+/// * `timeout` - *const TimeSecs，为空时一直等待
+pub async fn syscall_semtimedop(args: [usize; 6]) -> SyscallResult {
+    let timeout = args[3];
+    let deadline = if timeout != 0 {
+        let process = current_executor().await;
+        let timeout: TimeSecs = read_user(&process, timeout).await?;
+        if (timeout.tv_sec as isize) < 0 || timeout.tv_nsec >= 1_000_000_000 {
+            return Err(SyscallError::EINVAL);
+        }
+        // 溢出的截止时间视为一直等待
+        current_time().checked_add(Duration::new(timeout.tv_sec as u64, timeout.tv_nsec as u32))
+    } else {
+        None
+    };
+    semtimedop(args[0] as i32, args[1], args[2], deadline).await
+}
+
+async fn semtimedop(
+    semid: i32,
+    sops: usize,
+    nsops: usize,
+    deadline: Option<Duration>,
+) -> SyscallResult {
+    if semid < 0 || nsops == 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    if nsops > SEMOPM {
+        return Err(SyscallError::E2BIG);
+    }
+    let process = current_executor().await;
+    check_user_range(&process, sops, nsops * core::mem::size_of::<SemBuf>()).await?;
+    let ops: Vec<SemBuf> =
+        unsafe { core::slice::from_raw_parts(sops as *const SemBuf, nsops) }.to_vec();
+
+    let set = sem::get_sem_set(semid).ok_or(SyscallError::EINVAL)?;
+    let access = if ops.iter().any(|op| op.sem_op != 0) {
+        IPC_WRITE
+    } else {
+        IPC_READ
+    };
+    check_access(&process.cred(), &set.info().perm, access)?;
+    loop {
+        let version = set.version();
+        let Some(blocked) = set.try_semop(&ops, process.pid())? else {
+            return Ok(0);
+        };
+        set.set_waiting(&ops[blocked], true);
+        let result = process
+            .wait_interruptible(&set.wq, deadline, || set.version() != version)
+            .await;
+        set.set_waiting(&ops[blocked], false);
+        match result {
+            Ok(()) => {}
+            Err(AxError::Timeout) => return Err(SyscallError::EAGAIN),
+            Err(_) => return Err(SyscallError::EINTR),
+        }
+    }
+}
+
+/// 信号量集的控制操作
+/// # Arguments
+/// * `semid` - i32
+/// * `semnum` - i32
+/// * `cmd` - i32
+/// * `arg` - union semun，按照 `cmd` 为信号量的值或者指向用户缓冲区的指针
+pub async fn syscall_semctl(args: [usize; 6]) -> SyscallResult {
+    let semid = args[0] as i32;
+    let semnum = args[1];
+    let cmd = args[2] as i32 & !IPC_64;
+    let arg = args[3];
+    if semid < 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let process = current_executor().await;
+    let cred = process.cred();
+    let set = sem::get_sem_set(semid).ok_or(SyscallError::EINVAL)?;
+    let info = set.info();
+    match cmd {
+        IPC_STAT => {
+            check_access(&cred, &info.perm, IPC_READ)?;
+            let ds = SemidDs {
+                sem_perm: IpcPerm64::from(&info.perm),
+                sem_otime: info.otime as isize,
+                sem_ctime: info.ctime as isize,
+                sem_nsems: info.nsems,
+                ..Default::default()
+            };
+            write_user(&process, arg, ds).await
+        }
+        IPC_SET => {
+            let ds: SemidDs = read_user(&process, arg).await?;
+            check_owner(&cred, &info.perm)?;
+            set.set_perm(ds.sem_perm.uid, ds.sem_perm.gid, ds.sem_perm.mode);
+            Ok(0)
+        }
+        IPC_RMID => {
+            check_owner(&cred, &info.perm)?;
+            sem::remove_sem_set(semid);
+            Ok(0)
+        }
+        GETVAL | GETPID | GETNCNT | GETZCNT => {
+            check_access(&cred, &info.perm, IPC_READ)?;
+            let value = match cmd {
+                GETVAL => set.get_val(semnum)? as isize,
+                GETPID => set.get_pid(semnum)? as isize,
+                GETNCNT => set.get_ncnt(semnum)? as isize,
+                _ => set.get_zcnt(semnum)? as isize,
+            };
+            Ok(value)
+        }
+        GETALL => {
+            check_access(&cred, &info.perm, IPC_READ)?;
+            let vals = set.get_all();
+            check_user_range(&process, arg, vals.len() * 2).await?;
+            unsafe {
+                core::slice::from_raw_parts_mut(arg as *mut u16, vals.len()).copy_from_slice(&vals);
+            }
+            Ok(0)
+        }
+        SETVAL => {
+            check_access(&cred, &info.perm, IPC_WRITE)?;
+            set.set_val(semnum, arg as i32, process.pid())?;
+            Ok(0)
+        }
+        SETALL => {
+            check_access(&cred, &info.perm, IPC_WRITE)?;
+            check_user_range(&process, arg, info.nsems * 2).await?;
+            let vals =
+                unsafe { core::slice::from_raw_parts(arg as *const u16, info.nsems) }.to_vec();
+            set.set_all(&vals, process.pid())?;
+            Ok(0)
+        }
+        _ => Err(SyscallError::EINVAL),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{requested_access, IPC_READ, IPC_WRITE};
+    use async_mem::ipc::{IPC_CREAT, IPC_EXCL};
+
+    #[test]
+    fn test_requested_access() {
+        assert_eq!(0, requested_access(0));
+        assert_eq!(0, requested_access(IPC_CREAT | IPC_EXCL));
+        assert_eq!(IPC_READ | IPC_WRITE, requested_access(IPC_CREAT | 0o600));
+        assert_eq!(IPC_READ | IPC_WRITE, requested_access(0o640));
+        assert_eq!(IPC_READ, requested_access(0o004));
+        assert_eq!(IPC_WRITE, requested_access(0o020));
+    }
+}
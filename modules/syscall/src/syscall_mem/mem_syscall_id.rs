@@ -13,6 +13,14 @@ numeric_enum_macro::numeric_enum! {
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 pub enum MemSyscallId {
     // mem
+    MSGGET = 186,
+    MSGCTL = 187,
+    MSGRCV = 188,
+    MSGSND = 189,
+    SEMGET = 190,
+    SEMCTL = 191,
+    SEMTIMEDOP = 192,
+    SEMOP = 193,
     SHMGET = 194,
     SHMCTL = 195,
     SHMAT = 196,
@@ -22,7 +30,15 @@ pub enum MemSyscallId {
     MMAP = 222,
     MSYNC = 227,
     MPROTECT = 226,
+    SWAPON = 224,
+    SWAPOFF = 225,
+    MADVISE = 233,
+    MLOCK = 228,
+    MUNLOCK = 229,
+    MLOCKALL = 230,
+    MUNLOCKALL = 231,
     MEMBARRIER = 283,
+    MLOCK2 = 284,
 }
 }
 
@@ -43,7 +59,22 @@ numeric_enum_macro::numeric_enum! {
         MMAP = 9,
         MSYNC = 26,
         MPROTECT = 10,
+        SWAPON = 167,
+        SWAPOFF = 168,
+        MADVISE = 28,
         MEMBARRIER = 324,
         MLOCK = 149,
+        MUNLOCK = 150,
+        MLOCKALL = 151,
+        MUNLOCKALL = 152,
+        MLOCK2 = 325,
+        SEMGET = 64,
+        SEMOP = 65,
+        SEMCTL = 66,
+        MSGGET = 68,
+        MSGSND = 69,
+        MSGRCV = 70,
+        MSGCTL = 71,
+        SEMTIMEDOP = 220,
     }
 }
@@ -3,11 +3,13 @@
 use crate::SyscallResult;
 
 mod imp;
+mod ipc;
 
 mod mem_syscall_id;
 pub use mem_syscall_id::MemSyscallId::{self, *};
 
 use imp::*;
+use ipc::*;
 /// 与内存相关的系统调用
 pub async fn mem_syscall(
     syscall_id: mem_syscall_id::MemSyscallId,
@@ -21,12 +23,26 @@ pub async fn mem_syscall(
         MMAP => syscall_mmap(args).await,
         MSYNC => syscall_msync(args).await,
         MPROTECT => syscall_mprotect(args).await,
+        SWAPON => syscall_swapon(args).await,
+        SWAPOFF => syscall_swapoff(args).await,
+        MADVISE => syscall_madvise(args).await,
         MEMBARRIER => Ok(0),
         SHMGET => syscall_shmget(args).await,
         SHMCTL => Ok(0),
         SHMAT => syscall_shmat(args).await,
-        #[cfg(target_arch = "x86_64")]
-        MLOCK => syscall_mlock(args),
+        MLOCK => syscall_mlock(args).await,
+        MUNLOCK => syscall_munlock(args).await,
+        MLOCKALL => syscall_mlockall(args).await,
+        MUNLOCKALL => syscall_munlockall(args).await,
+        MLOCK2 => syscall_mlock2(args).await,
+        MSGGET => syscall_msgget(args).await,
+        MSGSND => syscall_msgsnd(args).await,
+        MSGRCV => syscall_msgrcv(args).await,
+        MSGCTL => syscall_msgctl(args).await,
+        SEMGET => syscall_semget(args).await,
+        SEMOP => syscall_semop(args).await,
+        SEMTIMEDOP => syscall_semtimedop(args).await,
+        SEMCTL => syscall_semctl(args).await,
         #[allow(unused)]
         _ => {
             panic!("Invalid Syscall Id: {:?}!", syscall_id);
@@ -2,18 +2,26 @@ mod signal;
 
 mod futex;
 
+mod ptrace;
+
 mod schedule;
 
 mod task;
 
+mod timer;
+
 mod utils;
 
 pub use signal::*;
 
 pub use futex::*;
 
+pub use ptrace::*;
+
 pub use schedule::*;
 
 pub use task::*;
 
+pub use timer::*;
+
 pub use utils::*;
@@ -0,0 +1,204 @@
+//! 进程跟踪（ptrace）相关的系统调用
+use alloc::sync::Arc;
+use core::mem::size_of;
+
+use axsignal::signal_no::SignalNo;
+use executor::{
+    current_executor, ptrace::*, send_signal_to_process, Executor, KERNEL_EXECUTOR_ID, PID2PC,
+    TID2TASK,
+};
+
+use crate::{IoVec, SyscallError, SyscallResult};
+
+/// PTRACE_GETREGSET 中表示通用寄存器
+const NT_PRSTATUS: usize = 1;
+
+/// 信号的最大编号
+const MAX_SIGNAL: usize = 64;
+
+/// 检查 `tracer` 能否跟踪 `tracee`：root 可以跟踪任何进程，
+/// 否则 tracee 的实际、有效与保存的用户 id 与组 id 都必须与 tracer 的实际 id 相同
+fn may_attach(tracer: &Executor, tracee: &Executor) -> bool {
+    let tracer = tracer.cred();
+    let tracee = tracee.cred();
+    let uids = [tracee.uid, tracee.euid, tracee.suid];
+    let gids = [tracee.gid, tracee.egid, tracee.sgid];
+    tracer.is_privileged()
+        || (uids.iter().all(|id| *id == tracer.uid) && gids.iter().all(|id| *id == tracer.gid))
+}
+
+/// 找到 `pid` 对应的进程：`pid` 为线程 id 时同时返回该线程，请求只作用于这个线程
+fn find_tracee(pid: u64) -> Option<(Arc<Executor>, Option<u64>)> {
+    if let Some(process) = PID2PC.get(&pid) {
+        return Some((process, None));
+    }
+    let task = TID2TASK.get(&pid)?;
+    Some((PID2PC.get(&task.get_process_id())?, Some(pid)))
+}
+
+/// 进程跟踪
+///
+/// 除 PTRACE_TRACEME、PTRACE_ATTACH 与 PTRACE_KILL 之外，其余请求都要求被跟踪的线程处于停止状态。
+/// `pid` 为进程 id 时作用于其中任意一个停止的线程
+/// # Arguments
+/// * `request` - usize
+/// * `pid` - u64，进程 id 或者线程 id
+/// * `addr` - usize
+/// * `data` - usize
+pub async fn syscall_ptrace(args: [usize; 6]) -> SyscallResult {
+    let request = args[0];
+    let pid = args[1] as u64;
+    let addr = args[2];
+    let data = args[3];
+    let curr_process = current_executor().await;
+    let tracer = curr_process.pid();
+    if request == PTRACE_TRACEME {
+        // 由父进程跟踪自身
+        return curr_process
+            .attach_tracer(curr_process.get_parent())
+            .map(|_| 0)
+            .map_err(|_| SyscallError::EPERM);
+    }
+    let (tracee, thread) = find_tracee(pid).ok_or(SyscallError::ESRCH)?;
+    let pid = tracee.pid();
+    match request {
+        PTRACE_ATTACH => {
+            if pid == tracer || pid == KERNEL_EXECUTOR_ID || !may_attach(&curr_process, &tracee) {
+                return Err(SyscallError::EPERM);
+            }
+            tracee
+                .attach_tracer(tracer)
+                .map_err(|_| SyscallError::EPERM)?;
+            let _ = send_signal_to_process(pid as isize, SignalNo::SIGSTOP as isize, None).await;
+            return Ok(0);
+        }
+        PTRACE_KILL => {
+            if tracee.tracer() != Some(tracer) {
+                return Err(SyscallError::ESRCH);
+            }
+            let _ = send_signal_to_process(pid as isize, SignalNo::SIGKILL as isize, None).await;
+            return Ok(0);
+        }
+        _ => {}
+    }
+    let tid = tracee
+        .ptrace_stopped_task(tracer, thread)
+        .ok_or(SyscallError::ESRCH)?;
+    match request {
+        PTRACE_PEEKTEXT | PTRACE_PEEKDATA => {
+            let mut word = [0u8; size_of::<usize>()];
+            tracee
+                .ptrace_access_memory(tracer, addr, &mut word, false)
+                .await
+                .map_err(|_| SyscallError::EIO)?;
+            if curr_process
+                .manual_alloc_type_for_lazy(data as *const usize)
+                .await
+                .is_err()
+            {
+                return Err(SyscallError::EFAULT);
+            }
+            unsafe {
+                *(data as *mut usize) = usize::from_ne_bytes(word);
+            }
+            Ok(0)
+        }
+        PTRACE_POKETEXT | PTRACE_POKEDATA => {
+            let mut word = data.to_ne_bytes();
+            tracee
+                .ptrace_access_memory(tracer, addr, &mut word, true)
+                .await
+                .map_err(|_| SyscallError::EIO)?;
+            Ok(0)
+        }
+        PTRACE_CONT | PTRACE_SYSCALL => {
+            if data > MAX_SIGNAL {
+                return Err(SyscallError::EIO);
+            }
+            let mode = if request == PTRACE_SYSCALL {
+                ResumeMode::Syscall
+            } else {
+                ResumeMode::Cont
+            };
+            tracee
+                .ptrace_resume(tid, mode, data)
+                .map_err(|_| SyscallError::ESRCH)?;
+            Ok(0)
+        }
+        // RISC-V 没有硬件单步执行，与 Linux 相同返回 EIO
+        PTRACE_SINGLESTEP => Err(SyscallError::EIO),
+        PTRACE_DETACH => {
+            if data > MAX_SIGNAL {
+                return Err(SyscallError::EIO);
+            }
+            tracee.detach_tracer(data);
+            Ok(0)
+        }
+        PTRACE_SETOPTIONS => {
+            tracee
+                .set_ptrace_options(data)
+                .map_err(|_| SyscallError::EINVAL)?;
+            Ok(0)
+        }
+        PTRACE_GETREGS | PTRACE_SETREGS => {
+            access_regs(&curr_process, &tracee, tid, data, request == PTRACE_SETREGS).await?;
+            Ok(0)
+        }
+        PTRACE_GETREGSET | PTRACE_SETREGSET => {
+            if addr != NT_PRSTATUS {
+                return Err(SyscallError::EINVAL);
+            }
+            let iov = data as *mut IoVec;
+            if curr_process
+                .manual_alloc_type_for_lazy(iov as *const IoVec)
+                .await
+                .is_err()
+            {
+                return Err(SyscallError::EFAULT);
+            }
+            let iov = unsafe { &mut *iov };
+            if iov.len < size_of::<UserRegs>() {
+                return Err(SyscallError::EINVAL);
+            }
+            access_regs(
+                &curr_process,
+                &tracee,
+                tid,
+                iov.base as usize,
+                request == PTRACE_SETREGSET,
+            )
+            .await?;
+            iov.len = size_of::<UserRegs>();
+            Ok(0)
+        }
+        _ => Err(SyscallError::EIO),
+    }
+}
+
+/// 在跟踪者的 `buf` 与被跟踪进程中停止的线程 `tid` 的寄存器之间复制数据
+async fn access_regs(
+    tracer: &Executor,
+    tracee: &Executor,
+    tid: u64,
+    buf: usize,
+    write: bool,
+) -> SyscallResult {
+    let buf = buf as *mut UserRegs;
+    if tracer
+        .manual_alloc_type_for_lazy(buf as *const UserRegs)
+        .await
+        .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    tracee
+        .with_stopped_trap_frame(tracer.pid(), tid, |tf| unsafe {
+            if write {
+                (*buf).write_to_trap_frame(tf);
+            } else {
+                *buf = UserRegs::from_trap_frame(tf);
+            }
+        })
+        .map_err(|_| SyscallError::ESRCH)?;
+    Ok(0)
+}
@@ -24,13 +24,10 @@ pub async fn syscall_sched_getaffinity(args: [usize; 6]) -> SyscallResult {
     let cpu_set_size = args[1];
     let mask = args[2] as *mut usize;
     // let task: LazyInit<AxTaskRef> = LazyInit::new();
-    let tid2task = TID2TASK.lock().await;
-    let pid2task = PID2PC.lock().await;
     let pid = pid as u64;
-    let task = if tid2task.contains_key(&pid) {
-        Arc::clone(tid2task.get(&pid).unwrap())
-    } else if pid2task.contains_key(&pid) {
-        let process = pid2task.get(&pid).unwrap();
+    let task = if let Some(task) = TID2TASK.get(&pid) {
+        task
+    } else if let Some(process) = PID2PC.get(&pid) {
         process.main_task.lock().await.clone().unwrap()
         // process
         //     .tasks
@@ -46,9 +43,6 @@ pub async fn syscall_sched_getaffinity(args: [usize; 6]) -> SyscallResult {
         return Err(SyscallError::ESRCH);
     };
 
-    drop(pid2task);
-    drop(tid2task);
-
     let process = current_executor().await;
     if process
         .manual_alloc_for_lazy(VirtAddr::from(mask as usize))
@@ -78,13 +72,10 @@ pub async fn syscall_sched_setaffinity(args: [usize; 6]) -> SyscallResult {
     let pid = args[0];
     let cpu_set_size = args[1];
     let mask = args[2] as *const usize;
-    let tid2task = TID2TASK.lock().await;
-    let pid2task = PID2PC.lock().await;
     let pid = pid as u64;
-    let task = if tid2task.contains_key(&pid) {
-        Arc::clone(tid2task.get(&pid).unwrap())
-    } else if pid2task.contains_key(&pid) {
-        let process = pid2task.get(&pid).unwrap();
+    let task = if let Some(task) = TID2TASK.get(&pid) {
+        task
+    } else if let Some(process) = PID2PC.get(&pid) {
         process.main_task.lock().await.clone().unwrap()
         // process
         //     .tasks
@@ -100,9 +91,6 @@ pub async fn syscall_sched_setaffinity(args: [usize; 6]) -> SyscallResult {
         return Err(SyscallError::ESRCH);
     };
 
-    drop(pid2task);
-    drop(tid2task);
-
     let process = current_executor().await;
     if process
         .manual_alloc_for_lazy(VirtAddr::from(mask as usize))
@@ -131,13 +119,10 @@ pub async fn syscall_sched_setscheduler(args: [usize; 6]) -> SyscallResult {
         return Err(SyscallError::EINVAL);
     }
 
-    let tid2task = TID2TASK.lock().await;
-    let pid2task = PID2PC.lock().await;
     let pid = pid as u64;
-    let task = if tid2task.contains_key(&pid) {
-        Arc::clone(tid2task.get(&pid).unwrap())
-    } else if pid2task.contains_key(&pid) {
-        let process = pid2task.get(&pid).unwrap();
+    let task = if let Some(task) = TID2TASK.get(&pid) {
+        task
+    } else if let Some(process) = PID2PC.get(&pid) {
         process.main_task.lock().await.clone().unwrap()
         // process
         //     .tasks
@@ -153,9 +138,6 @@ pub async fn syscall_sched_setscheduler(args: [usize; 6]) -> SyscallResult {
         return Err(SyscallError::ESRCH);
     };
 
-    drop(pid2task);
-    drop(tid2task);
-
     let process = current_executor().await;
     if process
         .manual_alloc_for_lazy(VirtAddr::from(param as usize))
@@ -197,13 +179,10 @@ pub async fn syscall_sched_getscheduler(args: [usize; 6]) -> SyscallResult {
         return Err(SyscallError::EINVAL);
     }
 
-    let tid2task = TID2TASK.lock().await;
-    let pid2task = PID2PC.lock().await;
     let pid = pid as u64;
-    let task = if tid2task.contains_key(&pid) {
-        Arc::clone(tid2task.get(&pid).unwrap())
-    } else if pid2task.contains_key(&pid) {
-        let process = pid2task.get(&pid).unwrap();
+    let task = if let Some(task) = TID2TASK.get(&pid) {
+        task
+    } else if let Some(process) = PID2PC.get(&pid) {
         process.main_task.lock().await.clone().unwrap()
         // process
         //     .tasks
@@ -219,9 +198,6 @@ pub async fn syscall_sched_getscheduler(args: [usize; 6]) -> SyscallResult {
         return Err(SyscallError::ESRCH);
     };
 
-    drop(pid2task);
-    drop(tid2task);
-
     let policy: isize = task.get_sched_status().policy.into();
     Ok(policy)
 }
@@ -3,9 +3,15 @@
 
 use axhal::cpu::this_cpu_id;
 // use axlog::{debug, info};
+use alloc::vec::Vec;
+use axerrno::AxError;
 use axsignal::signal_no::SignalNo;
-use axsignal::{action::SigAction, ucontext::SignalStack};
-use executor::{current_executor, current_task, yield_now};
+use axsignal::{action::SigAction, info::SigInfo, ucontext::SignalStack};
+use executor::{
+    current_executor, current_task, send_signal_to_process_group,
+    sigqueue::{SIGRTMAX, SI_TKILL},
+    yield_now, KERNEL_EXECUTOR_ID, PID2PC, TID2TASK,
+};
 
 use crate::{SigMaskFlag, SyscallError, SyscallResult, SIGSET_SIZE_IN_BYTE};
 
@@ -183,20 +189,50 @@ pub async fn syscall_sigprocmask(args: [usize; 6]) -> SyscallResult {
 /// 向pid指定的进程发送信号
 ///
 /// 由于处理信号的单位在线程上，所以若进程中有多个线程，则会发送给主线程
+///
+/// pid 为 0 时发送给当前进程所在的进程组，为 -1 时发送给除内核进程与当前进程之外的所有进程，
+/// 小于 -1 时发送给进程组 -pid
 /// # Arguments
 /// * `pid` - isize
 /// * `signum` - isize
 pub async fn syscall_kill(args: [usize; 6]) -> SyscallResult {
     let pid = args[0] as isize;
     let signum = args[1] as isize;
-    if pid > 0 && signum > 0 {
-        // 不关心是否成功
-        let _ = executor::signal::send_signal_to_process(pid, signum, None).await;
-        Ok(0)
-    } else if pid == 0 {
-        Err(SyscallError::ESRCH)
-    } else {
-        Err(SyscallError::EINVAL)
+    if signum <= 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    match pid {
+        pid if pid > 0 => {
+            // 不关心是否成功
+            let _ = executor::signal::send_signal_to_process(pid, signum, None).await;
+            Ok(0)
+        }
+        0 => {
+            let pgid = current_executor().await.pgid();
+            send_signal_to_process_group(pgid, signum, None)
+                .await
+                .map_err(|_| SyscallError::ESRCH)?;
+            Ok(0)
+        }
+        -1 => {
+            let curr_pid = current_executor().await.pid();
+            let pids: Vec<u64> = PID2PC
+                .read()
+                .keys()
+                .copied()
+                .filter(|pid| *pid != KERNEL_EXECUTOR_ID && *pid != curr_pid)
+                .collect();
+            for pid in pids {
+                let _ = executor::signal::send_signal_to_process(pid as isize, signum, None).await;
+            }
+            Ok(0)
+        }
+        pgid => {
+            send_signal_to_process_group((-pgid) as u64, signum, None)
+                .await
+                .map_err(|_| SyscallError::ESRCH)?;
+            Ok(0)
+        }
     }
 }
 
@@ -275,3 +311,84 @@ pub async fn syscall_sigaltstack(args: [usize; 6]) -> SyscallResult {
 
     Ok(0)
 }
+
+/// 读取 rt_sigqueueinfo 与 rt_tgsigqueueinfo 给出的 SigInfo
+///
+/// 与 Linux 相同，向其他进程发送时 si_code 必须小于 0 且不能为 SI_TKILL，
+/// 以免将信号伪装成由内核或 kill 发送的
+async fn read_queued_info(
+    tgid: u64,
+    signum: usize,
+    uinfo: *const SigInfo,
+) -> Result<SigInfo, SyscallError> {
+    if signum > SIGRTMAX {
+        return Err(SyscallError::EINVAL);
+    }
+    let curr_process = current_executor().await;
+    if curr_process
+        .manual_alloc_type_for_lazy(uinfo)
+        .await
+        .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    let mut info = unsafe { *uinfo };
+    if (info.si_code >= 0 || info.si_code == SI_TKILL) && tgid != curr_process.pid() {
+        return Err(SyscallError::EPERM);
+    }
+    info.si_signo = signum as i32;
+    Ok(info)
+}
+
+/// 发送实时信号失败时的错误码：排队的信号数达到 RLIMIT_SIGPENDING 时为 EAGAIN
+fn queue_error(err: AxError) -> SyscallError {
+    match err {
+        AxError::WouldBlock => SyscallError::EAGAIN,
+        _ => SyscallError::ESRCH,
+    }
+}
+
+/// 向进程发送带有 SigInfo 的信号，实时信号的每个实例都会排队
+/// # Arguments
+/// * `tgid` - u64
+/// * `signum` - usize
+/// * `uinfo` - *const SigInfo
+pub async fn syscall_rt_sigqueueinfo(args: [usize; 6]) -> SyscallResult {
+    let tgid = args[0] as u64;
+    let signum = args[1];
+    let info = read_queued_info(tgid, signum, args[2] as *const SigInfo).await?;
+    if PID2PC.get(&tgid).is_none() {
+        return Err(SyscallError::ESRCH);
+    }
+    if signum == 0 {
+        return Ok(0);
+    }
+    executor::signal::send_signal_to_process(tgid as isize, signum as isize, Some(info))
+        .await
+        .map_err(queue_error)?;
+    Ok(0)
+}
+
+/// 向线程组 `tgid` 中的线程 `tid` 发送带有 SigInfo 的信号
+/// # Arguments
+/// * `tgid` - u64
+/// * `tid` - u64
+/// * `signum` - usize
+/// * `uinfo` - *const SigInfo
+pub async fn syscall_rt_tgsigqueueinfo(args: [usize; 6]) -> SyscallResult {
+    let tgid = args[0] as u64;
+    let tid = args[1] as u64;
+    let signum = args[2];
+    let info = read_queued_info(tgid, signum, args[3] as *const SigInfo).await?;
+    match TID2TASK.get(&tid) {
+        Some(task) if task.get_process_id() == tgid => {}
+        _ => return Err(SyscallError::ESRCH),
+    }
+    if signum == 0 {
+        return Ok(0);
+    }
+    executor::signal::send_siginfo_to_thread(tid as isize, signum as isize, Some(info))
+        .await
+        .map_err(queue_error)?;
+    Ok(0)
+}
@@ -1,8 +1,7 @@
-use core::{sync::atomic::AtomicI32, time::Duration};
+use core::time::Duration;
 
 use alloc::{
     string::{String, ToString},
-    sync::Arc,
     vec::Vec,
 };
 use async_fs::api::{AsAny, OpenFlags};
@@ -10,24 +9,22 @@ use axhal::time::current_time;
 use axsignal::{info::SigInfo, signal_no::SignalNo};
 // use async_fs::api::OpenFlags;
 // use axhal::time::current_time;
-use executor::TaskId;
 use executor::{
+    cred::NGROUPS_MAX,
     current_executor,
     current_task,
-    flags::{CloneFlags, WaitStatus},
+    flags::{CloneFlags, WaitOptions, WaitStatus},
     link::{raw_ptr_to_ref_str, AT_FDCWD},
+    process_group_members,
     send_signal_to_process,
     sleep,
     wait_pid,
     yield_now,
-    Executor,
-    SignalModule,
     PID2PC, // flags::{CloneFlags, WaitStatus}, link::{raw_ptr_to_ref_str, AT_FDCWD}
             // set_child_tid,
             // signal::send_signal_to_process,
             // sleep_now_task, wait_pid, yield_now_task, Process, PID2PC,
 };
-use sync::Mutex;
 // use sync::Mutex;
 // use core::{future::poll_fn, sync::atomic::AtomicI32};
 // use core::time::Duration;
@@ -36,9 +33,9 @@ use crate::{
         ctype::pidfd::{new_pidfd, PidFd},
         imp::solve_path,
     },
-    CloneArgs, RLimit, SyscallError, SyscallResult, TimeSecs, WaitFlags, RLIMIT_AS, RLIMIT_NOFILE,
-    RLIMIT_STACK,
+    CloneArgs, RLimit, SyscallError, SyscallResult, TimeSecs, WaitFlags, RLIM_NLIMITS,
 };
+use axerrno::AxError;
 use axlog::info;
 // use axtask::TaskId;
 extern crate alloc;
@@ -123,6 +120,10 @@ pub async fn syscall_exec(args: [usize; 6]) -> SyscallResult {
     if path.is_dir() {
         return Err(SyscallError::EISDIR);
     }
+    // 检查执行权限，文件不存在等其他错误交给加载器处理
+    if let Err(AxError::PermissionDenied) = async_fs::api::check_executable(path.path()).await {
+        return Err(SyscallError::EACCES);
+    }
     let path = path.path().to_string();
 
     let mut args_vec = Vec::new();
@@ -154,14 +155,12 @@ pub async fn syscall_exec(args: [usize; 6]) -> SyscallResult {
     info!("envs: {:?}", envs_vec);
     let curr_process = current_executor().await;
 
-    // 设置 file_path
-    curr_process.set_file_path(path.clone()).await;
-
     let argc = args_vec.len();
-    if curr_process.exec(path, args_vec, &envs_vec).await.is_err() {
-        // exit_current_task(0);
-        executor::exit(0).await;
-    }
+    // 文件无法执行时进程保持不变，直接返回错误
+    curr_process.exec(path.clone(), args_vec, &envs_vec).await?;
+
+    // 设置 file_path
+    curr_process.set_file_path(path).await;
     Ok(argc as isize)
 }
 
@@ -217,29 +216,31 @@ pub async fn syscall_clone(args: [usize; 6]) -> SyscallResult {
     }
 
     // if let Ok(new_task_id) = curr_process.clone_task(flags, stack, ptid, tls, ctid, sig_child).await {
-    if let Ok(new_task_id) = curr_process
+    let new_task_id = match curr_process
         .clone_task(flags, stack, ptid, tls, ctid, sig_child)
         .await
     {
-        if clone_flags.contains(CloneFlags::CLONE_PIDFD) {
-            if clone_flags.contains(CloneFlags::CLONE_PARENT_SETTID) {
-                return Err(SyscallError::EINVAL);
-            }
-            if curr_process
-                .manual_alloc_for_lazy(ptid.into())
-                .await
-                .is_ok()
-            {
-                unsafe {
-                    *(ptid as *mut i32) = new_pidfd(new_task_id, OpenFlags::empty()).await? as i32;
-                }
+        Ok(new_task_id) => new_task_id,
+        // 超过了 RLIMIT_NPROC
+        Err(AxError::WouldBlock) => return Err(SyscallError::EAGAIN),
+        Err(_) => return Err(SyscallError::ENOMEM),
+    };
+    if clone_flags.contains(CloneFlags::CLONE_PIDFD) {
+        if clone_flags.contains(CloneFlags::CLONE_PARENT_SETTID) {
+            return Err(SyscallError::EINVAL);
+        }
+        if curr_process
+            .manual_alloc_for_lazy(ptid.into())
+            .await
+            .is_ok()
+        {
+            unsafe {
+                *(ptid as *mut i32) = new_pidfd(new_task_id, OpenFlags::empty()).await? as i32;
             }
         }
-
-        Ok(new_task_id as isize)
-    } else {
-        Err(SyscallError::ENOMEM)
     }
+
+    Ok(new_task_id as isize)
 }
 
 /// 创建子进程的新函数，所有信息保存在 CloneArgs
@@ -290,7 +291,7 @@ pub async fn syscall_clone3(args: [usize; 6]) -> SyscallResult {
         return Err(SyscallError::EINVAL);
     }
 
-    if let Ok(new_task_id) = curr_process
+    let new_task_id = match curr_process
         .clone_task(
             clone_args.flags as usize,
             stack,
@@ -301,16 +302,18 @@ pub async fn syscall_clone3(args: [usize; 6]) -> SyscallResult {
         )
         .await
     {
-        if clone_flags.contains(CloneFlags::CLONE_PIDFD) {
-            unsafe {
-                *(clone_args.pidfd as *mut u64) =
-                    new_pidfd(new_task_id, OpenFlags::empty()).await? as u64;
-            }
+        Ok(new_task_id) => new_task_id,
+        // 超过了 RLIMIT_NPROC
+        Err(AxError::WouldBlock) => return Err(SyscallError::EAGAIN),
+        Err(_) => return Err(SyscallError::ENOMEM),
+    };
+    if clone_flags.contains(CloneFlags::CLONE_PIDFD) {
+        unsafe {
+            *(clone_args.pidfd as *mut u64) =
+                new_pidfd(new_task_id, OpenFlags::empty()).await? as u64;
         }
-        Ok(new_task_id as isize)
-    } else {
-        Err(SyscallError::ENOMEM)
     }
+    Ok(new_task_id as isize)
 }
 
 /// 创建一个子进程，挂起父进程，直到子进程exec或者exit，父进程才继续执行
@@ -335,8 +338,10 @@ pub async fn syscall_wait4(args: [usize; 6]) -> SyscallResult {
     let pid: i32 = args[0] as i32;
     let exit_code_ptr = args[1] as *mut i32;
     let option = WaitFlags::from_bits(args[2] as u32).unwrap();
+    // WUNTRACED 与 WCONTINUED 要求同时报告被停止与被恢复运行的子进程
+    let wait_options = WaitOptions::from_bits_truncate(option.bits());
     loop {
-        let answer = unsafe { wait_pid(pid, exit_code_ptr).await };
+        let answer = wait_pid(pid, exit_code_ptr, wait_options).await;
         match answer {
             Ok(pid) => {
                 return Ok(pid as isize);
@@ -346,6 +351,9 @@ pub async fn syscall_wait4(args: [usize; 6]) -> SyscallResult {
                     WaitStatus::NotExist => {
                         return Err(SyscallError::ECHILD);
                     }
+                    WaitStatus::BadAddress => {
+                        return Err(SyscallError::EFAULT);
+                    }
                     WaitStatus::Running => {
                         if option.contains(WaitFlags::WNOHANG) {
                             // 不予等待，直接返回0
@@ -394,22 +402,16 @@ pub async fn syscall_sleep(args: [usize; 6]) -> SyscallResult {
     // 若被唤醒时时间小于请求时间，则将剩余时间写入rem
     let sleep_time = current_time() - start_to_sleep;
     if rem as usize != 0 {
-        if sleep_time < dur {
-            let delta = (dur - sleep_time).as_nanos() as usize;
-            unsafe {
-                *rem = TimeSecs {
-                    tv_sec: delta / 1_000_000_000,
-                    tv_nsec: delta % 1_000_000_000,
-                }
-            };
-        } else {
-            unsafe {
-                *rem = TimeSecs {
-                    tv_sec: 0,
-                    tv_nsec: 0,
-                }
-            };
-        }
+        let delta = dur.saturating_sub(sleep_time).as_nanos() as usize;
+        let remain = TimeSecs {
+            tv_sec: delta / 1_000_000_000,
+            tv_nsec: delta % 1_000_000_000,
+        };
+        current_executor()
+            .await
+            .write_user(rem, remain)
+            .await
+            .map_err(|_| SyscallError::EFAULT)?;
     }
 
     if current_executor().await.have_signals().await.is_some() {
@@ -429,7 +431,7 @@ pub async fn syscall_set_tid_address(args: [usize; 6]) -> SyscallResult {
     Ok(current_task().id().as_u64() as isize)
 }
 
-/// 设置任务资源限制
+/// 查询与设置进程的资源限制
 ///
 /// pid 设为0时，表示应用于自己
 ///
@@ -439,79 +441,141 @@ pub async fn syscall_set_tid_address(args: [usize; 6]) -> SyscallResult {
 /// * `new_limit` - *const RLimit
 /// * `old_limit` - *mut RLimit
 pub async fn syscall_prlimit64(args: [usize; 6]) -> SyscallResult {
-    let pid = args[0];
-    let resource = args[1] as i32;
+    let pid = args[0] as u64;
+    let resource = args[1];
     let new_limit = args[2] as *const RLimit;
     let old_limit = args[3] as *mut RLimit;
-    // 当pid不为0，其实没有权利去修改其他的进程的资源限制
+    if resource >= RLIM_NLIMITS {
+        return Err(SyscallError::EINVAL);
+    }
     let curr_process = current_executor().await;
-    if pid == 0 || pid == curr_process.pid() as usize {
-        match resource {
-            // TODO: 改变了新创建的任务栈大小，但未实现当前任务的栈扩展
-            RLIMIT_STACK => {
-                let mut stack_limit: u64 = curr_process.get_stack_limit();
-                if old_limit as usize != 0 {
-                    unsafe {
-                        *old_limit = RLimit {
-                            rlim_cur: stack_limit,
-                            rlim_max: stack_limit,
-                        };
-                    }
-                }
-                if new_limit as usize != 0 {
-                    let new_size = unsafe { (*new_limit).rlim_cur };
-                    if new_size > axconfig::TASK_STACK_SIZE as u64 {
-                        stack_limit = new_size;
-                        curr_process.set_stack_limit(stack_limit);
-                    }
-                }
-            }
-            RLIMIT_NOFILE => {
-                // 仅支持修改最大文件数
-                if old_limit as usize != 0 {
-                    let limit = curr_process.fd_manager.get_limit();
-                    unsafe {
-                        *old_limit = RLimit {
-                            rlim_cur: limit as u64,
-                            rlim_max: limit as u64,
-                        };
-                    }
-                }
-                if new_limit as usize != 0 {
-                    let new_limit = unsafe { (*new_limit).rlim_cur };
-                    curr_process.fd_manager.set_limit(new_limit);
-                }
-            }
-            RLIMIT_AS => {
-                const USER_MEMORY_LIMIT: usize = 0xffff_ffff;
-                if old_limit as usize != 0 {
-                    unsafe {
-                        *old_limit = RLimit {
-                            rlim_cur: USER_MEMORY_LIMIT as u64,
-                            rlim_max: USER_MEMORY_LIMIT as u64,
-                        };
-                    }
-                }
-            }
-            _ => {}
+    let process = if pid == 0 {
+        curr_process.clone()
+    } else {
+        PID2PC.get(&pid).ok_or(SyscallError::ESRCH)?
+    };
+    let cred = curr_process.cred();
+    // 非特权进程只能访问所有用户 id 与组 id 都和自己的实际 id 相同的进程
+    if process.pid() != curr_process.pid() && !cred.is_privileged() {
+        let target = process.cred();
+        if [target.uid, target.euid, target.suid]
+            .iter()
+            .any(|&uid| uid != cred.uid)
+            || [target.gid, target.egid, target.sgid]
+                .iter()
+                .any(|&gid| gid != cred.gid)
+        {
+            return Err(SyscallError::EPERM);
         }
     }
+    let new_limit = if new_limit.is_null() {
+        None
+    } else {
+        if curr_process
+            .manual_alloc_type_for_lazy(new_limit)
+            .await
+            .is_err()
+        {
+            return Err(SyscallError::EFAULT);
+        }
+        Some(unsafe { *new_limit })
+    };
+    if !old_limit.is_null() {
+        if curr_process
+            .manual_alloc_type_for_lazy(old_limit as *const RLimit)
+            .await
+            .is_err()
+        {
+            return Err(SyscallError::EFAULT);
+        }
+        unsafe {
+            *old_limit = process.get_rlimit(resource);
+        }
+    }
+    if let Some(new_limit) = new_limit {
+        // 只有特权进程可以提高硬上限
+        if new_limit.rlim_max > process.get_rlimit(resource).rlim_max && !cred.is_privileged() {
+            return Err(SyscallError::EPERM);
+        }
+        process
+            .set_rlimit(resource, new_limit)
+            .await
+            .map_err(|_| SyscallError::EINVAL)?;
+    }
     Ok(0)
 }
 
-/// not support
-pub fn syscall_getpgid() -> SyscallResult {
-    Ok(0)
+/// 获取进程组 id
+/// # Arguments
+/// * `pid`: usize，为 0 时表示当前进程
+pub async fn syscall_getpgid(args: [usize; 6]) -> SyscallResult {
+    let pid = args[0] as u64;
+    let process = if pid == 0 {
+        current_executor().await
+    } else {
+        PID2PC.get(&pid).ok_or(SyscallError::ESRCH)?
+    };
+    Ok(process.pgid() as isize)
 }
 
+/// 设置进程组 id
 /// # Arguments
-/// * `pgid`: usize
-pub fn syscall_setpgid(args: [usize; 6]) -> SyscallResult {
-    let pgid = args[0];
-    info!("not support setpgid, try to set {}", pgid);
+/// * `pid`: usize，为 0 时表示当前进程
+/// * `pgid`: usize，为 0 时表示使用 pid 作为进程组 id
+pub async fn syscall_setpgid(args: [usize; 6]) -> SyscallResult {
+    let curr_process = current_executor().await;
+    let pid = match args[0] as u64 {
+        0 => curr_process.pid(),
+        pid => pid,
+    };
+    let pgid = match args[1] as isize {
+        0 => pid,
+        pgid if pgid < 0 => return Err(SyscallError::EINVAL),
+        pgid => pgid as u64,
+    };
+    // 只能修改当前进程或者其子进程
+    let process = if pid == curr_process.pid() {
+        curr_process.clone()
+    } else {
+        curr_process
+            .children
+            .lock()
+            .await
+            .iter()
+            .find(|child| child.pid() == pid && !child.get_zombie())
+            .cloned()
+            .ok_or(SyscallError::ESRCH)?
+    };
+    // 会话首进程不能修改进程组，且只能在同一会话中移动
+    if process.is_session_leader() || process.sid() != curr_process.sid() {
+        return Err(SyscallError::EPERM);
+    }
+    // 加入已经存在的进程组时，该进程组必须属于同一会话
+    if pgid != pid
+        && !process_group_members(pgid)
+            .iter()
+            .filter_map(|pid| PID2PC.get(pid))
+            .any(|member| member.sid() == curr_process.sid())
+    {
+        return Err(SyscallError::EPERM);
+    }
+    process.set_pgid(pgid);
     Ok(0)
 }
 
+/// 获取会话 id
+/// # Arguments
+/// * `pid`: usize，为 0 时表示当前进程
+pub async fn syscall_getsid(args: [usize; 6]) -> SyscallResult {
+    let pid = args[0] as u64;
+    let process = if pid == 0 {
+        current_executor().await
+    } else {
+        PID2PC.get(&pid).ok_or(SyscallError::ESRCH)?
+    };
+    Ok(process.sid() as isize)
+}
+
 /// 当前不涉及多核情况
 pub async fn syscall_getpid() -> SyscallResult {
     Ok(current_executor().await.pid() as isize)
@@ -529,23 +593,246 @@ pub async fn syscall_umask(args: [usize; 6]) -> SyscallResult {
     Ok(current_executor().await.fd_manager.set_mask(new_mask) as isize)
 }
 
-/// 获取用户 id。在实现多用户权限前默认为最高权限
-pub fn syscall_getuid() -> SyscallResult {
+/// 设置进程的执行域，返回原来的执行域
+///
+/// 参数为 0xffffffff 时只查询不修改。执行域中的 ADDR_NO_RANDOMIZE 在下一次 exec 时生效
+pub async fn syscall_personality(args: [usize; 6]) -> SyscallResult {
+    let persona = args[0] as u32;
+    let process = current_executor().await;
+    if persona == u32::MAX {
+        return Ok(process.personality() as isize);
+    }
+    Ok(process.set_personality(persona) as isize)
+}
+
+/// 获取实际用户 id
+pub async fn syscall_getuid() -> SyscallResult {
+    Ok(current_executor().await.cred.lock().uid as isize)
+}
+
+/// 获取有效用户 id，即相当于哪个用户的权限
+pub async fn syscall_geteuid() -> SyscallResult {
+    Ok(current_executor().await.cred.lock().euid as isize)
+}
+
+/// 获取实际用户组 id
+pub async fn syscall_getgid() -> SyscallResult {
+    Ok(current_executor().await.cred.lock().gid as isize)
+}
+
+/// 获取有效用户组 id，即相当于哪个用户组的权限
+pub async fn syscall_getegid() -> SyscallResult {
+    Ok(current_executor().await.cred.lock().egid as isize)
+}
+
+/// 表示 setresuid 等调用中不修改对应的 id
+const ID_UNCHANGED: u32 = u32::MAX;
+
+/// 设置用户 id
+///
+/// 有效用户为 root 时同时设置实际、有效与保存的用户 id，否则只能将有效用户 id 设为实际或保存的用户 id
+/// # Arguments
+/// * `uid` - u32
+pub async fn syscall_setuid(args: [usize; 6]) -> SyscallResult {
+    let uid = args[0] as u32;
+    let process = current_executor().await;
+    let mut cred = process.cred.lock();
+    if cred.is_privileged() {
+        cred.uid = uid;
+        cred.euid = uid;
+        cred.suid = uid;
+    } else if uid == cred.uid || uid == cred.suid {
+        cred.euid = uid;
+    } else {
+        return Err(SyscallError::EPERM);
+    }
+    Ok(0)
+}
+
+/// 设置用户组 id，规则与 setuid 相同
+/// # Arguments
+/// * `gid` - u32
+pub async fn syscall_setgid(args: [usize; 6]) -> SyscallResult {
+    let gid = args[0] as u32;
+    let process = current_executor().await;
+    let mut cred = process.cred.lock();
+    if cred.is_privileged() {
+        cred.gid = gid;
+        cred.egid = gid;
+        cred.sgid = gid;
+    } else if gid == cred.gid || gid == cred.sgid {
+        cred.egid = gid;
+    } else {
+        return Err(SyscallError::EPERM);
+    }
+    Ok(0)
+}
+
+/// 检查非特权进程能否将 id 设为 `ids` 中的值：每个值都必须为当前的实际、有效或保存的 id 之一
+fn check_res_ids(ids: [u32; 3], current: [u32; 3]) -> SyscallResult {
+    if ids
+        .iter()
+        .all(|id| *id == ID_UNCHANGED || current.contains(id))
+    {
+        Ok(0)
+    } else {
+        Err(SyscallError::EPERM)
+    }
+}
+
+/// 设置实际、有效与保存的用户 id，值为 -1 时不修改
+/// # Arguments
+/// * `ruid` - u32
+/// * `euid` - u32
+/// * `suid` - u32
+pub async fn syscall_setresuid(args: [usize; 6]) -> SyscallResult {
+    let ids = [args[0] as u32, args[1] as u32, args[2] as u32];
+    let process = current_executor().await;
+    let mut cred = process.cred.lock();
+    if !cred.is_privileged() {
+        check_res_ids(ids, [cred.uid, cred.euid, cred.suid])?;
+    }
+    let cred = &mut *cred;
+    for (id, field) in ids
+        .into_iter()
+        .zip([&mut cred.uid, &mut cred.euid, &mut cred.suid])
+    {
+        if id != ID_UNCHANGED {
+            *field = id;
+        }
+    }
     Ok(0)
 }
 
-/// 获取有效用户 id，即相当于哪个用户的权限。在实现多用户权限前默认为最高权限
-pub fn syscall_geteuid() -> SyscallResult {
+/// 设置实际、有效与保存的用户组 id，值为 -1 时不修改
+/// # Arguments
+/// * `rgid` - u32
+/// * `egid` - u32
+/// * `sgid` - u32
+pub async fn syscall_setresgid(args: [usize; 6]) -> SyscallResult {
+    let ids = [args[0] as u32, args[1] as u32, args[2] as u32];
+    let process = current_executor().await;
+    let mut cred = process.cred.lock();
+    if !cred.is_privileged() {
+        check_res_ids(ids, [cred.gid, cred.egid, cred.sgid])?;
+    }
+    let cred = &mut *cred;
+    for (id, field) in ids
+        .into_iter()
+        .zip([&mut cred.gid, &mut cred.egid, &mut cred.sgid])
+    {
+        if id != ID_UNCHANGED {
+            *field = id;
+        }
+    }
     Ok(0)
 }
 
-/// 获取用户组 id。在实现多用户权限前默认为最高权限
-pub fn syscall_getgid() -> SyscallResult {
+/// 将三个 id 分别写入用户空间中 `args` 的前三个参数指向的位置
+async fn write_res_ids(args: [usize; 6], ids: [u32; 3]) -> SyscallResult {
+    let process = current_executor().await;
+    for ptr in &args[..3] {
+        if process
+            .manual_alloc_type_for_lazy(*ptr as *const u32)
+            .await
+            .is_err()
+        {
+            return Err(SyscallError::EFAULT);
+        }
+    }
+    for (ptr, id) in args[..3].iter().zip(ids) {
+        unsafe {
+            *(*ptr as *mut u32) = id;
+        }
+    }
     Ok(0)
 }
 
-/// 获取有效用户组 id，即相当于哪个用户的权限。在实现多用户权限前默认为最高权限
-pub fn syscall_getegid() -> SyscallResult {
+/// 获取实际、有效与保存的用户 id
+/// # Arguments
+/// * `ruid` - *mut u32
+/// * `euid` - *mut u32
+/// * `suid` - *mut u32
+pub async fn syscall_getresuid(args: [usize; 6]) -> SyscallResult {
+    let cred = current_executor().await.cred();
+    write_res_ids(args, [cred.uid, cred.euid, cred.suid]).await
+}
+
+/// 获取实际、有效与保存的用户组 id
+/// # Arguments
+/// * `rgid` - *mut u32
+/// * `egid` - *mut u32
+/// * `sgid` - *mut u32
+pub async fn syscall_getresgid(args: [usize; 6]) -> SyscallResult {
+    let cred = current_executor().await.cred();
+    write_res_ids(args, [cred.gid, cred.egid, cred.sgid]).await
+}
+
+/// 获取附加组
+///
+/// size 为 0 时只返回附加组的数量
+/// # Arguments
+/// * `size` - i32
+/// * `list` - *mut u32
+pub async fn syscall_getgroups(args: [usize; 6]) -> SyscallResult {
+    let size = args[0] as i32;
+    let list = args[1];
+    if size < 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let process = current_executor().await;
+    let groups = process.cred().groups;
+    if size == 0 {
+        return Ok(groups.len() as isize);
+    }
+    if (size as usize) < groups.len() {
+        return Err(SyscallError::EINVAL);
+    }
+    if !groups.is_empty() {
+        let end = list + groups.len() * core::mem::size_of::<u32>();
+        if process
+            .manual_alloc_range_for_lazy(list.into(), end.into())
+            .await
+            .is_err()
+        {
+            return Err(SyscallError::EFAULT);
+        }
+        unsafe {
+            core::slice::from_raw_parts_mut(list as *mut u32, groups.len())
+                .copy_from_slice(&groups);
+        }
+    }
+    Ok(groups.len() as isize)
+}
+
+/// 设置附加组，只有 root 可以调用
+/// # Arguments
+/// * `size` - usize
+/// * `list` - *const u32
+pub async fn syscall_setgroups(args: [usize; 6]) -> SyscallResult {
+    let size = args[0];
+    let list = args[1];
+    if size > NGROUPS_MAX {
+        return Err(SyscallError::EINVAL);
+    }
+    let process = current_executor().await;
+    if !process.cred.lock().is_privileged() {
+        return Err(SyscallError::EPERM);
+    }
+    let groups = if size == 0 {
+        Vec::new()
+    } else {
+        let end = list + size * core::mem::size_of::<u32>();
+        if process
+            .manual_alloc_range_for_lazy(list.into(), end.into())
+            .await
+            .is_err()
+        {
+            return Err(SyscallError::EFAULT);
+        }
+        unsafe { core::slice::from_raw_parts(list as *const u32, size) }.to_vec()
+    };
+    process.cred.lock().groups = groups;
     Ok(0)
 }
 
@@ -559,54 +846,15 @@ pub fn syscall_gettid() -> SyscallResult {
 /// The calling process is the leader of the new session
 pub async fn syscall_setsid() -> SyscallResult {
     let process = current_executor().await;
-    let task = current_task();
-
-    let task_id = task.id().as_u64();
-
-    // 当前 process 已经是 process group leader
-    if process.pid() == task_id {
+    let pid = process.pid();
+    // 当前进程已经是进程组首进程，或者已经存在以当前进程 id 为 id 的进程组
+    if !process_group_members(pid).is_empty() {
         return Err(SyscallError::EPERM);
     }
-
-    // 从当前 process 的 thread group 中移除 calling thread
-    process
-        .tasks
-        .lock()
-        .await
-        .retain(|t| t.id().as_u64() != task_id);
-
-    // 新建 process group 并加入
-    let new_process = Executor::new(
-        TaskId::new().as_u64(),
-        process.get_parent(),
-        process.memory_set.clone(),
-        process.get_heap_bottom(),
-        Arc::new(Mutex::new(process.fd_manager.fd_table.lock().await.clone())),
-        Arc::new(Mutex::new(String::from("/").into())),
-        Arc::new(AtomicI32::new(0o022)),
-    );
-
-    new_process
-        .signal_modules
-        .lock()
-        .await
-        .insert(task_id, SignalModule::init_signal(None));
-
-    new_process
-        .tasks
-        .lock()
-        .await
-        .push(task.as_task_ref().clone());
-    task.set_leader(true);
-    task.set_process_id(new_process.pid());
-
-    // 修改 PID2PC
-    PID2PC
-        .lock()
-        .await
-        .insert(new_process.pid(), Arc::new(new_process));
-
-    Ok(task_id as isize)
+    // 新的会话没有控制终端
+    process.set_sid(pid);
+    process.set_pgid(pid);
+    Ok(pid as isize)
 }
 
 /// arch_prc
@@ -614,7 +862,7 @@ pub async fn syscall_setsid() -> SyscallResult {
 /// # Arguments
 /// * `code` - usize
 /// * `addr` - *mut usize
-pub fn syscall_arch_prctl(args: [usize; 6]) -> SyscallResult {
+pub async fn syscall_arch_prctl(args: [usize; 6]) -> SyscallResult {
     /*
     #define ARCH_SET_GS			0x1001
     #define ARCH_SET_FS			0x1002
@@ -634,8 +882,13 @@ pub fn syscall_arch_prctl(args: [usize; 6]) -> SyscallResult {
         }
         0x1003 => {
             #[cfg(target_arch = "x86_64")]
-            unsafe {
-                *addr = *(axhal::arch::read_thread_pointer() as *mut usize);
+            {
+                let fs = unsafe { *(axhal::arch::read_thread_pointer() as *mut usize) };
+                current_executor()
+                    .await
+                    .write_user(addr, fs)
+                    .await
+                    .map_err(|_| SyscallError::EFAULT)?;
             }
             Ok(0)
         }
@@ -0,0 +1,179 @@
+//! POSIX 间隔计时器（timer_create 系列）相关的系统调用
+use executor::{
+    current_executor, current_task,
+    posix_timer::{
+        create_timer, delete_timer, find_timer, PosixTimer, TimerClock, TimerNotify, SIGEV_NONE,
+        SIGEV_SIGNAL, SIGEV_THREAD_ID,
+    },
+    sigqueue::SIGRTMAX,
+    TID2TASK,
+};
+extern crate alloc;
+use alloc::sync::Arc;
+use axerrno::AxError;
+
+use crate::{ClockId, ITimerSpec, SigEvent, SyscallError, SyscallResult, TimeSecs, NSEC_PER_SEC};
+
+/// 查找当前进程中的计时器，不存在时返回 EINVAL
+async fn current_timer(timer_id: usize) -> Result<Arc<PosixTimer>, SyscallError> {
+    find_timer(&current_executor().await, timer_id).ok_or(SyscallError::EINVAL)
+}
+
+/// 将 sigevent 转换为计时器的通知方式，未给出时使用默认的通知方式
+fn parse_sigevent(sevp: Option<SigEvent>, pid: u64) -> Result<Option<TimerNotify>, SyscallError> {
+    let Some(sev) = sevp else {
+        return Ok(None);
+    };
+    let signum = sev.sigev_signo as usize;
+    if sev.sigev_notify != SIGEV_NONE && !(1..=SIGRTMAX).contains(&signum) {
+        return Err(SyscallError::EINVAL);
+    }
+    let notify = match sev.sigev_notify {
+        SIGEV_NONE => TimerNotify::None,
+        SIGEV_SIGNAL => TimerNotify::Signal {
+            signum,
+            value: sev.sigev_value,
+        },
+        SIGEV_THREAD_ID => {
+            // 接收信号的线程必须属于当前进程
+            let tid = sev.sigev_notify_thread_id as u64;
+            match TID2TASK.get(&tid) {
+                Some(task) if task.get_process_id() == pid => {}
+                _ => return Err(SyscallError::EINVAL),
+            }
+            TimerNotify::ThreadSignal {
+                tid,
+                signum,
+                value: sev.sigev_value,
+            }
+        }
+        _ => return Err(SyscallError::EINVAL),
+    };
+    Ok(Some(notify))
+}
+
+/// 创建计时器
+/// # Arguments
+/// * `clock_id` - usize
+/// * `sevp` - *const SigEvent
+/// * `timer_id` - *mut i32
+pub async fn syscall_timer_create(args: [usize; 6]) -> SyscallResult {
+    let clock_id = args[0];
+    let sevp = args[1] as *const SigEvent;
+    let timer_id = args[2] as *mut i32;
+    let clock = match ClockId::try_from(clock_id) {
+        Ok(ClockId::CLOCK_REALTIME) => TimerClock::Realtime,
+        Ok(ClockId::CLOCK_MONOTONIC) => TimerClock::Monotonic,
+        Ok(ClockId::CLOCK_PROCESS_CPUTIME_ID) => TimerClock::ProcessCputime,
+        Ok(ClockId::CLOCK_THREAD_CPUTIME_ID) => {
+            TimerClock::ThreadCputime(current_task().id().as_u64())
+        }
+        Err(_) => return Err(SyscallError::EINVAL),
+    };
+    let process = current_executor().await;
+    let sev = if sevp.is_null() {
+        None
+    } else {
+        if process.manual_alloc_type_for_lazy(sevp).await.is_err() {
+            return Err(SyscallError::EFAULT);
+        }
+        Some(unsafe { *sevp })
+    };
+    if process.manual_alloc_type_for_lazy(timer_id).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    let notify = parse_sigevent(sev, process.pid())?;
+    let id = create_timer(&process, clock, notify)
+        .await
+        .map_err(|err| match err {
+            AxError::WouldBlock => SyscallError::EAGAIN,
+            _ => SyscallError::ENOMEM,
+        })?;
+    unsafe {
+        *timer_id = id as i32;
+    }
+    Ok(0)
+}
+
+/// 设置计时器
+/// # Arguments
+/// * `timer_id` - usize
+/// * `flags` - usize
+/// * `new_value` - *const ITimerSpec
+/// * `old_value` - *mut ITimerSpec
+pub async fn syscall_timer_settime(args: [usize; 6]) -> SyscallResult {
+    let timer_id = args[0];
+    let flags = args[1];
+    let new_value = args[2] as *const ITimerSpec;
+    let old_value = args[3] as *mut ITimerSpec;
+    let timer = current_timer(timer_id).await?;
+    let process = current_executor().await;
+    if new_value.is_null() || process.manual_alloc_type_for_lazy(new_value).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    if !old_value.is_null() && process.manual_alloc_type_for_lazy(old_value).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    let new_value = unsafe { *new_value };
+    if new_value.it_value.tv_nsec >= NSEC_PER_SEC || new_value.it_interval.tv_nsec >= NSEC_PER_SEC {
+        return Err(SyscallError::EINVAL);
+    }
+    let (old_interval_ns, old_remained_ns) = timer
+        .settime(
+            new_value.it_value.turn_to_nanos(),
+            new_value.it_interval.turn_to_nanos(),
+            flags,
+        )
+        .await;
+    if !old_value.is_null() {
+        unsafe {
+            *old_value = ITimerSpec {
+                it_interval: TimeSecs::from_nanos(old_interval_ns),
+                it_value: TimeSecs::from_nanos(old_remained_ns),
+            };
+        }
+    }
+    Ok(0)
+}
+
+/// 获取计时器的周期与剩余时间
+/// # Arguments
+/// * `timer_id` - usize
+/// * `curr_value` - *mut ITimerSpec
+pub async fn syscall_timer_gettime(args: [usize; 6]) -> SyscallResult {
+    let timer_id = args[0];
+    let curr_value = args[1] as *mut ITimerSpec;
+    let timer = current_timer(timer_id).await?;
+    if current_executor()
+        .await
+        .manual_alloc_type_for_lazy(curr_value)
+        .await
+        .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    let (interval_ns, remained_ns) = timer.gettime().await;
+    unsafe {
+        *curr_value = ITimerSpec {
+            it_interval: TimeSecs::from_nanos(interval_ns),
+            it_value: TimeSecs::from_nanos(remained_ns),
+        };
+    }
+    Ok(0)
+}
+
+/// 获取最近一次发送的信号对应的 overrun
+/// # Arguments
+/// * `timer_id` - usize
+pub async fn syscall_timer_getoverrun(args: [usize; 6]) -> SyscallResult {
+    let timer = current_timer(args[0]).await?;
+    Ok(timer.overrun() as isize)
+}
+
+/// 删除计时器
+/// # Arguments
+/// * `timer_id` - usize
+pub async fn syscall_timer_delete(args: [usize; 6]) -> SyscallResult {
+    delete_timer(&current_executor().await, args[0]).map_err(|_| SyscallError::EINVAL)?;
+    Ok(0)
+}
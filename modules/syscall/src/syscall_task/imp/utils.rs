@@ -28,32 +28,38 @@ pub fn time_stat_output() -> (usize, usize, usize, usize) {
 /// 返回值为当前经过的时钟中断数
 /// # Arguments
 /// * `tms` - *mut Tms
-pub fn syscall_time(args: [usize; 6]) -> SyscallResult {
+pub async fn syscall_time(args: [usize; 6]) -> SyscallResult {
     let tms = args[0] as *mut Tms;
     let (_, utime_us, _, stime_us) = time_stat_output();
-    unsafe {
-        *tms = Tms {
-            tms_utime: utime_us,
-            tms_stime: stime_us,
-            tms_cutime: utime_us,
-            tms_cstime: stime_us,
-        }
-    }
+    let tms_value = Tms {
+        tms_utime: utime_us,
+        tms_stime: stime_us,
+        tms_cutime: utime_us,
+        tms_cstime: stime_us,
+    };
+    current_executor()
+        .await
+        .write_user(tms, tms_value)
+        .await
+        .map_err(|_| SyscallError::EFAULT)?;
     Ok(nanos_to_ticks(current_time_nanos()) as isize)
 }
 
 /// 获取当前系统时间并且存储在给定结构体中
 /// # Arguments
 /// * `ts` - *mut TimeVal
-pub fn syscall_get_time_of_day(args: [usize; 6]) -> SyscallResult {
+pub async fn syscall_get_time_of_day(args: [usize; 6]) -> SyscallResult {
     let ts = args[0] as *mut TimeVal;
     let current_us = current_time_nanos() as usize / 1000;
-    unsafe {
-        *ts = TimeVal {
-            sec: current_us / 1_000_000,
-            usec: current_us % 1_000_000,
-        }
-    }
+    let time = TimeVal {
+        sec: current_us / 1_000_000,
+        usec: current_us % 1_000_000,
+    };
+    current_executor()
+        .await
+        .write_user(ts, time)
+        .await
+        .map_err(|_| SyscallError::EFAULT)?;
     Ok(0)
 }
 
@@ -61,27 +67,31 @@ pub fn syscall_get_time_of_day(args: [usize; 6]) -> SyscallResult {
 /// # Arguments
 /// * `clock_id` - usize
 /// * `ts` - *mut TimeSecs
-pub fn syscall_clock_get_time(args: [usize; 6]) -> SyscallResult {
+pub async fn syscall_clock_get_time(args: [usize; 6]) -> SyscallResult {
     let _clock_id = args[0];
     let ts = args[1] as *mut TimeSecs;
-    unsafe {
-        (*ts) = TimeSecs::now();
-    }
+    current_executor()
+        .await
+        .write_user(ts, TimeSecs::now())
+        .await
+        .map_err(|_| SyscallError::EFAULT)?;
     Ok(0)
 }
 
 /// 获取系统信息
 /// # Arguments
 /// * `uts` - *mut UtsName
-pub fn syscall_uname(args: [usize; 6]) -> SyscallResult {
+pub async fn syscall_uname(args: [usize; 6]) -> SyscallResult {
     let uts = args[0] as *mut UtsName;
-    unsafe {
-        *uts = UtsName::default();
-    }
+    current_executor()
+        .await
+        .write_user(uts, UtsName::default())
+        .await
+        .map_err(|_| SyscallError::EFAULT)?;
     Ok(0)
 }
 
-/// 获取系统的启动时间和内存信息，当前仅支持启动时间
+/// 获取系统的启动时间和内存信息，当前仅支持启动时间与交换空间的大小
 /// # Arguments
 /// * `info` - *mut SysInfo
 pub async fn syscall_sysinfo(args: [usize; 6]) -> SyscallResult {
@@ -98,6 +108,10 @@ pub async fn syscall_sysinfo(args: [usize; 6]) -> SyscallResult {
     unsafe {
         // 获取以秒为单位的时间
         (*info).uptime = (current_time_nanos() / NANOS_PER_SEC) as isize;
+        let (totalswap, freeswap) = async_mem::swap::swap_info();
+        (*info).totalswap = totalswap;
+        (*info).freeswap = freeswap;
+        (*info).mem_unit = 1;
     }
     Ok(0)
 }
@@ -21,11 +21,12 @@ pub async fn task_syscall(
         CLONE3 => syscall_clone3(args).await,
         NANO_SLEEP => syscall_sleep(args).await,
         SCHED_YIELD => syscall_yield().await,
-        TIMES => syscall_time(args),
-        UNAME => syscall_uname(args),
-        GETTIMEOFDAY => syscall_get_time_of_day(args),
-        GETPGID => syscall_getpgid(),
-        SETPGID => syscall_setpgid(args),
+        TIMES => syscall_time(args).await,
+        UNAME => syscall_uname(args).await,
+        GETTIMEOFDAY => syscall_get_time_of_day(args).await,
+        GETPGID => syscall_getpgid(args).await,
+        SETPGID => syscall_setpgid(args).await,
+        GETSID => syscall_getsid(args).await,
         GETPID => syscall_getpid().await,
 
         GETPPID => syscall_getppid().await,
@@ -45,15 +46,25 @@ pub async fn task_syscall(
         SIGPROCMASK => syscall_sigprocmask(args).await,
         SIGALTSTACK => syscall_sigaltstack(args).await,
         SIGRETURN => syscall_sigreturn().await,
+        RT_SIGQUEUEINFO => syscall_rt_sigqueueinfo(args).await,
+        RT_TGSIGQUEUEINFO => syscall_rt_tgsigqueueinfo(args).await,
         EXIT_GROUP => syscall_exit(args).await,
         SET_TID_ADDRESS => syscall_set_tid_address(args).await,
         PRLIMIT64 => syscall_prlimit64(args).await,
-        CLOCK_GET_TIME => syscall_clock_get_time(args),
-        GETUID => syscall_getuid(),
-        GETEUID => syscall_geteuid(),
-        GETGID => syscall_getgid(),
-        SETGID => Ok(0),
-        GETEGID => syscall_getegid(),
+        PTRACE => syscall_ptrace(args).await,
+        CLOCK_GET_TIME => syscall_clock_get_time(args).await,
+        GETUID => syscall_getuid().await,
+        GETEUID => syscall_geteuid().await,
+        GETGID => syscall_getgid().await,
+        GETEGID => syscall_getegid().await,
+        SETUID => syscall_setuid(args).await,
+        SETGID => syscall_setgid(args).await,
+        SETRESUID => syscall_setresuid(args).await,
+        SETRESGID => syscall_setresgid(args).await,
+        GETRESUID => syscall_getresuid(args).await,
+        GETRESGID => syscall_getresgid(args).await,
+        GETGROUPS => syscall_getgroups(args).await,
+        SETGROUPS => syscall_setgroups(args).await,
         GETTID => syscall_gettid(),
         FUTEX => syscall_futex(args).await,
         SET_ROBUST_LIST => syscall_set_robust_list(args).await,
@@ -61,13 +72,18 @@ pub async fn task_syscall(
         SYSINFO => syscall_sysinfo(args).await,
         SETITIMER => syscall_settimer(args).await,
         GETTIMER => syscall_gettimer(args).await,
+        TIMER_CREATE => syscall_timer_create(args).await,
+        TIMER_SETTIME => syscall_timer_settime(args).await,
+        TIMER_GETTIME => syscall_timer_gettime(args).await,
+        TIMER_GETOVERRUN => syscall_timer_getoverrun(args).await,
+        TIMER_DELETE => syscall_timer_delete(args).await,
         SETSID => syscall_setsid().await,
         GETRUSAGE => syscall_getrusage(args).await,
         UMASK => syscall_umask(args).await,
+        PERSONALITY => syscall_personality(args).await,
         // 不做处理即可
         SIGTIMEDWAIT => Ok(0),
         SYSLOG => Ok(0),
-        MADVICE => Ok(0),
         SCHED_SETAFFINITY => Ok(0),
         SCHED_GETAFFINITY => syscall_sched_getaffinity(args).await,
         SCHED_SETSCHEDULER => syscall_sched_setscheduler(args).await,
@@ -81,7 +97,7 @@ pub async fn task_syscall(
         #[cfg(target_arch = "x86_64")]
         VFORK => syscall_vfork(),
         #[cfg(target_arch = "x86_64")]
-        ARCH_PRCTL => syscall_arch_prctl(args),
+        ARCH_PRCTL => syscall_arch_prctl(args).await,
         #[cfg(target_arch = "x86_64")]
         FORK => syscall_fork(),
         #[cfg(target_arch = "x86_64")]
@@ -90,6 +106,8 @@ pub async fn task_syscall(
         RSEQ => Ok(0),
         #[cfg(target_arch = "x86_64")]
         TIME => Ok(0),
+        #[cfg(target_arch = "x86_64")]
+        GETPGRP => syscall_getpgid([0; 6]).await,
         #[allow(unused)]
         _ => {
             panic!("Invalid Syscall Id: {:?}!", syscall_id);
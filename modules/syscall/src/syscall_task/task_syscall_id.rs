@@ -12,6 +12,7 @@ numeric_enum_macro::numeric_enum! {
 #[allow(missing_docs)]
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 pub enum TaskSyscallId {
+    PERSONALITY = 92,
     EXIT = 93,
     EXIT_GROUP = 94,
     SET_TID_ADDRESS = 96,
@@ -21,6 +22,11 @@ pub enum TaskSyscallId {
     NANO_SLEEP = 101,
     GETTIMER = 102,
     SETITIMER = 103,
+    TIMER_CREATE = 107,
+    TIMER_GETTIME = 108,
+    TIMER_GETOVERRUN = 109,
+    TIMER_SETTIME = 110,
+    TIMER_DELETE = 111,
     CLOCK_GETRES = 114,
     CLOCK_NANOSLEEP = 115,
     SYSLOG = 116,
@@ -32,6 +38,7 @@ pub enum TaskSyscallId {
     GET_MEMPOLICY = 236,
     SETPGID = 154,
     GETPGID = 155,
+    GETSID = 156,
     SETSID = 157,
     GETRUSAGE = 165,
     UMASK = 166,
@@ -41,14 +48,21 @@ pub enum TaskSyscallId {
     GETUID = 174,
     GETEUID = 175,
     GETGID = 176,
-    SETGID = 144,
     GETEGID = 177,
+    PTRACE = 117,
+    SETGID = 144,
+    SETUID = 146,
+    SETRESUID = 147,
+    GETRESUID = 148,
+    SETRESGID = 149,
+    GETRESGID = 150,
+    GETGROUPS = 158,
+    SETGROUPS = 159,
     GETTID = 178,
     SYSINFO = 179,
     CLONE = 220,
     CLONE3 = 435,
     EXECVE = 221,
-    MADVICE = 233,
     WAIT4 = 260,
     GETRANDOM = 278,
     SCHED_YIELD = 124,
@@ -66,6 +80,8 @@ pub enum TaskSyscallId {
     SIGACTION = 134,
     SIGPROCMASK = 135,
     SIGRETURN = 139,
+    RT_SIGQUEUEINFO = 138,
+    RT_TGSIGQUEUEINFO = 240,
     PIDFD_SEND_SIGNAL = 424,
 }
 }
@@ -87,6 +103,11 @@ numeric_enum_macro::numeric_enum! {
         NANO_SLEEP = 35,
         GETTIMER = 36,
         SETITIMER = 38,
+        TIMER_CREATE = 222,
+        TIMER_SETTIME = 223,
+        TIMER_GETTIME = 224,
+        TIMER_GETOVERRUN = 225,
+        TIMER_DELETE = 226,
         CLOCK_GETRES = 229,
         CLOCK_NANOSLEEP = 230,
         TIME = 201,
@@ -99,22 +120,32 @@ numeric_enum_macro::numeric_enum! {
         SETSID = 112,
         GETRUSAGE = 98,
         UMASK = 95,
+        PERSONALITY = 135,
         PRCTL = 157,
         GETPID = 39,
         GETPPID = 110,
         GETUID = 102,
         GETEUID = 107,
         GETGID = 104,
+        SETUID = 105,
         SETGID = 106,
+        GETGROUPS = 115,
+        SETGROUPS = 116,
+        SETRESUID = 117,
+        GETRESUID = 118,
+        SETRESGID = 119,
+        GETRESGID = 120,
         GETPGID = 121,
         SETPGID = 109,
+        GETPGRP = 111,
+        GETSID = 124,
         GETEGID = 108,
+        PTRACE = 101,
         GETTID = 186,
         SYSINFO = 99,
         CLONE = 56,
         CLONE3 = 435,
         EXECVE = 59,
-        MADVICE = 28,
         WAIT4 = 61,
         GETRANDOM = 318,
         SCHED_YIELD = 24,
@@ -137,6 +168,8 @@ numeric_enum_macro::numeric_enum! {
         VFORK = 58,
         ALARM = 37,
         SIGALTSTACK = 131,
+        RT_SIGQUEUEINFO = 129,
+        RT_TGSIGQUEUEINFO = 297,
         PIDFD_SEND_SIGNAL = 424,
     }
 }
@@ -140,13 +140,16 @@ pub async fn syscall_init_async_batch(_waker: usize, res_ptr: usize) -> SyscallR
     //     unsafe { TaskId::virt(0) },
     //     recv_task_id,
     // );
-    let res_ptr = unsafe { &mut *(res_ptr as *mut AsyncBatchSyscallResult) };
-    *res_ptr = AsyncBatchSyscallResult {
+    let result = AsyncBatchSyscallResult {
         send_channel: syscall_send_page_start.as_usize(),
         recv_channel: syscall_recv_page_start.as_usize(),
         recv_os_id: 1,
         recv_process_id: 0,
     };
+    current_executor
+        .write_user(res_ptr as *mut AsyncBatchSyscallResult, result)
+        .await
+        .map_err(|_| SyscallError::EFAULT)?;
     Ok(0)
 }
 
@@ -1,7 +1,12 @@
 //! Define the trap handler for the whole kernel
+use async_mem::MemorySet;
 pub use axhal::{mem::VirtAddr, paging::MappingFlags, time::current_time_nanos};
 use axsignal::signal_no::SignalNo;
-use executor::{current_executor, current_task, send_signal_to_thread};
+use executor::{
+    current_executor, current_task,
+    ptrace::{syscall_enter_stop, syscall_exit_stop},
+    send_signal_to_thread,
+};
 
 use super::syscall::syscall;
 
@@ -42,7 +47,15 @@ pub fn handle_irq(_irq_num: usize, from_user: bool) {
 /// * `args` - The arguments of the syscall
 pub async fn handle_syscall(syscall_id: usize, args: [usize; 6]) -> isize {
     time_stat_from_user_to_kernel();
+    // 被跟踪的进程在进入系统调用时停止，跟踪者可能修改了系统调用号与参数
+    let (syscall_id, args) = if syscall_enter_stop().await {
+        let tf = current_task().utrap_frame().unwrap();
+        (tf.get_syscall_num(), tf.get_syscall_args())
+    } else {
+        (syscall_id, args)
+    };
     let ans = syscall(syscall_id, args).await;
+    let ans = syscall_exit_stop(ans).await;
     time_stat_from_kernel_to_user();
     ans
 }
@@ -57,11 +70,8 @@ pub async fn handle_syscall(syscall_id: usize, args: [usize; 6]) -> isize {
 pub async fn handle_page_fault(addr: VirtAddr, flags: MappingFlags) {
     time_stat_from_user_to_kernel();
     let current_executor = current_executor().await;
-    if current_executor
-        .memory_set
-        .lock()
-        .await
-        .handle_page_fault(addr, flags)
+    // 需要读取文件或者交换空间时只挂起当前任务，读取期间不持有地址空间的锁
+    if MemorySet::handle_user_page_fault(&current_executor.memory_set, addr, flags)
         .await
         .is_ok()
     {
@@ -28,7 +28,9 @@ pub use block::BlockFuture;
 pub use exit::ExitFuture;
 pub use join::JoinFuture;
 pub use sleep::SleepFuture;
-pub use timers::{cancel_alarm, check_events, init, set_alarm_wakeup};
+pub use timers::{
+    cancel_alarm, cancel_alarm_callback, check_events, init, set_alarm_callback, set_alarm_wakeup,
+};
 pub use wait_list::{WaitTaskList, WaitWakerNode};
 pub use yield_::YieldFuture;
 
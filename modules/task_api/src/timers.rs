@@ -1,3 +1,4 @@
+use alloc::boxed::Box;
 use axhal::time::current_time;
 use core::task::Waker;
 use lazy_init::LazyInit;
@@ -5,13 +6,22 @@ use spinlock::SpinNoIrq;
 use timer_list::{TimeValue, TimerEvent, TimerList};
 
 // TODO: per-CPU
-static TIMER_LIST: LazyInit<SpinNoIrq<TimerList<TaskWakeupEvent>>> = LazyInit::new();
+static TIMER_LIST: LazyInit<SpinNoIrq<TimerList<AlarmEvent>>> = LazyInit::new();
 
-struct TaskWakeupEvent(Waker);
+/// 定时器到期时触发的事件
+enum AlarmEvent {
+    /// 唤醒睡眠的任务
+    Wakeup(Waker),
+    /// 执行回调函数，`key` 用于取消
+    Callback(usize, Box<dyn FnOnce(TimeValue) + Send>),
+}
 
-impl TimerEvent for TaskWakeupEvent {
-    fn callback(self, _now: TimeValue) {
-        self.0.wake();
+impl TimerEvent for AlarmEvent {
+    fn callback(self, now: TimeValue) {
+        match self {
+            Self::Wakeup(waker) => waker.wake(),
+            Self::Callback(_, callback) => callback(now),
+        }
     }
 }
 
@@ -19,12 +29,35 @@ pub fn set_alarm_wakeup(deadline: TimeValue, waker: Waker) {
     let task = waker.data() as *const taskctx::Task;
     unsafe { &*task }.set_state(taskctx::TaskState::Blocking);
     let mut timer_list = TIMER_LIST.lock();
-    timer_list.set(deadline, TaskWakeupEvent(waker));
+    timer_list.set(deadline, AlarmEvent::Wakeup(waker));
     drop(timer_list)
 }
 
 pub fn cancel_alarm(waker: &Waker) {
-    TIMER_LIST.lock().cancel(|t| Waker::will_wake(&t.0, waker));
+    TIMER_LIST.lock().cancel(|event| match event {
+        AlarmEvent::Wakeup(w) => Waker::will_wake(w, waker),
+        AlarmEvent::Callback(..) => false,
+    });
+}
+
+/// 在 `deadline` 时执行 `callback`
+///
+/// 回调在时钟中断中执行，不能阻塞；`key` 由调用者保证唯一，用于 [`cancel_alarm_callback`]
+pub fn set_alarm_callback<F>(deadline: TimeValue, key: usize, callback: F)
+where
+    F: FnOnce(TimeValue) + Send + 'static,
+{
+    TIMER_LIST
+        .lock()
+        .set(deadline, AlarmEvent::Callback(key, Box::new(callback)));
+}
+
+/// 取消所有以 `key` 注册且尚未执行的回调
+pub fn cancel_alarm_callback(key: usize) {
+    TIMER_LIST.lock().cancel(|event| match event {
+        AlarmEvent::Wakeup(_) => false,
+        AlarmEvent::Callback(k, _) => *k == key,
+    });
 }
 
 pub fn check_events() {
@@ -1,8 +1,17 @@
+use alloc::{format, string::String};
+use axhal::mem::PAGE_SIZE_4K;
 use executor::link::{create_link, FilePath};
 
 pub async fn fs_init() {
-    use alloc::format;
     use alloc::string::ToString;
+    // 内容随内核状态变化的 procfs 文件，每次读取时重新生成
+    let generated: [(&str, fn() -> String); 2] = [("meminfo", meminfo), ("vmstat", vmstat)];
+    for (name, render) in generated {
+        if let Err(err) = async_fs::api::add_generated_file("/proc", name, render).await {
+            log::warn!("failed to add /proc/{}: {:?}", name, err);
+        }
+    }
+
     #[cfg(target_arch = "riscv64")]
     let libc_so = &"ld-musl-riscv64-sf.so.1";
     #[cfg(target_arch = "riscv64")]
@@ -121,8 +130,6 @@ pub async fn fs_init() {
         .await;
     }
 
-    // let mem_file = axfs::api::lookup("/proc/meminfo").await.unwrap();
-    // mem_file.write_at(0, meminfo().as_bytes()).unwrap();
     // let oom_file = axfs::api::lookup("/proc/sys/vm/overcommit_memory").await.unwrap();
     // oom_file.write_at(0, oominfo().as_bytes()).unwrap();
     // let fs_file = axfs::api::lookup("/proc/filesystems").await.unwrap();
@@ -241,63 +248,46 @@ pub async fn fs_init() {
     .await;
 }
 
-// fn meminfo() -> &'static str {
-//     "MemTotal:       32246488 kB
-// MemFree:         5239804 kB
-// MemAvailable:   10106000 kB
-// Buffers:          235604 kB
-// Cached:          5204940 kB
-// SwapCached:            0 kB
-// Active:         17890456 kB
-// Inactive:        2119348 kB
-// Active(anon):   14891328 kB
-// Inactive(anon):        0 kB
-// Active(file):    2999128 kB
-// Inactive(file):  2119348 kB
-// Unevictable:         144 kB
-// Mlocked:             144 kB
-// SwapTotal:       8388604 kB
-// SwapFree:        8388604 kB
-// Zswap:                 0 kB
-// Zswapped:              0 kB
-// Dirty:               784 kB
-// Writeback:             0 kB
-// AnonPages:      14560300 kB
-// Mapped:          2108592 kB
-// Shmem:            323608 kB
-// KReclaimable:     205804 kB
-// Slab:            1539752 kB
-// SReclaimable:     205804 kB
-// SUnreclaim:      1333948 kB
-// KernelStack:      630704 kB
-// PageTables:      2007248 kB
-// SecPageTables:         0 kB
-// NFS_Unstable:          0 kB
-// Bounce:                0 kB
-// WritebackTmp:          0 kB
-// CommitLimit:    24511848 kB
-// Committed_AS:   42466972 kB
-// VmallocTotal:   34359738367 kB
-// VmallocUsed:      762644 kB
-// VmallocChunk:          0 kB
-// Percpu:            35776 kB
-// HardwareCorrupted:     0 kB
-// AnonHugePages:     79872 kB
-// ShmemHugePages:        0 kB
-// ShmemPmdMapped:        0 kB
-// FileHugePages:         0 kB
-// FilePmdMapped:         0 kB
-// Unaccepted:            0 kB
-// HugePages_Total:       0
-// HugePages_Free:        0
-// HugePages_Rsvd:        0
-// HugePages_Surp:        0
-// Hugepagesize:       2048 kB
-// Hugetlb:               0 kB
-// DirectMap4k:     6500036 kB
-// DirectMap2M:    23283712 kB
-// DirectMap1G:     3145728 kB"
-// }
+/// `/proc/meminfo` 的内容：物理内存、页缓存、交换空间与大页的使用情况，单位为 KiB
+fn meminfo() -> String {
+    let allocator = axalloc::global_allocator();
+    let free = allocator.available_pages() * PAGE_SIZE_4K;
+    let total = free + allocator.used_pages() * PAGE_SIZE_4K;
+    // 没有被映射的页缓存可以被回收，也算作可用的内存
+    let (cached, reclaimable) = async_fs::page_cache::stats();
+    let available = free + reclaimable * PAGE_SIZE_4K;
+    let (swap_total, swap_free) = async_mem::swap::swap_info();
+    let huge = async_mem::huge::stats();
+    format!(
+        concat!(
+            "MemTotal:       {:>8} kB\n",
+            "MemFree:        {:>8} kB\n",
+            "MemAvailable:   {:>8} kB\n",
+            "Cached:         {:>8} kB\n",
+            "SwapTotal:      {:>8} kB\n",
+            "SwapFree:       {:>8} kB\n",
+            "AnonHugePages:  {:>8} kB\n",
+            "Hugepagesize:   {:>8} kB\n",
+        ),
+        total / 1024,
+        free / 1024,
+        available / 1024,
+        cached * PAGE_SIZE_4K / 1024,
+        swap_total / 1024,
+        swap_free / 1024,
+        huge.mapped / 1024,
+        async_mem::huge::PAGE_SIZE_2M / 1024,
+    )
+}
+
+/// `/proc/vmstat` 的内容：大页的分配、退回与拆分次数
+fn vmstat() -> String {
+    let huge = async_mem::huge::stats();
+    format!(
+        "thp_fault_alloc {}\nthp_fault_fallback {}\nthp_split_page {}\n",
+        huge.fault_alloc, huge.fault_fallback, huge.split,
+    )
+}
 
 // // TODO: Implement the real content of overcommit_memory
 // fn oominfo() -> &'static str {
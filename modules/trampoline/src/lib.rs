@@ -53,6 +53,8 @@ pub fn trampoline(tf: &mut TrapFrame, has_trap: bool, from_user: bool) {
             }
             return;
         } else {
+            // 两次任务调度之间不会处于 RCU 读临界区中，报告静止状态
+            sync::rcu::rcu_quiescent_state();
             // 用户态发生了 Trap 或者需要调度
             if let Some(curr) = CurrentTask::try_get().or_else(|| {
                 if let Some(task) = CurrentExecutor::get().pick_next_task() {
@@ -109,6 +111,9 @@ pub fn run_task(curr: CurrentTask) {
             CurrentTask::clean_current();
         }
         Poll::Pending => {
+            // 检查任务是否在让权时仍持有锁
+            #[cfg(feature = "lockdep")]
+            sync::lockdep::check_held_across_await();
             let mut state = curr.state_lock_manual();
             match **state {
                 // await 主动让权，将任务的状态修改为就绪后，放入就绪队列中
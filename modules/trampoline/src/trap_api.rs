@@ -22,7 +22,9 @@ pub fn handle_irq(_irq_num: usize, tf: &mut TrapFrame) {
     #[cfg(feature = "irq")]
     {
         let guard = kernel_guard::NoPreempt::new();
+        axhal::irq::irq_enter();
         axhal::irq::dispatch_irq(_irq_num);
+        axhal::irq::irq_exit();
         drop(guard); // rescheduling may occur when preemption is re-enabled.
         tf.trap_status = TrapStatus::Done;
 
@@ -35,7 +37,9 @@ pub async fn handle_user_irq(_irq_num: usize, tf: &mut TrapFrame) {
     #[cfg(feature = "irq")]
     {
         let guard = kernel_guard::NoPreempt::new();
+        axhal::irq::irq_enter();
         axhal::irq::dispatch_irq(_irq_num);
+        axhal::irq::irq_exit();
         drop(guard); // rescheduling may occur when preemption is re-enabled.
 
         tf.trap_status = TrapStatus::Done;
@@ -0,0 +1,27 @@
+//! fork 之后父子进程的私有页面被写保护。父进程在 fork 之后不再写入栈上的 `slots`，
+//! 由内核通过 wait4 将子进程的退出状态写入其中，内核需要先解除写时复制再写入。
+use std::hint::black_box;
+
+extern "C" {
+    fn fork() -> i32;
+    fn waitpid(pid: i32, status: *mut i32, options: i32) -> i32;
+    fn _exit(code: i32) -> !;
+}
+
+fn main() {
+    println!("fork wait test:");
+    // 在 fork 之前写入，保证页面已经被映射
+    let mut slots = black_box([-1i32; 1024]);
+    let pid = unsafe { fork() };
+    assert!(pid >= 0, "fork failed");
+    if pid == 0 {
+        unsafe { _exit(42) };
+    }
+    let status = &mut slots[512] as *mut i32;
+    let ret = unsafe { waitpid(pid, status, 0) };
+    assert_eq!(ret, pid);
+    let status = unsafe { status.read_volatile() };
+    assert_eq!(status & 0x7f, 0, "child did not exit normally");
+    assert_eq!((status >> 8) & 0xff, 42);
+    println!("fork wait test passed");
+}
@@ -0,0 +1,41 @@
+//! 关闭地址空间随机化之后重新执行自身，使用固定布局的主线程栈需要能够扩展到初始的栈区域之外。
+use std::{env, hint::black_box, os::unix::process::CommandExt, process::Command};
+
+extern "C" {
+    fn personality(persona: u64) -> i32;
+}
+
+const ADDR_NO_RANDOMIZE: u64 = 0x0040000;
+/// 查询当前的执行域而不修改
+const PERSONALITY_QUERY: u64 = 0xffff_ffff;
+
+/// 每层占用 64 KiB 栈，共约 4 MiB：超过初始的栈区域，小于默认的 RLIMIT_STACK
+const DEPTH: usize = 64;
+
+#[inline(never)]
+fn recurse(depth: usize) -> u64 {
+    let frame = black_box([depth as u8; 64 * 1024]);
+    if depth == 0 {
+        return frame[0] as u64;
+    }
+    recurse(depth - 1) + frame[frame.len() - 1] as u64
+}
+
+fn main() {
+    let mut args = env::args();
+    let exe = args.next().unwrap();
+    if args.next().as_deref() != Some("child") {
+        println!("stack growth test:");
+        let persona = unsafe { personality(PERSONALITY_QUERY) };
+        assert!(persona >= 0, "personality failed");
+        assert!(unsafe { personality(persona as u64 | ADDR_NO_RANDOMIZE) } >= 0);
+        let err = Command::new(&exe).arg("child").exec();
+        panic!("exec {exe} failed: {err}");
+    }
+    assert_eq!(
+        unsafe { personality(PERSONALITY_QUERY) } as u64 & ADDR_NO_RANDOMIZE,
+        ADDR_NO_RANDOMIZE
+    );
+    assert_eq!(recurse(DEPTH), (1..=DEPTH as u64).sum());
+    println!("stack growth test passed");
+}